@@ -0,0 +1,137 @@
+//! Binding guardian APIs to other addresses than localhost.
+//!
+//! By default guardians only listen on `127.0.0.1`. To reach a federation from
+//! another host or container, a guardian's API can be bound to any local
+//! address, while the federation config, and with it the invite code,
+//! advertises the address other hosts reach it at.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, TcpListener};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use fedimint_core::util::SafeUrl;
+use fedimint_core::PeerId;
+use fedimint_server::config::ConfigGenParams;
+
+use crate::envs::FM_GUARDIAN_API_BIND_ENV;
+use crate::util::guardian_list_from_env;
+
+/// Where a guardian's API listens and how other hosts reach it, parsed from
+/// `ADDR` or `ADDR@HOST`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiBind {
+    /// Local address the API listens on
+    pub bind: IpAddr,
+    /// Host put into the federation config and invite code
+    pub advertised: String,
+}
+
+impl ApiBind {
+    pub fn new(bind: IpAddr, advertised: impl Into<String>) -> Self {
+        Self {
+            bind,
+            advertised: advertised.into(),
+        }
+    }
+
+    /// `url` with its host replaced by the advertised one
+    fn advertised_url(&self, url: &SafeUrl) -> Result<SafeUrl> {
+        let port = url.port().context("guardian url without port")?;
+        let host = match self.advertised.parse::<Ipv6Addr>() {
+            Ok(addr) => format!("[{addr}]"),
+            Err(_) => self.advertised.clone(),
+        };
+        Ok(SafeUrl::parse(&format!(
+            "{}://{host}:{port}",
+            url.scheme()
+        ))?)
+    }
+}
+
+impl FromStr for ApiBind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (bind, advertised) = match s.split_once('@') {
+            Some((bind, advertised)) => (bind, Some(advertised)),
+            None => (s, None),
+        };
+        let bind: IpAddr = bind
+            .parse()
+            .with_context(|| format!("invalid bind address {bind}"))?;
+        let advertised = match advertised {
+            Some("") => bail!("empty advertised host in {s}"),
+            Some(advertised) => advertised.to_owned(),
+            None if bind.is_unspecified() => {
+                bail!("binding {bind} needs the host to advertise, like {bind}@192.168.1.5")
+            }
+            None => bind.to_string(),
+        };
+        Ok(Self { bind, advertised })
+    }
+}
+
+impl fmt::Display for ApiBind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.bind, self.advertised)
+    }
+}
+
+/// API binds of `servers` guardians from `FM_GUARDIAN_API_BIND`, by peer id.
+/// Guardians with an empty entry keep listening on localhost and are left
+/// out.
+pub fn guardian_api_binds(servers: usize) -> Result<BTreeMap<usize, ApiBind>> {
+    guardian_list_from_env(FM_GUARDIAN_API_BIND_ENV, servers, str::parse)
+}
+
+/// Binds the API of each guardian in `binds` to its address and advertises
+/// it to the other guardians and clients. Errors if a guardian couldn't
+/// listen there, before any guardian is launched.
+pub fn rewrite_config_gen_params(
+    params: &mut HashMap<PeerId, ConfigGenParams>,
+    binds: &BTreeMap<usize, ApiBind>,
+) -> Result<()> {
+    for peer_params in params.values_mut() {
+        let peer_id = peer_params.local.our_id.to_usize();
+        if let Some(bind) = binds.get(&peer_id) {
+            peer_params.local.api_bind.set_ip(bind.bind);
+            ensure_bindable(peer_params.local.api_bind)
+                .with_context(|| format!("guardian {peer_id} can't bind its api to {bind}"))?;
+        }
+        for (peer, peer_server_params) in &mut peer_params.consensus.peers {
+            if let Some(bind) = binds.get(&peer.to_usize()) {
+                peer_server_params.api_url = bind.advertised_url(&peer_server_params.api_url)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn ensure_bindable(addr: SocketAddr) -> Result<()> {
+    TcpListener::bind(addr).with_context(|| format!("binding {addr}"))?;
+    Ok(())
+}
+
+#[test]
+fn test_parse_api_bind() -> Result<()> {
+    assert_eq!(
+        "0.0.0.0@192.168.1.5".parse::<ApiBind>()?,
+        ApiBind::new([0, 0, 0, 0].into(), "192.168.1.5")
+    );
+    assert_eq!(
+        "10.0.0.2".parse::<ApiBind>()?,
+        ApiBind::new([10, 0, 0, 2].into(), "10.0.0.2")
+    );
+    assert!("0.0.0.0".parse::<ApiBind>().is_err());
+    assert!("guardian-0".parse::<ApiBind>().is_err());
+
+    let bind = ApiBind::new([0, 0, 0, 0].into(), "::1");
+    assert_eq!(
+        bind.advertised_url(&SafeUrl::parse("ws://127.0.0.1:18174")?)?
+            .to_string(),
+        "ws://[::1]:18174/"
+    );
+    Ok(())
+}
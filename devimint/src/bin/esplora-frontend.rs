@@ -0,0 +1,42 @@
+//! Web frontend for browsing devimint's regtest chain, which the browser
+//! loads from esplora's HTTP API, see `devimint::external::Esplora`
+//!
+//! Reads `API_URL` and `PORT` like Blockstream's esplora frontend, so that
+//! can be used in its place with `FM_ESPLORA_FRONTEND_BASE_EXECUTABLE`.
+
+use anyhow::Context;
+use axum::response::Html;
+use axum::routing::get;
+use axum::Router;
+use clap::Parser;
+use fedimint_logging::TracingSetup;
+use tokio::net::TcpListener;
+use tracing::info;
+
+/// Explorer page, with `__API_URL__` standing in for esplora's url
+const INDEX_HTML: &str = include_str!("esplora-frontend.html");
+
+#[derive(clap::Parser)]
+struct Cmd {
+    /// Url of esplora's HTTP API, which the browser calls
+    #[clap(long, env = "API_URL")]
+    api_url: String,
+    #[clap(long, env = "PORT")]
+    port: u16,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    TracingSetup::default().init()?;
+    let cmd = Cmd::parse();
+    let index = INDEX_HTML.replace("__API_URL__", &serde_json::to_string(&cmd.api_url)?);
+    let router = Router::new().route("/", get(|| async move { Html(index) }));
+
+    let bind_addr = format!("127.0.0.1:{}", cmd.port);
+    let listener = TcpListener::bind(&bind_addr)
+        .await
+        .with_context(|| format!("couldn't bind {bind_addr}"))?;
+    info!(%bind_addr, api_url = %cmd.api_url, "Serving esplora frontend");
+    axum::serve(listener, router.into_make_service()).await?;
+    Ok(())
+}
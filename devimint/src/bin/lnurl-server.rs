@@ -0,0 +1,210 @@
+//! LNURL-pay server for testing LNURL and lightning address payments
+//! without external services, see `devimint::lnurl`
+
+use std::collections::HashMap;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{ensure, Context};
+use axum::extract::{Path, Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::Parser;
+use cln_rpc::primitives::{Amount as ClnAmount, AmountOrAny};
+use cln_rpc::ClnRpc;
+use devimint::envs::{FM_CLN_SOCKET_ENV, FM_LNURL_SERVER_BIND_ADDR_ENV, FM_LNURL_SERVER_CERT_ENV};
+use devimint::lnurl::{metadata, MAX_SENDABLE_MSAT, MIN_SENDABLE_MSAT, TLS_DOMAIN};
+use fedimint_logging::TracingSetup;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use serde_json::json;
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+use tokio_rustls::{rustls, TlsAcceptor};
+use tracing::{debug, info, warn};
+
+/// Longest `CONNECT` request head read from https clients
+const MAX_CONNECT_REQUEST_LEN: usize = 8 * 1024;
+
+#[derive(clap::Parser)]
+struct Cmd {
+    #[clap(long, env = FM_LNURL_SERVER_BIND_ADDR_ENV)]
+    bind_addr: String,
+    #[clap(long, env = FM_CLN_SOCKET_ENV)]
+    cln_socket: String,
+    /// Where to write the self-signed certificate https is served with
+    #[clap(long, env = FM_LNURL_SERVER_CERT_ENV)]
+    cert_file: PathBuf,
+}
+
+/// Issues the invoices of the pay endpoints under `base_url`
+#[derive(Clone)]
+struct InvoiceIssuer {
+    base_url: String,
+    ln_rpc: Arc<Mutex<ClnRpc>>,
+}
+
+impl InvoiceIssuer {
+    async fn generate_invoice(
+        &self,
+        name: &str,
+        params: &HashMap<String, String>,
+    ) -> anyhow::Result<String> {
+        let amount_msat: u64 = params
+            .get("amount")
+            .context("missing amount")?
+            .parse()
+            .context("invalid amount")?;
+        ensure!(
+            (MIN_SENDABLE_MSAT..=MAX_SENDABLE_MSAT).contains(&amount_msat),
+            "amount {amount_msat} msat out of range"
+        );
+        let invoice = self
+            .ln_rpc
+            .lock()
+            .await
+            .call_typed(&cln_rpc::model::requests::InvoiceRequest {
+                amount_msat: AmountOrAny::Amount(ClnAmount::from_msat(amount_msat)),
+                // the invoice commits to the hash of the metadata, as LNURL-pay requires
+                description: metadata(name),
+                deschashonly: Some(true),
+                label: format!("lnurl-{name}-{}", rand::random::<u64>()),
+                expiry: None,
+                fallbacks: None,
+                preimage: None,
+                cltv: None,
+                exposeprivatechannels: None,
+            })
+            .await?
+            .bolt11;
+        info!(%name, amount_msat, %invoice, "Issued invoice");
+        Ok(invoice)
+    }
+
+    fn router(self) -> Router {
+        Router::new()
+            .route(
+                "/.well-known/lnurlp/:name",
+                get(
+                    |State(issuer): State<InvoiceIssuer>, Path(name): Path<String>| async move {
+                        Json(json!({
+                            "tag": "payRequest",
+                            "callback": format!("{}/.well-known/lnurlp/{name}/callback", issuer.base_url),
+                            "minSendable": MIN_SENDABLE_MSAT,
+                            "maxSendable": MAX_SENDABLE_MSAT,
+                            "metadata": metadata(&name),
+                        }))
+                    },
+                ),
+            )
+            .route(
+                "/.well-known/lnurlp/:name/callback",
+                get(
+                    |State(issuer): State<InvoiceIssuer>,
+                     Path(name): Path<String>,
+                     Query(params): Query<HashMap<String, String>>| async move {
+                        match issuer.generate_invoice(&name, &params).await {
+                            Ok(invoice) => Json(json!({ "pr": invoice, "routes": [] })),
+                            Err(err) => {
+                                warn!(%name, %err, "Couldn't issue invoice");
+                                Json(json!({ "status": "ERROR", "reason": format!("{err:#}") }))
+                            }
+                        }
+                    },
+                ),
+            )
+            .with_state(self)
+    }
+}
+
+/// Generates a self-signed certificate for [`TLS_DOMAIN`], writing it to
+/// `cert_file` for clients to trust
+fn tls_acceptor(cert_file: &FsPath) -> anyhow::Result<TlsAcceptor> {
+    let keypair = rcgen::KeyPair::generate()?;
+    let cert = rcgen::CertificateParams::new(vec![TLS_DOMAIN.to_owned()])?.self_signed(&keypair)?;
+    std::fs::write(cert_file, cert.pem())
+        .with_context(|| format!("writing certificate to {}", cert_file.display()))?;
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(cert.der().to_vec())],
+            rustls::PrivateKey(keypair.serialize_der()),
+        )?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serves `stream` plain http with `http`, unless it opens with a `CONNECT`
+/// request, which https clients using the server as their proxy send. Those
+/// get https with `https` in place of the host they asked for.
+async fn serve_connection(
+    mut stream: TcpStream,
+    http: Router,
+    https: Router,
+    tls: TlsAcceptor,
+) -> anyhow::Result<()> {
+    const CONNECT: &[u8] = b"CONNECT ";
+    let mut method = [0; CONNECT.len()];
+    let peeked = stream.peek(&mut method).await?;
+    if &method[..peeked] != CONNECT {
+        return serve_http(stream, http).await;
+    }
+
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        ensure!(
+            request.len() < MAX_CONNECT_REQUEST_LEN,
+            "CONNECT request too long"
+        );
+        request.push(stream.read_u8().await?);
+    }
+    debug!(request = %String::from_utf8_lossy(&request).trim_end(), "Tunneling https");
+    stream
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await?;
+    serve_http(tls.accept(stream).await?, https).await
+}
+
+async fn serve_http<S>(stream: S, router: Router) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), TowerToHyperService::new(router))
+        .await
+        .context("serving http")
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    TracingSetup::default().init()?;
+    let cmd = Cmd::parse();
+    let ln_rpc = Arc::new(Mutex::new(
+        ClnRpc::new(&cmd.cln_socket)
+            .await
+            .with_context(|| format!("couldn't open CLN socket {}", &cmd.cln_socket))?,
+    ));
+    let http = InvoiceIssuer {
+        base_url: format!("http://{}", cmd.bind_addr),
+        ln_rpc: ln_rpc.clone(),
+    }
+    .router();
+    let https = InvoiceIssuer {
+        base_url: format!("https://{TLS_DOMAIN}"),
+        ln_rpc,
+    }
+    .router();
+    let tls = tls_acceptor(&cmd.cert_file)?;
+
+    let listener = TcpListener::bind(&cmd.bind_addr).await?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let (http, https, tls) = (http.clone(), https.clone(), tls.clone());
+        tokio::spawn(async move {
+            if let Err(err) = serve_connection(stream, http, https, tls).await {
+                debug!(%peer, err = %format!("{err:#}"), "Connection failed");
+            }
+        });
+    }
+}
@@ -0,0 +1,57 @@
+//! Pointing guardians at different bitcoin backends.
+//!
+//! By default the wallet module of every guardian talks to devimint's
+//! bitcoind. Giving guardians other backends, e.g. esplora or a bitcoind
+//! that lags behind, tests how consensus copes with backends that disagree.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use fedimint_core::envs::BitcoinRpcConfig;
+
+use crate::envs::FM_GUARDIAN_BITCOIN_RPC_ENV;
+use crate::util::guardian_list_from_env;
+use crate::vars::Global;
+
+/// devimint's bitcoind
+pub fn bitcoind(globals: &Global) -> Result<BitcoinRpcConfig> {
+    Ok(BitcoinRpcConfig {
+        kind: "bitcoind".to_owned(),
+        url: globals.FM_BITCOIN_RPC_URL.parse()?,
+    })
+}
+
+/// devimint's esplora, which indexes devimint's bitcoind
+pub fn esplora(globals: &Global) -> Result<BitcoinRpcConfig> {
+    Ok(BitcoinRpcConfig {
+        kind: "esplora".to_owned(),
+        url: format!("http://127.0.0.1:{}", globals.FM_PORT_ESPLORA).parse()?,
+    })
+}
+
+/// Parses `bitcoind`, `esplora` or `KIND=URL` for any other backend
+pub fn parse(globals: &Global, backend: &str) -> Result<BitcoinRpcConfig> {
+    match backend.split_once('=') {
+        None if backend == "bitcoind" => bitcoind(globals),
+        None if backend == "esplora" => esplora(globals),
+        None => bail!("unknown bitcoin backend {backend}, expected bitcoind, esplora or KIND=URL"),
+        Some((kind, url)) => Ok(BitcoinRpcConfig {
+            kind: kind.to_owned(),
+            url: url
+                .parse()
+                .with_context(|| format!("invalid url of bitcoin backend {kind}"))?,
+        }),
+    }
+}
+
+/// Bitcoin backends of `servers` guardians from `FM_GUARDIAN_BITCOIN_RPC`, by
+/// peer id. Guardians with an empty entry use devimint's bitcoind and are
+/// left out.
+pub fn guardian_backends(
+    globals: &Global,
+    servers: usize,
+) -> Result<BTreeMap<usize, BitcoinRpcConfig>> {
+    guardian_list_from_env(FM_GUARDIAN_BITCOIN_RPC_ENV, servers, |backend| {
+        parse(globals, backend)
+    })
+}
@@ -0,0 +1,161 @@
+//! Recording the bitcoind RPC calls devimint makes.
+//!
+//! With [`FM_TRACE_BITCOIND_RPC_ENV`] set to a file path, every call made
+//! through a [`BitcoindRpc`] is appended to that file as one JSON line with
+//! its method, params, duration and result or error, to see what devimint
+//! asked bitcoind for when a test misbehaves. Nothing is redacted, the chain
+//! only ever is regtest or a test network.
+
+use std::fs::File;
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Context as _;
+use bitcoincore_rpc::RpcApi;
+use fedimint_logging::LOG_DEVIMINT;
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::envs::FM_TRACE_BITCOIND_RPC_ENV;
+
+/// bitcoind RPC client recording its calls to the trace file, if one is
+/// configured
+pub struct BitcoindRpc {
+    inner: bitcoincore_rpc::Client,
+    trace: Option<Arc<Mutex<File>>>,
+}
+
+impl BitcoindRpc {
+    /// Wraps `inner`, tracing to the file of [`FM_TRACE_BITCOIND_RPC_ENV`]
+    /// if set
+    pub fn new(inner: bitcoincore_rpc::Client) -> anyhow::Result<Self> {
+        let trace = match std::env::var_os(FM_TRACE_BITCOIND_RPC_ENV) {
+            Some(path) if !path.is_empty() => Some(open_trace(Path::new(&path))?),
+            _ => None,
+        };
+        Ok(Self { inner, trace })
+    }
+
+    pub fn get_jsonrpc_client(&self) -> &bitcoincore_rpc::jsonrpc::Client {
+        self.inner.get_jsonrpc_client()
+    }
+
+    fn record(&self, line: &Value) {
+        let Some(trace) = &self.trace else {
+            return;
+        };
+        let mut file = trace.lock().expect("locking poisoned");
+        // one write per line, so lines from concurrent clients don't interleave
+        if let Err(err) = writeln!(file, "{line}") {
+            warn!(target: LOG_DEVIMINT, %err, "Failed to write bitcoind rpc trace");
+        }
+    }
+}
+
+impl RpcApi for BitcoindRpc {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Value],
+    ) -> bitcoincore_rpc::Result<T> {
+        if self.trace.is_none() {
+            return self.inner.call(cmd, args);
+        }
+        let time = SystemTime::now();
+        let start = Instant::now();
+        let res = self.inner.call::<Value>(cmd, args);
+        self.record(&trace_line(
+            time,
+            cmd,
+            args,
+            start.elapsed(),
+            res.as_ref().map_err(ToString::to_string),
+        ));
+        serde_json::from_value(res?).map_err(bitcoincore_rpc::Error::Json)
+    }
+}
+
+/// Opens the trace at `path` for appending, shared by every client tracing
+/// to it, so the clients of several `Bitcoind`s end up in one file
+fn open_trace(path: &Path) -> anyhow::Result<Arc<Mutex<File>>> {
+    static TRACES: Mutex<Vec<(std::path::PathBuf, Arc<Mutex<File>>)>> = Mutex::new(Vec::new());
+
+    let mut traces = TRACES.lock().expect("locking poisoned");
+    if let Some((_, trace)) = traces.iter().find(|(traced, _)| traced == path) {
+        return Ok(trace.clone());
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| {
+            format!(
+                "opening {FM_TRACE_BITCOIND_RPC_ENV} file {}",
+                path.display()
+            )
+        })?;
+    let trace = Arc::new(Mutex::new(file));
+    traces.push((path.to_owned(), trace.clone()));
+    Ok(trace)
+}
+
+/// JSON line recording a call of `method` that started at `time`
+fn trace_line(
+    time: SystemTime,
+    method: &str,
+    params: &[Value],
+    duration: Duration,
+    res: Result<&Value, String>,
+) -> Value {
+    let time_ms = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let mut line = json!({
+        "time_ms": time_ms,
+        "method": method,
+        "params": params,
+        "duration_ms": duration.as_micros() as f64 / 1000.0,
+    });
+    match res {
+        Ok(result) => line["result"] = result.clone(),
+        Err(error) => line["error"] = error.into(),
+    }
+    line
+}
+
+#[test]
+fn test_trace_line() {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+    let line = trace_line(
+        time,
+        "getblockhash",
+        &[json!(101)],
+        Duration::from_micros(1_500),
+        Ok(&json!("00ff")),
+    );
+    let parsed: Value = serde_json::from_str(&line.to_string()).unwrap();
+    assert_eq!(
+        parsed,
+        json!({
+            "time_ms": 1_700_000_000_123_u64,
+            "method": "getblockhash",
+            "params": [101],
+            "duration_ms": 1.5,
+            "result": "00ff",
+        })
+    );
+    assert!(!line.to_string().contains('\n'));
+
+    let line = trace_line(
+        time,
+        "getblock",
+        &[],
+        Duration::ZERO,
+        Err("JSON-RPC error".to_owned()),
+    );
+    assert_eq!(line["error"], "JSON-RPC error");
+    assert!(line.get("result").is_none());
+}
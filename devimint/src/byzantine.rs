@@ -0,0 +1,300 @@
+//! Making a guardian's API misbehave.
+//!
+//! A guardian can get a [`CorruptingProxy`] in front of its API, which its
+//! advertised API url points at. Responses pass through untouched until the
+//! proxy is told to corrupt them, to test clients tolerate a byzantine
+//! guardian by relying on the others.
+//!
+//! The API speaks JSON-RPC over websockets, so the proxy passes the upgrade
+//! handshake and everything the client sends through as is, and only
+//! rewrites the text frames of the guardian's responses.
+
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{ensure, Result};
+use fedimint_logging::LOG_DEVIMINT;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::info;
+
+use crate::envs::FM_GUARDIAN_RESPONSE_PROXY_ENV;
+use crate::proxy::{Passthrough, TcpProxy, Transform};
+use crate::util::guardian_ids_from_env;
+
+/// Largest websocket frame the proxy reads, far above any API response
+const MAX_FRAME_LEN: u64 = 64 * 1024 * 1024;
+
+/// Marker put into corrupted responses
+const CORRUPTED: &str = "corrupted by devimint";
+
+/// How a [`CorruptingProxy`] corrupts the guardian's responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptMode {
+    /// Replaces each response with text that isn't JSON
+    Garbage,
+    /// Keeps the JSON-RPC envelope, but replaces each result with a value of
+    /// the wrong type
+    InvalidResult,
+    /// Answers each request with a JSON-RPC error
+    RpcError,
+}
+
+/// Peer ids of the guardians of a federation of `servers` that get a
+/// [`CorruptingProxy`] in front of their API, from
+/// `FM_GUARDIAN_RESPONSE_PROXY`
+pub fn guardian_response_proxies(servers: usize) -> Result<BTreeSet<usize>> {
+    guardian_ids_from_env(FM_GUARDIAN_RESPONSE_PROXY_ENV, servers)
+}
+
+/// TCP proxy on `addr` to a guardian's API on `target`, corrupting the
+/// guardian's responses once [`Self::set_mode`] says how.
+///
+/// Stops accepting and drops all connections once the last clone is dropped.
+#[derive(Clone)]
+pub struct CorruptingProxy {
+    proxy: TcpProxy,
+    mode: Arc<Mutex<Option<CorruptMode>>>,
+    corrupted: Arc<AtomicU64>,
+}
+
+/// [`Transform`] corrupting the guardian's responses
+struct Corrupt {
+    mode: Arc<Mutex<Option<CorruptMode>>>,
+    corrupted: Arc<AtomicU64>,
+}
+
+impl Transform for Corrupt {
+    fn forward<'a>(
+        &'a self,
+        mut reader: &'a mut (dyn AsyncBufRead + Unpin + Send),
+        mut writer: &'a mut (dyn AsyncWrite + Unpin + Send),
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            forward_responses(&mut reader, &mut writer, &self.mode, &self.corrupted).await
+        })
+    }
+}
+
+impl CorruptingProxy {
+    /// Starts proxying from `addr`, where clients expect the guardian, to
+    /// `target`, where it actually listens
+    pub async fn start(addr: SocketAddr, target: SocketAddr) -> Result<Self> {
+        let mode = Arc::new(Mutex::new(None));
+        let corrupted = Arc::new(AtomicU64::new(0));
+        let proxy = TcpProxy::start(
+            "corrupting proxy",
+            addr,
+            target,
+            Passthrough,
+            Corrupt {
+                mode: mode.clone(),
+                corrupted: corrupted.clone(),
+            },
+        )
+        .await?;
+        Ok(Self {
+            proxy,
+            mode,
+            corrupted,
+        })
+    }
+
+    /// Address clients connect to instead of the target
+    pub fn addr(&self) -> SocketAddr {
+        self.proxy.addr()
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.proxy.target()
+    }
+
+    /// Corrupts the responses of all connections in `mode` from now on, or
+    /// passes them through untouched again with `None`
+    pub fn set_mode(&self, mode: Option<CorruptMode>) {
+        info!(target: LOG_DEVIMINT, addr = %self.addr(), ?mode, "Setting corrupting proxy mode");
+        *self.mode.lock().expect("locking poisoned") = mode;
+    }
+
+    /// Total responses corrupted so far
+    pub fn responses_corrupted(&self) -> u64 {
+        self.corrupted.load(Ordering::Relaxed)
+    }
+}
+
+/// Copies the guardian's responses from `reader` to `writer`, corrupting the
+/// text frames once upgraded to a websocket
+async fn forward_responses(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+    mode: &Mutex<Option<CorruptMode>>,
+    corrupted: &AtomicU64,
+) -> Result<()> {
+    // the http response to the upgrade request goes through as is
+    let mut upgraded = None;
+    loop {
+        let mut line = vec![];
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(());
+        }
+        writer.write_all(&line).await?;
+        upgraded.get_or_insert_with(|| line.starts_with(b"HTTP/1.1 101"));
+        if line == b"\r\n" {
+            break;
+        }
+    }
+    if upgraded != Some(true) {
+        tokio::io::copy_buf(reader, writer).await?;
+        return Ok(());
+    }
+
+    while let Some(mut frame) = Frame::read(reader).await? {
+        let current = *mode.lock().expect("locking poisoned");
+        if let Some(current) = current.filter(|_| frame.is_complete_text()) {
+            if let Some(payload) = corrupt(current, &frame.payload) {
+                frame.payload = payload;
+                corrupted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        writer.write_all(&frame.encode()).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
+
+/// A websocket frame as the guardian sent it
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    /// First byte of the frame, with the FIN and RSV bits and the opcode
+    head: u8,
+    mask: Option<[u8; 4]>,
+    payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Reads the next frame, `None` once `reader` is exhausted
+    async fn read(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<Self>> {
+        if reader.fill_buf().await?.is_empty() {
+            return Ok(None);
+        }
+        let head = reader.read_u8().await?;
+        let len_byte = reader.read_u8().await?;
+        let len = match len_byte & 0x7f {
+            126 => u64::from(reader.read_u16().await?),
+            127 => reader.read_u64().await?,
+            len => u64::from(len),
+        };
+        ensure!(len <= MAX_FRAME_LEN, "websocket frame of {len} bytes");
+        let mask = if len_byte & 0x80 == 0 {
+            None
+        } else {
+            let mut mask = [0; 4];
+            reader.read_exact(&mut mask).await?;
+            Some(mask)
+        };
+        let mut payload = vec![0; len as usize];
+        reader.read_exact(&mut payload).await?;
+        Ok(Some(Self {
+            head,
+            mask,
+            payload,
+        }))
+    }
+
+    /// Whether this is an unmasked and uncompressed text frame holding a
+    /// whole message, the only kind the proxy corrupts
+    fn is_complete_text(&self) -> bool {
+        // FIN set, no RSV bits for extensions, text opcode
+        self.head == 0x81 && self.mask.is_none()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.head];
+        let mask_bit = if self.mask.is_some() { 0x80 } else { 0 };
+        let len = self.payload.len();
+        if len < 126 {
+            bytes.push(mask_bit | len as u8);
+        } else if let Ok(len) = u16::try_from(len) {
+            bytes.push(mask_bit | 126);
+            bytes.extend_from_slice(&len.to_be_bytes());
+        } else {
+            bytes.push(mask_bit | 127);
+            bytes.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+        if let Some(mask) = self.mask {
+            bytes.extend_from_slice(&mask);
+        }
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+}
+
+/// `payload` of a response corrupted in `mode`, `None` if there is nothing to
+/// corrupt, like in notifications without a request id
+fn corrupt(mode: CorruptMode, payload: &[u8]) -> Option<Vec<u8>> {
+    if mode == CorruptMode::Garbage {
+        return Some(format!("}}{CORRUPTED}{{").into_bytes());
+    }
+    let mut response: Value = serde_json::from_slice(payload).ok()?;
+    let response = response.as_object_mut()?;
+    let id = response.get("id")?.clone();
+    let corrupted = match mode {
+        CorruptMode::Garbage => unreachable!("handled above"),
+        CorruptMode::InvalidResult => {
+            let result = response.get_mut("result")?;
+            *result = CORRUPTED.into();
+            Value::Object(response.clone())
+        }
+        CorruptMode::RpcError => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": -32603, "message": CORRUPTED},
+        }),
+    };
+    Some(corrupted.to_string().into_bytes())
+}
+
+#[tokio::test]
+async fn test_frame_roundtrip() -> Result<()> {
+    for (head, mask, len) in [
+        (0x81, None, 5),
+        (0x81, None, 300),
+        (0x82, Some([1, 2, 3, 4]), 70_000),
+    ] {
+        let frame = Frame {
+            head,
+            mask,
+            payload: vec![7; len],
+        };
+        let encoded = frame.encode();
+        let mut reader = tokio::io::BufReader::new(encoded.as_slice());
+        assert_eq!(Frame::read(&mut reader).await?, Some(frame));
+        assert_eq!(Frame::read(&mut reader).await?, None);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_corrupt() {
+    let response = br#"{"jsonrpc":"2.0","result":{"count":3},"id":7}"#;
+    assert!(
+        serde_json::from_slice::<Value>(&corrupt(CorruptMode::Garbage, response).unwrap()).is_err()
+    );
+    let invalid: Value =
+        serde_json::from_slice(&corrupt(CorruptMode::InvalidResult, response).unwrap()).unwrap();
+    assert_eq!(
+        invalid,
+        json!({"jsonrpc": "2.0", "result": CORRUPTED, "id": 7})
+    );
+    let error: Value =
+        serde_json::from_slice(&corrupt(CorruptMode::RpcError, response).unwrap()).unwrap();
+    assert_eq!(error["id"], 7);
+    assert_eq!(error["error"]["message"], CORRUPTED);
+
+    let notification = br#"{"jsonrpc":"2.0","method":"subscription","params":{}}"#;
+    assert_eq!(corrupt(CorruptMode::InvalidResult, notification), None);
+    assert_eq!(corrupt(CorruptMode::RpcError, notification), None);
+}
@@ -0,0 +1,317 @@
+//! Running daemons under linux cgroups with CPU and memory limits.
+//!
+//! [`crate::util::ProcessManager::with_resource_limits`] puts each daemon it
+//! matches into a cgroup v2 of its own below devimint's, to test how daemons
+//! behave with constrained resources and to keep a runaway one from taking
+//! down a CI runner. Controllers can only be handed down from a cgroup that
+//! has no processes of its own, so before creating the first daemon cgroup
+//! devimint moves itself into a leaf cgroup next to them. That only works
+//! where devimint is the only process of its cgroup, like in a container with
+//! a private cgroup namespace or a systemd scope of its own. Elsewhere
+//! spawning daemons with limits fails.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::{bail, ensure, Context, Result};
+use fedimint_logging::LOG_DEVIMINT;
+use tracing::debug;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Period of `cpu.max`, the kernel's default
+const CPU_PERIOD_USECS: u64 = 100_000;
+
+/// Limits of the cgroup a daemon runs in, see
+/// [`crate::util::ProcessManager::with_resource_limits`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// Memory the daemon may use without swapping, beyond which the kernel
+    /// OOM kills it
+    pub memory_max_bytes: Option<u64>,
+    /// CPU time the daemon may use, in percent of a single CPU
+    pub cpu_max_percent: Option<u32>,
+}
+
+impl ResourceLimits {
+    /// `cpu.max` enforcing [`Self::cpu_max_percent`]
+    fn cpu_max(&self) -> Option<String> {
+        self.cpu_max_percent.map(|percent| {
+            format!(
+                "{} {CPU_PERIOD_USECS}",
+                u64::from(percent) * CPU_PERIOD_USECS / 100
+            )
+        })
+    }
+}
+
+/// Parses `<daemon>:<limits>` pairs separated by `;`, with limits like
+/// `memory=512M,cpu=50`, see
+/// [`crate::envs::FM_DAEMON_RESOURCE_LIMITS_ENV`]
+pub fn parse_resource_limits(s: &str) -> Result<BTreeMap<String, ResourceLimits>> {
+    s.split(';')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (daemon, limits) = entry
+                .split_once(':')
+                .with_context(|| format!("Invalid resource limits: {entry}"))?;
+            let mut parsed = ResourceLimits::default();
+            for limit in limits.split(',') {
+                match limit.trim().split_once('=') {
+                    Some(("memory", bytes)) => {
+                        parsed.memory_max_bytes = Some(parse_bytes(bytes)?);
+                    }
+                    Some(("cpu", percent)) => {
+                        let percent = percent
+                            .parse()
+                            .with_context(|| format!("Invalid cpu percentage: {percent}"))?;
+                        ensure!(0 < percent, "cpu limit of {daemon} must be positive");
+                        parsed.cpu_max_percent = Some(percent);
+                    }
+                    _ => bail!("Invalid resource limit of {daemon}: {limit}"),
+                }
+            }
+            Ok((daemon.trim().to_owned(), parsed))
+        })
+        .collect()
+}
+
+/// Bytes with an optional `K`, `M` or `G` suffix for KiB, MiB or GiB
+fn parse_bytes(s: &str) -> Result<u64> {
+    let (digits, unit) = match s.chars().last() {
+        Some('K') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    let value: u64 = digits
+        .parse()
+        .with_context(|| format!("Invalid memory size: {s}"))?;
+    value
+        .checked_mul(unit)
+        .with_context(|| format!("Memory size too large: {s}"))
+}
+
+/// The cgroup a single daemon runs in, along with whatever it forks. Removed
+/// again when dropped, once its processes exited.
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Creates the cgroup of the daemon `name` below devimint's own, with
+    /// `limits`
+    pub fn create(name: &str, limits: ResourceLimits) -> Result<Self> {
+        ensure!(
+            cfg!(target_os = "linux"),
+            "cgroups are only available on linux"
+        );
+        let parent = delegating_cgroup()?;
+        let controllers: Vec<&str> = [
+            limits.memory_max_bytes.map(|_| "+memory"),
+            limits.cpu_max_percent.map(|_| "+cpu"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        std::fs::write(parent.join("cgroup.subtree_control"), controllers.join(" ")).with_context(
+            || {
+                format!(
+                    "enabling {} for cgroups below {}",
+                    controllers.join(" "),
+                    parent.display()
+                )
+            },
+        )?;
+        let path = parent.join(format!("devimint-{}-{name}", std::process::id()));
+        // left behind by a daemon of the same name whose processes hadn't
+        // exited yet when it was dropped
+        if let Err(err) = std::fs::create_dir(&path) {
+            ensure!(
+                err.kind() == std::io::ErrorKind::AlreadyExists,
+                "creating cgroup {}: {err}",
+                path.display()
+            );
+        }
+        let cgroup = Self { path };
+        if let Some(bytes) = limits.memory_max_bytes {
+            cgroup.set_memory_max(bytes)?;
+            // otherwise the limit just makes it swap, where swap is accounted
+            if let Err(err) = cgroup.write("memory.swap.max", "0") {
+                debug!(target: LOG_DEVIMINT, %name, err = %format!("{err:#}"), "Failed to disable swap of cgroup");
+            }
+        }
+        if let Some(cpu_max) = limits.cpu_max() {
+            cgroup.write("cpu.max", &cpu_max)?;
+        }
+        Ok(cgroup)
+    }
+
+    /// Moves the process `pid` into the cgroup, with the children it forks
+    /// from now on
+    pub fn add(&self, pid: u32) -> Result<()> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    /// Changes the memory limit, which the kernel enforces right away by
+    /// reclaiming memory and OOM killing if that's not enough
+    pub fn set_memory_max(&self, bytes: u64) -> Result<()> {
+        self.write("memory.max", &bytes.to_string())
+    }
+
+    /// Memory used by the processes in the cgroup, in bytes
+    pub fn memory_current(&self) -> Result<u64> {
+        let current = self.read("memory.current")?;
+        current
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid memory.current of {}", self.path.display()))
+    }
+
+    /// How many processes in the cgroup the kernel OOM killed
+    pub fn oom_kills(&self) -> Result<u64> {
+        parse_oom_kills(&self.read("memory.events")?)
+            .with_context(|| format!("No oom_kill in memory.events of {}", self.path.display()))
+    }
+
+    fn read(&self, file: &str) -> Result<String> {
+        let path = self.path.join(file);
+        std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        let path = self.path.join(file);
+        std::fs::write(&path, value)
+            .with_context(|| format!("writing {value} to {}", path.display()))
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // fails while some process is still in it, which leaves an empty
+        // cgroup behind at worst
+        if let Err(err) = std::fs::remove_dir(&self.path) {
+            debug!(target: LOG_DEVIMINT, path = %self.path.display(), %err, "Failed to remove cgroup");
+        }
+    }
+}
+
+/// Errors unless daemons can be run in cgroups of their own, see
+/// [`crate::cgroup`], moving devimint into its leaf cgroup if it wasn't yet
+pub fn ensure_available() -> Result<()> {
+    ensure!(
+        cfg!(target_os = "linux"),
+        "cgroups are only available on linux"
+    );
+    delegating_cgroup().map(drop)
+}
+
+/// The cgroup devimint started in, which daemon cgroups get created below.
+///
+/// The first call moves devimint into the leaf `devimint-<pid>` below it, so
+/// it has no processes of its own and may hand down controllers. Fails if
+/// other processes are left in it, whose cgroup devimint can't take over.
+fn delegating_cgroup() -> Result<PathBuf> {
+    /// Only tried once, as afterwards [`own_cgroup`] may be the leaf
+    static DELEGATING: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+    DELEGATING
+        .get_or_init(|| delegate().map_err(|err| format!("{err:#}")))
+        .clone()
+        .map_err(anyhow::Error::msg)
+}
+
+fn delegate() -> Result<PathBuf> {
+    let parent = own_cgroup()?;
+    let leaf = parent.join(format!("devimint-{}", std::process::id()));
+    if let Err(err) = std::fs::create_dir(&leaf) {
+        ensure!(
+            err.kind() == std::io::ErrorKind::AlreadyExists,
+            "creating devimint's cgroup {}: {err}",
+            leaf.display()
+        );
+    }
+    std::fs::write(leaf.join("cgroup.procs"), std::process::id().to_string())
+        .with_context(|| format!("moving devimint into its cgroup {}", leaf.display()))?;
+    let others = std::fs::read_to_string(parent.join("cgroup.procs"))
+        .with_context(|| format!("reading processes of {}", parent.display()))?;
+    let others: Vec<&str> = others.split_whitespace().collect();
+    ensure!(
+        others.is_empty(),
+        "{} has processes besides devimint, pids {}, so it can't hand down controllers \
+         to daemons; run devimint in a cgroup of its own",
+        parent.display(),
+        others.join(", ")
+    );
+    debug!(target: LOG_DEVIMINT, cgroup = %leaf.display(), "Moved devimint into its own cgroup");
+    Ok(parent)
+}
+
+/// Path of the cgroup v2 devimint runs in
+fn own_cgroup() -> Result<PathBuf> {
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").context("reading own cgroup")?;
+    let own = parse_own_cgroup(&cgroups).context("devimint runs in no cgroup v2")?;
+    Ok(Path::new(CGROUP_ROOT).join(own.trim_start_matches('/')))
+}
+
+/// The cgroup v2 of a `/proc/<pid>/cgroup`, which has hierarchy id 0
+fn parse_own_cgroup(cgroups: &str) -> Option<&str> {
+    cgroups.lines().find_map(|line| line.strip_prefix("0::"))
+}
+
+fn parse_oom_kills(events: &str) -> Option<u64> {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[test]
+fn test_parse_resource_limits() -> Result<()> {
+    assert_eq!(parse_resource_limits("")?, BTreeMap::new());
+    assert_eq!(
+        parse_resource_limits("fedimintd:memory=512M,cpu=50; gatewayd-lnd:memory=1073741824;")?,
+        BTreeMap::from([
+            (
+                "fedimintd".to_owned(),
+                ResourceLimits {
+                    memory_max_bytes: Some(512 << 20),
+                    cpu_max_percent: Some(50),
+                }
+            ),
+            (
+                "gatewayd-lnd".to_owned(),
+                ResourceLimits {
+                    memory_max_bytes: Some(1 << 30),
+                    cpu_max_percent: None,
+                }
+            ),
+        ])
+    );
+    assert!(parse_resource_limits("fedimintd:memory=lots").is_err());
+    assert!(parse_resource_limits("fedimintd:swap=1G").is_err());
+    assert!(parse_resource_limits("fedimintd:cpu=0").is_err());
+    assert_eq!(
+        ResourceLimits {
+            memory_max_bytes: None,
+            cpu_max_percent: Some(250),
+        }
+        .cpu_max()
+        .as_deref(),
+        Some("250000 100000")
+    );
+
+    assert_eq!(
+        parse_own_cgroup("12:pids:/user.slice\n0::/user.slice/session-1.scope\n"),
+        Some("/user.slice/session-1.scope")
+    );
+    assert_eq!(
+        parse_oom_kills("low 0\nhigh 0\nmax 12\noom 2\noom_kill 1\noom_group_kill 0\n"),
+        Some(1)
+    );
+    Ok(())
+}
@@ -1,8 +1,9 @@
-use std::ffi;
 use std::fmt::Write;
 use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
+use std::{ffi, iter};
 
 use anyhow::{anyhow, ensure, Context, Result};
 use clap::{Parser, Subcommand};
@@ -16,15 +17,28 @@
 use tokio::time::Instant;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::devfed::DevJitFed;
+use crate::devfed::{DevFed, DevJitFed};
 use crate::envs::{
-    FM_FED_SIZE_ENV, FM_INVITE_CODE_ENV, FM_LINK_TEST_DIR_ENV, FM_OFFLINE_NODES_ENV,
-    FM_TEST_DIR_ENV,
+    FM_BLOCK_INTERVAL_ENV, FM_CLOSE_CHANNELS_ON_SHUTDOWN_ENV, FM_DAEMON_RESOURCE_LIMITS_ENV,
+    FM_DAEMON_RUST_LOG_ENV, FM_DEVIMINT_OTLP_ENDPOINT_ENV, FM_DEVIMINT_PROCESS_GROUP_ENV,
+    FM_DEVIMINT_RUN_ID_ENV, FM_DEVIMINT_SETUP_EVENTS_ENV, FM_ESPLORA_CORS_ENV,
+    FM_ESPLORA_FRONTEND_ENV, FM_FED_SIZE_ENV, FM_GUARDIAN_JSON_LOGS_ENV, FM_GUARDIAN_LABELS_ENV,
+    FM_INVITE_CODE_ENV, FM_LINK_TEST_DIR_ENV, FM_MAX_DAEMON_RESTARTS_ENV, FM_NUM_FEDS_ENV,
+    FM_OFFLINE_NODES_ENV, FM_TEST_DIR_ENV,
+};
+use crate::external::Bitcoind;
+use crate::federation::{Federation, Fedimintd};
+use crate::manifest::ManifestFormat;
+use crate::setup_events::JsonLinesObserver;
+use crate::util::{
+    env_exports, parse_rust_log_overrides, poll, read_process_list, run_env_prefix, ProcessManager,
+    ProcessStatus,
 };
-use crate::federation::Fedimintd;
-use crate::util::{poll, ProcessManager};
 use crate::vars::mkdir;
-use crate::{external_daemons, vars, ExternalDaemons};
+use crate::{
+    external_daemons, lightning_daemons, vars, ExternalDaemons, Gatewayd, LightningNode,
+    Lightningd, Lnd,
+};
 
 fn random_test_dir_suffix() -> String {
     rand::thread_rng()
@@ -58,6 +72,45 @@ pub struct CommonArgs {
     /// Run degraded federation with FM_OFFLINE_NODES shutdown
     #[clap(long, env = FM_OFFLINE_NODES_ENV, default_value = "0")]
     pub offline_nodes: usize,
+
+    /// Also export env vars prefixed with `FM_RUN_<RUN_ID>_`, to tell apart
+    /// devimints running concurrently on one machine
+    #[clap(long, env = FM_DEVIMINT_RUN_ID_ENV)]
+    pub run_id: Option<String>,
+
+    /// Restart daemons that crash up to this many times each instead of
+    /// leaving them dead, for long soak tests
+    #[clap(long, env = FM_MAX_DAEMON_RESTARTS_ENV)]
+    pub max_daemon_restarts: Option<u32>,
+
+    /// Spawn all daemons into one process group, so `kill -- -<pgid>` stops
+    /// them all even if devimint itself got killed
+    #[clap(long, env = FM_DEVIMINT_PROCESS_GROUP_ENV)]
+    pub process_group: bool,
+
+    /// Let browsers on these origins call esplora's HTTP API cross-origin,
+    /// any origin if passed without a value. Off by default.
+    #[clap(long, env = FM_ESPLORA_CORS_ENV, num_args = 0..=1, default_missing_value = "*")]
+    pub esplora_cors: Option<String>,
+
+    /// Also launch the esplora web frontend, to browse the regtest chain
+    #[clap(long, env = FM_ESPLORA_FRONTEND_ENV)]
+    pub esplora_frontend: bool,
+
+    /// Append a JSON line to this file as each component becomes ready during
+    /// setup, for harnesses waiting on them. Pass `/dev/fd/<n>` to get them
+    /// on an fd of their own, stdout is left to the user command.
+    #[clap(long, env = FM_DEVIMINT_SETUP_EVENTS_ENV)]
+    pub setup_events: Option<PathBuf>,
+
+    /// Have guardians log JSON lines, for tests to parse their logs
+    #[clap(long, env = FM_GUARDIAN_JSON_LOGS_ENV)]
+    pub guardian_json_logs: bool,
+
+    /// Label guardians like `alice,,bob`, by peer id. Guardians without a
+    /// label are `guardian-<id>`.
+    #[clap(long, env = FM_GUARDIAN_LABELS_ENV)]
+    pub guardian_labels: Option<String>,
 }
 
 impl CommonArgs {
@@ -95,11 +148,48 @@ pub enum Cmd {
         #[arg(long, trailing_var_arg = true, allow_hyphen_values = true, num_args=1..)]
         exec: Option<Vec<ffi::OsString>>,
     },
+    /// Spins up bitcoind, cln and lnd, and opens a channel between the two
+    /// lightning nodes, without electrs, esplora, any federation or gateway
+    Lightning {
+        #[arg(long, trailing_var_arg = true, allow_hyphen_values = true, num_args=1..)]
+        exec: Option<Vec<ffi::OsString>>,
+    },
     /// Spins up bitcoind, cln w/ gateway, lnd w/ gateway, a faucet, electrs,
     /// esplora, and a federation sized from FM_FED_SIZE it opens LN channel
     /// between the two nodes. it connects the gateways to the federation.
     /// it finally switches to use the CLN gateway using the fedimint-cli
     DevFed {
+        /// Print what would be launched as JSON and exit, without starting
+        /// any daemon, binding ports or creating the test dir
+        #[arg(long)]
+        dry_run: bool,
+        /// Cooperatively close the gateways' channels and mine the closing
+        /// transactions before stopping the daemons
+        #[arg(long, env = FM_CLOSE_CHANNELS_ON_SHUTDOWN_ENV)]
+        close_channels_on_shutdown: bool,
+        /// Number of federations to launch, all sharing bitcoind and the
+        /// gateways. The extra ones are named `fed1`, `fed2`, ... and their
+        /// invite codes exported as `FM_FED1_INVITE_CODE`, ...
+        #[arg(long, env = FM_NUM_FEDS_ENV, default_value = "1")]
+        num_feds: usize,
+        /// Once ready, write a manifest of the launched daemons to this path,
+        /// to reproduce the dev federation without devimint
+        #[arg(long)]
+        export_manifest: Option<PathBuf>,
+        /// Format of the `--export-manifest` manifest
+        #[arg(long, value_enum, default_value = "docker-compose")]
+        manifest_format: ManifestFormat,
+        #[arg(long, trailing_var_arg = true, allow_hyphen_values = true, num_args=1..)]
+        exec: Option<Vec<ffi::OsString>>,
+    },
+    /// Spins up bitcoind, a single lightning node and a gateway on it,
+    /// without any federation
+    Gateway {
+        #[arg(long, value_enum, default_value = "lnd")]
+        ln: StandaloneGatewayNode,
+        /// Connect the gateway to this federation once it's running
+        #[arg(long)]
+        invite_code: Option<String>,
         #[arg(long, trailing_var_arg = true, allow_hyphen_values = true, num_args=1..)]
         exec: Option<Vec<ffi::OsString>>,
     },
@@ -111,10 +201,24 @@ pub enum Cmd {
     Rpc(RpcCmd),
 }
 
+/// Lightning node a standalone gateway runs on
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum StandaloneGatewayNode {
+    Cln,
+    Lnd,
+}
+
 #[derive(Subcommand)]
 pub enum RpcCmd {
     Wait,
     Env,
+    /// Lists the daemons of the running devimint with their role, pid,
+    /// status and uptime
+    Ps {
+        /// Print the list as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 pub async fn setup(arg: CommonArgs) -> Result<(ProcessManager, TaskGroup)> {
@@ -132,11 +236,18 @@ pub async fn setup(arg: CommonArgs) -> Result<(ProcessManager, TaskGroup)> {
         .into_std()
         .await;
 
-    fedimint_logging::TracingSetup::default()
+    let mut tracing_setup = fedimint_logging::TracingSetup::default();
+    tracing_setup
         .with_file(Some(log_file))
         // jsonrpsee is expected to fail during startup
-        .with_directive("jsonrpsee-client=off")
-        .init()?;
+        .with_directive("jsonrpsee-client=off");
+    #[cfg(feature = "telemetry")]
+    tracing_setup.with_otlp_endpoint(std::env::var(FM_DEVIMINT_OTLP_ENDPOINT_ENV).ok());
+    tracing_setup.init()?;
+    #[cfg(not(feature = "telemetry"))]
+    if std::env::var(FM_DEVIMINT_OTLP_ENDPOINT_ENV).is_ok() {
+        warn!(target: LOG_DEVIMINT, "{FM_DEVIMINT_OTLP_ENDPOINT_ENV} is set but devimint was built without the telemetry feature");
+    }
 
     let globals = vars::Global::new(test_dir, arg.fed_size, arg.offline_nodes).await?;
 
@@ -145,19 +256,88 @@ pub async fn setup(arg: CommonArgs) -> Result<(ProcessManager, TaskGroup)> {
     }
     info!(target: LOG_DEVIMINT, path=%globals.FM_DATA_DIR.display() , "Devimint data dir");
 
+    let mut process_mgr = ProcessManager::new(globals);
+    if let Some(run_id) = &arg.run_id {
+        process_mgr = process_mgr.with_run_id(run_id)?;
+    }
+    if let Some(max_restarts) = arg.max_daemon_restarts {
+        process_mgr = process_mgr.with_watchdog(max_restarts);
+    }
+    if let Some(path) = &arg.setup_events {
+        process_mgr = process_mgr.with_setup_observer(Arc::new(JsonLinesObserver::open(path)?));
+    }
+    if arg.process_group {
+        process_mgr = process_mgr.with_process_group()?;
+    }
+    if arg.guardian_json_logs {
+        process_mgr = process_mgr.with_guardian_json_logs();
+    }
+    if let Some(labels) = &arg.guardian_labels {
+        process_mgr = process_mgr.with_guardian_labels(labels);
+    }
+
     let mut env_string = String::new();
-    for (var, value) in globals.vars() {
+    for (var, value) in process_mgr.globals.vars() {
         debug!(var, value, "Env variable set");
         writeln!(env_string, r#"export {var}="{value}""#)?; // hope that value doesn't contain a "
         std::env::set_var(var, value);
     }
-    write_overwrite_async(globals.FM_TEST_DIR.join("env"), env_string).await?;
-    let process_mgr = ProcessManager::new(globals);
+    // esplora is launched as these say unless told otherwise, see
+    // `EsploraOptions`, and devimints reattaching expect the same
+    let esplora_vars = [
+        (FM_ESPLORA_CORS_ENV, arg.esplora_cors.clone()),
+        (
+            FM_ESPLORA_FRONTEND_ENV,
+            arg.esplora_frontend.then(|| "1".to_owned()),
+        ),
+    ];
+    for (var, value) in esplora_vars {
+        if let Some(value) = value {
+            writeln!(env_string, r#"export {var}="{value}""#)?;
+            std::env::set_var(var, value);
+        }
+    }
+    if process_mgr.run_id().is_some() {
+        for (var, value) in process_mgr.env_vars() {
+            writeln!(env_string, r#"export {var}="{value}""#)?;
+        }
+    }
+    write_overwrite_async(process_mgr.globals.FM_TEST_DIR.join("env"), env_string).await?;
+    if let Ok(overrides) = std::env::var(FM_DAEMON_RUST_LOG_ENV) {
+        for (daemon, directives) in parse_rust_log_overrides(&overrides)? {
+            process_mgr = process_mgr.with_rust_log(&daemon, &directives);
+        }
+    }
+    if let Ok(limits) = std::env::var(FM_DAEMON_RESOURCE_LIMITS_ENV) {
+        let limits = crate::cgroup::parse_resource_limits(&limits)?;
+        if !limits.is_empty() {
+            // before spawning anything, which would be left in devimint's cgroup
+            crate::cgroup::ensure_available()
+                .with_context(|| format!("{FM_DAEMON_RESOURCE_LIMITS_ENV} needs cgroups"))?;
+        }
+        for (daemon, limits) in limits {
+            process_mgr = process_mgr.with_resource_limits(&daemon, limits);
+        }
+    }
     let task_group = TaskGroup::new();
     task_group.install_kill_handler();
     Ok((process_mgr, task_group))
 }
 
+/// Interval for the background block miner, if `FM_BLOCK_INTERVAL` is set
+pub fn block_interval() -> Result<Option<Duration>> {
+    match std::env::var(FM_BLOCK_INTERVAL_ENV) {
+        Ok(secs) if !secs.is_empty() => {
+            let secs: u64 = secs
+                .parse()
+                .with_context(|| format!("Invalid {FM_BLOCK_INTERVAL_ENV}: {secs}"))?;
+            ensure!(0 < secs, "{FM_BLOCK_INTERVAL_ENV} must be positive");
+            Ok(Some(Duration::from_secs(secs)))
+        }
+        _ => Ok(None),
+    }
+}
+
 pub async fn update_test_dir_link(
     link_test_dir: &Path,
     test_dir: &Path,
@@ -237,11 +417,47 @@ pub async fn handle_command(cmd: Cmd, common_args: CommonArgs) -> Result<()> {
             }
             task_group.make_handle().make_shutdown_rx().await;
         }
-        Cmd::DevFed { exec } => {
+        Cmd::Lightning { exec } => {
+            let (process_mgr, task_group) = setup(common_args).await?;
+            let _daemons =
+                write_ready_file(&process_mgr.globals, lightning_daemons(&process_mgr).await)
+                    .await?;
+            if let Some(exec) = exec {
+                exec_user_command(exec).await?;
+                task_group.shutdown();
+            }
+            task_group.make_handle().make_shutdown_rx().await;
+        }
+        Cmd::DevFed {
+            dry_run: true,
+            close_channels_on_shutdown: _,
+            num_feds: _,
+            export_manifest: _,
+            manifest_format: _,
+            exec: _,
+        } => {
+            let plan = DevFed::dry_run()
+                .with_test_dir(&common_args.test_dir())
+                .with_fed_size(common_args.fed_size)
+                .with_offline_nodes(common_args.offline_nodes)
+                .plan()
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&plan)?);
+        }
+        Cmd::DevFed {
+            dry_run: false,
+            close_channels_on_shutdown,
+            num_feds,
+            export_manifest,
+            manifest_format,
+            exec,
+        } => {
+            ensure!(0 < num_feds, "--num-feds must be at least 1");
             trace!(target: LOG_DEVIMINT, "Starting dev fed");
             let start_time = Instant::now();
             let skip_setup = common_args.skip_setup;
             let (process_mgr, task_group) = setup(common_args).await?;
+            let shutdown_process_mgr = process_mgr.clone();
             let main = {
                 let task_group = task_group.clone();
                 async move {
@@ -322,6 +538,28 @@ pub async fn handle_command(cmd: Cmd, common_args: CommonArgs) -> Result<()> {
 
                     dev_fed.finalize(&process_mgr).await?;
 
+                    if let Some(path) = &export_manifest {
+                        dev_fed
+                            .clone()
+                            .to_dev_fed(&process_mgr)
+                            .await?
+                            .export_manifest(&process_mgr, manifest_format, path)
+                            .await?;
+                    }
+
+                    let extra_feds =
+                        extra_federations(&process_mgr, &dev_fed, num_feds - 1, skip_setup).await?;
+                    if !extra_feds.is_empty() {
+                        print_federations(dev_fed.fed().await?, &extra_feds)?;
+                    }
+
+                    if let Some(interval) = block_interval()? {
+                        dev_fed
+                            .bitcoind()
+                            .await?
+                            .spawn_block_miner(&task_group, interval);
+                    }
+
                     let daemons = write_ready_file(&process_mgr.globals, Ok(dev_fed)).await?;
 
                     info!(target: LOG_DEVIMINT, elapsed_ms = %start_time.elapsed().as_millis(), "Devfed ready");
@@ -334,13 +572,45 @@ pub async fn handle_command(cmd: Cmd, common_args: CommonArgs) -> Result<()> {
                     debug!(target: LOG_DEVIMINT, "Waiting for group task shutdown");
                     task_group.make_handle().make_shutdown_rx().await;
 
-                    Ok::<_, anyhow::Error>(daemons)
+                    Ok::<_, anyhow::Error>((daemons, extra_feds))
                 }
             };
-            if let Some(fed) = cleanup_on_exit(main, task_group).await? {
-                fed.fast_terminate().await;
+            if let Some((fed, extra_feds)) = cleanup_on_exit(main, task_group).await? {
+                drop(extra_feds);
+                if close_channels_on_shutdown {
+                    fed.to_dev_fed(&shutdown_process_mgr)
+                        .await?
+                        .shutdown(&shutdown_process_mgr, true)
+                        .await?;
+                } else {
+                    fed.shutdown(&shutdown_process_mgr).await?;
+                }
             }
         }
+        Cmd::Gateway {
+            ln,
+            invite_code,
+            exec,
+        } => {
+            let (process_mgr, task_group) = setup(common_args).await?;
+            let main = {
+                let task_group = task_group.clone();
+                async move {
+                    let gatewayd = write_ready_file(
+                        &process_mgr.globals,
+                        standalone_gateway(&process_mgr, ln, invite_code.as_deref()).await,
+                    )
+                    .await?;
+                    if let Some(exec) = exec {
+                        exec_user_command(exec).await?;
+                        task_group.shutdown();
+                    }
+                    task_group.make_handle().make_shutdown_rx().await;
+                    Ok::<_, anyhow::Error>(gatewayd)
+                }
+            };
+            cleanup_on_exit(main, task_group).await?;
+        }
         Cmd::Rpc(rpc_cmd) => rpc_command(rpc_cmd, common_args).await?,
         Cmd::RunUi => {
             let (process_mgr, task_group) = setup(common_args).await?;
@@ -427,14 +697,120 @@ pub async fn rpc_command(rpc: RpcCmd, common: CommonArgs) -> Result<()> {
             {
                 let invite = fs::read_to_string(&invite_file).await?;
                 let mut env_string = fs::read_to_string(&env_file).await?;
-                writeln!(env_string, r#"export FM_INVITE_CODE="{invite}""#)?;
+                let prefix = match &common.run_id {
+                    Some(run_id) => run_env_prefix(run_id)?,
+                    None => String::new(),
+                };
+                env_string.push_str(&env_exports(&prefix, FM_INVITE_CODE_ENV, &invite));
                 std::env::set_var(FM_INVITE_CODE_ENV, invite);
                 write_overwrite_async(env_file, env_string).await?;
             }
 
             Ok(())
         }
+        RpcCmd::Ps { json } => {
+            let list = read_process_list(&common.test_dir()).await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+                return Ok(());
+            }
+            println!(
+                "{:<24} {:<10} {:>8} {:<10} {:>8}",
+                "NAME", "ROLE", "PID", "STATUS", "UPTIME"
+            );
+            for info in list {
+                let role = format!("{:?}", info.role);
+                let status = match info.status {
+                    ProcessStatus::Running => "running".to_owned(),
+                    ProcessStatus::Exited { code: Some(code) } => format!("exited({code})"),
+                    ProcessStatus::Exited { code: None } => "exited".to_owned(),
+                    ProcessStatus::Stopped => "stopped".to_owned(),
+                };
+                println!(
+                    "{:<24} {:<10} {:>8} {:<10} {:>8}",
+                    info.name,
+                    role,
+                    info.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+                    status,
+                    info.uptime()
+                        .map(|uptime| format!("{}s", uptime.as_secs()))
+                        .unwrap_or_default(),
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Starts `count` federations in addition to the one of `dev_fed`, sharing its
+/// bitcoind and connected to its CLN and LND gateways, and exports their
+/// invite codes
+async fn extra_federations(
+    process_mgr: &ProcessManager,
+    dev_fed: &DevJitFed,
+    count: usize,
+    skip_setup: bool,
+) -> Result<Vec<Federation>> {
+    let bitcoind = dev_fed.bitcoind().await?;
+    let feds = futures::future::try_join_all((1..=count).map(|idx| {
+        Federation::new(
+            process_mgr,
+            bitcoind.clone(),
+            process_mgr.globals.FM_FED_SIZE,
+            skip_setup,
+            format!("fed{idx}"),
+        )
+    }))
+    .await?;
+
+    let env_file = process_mgr.globals.FM_TEST_DIR.join("env");
+    let mut env_string = fs::read_to_string(&env_file).await?;
+    for (idx, fed) in (1..).zip(&feds) {
+        if !skip_setup {
+            dev_fed.gw_cln().await?.connect_fed(fed).await?;
+            dev_fed.gw_lnd().await?.connect_fed(fed).await?;
+        }
+        let var = format!("FM_FED{idx}_INVITE_CODE");
+        let invite_code = fed.invite_code()?;
+        env_string.push_str(&process_mgr.env_exports(&var, &invite_code));
+        std::env::set_var(var, invite_code);
+    }
+    write_overwrite_async(env_file, env_string).await?;
+    Ok(feds)
+}
+
+/// Prints a table of the devfed's federations and their invite codes to
+/// stderr, stdout is left to the user command
+fn print_federations(default_fed: &Federation, extra_feds: &[Federation]) -> Result<()> {
+    eprintln!("{:<10} {:<10} INVITE CODE", "NAME", "GUARDIANS");
+    for fed in iter::once(default_fed).chain(extra_feds) {
+        eprintln!(
+            "{:<10} {:<10} {}",
+            fed.name(),
+            fed.members.len(),
+            fed.invite_code()?.trim()
+        );
+    }
+    Ok(())
+}
+
+async fn standalone_gateway(
+    process_mgr: &ProcessManager,
+    ln: StandaloneGatewayNode,
+    invite_code: Option<&str>,
+) -> Result<Gatewayd> {
+    let bitcoind = Bitcoind::new(process_mgr, false).await?;
+    let node = match ln {
+        StandaloneGatewayNode::Cln => {
+            LightningNode::Cln(Lightningd::new(process_mgr, bitcoind).await?)
+        }
+        StandaloneGatewayNode::Lnd => LightningNode::Lnd(Lnd::new(process_mgr, bitcoind).await?),
+    };
+    let gatewayd = Gatewayd::standalone(process_mgr, node).await?;
+    if let Some(invite_code) = invite_code {
+        gatewayd.connect_invite_code(invite_code).await?;
     }
+    Ok(gatewayd)
 }
 
 async fn run_ui(process_mgr: &ProcessManager) -> Result<(Vec<Fedimintd>, ExternalDaemons)> {
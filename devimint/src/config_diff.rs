@@ -0,0 +1,220 @@
+//! Comparing the consensus configs of federations.
+//!
+//! An upgraded federation must keep the config it was set up with, so
+//! comparing its config before and after the upgrade, or with a federation
+//! set up by the old version, shows whether anything drifted.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use fedimint_core::core::{ModuleInstanceId, ModuleKind};
+use fedimint_core::module::ModuleConsensusVersion;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::federation::Federation;
+
+/// Consensus configs of a federation, as exported by
+/// [`Federation::export_config`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConsensusConfigExport {
+    /// Core consensus config of the guardians, without the modules
+    pub core: Value,
+    /// Consensus config of each module
+    pub modules: BTreeMap<ModuleInstanceId, ModuleConfigExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleConfigExport {
+    pub kind: ModuleKind,
+    pub version: ModuleConsensusVersion,
+    /// Consensus params of the module, from its client config
+    pub params: Value,
+}
+
+/// A value that differs between two configs, missing on the side that lacks
+/// it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueChange {
+    pub a: Option<Value>,
+    pub b: Option<Value>,
+}
+
+/// Differences between the configs of two federations `a` and `b`, empty if
+/// they agree
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    /// Core consensus settings that differ, by JSON pointer, like
+    /// `/version` or `/meta/federation_name`
+    pub core: BTreeMap<String, ValueChange>,
+    /// Modules only `a` runs
+    pub removed_modules: BTreeMap<ModuleInstanceId, ModuleKind>,
+    /// Modules only `b` runs, including the ones of another kind than in `a`
+    pub added_modules: BTreeMap<ModuleInstanceId, ModuleKind>,
+    /// Modules of the same kind in both that differ
+    pub changed_modules: BTreeMap<ModuleInstanceId, ModuleDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleDiff {
+    pub kind: ModuleKind,
+    /// Consensus versions of `a` and `b`, if they differ
+    pub version: Option<(ModuleConsensusVersion, ModuleConsensusVersion)>,
+    /// Params that differ, by JSON pointer
+    pub params: BTreeMap<String, ValueChange>,
+}
+
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Compares the consensus configs of `a` and `b`
+pub async fn diff_configs(a: &Federation, b: &Federation) -> Result<ConfigDiff> {
+    let a = a.export_config().await.context("exporting config of a")?;
+    let b = b.export_config().await.context("exporting config of b")?;
+    Ok(diff_exports(&a, &b))
+}
+
+/// Compares two exported configs, like ones of a federation from before and
+/// after an upgrade
+pub fn diff_exports(a: &ConsensusConfigExport, b: &ConsensusConfigExport) -> ConfigDiff {
+    let mut diff = ConfigDiff::default();
+    diff_values("", Some(&a.core), Some(&b.core), &mut diff.core);
+
+    for (instance_id, a_module) in &a.modules {
+        match b.modules.get(instance_id) {
+            Some(b_module) if b_module.kind == a_module.kind => {
+                let mut params = BTreeMap::new();
+                diff_values(
+                    "",
+                    Some(&a_module.params),
+                    Some(&b_module.params),
+                    &mut params,
+                );
+                let version = (a_module.version != b_module.version)
+                    .then_some((a_module.version, b_module.version));
+                if version.is_some() || !params.is_empty() {
+                    diff.changed_modules.insert(
+                        *instance_id,
+                        ModuleDiff {
+                            kind: a_module.kind.clone(),
+                            version,
+                            params,
+                        },
+                    );
+                }
+            }
+            Some(b_module) => {
+                diff.removed_modules
+                    .insert(*instance_id, a_module.kind.clone());
+                diff.added_modules
+                    .insert(*instance_id, b_module.kind.clone());
+            }
+            None => {
+                diff.removed_modules
+                    .insert(*instance_id, a_module.kind.clone());
+            }
+        }
+    }
+    for (instance_id, b_module) in &b.modules {
+        if !a.modules.contains_key(instance_id) {
+            diff.added_modules
+                .insert(*instance_id, b_module.kind.clone());
+        }
+    }
+    diff
+}
+
+/// Records the differences of `a` and `b` at `path` into `changes`, descending
+/// into objects so only the fields that differ are recorded
+fn diff_values(
+    path: &str,
+    a: Option<&Value>,
+    b: Option<&Value>,
+    changes: &mut BTreeMap<String, ValueChange>,
+) {
+    match (a, b) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            for key in a.keys().chain(b.keys().filter(|key| !a.contains_key(*key))) {
+                let path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                diff_values(&path, a.get(key), b.get(key), changes);
+            }
+        }
+        _ if a == b => {}
+        _ => {
+            changes.insert(
+                path.to_owned(),
+                ValueChange {
+                    a: a.cloned(),
+                    b: b.cloned(),
+                },
+            );
+        }
+    }
+}
+
+#[test]
+fn test_diff_exports() {
+    use serde_json::json;
+
+    let module = |kind: &'static str, minor: u32, params: Value| ModuleConfigExport {
+        kind: ModuleKind::from_static_str(kind),
+        version: ModuleConsensusVersion::new(2, minor),
+        params,
+    };
+    let a = ConsensusConfigExport {
+        core: json!({"version": {"major": 2, "minor": 0}, "meta": {"federation_name": "a"}}),
+        modules: BTreeMap::from([
+            (0, module("ln", 0, json!({"network": "regtest"}))),
+            (
+                1,
+                module("mint", 0, json!({"fee_consensus": {"note_spend_abs": 0}})),
+            ),
+            (2, module("wallet", 0, json!({}))),
+        ]),
+    };
+    assert!(diff_exports(&a, &a).is_empty());
+
+    let b = ConsensusConfigExport {
+        core: json!({"version": {"major": 2, "minor": 1}, "meta": {}}),
+        modules: BTreeMap::from([
+            (0, module("lnv2", 0, json!({"network": "regtest"}))),
+            (
+                1,
+                module("mint", 1, json!({"fee_consensus": {"note_spend_abs": 20}})),
+            ),
+            (3, module("meta", 0, json!({}))),
+        ]),
+    };
+    let diff = diff_exports(&a, &b);
+    assert_eq!(
+        diff.core.keys().collect::<Vec<_>>(),
+        ["/meta/federation_name", "/version/minor"]
+    );
+    assert_eq!(diff.core["/meta/federation_name"].b, None);
+    assert_eq!(
+        diff.removed_modules.keys().copied().collect::<Vec<_>>(),
+        [0, 2]
+    );
+    assert_eq!(
+        diff.added_modules.keys().copied().collect::<Vec<_>>(),
+        [0, 3]
+    );
+    let mint = &diff.changed_modules[&1];
+    assert_eq!(
+        mint.version,
+        Some((
+            ModuleConsensusVersion::new(2, 0),
+            ModuleConsensusVersion::new(2, 1)
+        ))
+    );
+    assert_eq!(
+        mint.params["/fee_consensus/note_spend_abs"],
+        ValueChange {
+            a: Some(json!(0)),
+            b: Some(json!(20)),
+        }
+    );
+}
@@ -1,21 +1,51 @@
-use std::ops::Deref as _;
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::ops::{ControlFlow, Deref as _};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use anyhow::Result;
-use fedimint_core::runtime;
+use anyhow::{anyhow, bail, ensure, Context as _, Result};
+use fedimint_core::admin_client::ConfigGenParamsConsensus;
+use fedimint_core::config::ServerModuleConfigGenParamsRegistry;
+use fedimint_core::envs::{is_env_var_set, BitcoinRpcConfig};
 use fedimint_core::task::jit::{JitTry, JitTryAnyhow};
+use fedimint_core::{runtime, Amount, PeerId};
 use fedimint_logging::LOG_DEVIMINT;
-use tokio::join;
-use tracing::debug;
+use fedimint_testing::federation::local_config_gen_params;
+use ln_gateway::rpc::GatewayMode;
+use rand::rngs::StdRng;
+use rand::{Rng as _, SeedableRng as _};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::time::Instant;
+use tokio::{fs, join};
+use tracing::instrument::Instrumented;
+use tracing::{debug, debug_span, info, warn, Instrument, Span};
 
+use crate::envs::{FM_DEVIMINT_JIT_FUZZ_SEED_ENV, FM_LOGS_DIR_ENV};
 use crate::external::{
-    open_channel, open_channels_between_gateways, Bitcoind, Electrs, Esplora, Lightningd, Lnd,
+    open_channel, open_channels_between_gateways, Bitcoind, Electrs, Esplora, EsploraOptions,
+    Lightningd, Lnd,
 };
-use crate::federation::{Client, Federation};
+use crate::federation::config::ModuleOptions;
+use crate::federation::{Client, Federation, FederationHandle};
 use crate::gatewayd::Gatewayd;
-use crate::util::ProcessManager;
+use crate::manifest::{service_dependencies, Manifest, ManifestFormat, ServiceManifest};
+use crate::memory::{format_mib, rss_bytes, MemoryBaseline};
+use crate::payments::{ln_invoice, ln_pay};
+use crate::quiescence::{electrs_tip_height, height_mismatches, timed, QuiescenceReport};
+use crate::replay::{Recorder, Script};
+use crate::setup_events::SetupObserver;
+use crate::util::{
+    poll, poll_with_timeout, stats_for, Command, FedimintdCmd, ProcessHandle, ProcessManager,
+    Stats, GATEWAY_CLN_EXTENSION_FALLBACK,
+};
+use crate::vars::{self, Global, Reserve};
 use crate::version_constants::{VERSION_0_4_0_ALPHA, VERSION_0_5_0_ALPHA};
-use crate::LightningNode;
+use crate::{cmd, LightningNode};
 
 async fn spawn_drop<T>(t: T)
 where
@@ -28,6 +58,12 @@ async fn spawn_drop<T>(t: T)
     .expect("drop panic");
 }
 
+/// A running dev federation.
+///
+/// Dropping it terminates the daemons it spawned, blocking until they exited,
+/// so a test that panics or bails doesn't leave them behind. Clones share the
+/// daemons, which keep running until the last clone is dropped.
+/// [`Self::shutdown`] additionally checks nothing survived.
 #[derive(Clone)]
 pub struct DevFed {
     pub bitcoind: Bitcoind,
@@ -41,7 +77,119 @@ pub struct DevFed {
     pub esplora: Esplora,
 }
 
+/// Serializable description of a running [`DevFed`], so a process other than
+/// the one that spawned it can attach to it, see [`DevFed::to_handle`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevFedHandle {
+    /// Env vars of [`Global`], which carry the ports and data dirs
+    pub globals: BTreeMap<String, String>,
+    pub federation: FederationHandle,
+    pub gw_cln: GatewayHandle,
+    pub gw_lnd: GatewayHandle,
+    pub gw_ldk: Option<GatewayHandle>,
+}
+
+/// Settings of a gateway in a [`DevFedHandle`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayHandle {
+    pub registration_ttl_secs: Option<u64>,
+    pub mode: GatewayMode,
+}
+
+impl GatewayHandle {
+    fn new(gw: &Gatewayd) -> Self {
+        Self {
+            registration_ttl_secs: gw.registration_ttl.map(|ttl| ttl.as_secs()),
+            mode: gw.mode,
+        }
+    }
+
+    async fn reattach(&self, process_mgr: &ProcessManager, ln: LightningNode) -> Result<Gatewayd> {
+        Gatewayd::reattach(
+            process_mgr,
+            ln,
+            self.registration_ttl_secs.map(Duration::from_secs),
+            self.mode,
+        )
+        .await
+    }
+}
+
+impl DevFedHandle {
+    /// [`ProcessManager`] with the globals of the dev federation, which also
+    /// exports them as env variables like `devimint` does on setup
+    pub fn process_manager(&self) -> Result<ProcessManager> {
+        let globals = Global::from_vars(&self.globals).context("reading globals from handle")?;
+        for (var, value) in globals.vars() {
+            std::env::set_var(var, value);
+        }
+        Ok(ProcessManager::new(globals))
+    }
+}
+
 impl DevFed {
+    /// Describes the running dev federation, so another process can attach to
+    /// it with [`Self::from_handle`] while this one keeps owning the daemons
+    pub fn to_handle(&self, process_mgr: &ProcessManager) -> Result<DevFedHandle> {
+        Ok(DevFedHandle {
+            globals: process_mgr
+                .globals
+                .vars()
+                .map(|(var, value)| (var.to_owned(), value))
+                .collect(),
+            federation: self.fed.to_handle()?,
+            gw_cln: GatewayHandle::new(&self.gw_cln),
+            gw_lnd: GatewayHandle::new(&self.gw_lnd),
+            gw_ldk: self.gw_ldk.as_ref().map(GatewayHandle::new),
+        })
+    }
+
+    /// Attaches to the dev federation described by `handle`, with
+    /// `process_mgr` from [`DevFedHandle::process_manager`]. None of the
+    /// daemons are owned by the returned [`DevFed`], dropping it leaves them
+    /// running and they can't be restarted or killed through it.
+    pub async fn from_handle(process_mgr: &ProcessManager, handle: &DevFedHandle) -> Result<Self> {
+        let globals = &process_mgr.globals;
+        let bitcoind = Bitcoind::reattach(process_mgr).await?;
+        let cln =
+            Lightningd::connect_existing(bitcoind.clone(), globals.FM_CLN_SOCKET.clone()).await?;
+        let lnd = Lnd::connect_existing(
+            bitcoind.clone(),
+            globals.FM_LND_RPC_ADDR.clone(),
+            globals.FM_LND_TLS_CERT.clone(),
+            globals.FM_LND_MACAROON.clone(),
+        )
+        .await?;
+        let electrs = Electrs::reattach(bitcoind.clone());
+        let esplora = Esplora::reattach(process_mgr, bitcoind.clone()).await?;
+        let fed =
+            Federation::from_handle(process_mgr, bitcoind.clone(), &handle.federation).await?;
+        let gw_cln = handle
+            .gw_cln
+            .reattach(process_mgr, LightningNode::Cln(cln.clone()))
+            .await?;
+        let gw_lnd = handle
+            .gw_lnd
+            .reattach(process_mgr, LightningNode::Lnd(lnd.clone()))
+            .await?;
+        let gw_ldk = match &handle.gw_ldk {
+            Some(gw_ldk) => Some(gw_ldk.reattach(process_mgr, LightningNode::Ldk).await?),
+            None => None,
+        };
+
+        Ok(Self {
+            bitcoind,
+            cln,
+            lnd,
+            fed,
+            gw_cln,
+            gw_lnd,
+            gw_ldk,
+            electrs,
+            esplora,
+        })
+    }
+
     pub async fn fast_terminate(self) {
         let Self {
             bitcoind,
@@ -67,7 +215,992 @@ pub async fn fast_terminate(self) {
             spawn_drop(bitcoind),
         );
     }
+
+    /// Stops all daemons, then checks with
+    /// [`ProcessManager::assert_no_leaked_processes`] that none of them
+    /// survived.
+    ///
+    /// With `close_channels` the gateways' channels are cooperatively closed
+    /// first and the closing transactions mined, so resuming from the same
+    /// data dir with `--skip-setup` doesn't find them pending a force close.
+    pub async fn shutdown(self, process_mgr: &ProcessManager, close_channels: bool) -> Result<()> {
+        if close_channels {
+            self.close_channels().await?;
+        }
+        self.fast_terminate().await;
+        process_mgr.assert_no_leaked_processes().await
+    }
+
+    async fn close_channels(&self) -> Result<()> {
+        let gateway_cli_version = crate::util::GatewayCli::version_or_default().await;
+        let gatewayd_version = crate::util::Gatewayd::version_or_default().await;
+        if gateway_cli_version < *VERSION_0_4_0_ALPHA || gatewayd_version < *VERSION_0_4_0_ALPHA {
+            warn!(target: LOG_DEVIMINT, "Closing channels needs gateways of at least v0.4.0, leaving them open");
+            return Ok(());
+        }
+
+        let gateways: Vec<&Gatewayd> = [&self.gw_cln, &self.gw_lnd]
+            .into_iter()
+            .chain(self.gw_ldk.as_ref())
+            .collect();
+        // a channel closed by one side must not be closed again by the other
+        let mut closed = BTreeSet::new();
+        let mut funding_outpoints = vec![];
+        for gw in &gateways {
+            let pubkey = gw.lightning_pubkey().await?;
+            let channels = gw.list_active_channels().await?;
+            for channel in &channels {
+                let peers = if pubkey < channel.remote_pubkey {
+                    (pubkey, channel.remote_pubkey)
+                } else {
+                    (channel.remote_pubkey, pubkey)
+                };
+                if closed.insert(peers) {
+                    info!(target: LOG_DEVIMINT, %pubkey, remote_pubkey = %channel.remote_pubkey, "Closing channel before shutdown");
+                    // closing with a peer closes every channel with it
+                    for peer_channel in channels
+                        .iter()
+                        .filter(|c| c.remote_pubkey == channel.remote_pubkey)
+                    {
+                        funding_outpoints.push(
+                            self.bitcoind
+                                .channel_funding_outpoint(peer_channel.short_channel_id)
+                                .await?,
+                        );
+                    }
+                    gw.close_channels_with_peer(channel.remote_pubkey).await?;
+                }
+            }
+        }
+
+        // mining before the closing transactions are broadcast would leave the
+        // channels closing
+        for outpoint in &funding_outpoints {
+            let txid = self
+                .bitcoind
+                .await_spent_in_mempool(outpoint, CLOSE_TX_TIMEOUT)
+                .await?;
+            self.bitcoind
+                .await_in_mempool(&txid, CLOSE_TX_TIMEOUT)
+                .await?;
+        }
+        self.bitcoind.mine_blocks(10).await?;
+        for gw in gateways {
+            gw.wait_for_chain_sync(&self.bitcoind).await?;
+        }
+        Ok(())
+    }
+
+    /// Handles of the daemons of the dev federation, in the order they
+    /// depend on each other, without the lightning nodes connected to rather
+    /// than spawned by devimint
+    pub(crate) fn process_handles(&self) -> Vec<&ProcessHandle> {
+        let mut handles: Vec<&ProcessHandle> = vec![&self.bitcoind.process];
+        handles.extend(self.cln.process.as_deref().map(|process| &process.0));
+        handles.extend(self.lnd.process.as_ref());
+        handles.extend([&self.electrs.process, &self.esplora.process]);
+        handles.extend(self.esplora.frontend.as_ref());
+        handles.extend(self.fed.members.values().map(|member| &member.process));
+        handles.extend([&self.gw_cln.process, &self.gw_lnd.process]);
+        handles.extend(self.gw_ldk.as_ref().map(|gw| &gw.process));
+        handles
+    }
+
+    /// Copies every log file in `$FM_LOGS_DIR`, including devimint's own
+    /// `devimint.log`, into `dest` together with a `status.json` snapshot of
+    /// the chain, federation and gateways. Meant to be called when a test
+    /// fails, so CI can upload `dest` as an artifact.
+    pub async fn collect_logs(&self, dest: &Path) -> Result<()> {
+        let logs_dir = PathBuf::from(std::env::var(FM_LOGS_DIR_ENV)?);
+        fs::create_dir_all(dest).await?;
+        let mut entries = fs::read_dir(&logs_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                fs::copy(entry.path(), dest.join(entry.file_name())).await?;
+            }
+        }
+
+        fs::write(
+            dest.join("status.json"),
+            serde_json::to_vec_pretty(&self.status().await)?,
+        )
+        .await?;
+        info!(target: LOG_DEVIMINT, dest = %dest.display(), "Collected logs");
+        Ok(())
+    }
+
+    /// Best-effort status dump, errors are recorded instead of returned since
+    /// this is usually called with something already broken
+    async fn status(&self) -> serde_json::Value {
+        fn or_error(res: Result<serde_json::Value>) -> serde_json::Value {
+            res.unwrap_or_else(|err| json!({ "error": format!("{err:#}") }))
+        }
+
+        let session_count = async {
+            Ok(json!(
+                self.fed
+                    .internal_client()
+                    .await?
+                    .get_session_count()
+                    .await?
+            ))
+        };
+        let mut gateways = serde_json::Map::new();
+        for gw in [Some(&self.gw_cln), Some(&self.gw_lnd), self.gw_ldk.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            gateways.insert(gw.addr.clone(), or_error(gw.get_info().await));
+        }
+
+        json!({
+            "block_count": or_error(self.bitcoind.get_block_count().map(|count| json!(count))),
+            "session_count": or_error(session_count.await),
+            "dkg_duration_ms": self.fed.dkg_duration().as_millis() as u64,
+            "esplora_frontend_url": self.esplora.frontend_url(),
+            "gateways": gateways,
+        })
+    }
+}
+
+/// Everything [`dev_fed`] would launch, see [`DevFed::dry_run`]
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchPlan {
+    pub test_dir: PathBuf,
+    pub fed_size: usize,
+    pub offline_nodes: usize,
+    /// Env variables of [`Global`], which every daemon and client gets
+    pub globals: BTreeMap<&'static str, String>,
+    /// What the guardians run DKG with, by guardian
+    pub config_gen_params: BTreeMap<PeerId, PlannedConfigGenParams>,
+    pub daemons: Vec<DaemonPlan>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DaemonPlan {
+    /// Name of the daemon and of its log file
+    pub name: String,
+    /// Program and arguments
+    pub command: Vec<String>,
+    /// Env variables set on top of [`LaunchPlan::globals`]
+    pub env: BTreeMap<String, String>,
+    pub data_dir: PathBuf,
+    /// Bound ports, by what they are for
+    pub ports: BTreeMap<&'static str, u16>,
+}
+
+/// The [`fedimint_server::config::ConfigGenParams`] of a guardian, without its TLS private key and
+/// api auth
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedConfigGenParams {
+    pub our_id: PeerId,
+    pub p2p_bind: SocketAddr,
+    pub api_bind: SocketAddr,
+    pub max_connections: u32,
+    /// With the module params and meta set during DKG
+    pub consensus: ConfigGenParamsConsensus,
+}
+
+/// Plans a launch like [`dev_fed`] without spawning processes, binding ports
+/// or creating directories, see [`DevFed::dry_run`].
+///
+/// Versions of the binaries aren't queried, as that spawns them, they are
+/// taken to be devimint's own unless set. Guardians are planned with their
+/// default binds, without network namespaces, api binds or proxies.
+#[derive(Debug, Clone)]
+pub struct DryRun {
+    test_dir: PathBuf,
+    fed_size: usize,
+    offline_nodes: usize,
+    base_port: u16,
+    fedimintd_version: Version,
+    gatewayd_version: Version,
+}
+
+impl Default for DryRun {
+    fn default() -> Self {
+        let version = Version::parse(env!("CARGO_PKG_VERSION")).expect("version is semver");
+        Self {
+            test_dir: std::env::temp_dir().join("devimint-dry-run"),
+            fed_size: 4,
+            offline_nodes: 0,
+            base_port: 10000,
+            fedimintd_version: version.clone(),
+            gatewayd_version: version,
+        }
+    }
 }
+
+impl DryRun {
+    pub fn with_test_dir(mut self, test_dir: &Path) -> Self {
+        self.test_dir = test_dir.to_owned();
+        self
+    }
+
+    pub fn with_fed_size(mut self, fed_size: usize) -> Self {
+        self.fed_size = fed_size;
+        self
+    }
+
+    pub fn with_offline_nodes(mut self, offline_nodes: usize) -> Self {
+        self.offline_nodes = offline_nodes;
+        self
+    }
+
+    /// Ports are numbered consecutively from `base_port`, as the shared
+    /// allocator would hand them out if none were taken
+    pub fn with_base_port(mut self, base_port: u16) -> Self {
+        self.base_port = base_port;
+        self
+    }
+
+    /// Version of the fedimintd the guardians would run, which decides the
+    /// modules they are configured with
+    pub fn with_fedimintd_version(mut self, version: Version) -> Self {
+        self.fedimintd_version = version;
+        self
+    }
+
+    /// Version of the gatewayd the gateways would run, which decides whether
+    /// the ldk gateway is launched
+    pub fn with_gatewayd_version(mut self, version: Version) -> Self {
+        self.gatewayd_version = version;
+        self
+    }
+
+    pub async fn plan(&self) -> Result<LaunchPlan> {
+        let reserve = Reserve::dry_run(self.base_port);
+        let globals =
+            &Global::new_with_reserve(&self.test_dir, self.fed_size, self.offline_nodes, &reserve)
+                .await?;
+        let daemon =
+            |name: &str, cmd: Command, data_dir: &Path, ports: &[(&'static str, u16)]| DaemonPlan {
+                name: name.to_owned(),
+                env: cmd
+                    .cmd
+                    .as_std()
+                    .get_envs()
+                    .filter_map(|(var, value)| {
+                        Some((var.to_str()?.to_owned(), value?.to_str()?.to_owned()))
+                    })
+                    .collect(),
+                command: cmd.args_debug,
+                data_dir: data_dir.to_owned(),
+                ports: ports.iter().copied().collect(),
+            };
+
+        // at launch `which` resolves the extension when it isn't configured
+        let extension_path =
+            crate::util::get_gateway_cln_extension_path(GATEWAY_CLN_EXTENSION_FALLBACK);
+        let mut daemons = vec![
+            daemon(
+                "bitcoind",
+                Bitcoind::launch_cmd(globals),
+                &globals.FM_BTC_DIR,
+                &[
+                    ("rpc", globals.FM_PORT_BTC_RPC),
+                    ("p2p", globals.FM_PORT_BTC_P2P),
+                    ("zmq_pub_raw_block", globals.FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK),
+                    ("zmq_pub_raw_tx", globals.FM_PORT_BTC_ZMQ_PUB_RAW_TX),
+                ],
+            ),
+            daemon(
+                "lightningd",
+                Lightningd::launch_cmd(globals, &globals.FM_CLN_DIR, &extension_path),
+                &globals.FM_CLN_DIR,
+                &[
+                    ("p2p", globals.FM_PORT_CLN),
+                    ("gateway_extension", globals.FM_PORT_CLN_EXTENSION),
+                ],
+            ),
+            daemon(
+                "lnd",
+                Lnd::launch_cmd(globals),
+                &globals.FM_LND_DIR,
+                &[
+                    ("p2p", globals.FM_PORT_LND_LISTEN),
+                    ("rpc", globals.FM_PORT_LND_RPC),
+                    ("rest", globals.FM_PORT_LND_REST),
+                ],
+            ),
+            daemon(
+                "electrs",
+                Electrs::launch_cmd(globals),
+                &globals.FM_ELECTRS_DIR,
+                &[
+                    ("api", globals.FM_PORT_ELECTRS),
+                    ("monitoring", globals.FM_PORT_ELECTRS_MONITORING),
+                ],
+            ),
+        ];
+        let esplora_options = EsploraOptions::default();
+        daemons.push(daemon(
+            "esplora",
+            Esplora::launch_cmd(globals, &esplora_options),
+            &globals.FM_ESPLORA_DIR,
+            &[("api", globals.FM_PORT_ESPLORA)],
+        ));
+        if esplora_options.frontend() {
+            daemons.push(daemon(
+                "esplora-frontend",
+                Esplora::frontend_launch_cmd(globals),
+                &globals.FM_ESPLORA_DIR,
+                &[("frontend", globals.FM_PORT_ESPLORA_FRONTEND)],
+            ));
+        }
+
+        // like `Federation::new` and the DKG it runs
+        let fed_size = globals.FM_FED_SIZE;
+        let peers: Vec<_> = (0..fed_size).map(|id| PeerId::from(id as u16)).collect();
+        let base_port = reserve.ports((3 * fed_size).try_into()?)?;
+        let params = local_config_gen_params(
+            &peers,
+            base_port,
+            &ServerModuleConfigGenParamsRegistry::default(),
+        )?;
+        let bitcoin_rpc = BitcoinRpcConfig {
+            kind: globals.FM_DEFAULT_BITCOIN_RPC_KIND.clone(),
+            url: globals.FM_DEFAULT_BITCOIN_RPC_URL.parse()?,
+        };
+        let module_options = ModuleOptions {
+            kinds: None,
+            mint_fees: crate::federation::config::mint_fees_from_env()?,
+        };
+        let global_vars: BTreeMap<_, _> = globals.vars().collect();
+        let mut config_gen_params = BTreeMap::new();
+        for peer in &peers {
+            let peer_params = &params[peer];
+            let env = vars::Fedimintd::init(
+                globals,
+                peer_params.clone(),
+                "default".to_owned(),
+                base_port,
+                &reserve,
+            )
+            .await?;
+            daemons.push(daemon(
+                &format!("fedimintd-default-{}", peer.to_usize()),
+                FedimintdCmd.cmd().envs(env.vars()),
+                &env.FM_DATA_DIR,
+                &[
+                    ("p2p", peer_params.local.p2p_bind.port()),
+                    ("api", peer_params.local.api_bind.port()),
+                    (
+                        "metrics",
+                        base_port + (2 * fed_size + peer.to_usize()) as u16,
+                    ),
+                ],
+            ));
+
+            let mut consensus = peer_params.consensus.clone();
+            module_options.apply(
+                &bitcoin_rpc,
+                &mut consensus.modules,
+                crate::external::parse_bitcoin_network(&globals.FM_BITCOIN_NETWORK)?,
+                10,
+                &self.fedimintd_version,
+                // launching exports the globals to the env first
+                |var| match global_vars.get(var) {
+                    Some(value) => value != "0" && value != "false",
+                    None => is_env_var_set(var),
+                },
+            );
+            consensus.meta = crate::federation::dkg_meta();
+            config_gen_params.insert(
+                *peer,
+                PlannedConfigGenParams {
+                    our_id: peer_params.local.our_id,
+                    p2p_bind: peer_params.local.p2p_bind,
+                    api_bind: peer_params.local.api_bind,
+                    max_connections: peer_params.local.max_connections,
+                    consensus,
+                },
+            );
+        }
+
+        let mut gateways = vec![
+            ("cln", vec![("api", globals.FM_PORT_GW_CLN)]),
+            ("lnd", vec![("api", globals.FM_PORT_GW_LND)]),
+        ];
+        // like `DevJitFed`, which only launches the ldk gateway where supported
+        if self.gatewayd_version >= *VERSION_0_5_0_ALPHA {
+            gateways.push((
+                "ldk",
+                vec![
+                    ("api", globals.FM_PORT_GW_LDK),
+                    ("ldk_p2p", globals.FM_PORT_LDK),
+                ],
+            ));
+        }
+        for (ln, ports) in gateways {
+            daemons.push(daemon(
+                &format!("gatewayd-{ln}"),
+                crate::util::Gatewayd.cmd().arg(&ln),
+                &globals.FM_TEST_DIR.join(ln),
+                &ports,
+            ));
+        }
+
+        Ok(LaunchPlan {
+            test_dir: globals.FM_TEST_DIR.clone(),
+            fed_size,
+            offline_nodes: globals.FM_OFFLINE_NODES,
+            globals: globals.vars().collect(),
+            config_gen_params,
+            daemons,
+        })
+    }
+}
+
+impl DevFed {
+    /// Plans what [`dev_fed`] would launch instead of launching it
+    pub fn dry_run() -> DryRun {
+        DryRun::default()
+    }
+}
+
+/// Latencies of ecash to lightning to ecash round trips, see
+/// [`DevFed::measure_payment_latency`]
+#[derive(Debug, Clone)]
+pub struct PaymentLatency {
+    /// Of the round trips that succeeded
+    pub stats: Stats,
+    /// Round trips that failed, they are not part of the stats
+    pub failures: usize,
+}
+
+impl std::fmt::Display for PaymentLatency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, failures: {}", self.stats, self.failures)
+    }
+}
+
+/// How [`DevFed::prefund_client`] funded a client
+#[derive(Debug, Clone)]
+pub struct Prefunded {
+    /// From sending the deposit until the ecash was spendable
+    pub elapsed: Duration,
+    /// Spendable notes the client held afterwards, per denomination
+    pub notes: BTreeMap<Amount, usize>,
+}
+
+impl DevFed {
+    /// Mints `amount` of ecash into `client` with a single peg-in, so however
+    /// large `amount` is, funding costs one on-chain transaction, one batch of
+    /// confirmations and one claim transaction in consensus.
+    ///
+    /// `amount` has to be whole sats, deposit fees are paid on top of it.
+    pub async fn prefund_client(&self, client: &Client, amount: Amount) -> Result<Prefunded> {
+        anyhow::ensure!(
+            amount.msats % 1000 == 0,
+            "can only peg in whole sats, not {amount}"
+        );
+        let initial_balance = client.balance().await?;
+
+        let start_time = Instant::now();
+        self.fed.pegin_client(amount.msats / 1000, client).await?;
+        let elapsed = start_time.elapsed();
+
+        let balance = client.balance().await?;
+        anyhow::ensure!(
+            balance == initial_balance + amount.msats,
+            "client balance went from {initial_balance} to {balance} msat, expected it to grow by {amount}"
+        );
+        let notes = client.notes().await?;
+        info!(
+            target: LOG_DEVIMINT,
+            %amount,
+            elapsed_secs = elapsed.as_secs_f32(),
+            ?notes,
+            "Prefunded client"
+        );
+        Ok(Prefunded { elapsed, notes })
+    }
+
+    /// Pays `iterations` invoices of `amount` from one client through the CLN
+    /// gateway to another client receiving through the LND gateway, so every
+    /// payment leaves the federation over lightning and comes back as ecash.
+    ///
+    /// Each round trip is timed from `ln-pay` until the receiver's
+    /// `await-invoice` returns. Failed round trips are counted, not retried.
+    pub async fn measure_payment_latency(
+        &self,
+        amount: Amount,
+        iterations: usize,
+    ) -> Result<PaymentLatency> {
+        anyhow::ensure!(iterations > 0, "need at least one iteration");
+        let sender = self.fed.new_joined_client("payment-latency-sender").await?;
+        let receiver = self
+            .fed
+            .new_joined_client("payment-latency-receiver")
+            .await?;
+        // twice the amount leaves plenty of room for gateway fees
+        self.fed
+            .pegin_client(
+                2 * amount.msats * iterations as u64 / 1000 + 10_000,
+                &sender,
+            )
+            .await?;
+        let send_gw_id = self.gw_cln.gateway_id().await?;
+        let receive_gw_id = self.gw_lnd.gateway_id().await?;
+
+        let mut latencies = Vec::with_capacity(iterations);
+        let mut failures = 0;
+        for iteration in 0..iterations {
+            let result = async {
+                let invoice = ln_invoice(
+                    &receiver,
+                    amount,
+                    format!("payment-latency-{iteration}"),
+                    receive_gw_id.clone(),
+                )
+                .await?;
+                let start_time = Instant::now();
+                ln_pay(&sender, invoice.invoice, send_gw_id.clone(), false).await?;
+                cmd!(receiver, "await-invoice", invoice.operation_id.fmt_full())
+                    .run()
+                    .await?;
+                Ok::<_, anyhow::Error>(start_time.elapsed())
+            }
+            .await;
+            match result {
+                Ok(latency) => latencies.push(latency),
+                Err(err) => {
+                    warn!(target: LOG_DEVIMINT, iteration, %err, "Payment round trip failed");
+                    failures += 1;
+                }
+            }
+        }
+        anyhow::ensure!(
+            !latencies.is_empty(),
+            "all {iterations} payment round trips failed"
+        );
+
+        Ok(PaymentLatency {
+            stats: stats_for(latencies),
+            failures,
+        })
+    }
+}
+
+impl DevFed {
+    /// Samples the resident set size of every running daemon, to compare
+    /// with later in [`Self::assert_memory_growth_under`]
+    pub async fn memory_baseline(&self) -> Result<MemoryBaseline> {
+        let mut daemons = BTreeMap::new();
+        for (name, pid) in self.daemon_pids().await {
+            daemons.insert(name, (pid, rss_bytes(pid)?));
+        }
+        Ok(MemoryBaseline {
+            daemons,
+            taken_at: Instant::now(),
+        })
+    }
+
+    /// Errors if the resident set size of daemon `name`, like
+    /// `fedimintd-default-0`, grew by more than `limit_bytes` since
+    /// `baseline`, reporting by how much. Returns the growth in bytes
+    /// otherwise, which is 0 if it shrank.
+    ///
+    /// The daemon must not have been restarted since, as a new process starts
+    /// its memory usage over.
+    pub async fn assert_memory_growth_under(
+        &self,
+        baseline: &MemoryBaseline,
+        name: &str,
+        limit_bytes: u64,
+    ) -> Result<u64> {
+        let Some((baseline_pid, before)) = baseline.daemons.get(name).copied() else {
+            bail!(
+                "No memory baseline of {name}, it has one of {:?}",
+                baseline.daemons.keys().collect::<Vec<_>>()
+            );
+        };
+        let pid = self
+            .daemon_pids()
+            .await
+            .remove(name)
+            .with_context(|| format!("{name} is not running anymore"))?;
+        ensure!(
+            pid == baseline_pid,
+            "{name} was restarted since its memory baseline, pid {baseline_pid} is now {pid}"
+        );
+        let after = rss_bytes(pid)?;
+        let growth = after.saturating_sub(before);
+        let elapsed = baseline.taken_at.elapsed();
+        ensure!(
+            growth <= limit_bytes,
+            "{name} grew by {} from {} to {} in {elapsed:?}, more than the limit of {}",
+            format_mib(growth),
+            format_mib(before),
+            format_mib(after),
+            format_mib(limit_bytes)
+        );
+        info!(
+            target: LOG_DEVIMINT,
+            %name,
+            growth = %format_mib(growth),
+            limit = %format_mib(limit_bytes),
+            ?elapsed,
+            "Memory growth within limit"
+        );
+        Ok(growth)
+    }
+
+    /// Pids of the daemons of the dev federation that are running, by name
+    async fn daemon_pids(&self) -> BTreeMap<String, u32> {
+        let mut pids = BTreeMap::new();
+        for handle in self.process_handles() {
+            if let Some(pid) = handle.pid().await {
+                pids.insert(handle.name().await, pid);
+            }
+        }
+        pids
+    }
+}
+
+impl DevFed {
+    /// Waits until everything in the dev federation settled: bitcoind's
+    /// mempool is empty, electrs and esplora are at the tip, the federation
+    /// is synced to the chain, every client is idle and the lightning nodes of
+    /// the cln and lnd gateways have no payments in flight. The ldk gateway's
+    /// node runs inside gatewayd and isn't checked.
+    ///
+    /// Nothing is mined, so a transaction waiting for a block keeps the
+    /// mempool from emptying until `timeout` is hit. If settling made a
+    /// subsystem broadcast a new transaction, everything is waited for again.
+    /// Errors with the subsystem that didn't settle in time.
+    pub async fn await_quiescent(
+        &self,
+        process_mgr: &ProcessManager,
+        timeout: Duration,
+    ) -> Result<QuiescenceReport> {
+        let start = Instant::now();
+        for rounds in 1.. {
+            let remaining = timeout
+                .checked_sub(start.elapsed())
+                .context("dev federation didn't settle in time")?;
+            let settled_after = self.settle(process_mgr, remaining).await?;
+            let mempool = self.bitcoind.mempool_txids()?;
+            if mempool.is_empty() {
+                let report = QuiescenceReport {
+                    settled_after,
+                    rounds,
+                    total: start.elapsed(),
+                };
+                info!(target: LOG_DEVIMINT, ?report, slowest = ?report.slowest(), "Dev federation is quiescent");
+                return Ok(report);
+            }
+            debug!(target: LOG_DEVIMINT, rounds, ?mempool, "New transactions while settling, waiting again");
+        }
+        unreachable!()
+    }
+
+    /// Waits for every subsystem to settle at once, returning how long each
+    /// took
+    async fn settle(
+        &self,
+        process_mgr: &ProcessManager,
+        timeout: Duration,
+    ) -> Result<BTreeMap<&'static str, Duration>> {
+        let clients = Client::list_joined()?;
+        let settled = tokio::try_join!(
+            timed("mempool", self.await_empty_mempool(timeout)),
+            timed("electrs", self.await_electrs_tip(process_mgr, timeout)),
+            timed("esplora", self.await_esplora_tip(process_mgr, timeout)),
+            timed("federation", async {
+                fedimint_core::runtime::timeout(timeout, self.fed.await_block_sync())
+                    .await
+                    .map_err(|_| {
+                        anyhow!("federation not synced to the chain after {timeout:?}")
+                    })??;
+                Ok(())
+            }),
+            timed("clients", async {
+                futures::future::try_join_all(
+                    clients.iter().map(|client| client.await_idle(timeout)),
+                )
+                .await?;
+                Ok(())
+            }),
+            timed("gateways", self.await_no_pending_htlcs(timeout)),
+        )?;
+        Ok(BTreeMap::from([
+            settled.0, settled.1, settled.2, settled.3, settled.4, settled.5,
+        ]))
+    }
+
+    /// Checks bitcoind is at height `expected`, and waits for electrs,
+    /// esplora, the lightning nodes of the cln and lnd gateways and the
+    /// federation to be at it too, e.g. right after mining. The federation is
+    /// at it when its consensus block count trails the chain by the finality
+    /// delay.
+    ///
+    /// Errors with the height of each component that lags behind once the
+    /// poll times out, or right away if one is ahead.
+    pub async fn assert_synced_height(
+        &self,
+        process_mgr: &ProcessManager,
+        expected: u64,
+    ) -> Result<()> {
+        let bitcoind = self.bitcoind.get_block_count()? - 1;
+        ensure!(
+            bitcoind == expected,
+            "bitcoind at height {bitcoind}, expected {expected}"
+        );
+        let fed_expected = (expected + 1).saturating_sub(self.fed.get_finality_delay()?.into());
+        let electrs_port = process_mgr.globals.FM_PORT_ELECTRS;
+        let esplora_client = esplora_client::Builder::new(&format!(
+            "http://127.0.0.1:{}",
+            process_mgr.globals.FM_PORT_ESPLORA
+        ))
+        .build_async()?;
+        poll(&format!("stack synced to height {expected}"), || async {
+            let (electrs, esplora, cln, lnd, fed) = tokio::try_join!(
+                electrs_tip_height(electrs_port),
+                async { anyhow::Ok(u64::from(esplora_client.get_height().await?)) },
+                self.cln.block_height(),
+                self.lnd.block_height(),
+                self.fed.consensus_block_count(),
+            )
+            .map_err(ControlFlow::Continue)?;
+            let heights = BTreeMap::from([
+                ("electrs", (electrs, expected)),
+                ("esplora", (esplora, expected)),
+                ("cln", (cln, expected)),
+                ("lnd", (lnd, expected)),
+                ("federation block count", (fed, fed_expected)),
+            ]);
+            let Some(mismatches) = height_mismatches(&heights) else {
+                return Ok(());
+            };
+            if heights.values().any(|(height, expected)| expected < height) {
+                return Err(ControlFlow::Break(anyhow!(
+                    "ahead of the chain: {mismatches}"
+                )));
+            }
+            Err(ControlFlow::Continue(anyhow!(
+                "lagging behind: {mismatches}"
+            )))
+        })
+        .await?;
+        info!(target: LOG_DEVIMINT, expected, "Stack synced to height");
+        Ok(())
+    }
+
+    async fn await_empty_mempool(&self, timeout: Duration) -> Result<()> {
+        poll_with_timeout("bitcoind mempool empty", timeout, || async {
+            let mempool = self
+                .bitcoind
+                .mempool_txids()
+                .map_err(ControlFlow::Continue)?;
+            if !mempool.is_empty() {
+                return Err(ControlFlow::Continue(anyhow!(
+                    "{} transactions in the mempool: {mempool:?}",
+                    mempool.len()
+                )));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn await_electrs_tip(
+        &self,
+        process_mgr: &ProcessManager,
+        timeout: Duration,
+    ) -> Result<()> {
+        let port = process_mgr.globals.FM_PORT_ELECTRS;
+        poll_with_timeout("electrs at tip", timeout, || async {
+            let height = electrs_tip_height(port)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            self.at_tip("electrs", height)
+        })
+        .await
+    }
+
+    async fn await_esplora_tip(
+        &self,
+        process_mgr: &ProcessManager,
+        timeout: Duration,
+    ) -> Result<()> {
+        let client = esplora_client::Builder::new(&format!(
+            "http://127.0.0.1:{}",
+            process_mgr.globals.FM_PORT_ESPLORA
+        ))
+        .build_async()?;
+        poll_with_timeout("esplora at tip", timeout, || async {
+            let height = client
+                .get_height()
+                .await
+                .map_err(|err| ControlFlow::Continue(anyhow!(err)))?;
+            self.at_tip("esplora", height.into())
+        })
+        .await
+    }
+
+    fn at_tip(
+        &self,
+        indexer: &str,
+        height: u64,
+    ) -> Result<(), ControlFlow<anyhow::Error, anyhow::Error>> {
+        let tip = self
+            .bitcoind
+            .get_block_count()
+            .map_err(ControlFlow::Continue)?
+            - 1;
+        if height < tip {
+            return Err(ControlFlow::Continue(anyhow!(
+                "{indexer} at height {height}, tip is at {tip}"
+            )));
+        }
+        Ok(())
+    }
+
+    async fn await_no_pending_htlcs(&self, timeout: Duration) -> Result<()> {
+        poll_with_timeout("gateway payments settled", timeout, || async {
+            let cln = self
+                .cln
+                .pending_htlc_count()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            let lnd = self
+                .lnd
+                .pending_htlc_count()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            if cln != 0 || lnd != 0 {
+                return Err(ControlFlow::Continue(anyhow!(
+                    "HTLCs in flight: {cln} on cln, {lnd} on lnd"
+                )));
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl DevFed {
+    /// Describes the daemons devimint spawned for this dev federation, see
+    /// [`Self::export_manifest`]. Lightning nodes devimint connected to rather
+    /// than spawned are left out.
+    pub async fn manifest(&self, process_mgr: &ProcessManager) -> Result<Manifest> {
+        let globals: BTreeMap<&'static str, String> = process_mgr.globals.vars().collect();
+        let mut services = vec![];
+        for handle in self.process_handles() {
+            let Some(command) = handle.command().await else {
+                continue;
+            };
+            let mut environment: BTreeMap<String, String> = globals
+                .iter()
+                .map(|(var, value)| ((*var).to_owned(), value.clone()))
+                .collect();
+            for (var, value) in &command.envs {
+                let var = var.to_string_lossy().into_owned();
+                match value {
+                    Some(value) => environment.insert(var, value.to_string_lossy().into_owned()),
+                    None => environment.remove(&var),
+                };
+            }
+            services.push(ServiceManifest {
+                name: handle.name().await,
+                command: std::iter::once(&command.program)
+                    .chain(&command.args)
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect(),
+                environment,
+                working_dir: command.current_dir,
+                depends_on: vec![],
+            });
+        }
+        let names: Vec<String> = services
+            .iter()
+            .map(|service| service.name.clone())
+            .collect();
+        for service in &mut services {
+            let needs = service_dependencies(&service.name);
+            service.depends_on = names
+                .iter()
+                .filter(|name| {
+                    needs.iter().any(|need| {
+                        *name == need || (need.ends_with('-') && name.starts_with(need))
+                    })
+                })
+                .cloned()
+                .collect();
+        }
+
+        Ok(Manifest {
+            federation_id: self.fed.calculate_federation_id(),
+            invite_code: self.fed.invite_code()?,
+            test_dir: process_mgr.globals.FM_TEST_DIR.clone(),
+            versions: BTreeMap::from([
+                (
+                    "fedimintd",
+                    FedimintdCmd::version_or_default().await.to_string(),
+                ),
+                (
+                    "gatewayd",
+                    crate::util::Gatewayd::version_or_default()
+                        .await
+                        .to_string(),
+                ),
+                (
+                    "fedimint-cli",
+                    crate::util::FedimintCli::version_or_default()
+                        .await
+                        .to_string(),
+                ),
+                (
+                    "gateway-cli",
+                    crate::util::GatewayCli::version_or_default()
+                        .await
+                        .to_string(),
+                ),
+            ]),
+            ports: globals
+                .into_iter()
+                .filter(|(var, _)| var.starts_with("FM_PORT_"))
+                .collect(),
+            services,
+        })
+    }
+
+    /// Writes the [`Self::manifest`] of the dev federation to `path` as
+    /// `format`, for others to reproduce it without devimint. It's a
+    /// description of what got launched: writing it doesn't touch the
+    /// running daemons.
+    pub async fn export_manifest(
+        &self,
+        process_mgr: &ProcessManager,
+        format: ManifestFormat,
+        path: &Path,
+    ) -> Result<()> {
+        let manifest = self.manifest(process_mgr).await?;
+        tokio::fs::write(path, manifest.render(format))
+            .await
+            .with_context(|| format!("writing manifest to {}", path.display()))?;
+        info!(target: LOG_DEVIMINT, path = %path.display(), ?format, "Exported dev federation manifest");
+        Ok(())
+    }
+}
+
+impl DevFed {
+    /// Re-executes every op of `script` in order, stopping at the first
+    /// failure.
+    pub async fn replay(&mut self, process_mgr: &ProcessManager, script: &Script) -> Result<()> {
+        let mut recorder = Recorder::new();
+        for (idx, op) in script.ops.iter().enumerate() {
+            recorder
+                .run(self, process_mgr, op.clone())
+                .await
+                .with_context(|| format!("replaying op {idx}: {op:?}"))?;
+        }
+        Ok(())
+    }
+}
+
 pub async fn dev_fed(process_mgr: &ProcessManager) -> Result<DevFed> {
     DevJitFed::new(process_mgr, false)?
         .to_dev_fed(process_mgr)
@@ -76,6 +1209,94 @@ pub async fn dev_fed(process_mgr: &ProcessManager) -> Result<DevFed> {
 
 type JitArc<T> = JitTryAnyhow<Arc<T>>;
 
+/// Resolves once `jit` is, without borrowing it or returning its value
+fn ready<T>(jit: &JitArc<T>) -> impl Future<Output = Result<()>> + Send + 'static
+where
+    T: Send + Sync + 'static,
+{
+    let jit = jit.clone();
+    async move {
+        jit.get_try().await?;
+        Ok(())
+    }
+}
+
+/// Makes the future of a [`JitTry`] run in `span`, so the spawned daemon
+/// launches nest under the setup span in traces
+fn in_span<F, Fut>(span: &Span, f: F) -> impl FnOnce() -> Instrumented<Fut> + Send + 'static
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future,
+{
+    let span = span.clone();
+    move || f().instrument(span)
+}
+
+/// Longest a setup step is delayed by with [`FM_DEVIMINT_JIT_FUZZ_SEED_ENV`]
+const JIT_FUZZ_MAX_DELAY: Duration = Duration::from_secs(2);
+/// How long to wait for a closing transaction to reach the mempool
+const CLOSE_TX_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Seed of [`FM_DEVIMINT_JIT_FUZZ_SEED_ENV`] to delay the setup steps with, if
+/// set
+fn jit_fuzz_seed() -> Result<Option<u64>> {
+    match std::env::var(FM_DEVIMINT_JIT_FUZZ_SEED_ENV) {
+        Ok(seed) if seed == "random" => Ok(Some(rand::random())),
+        Ok(seed) if !seed.is_empty() => {
+            Ok(Some(seed.parse().with_context(|| {
+                format!("Invalid {FM_DEVIMINT_JIT_FUZZ_SEED_ENV}: {seed}")
+            })?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Runs the setup futures of a [`DevJitFed`] in its setup span, reporting each
+/// to the setup observer of the process manager once it's ready
+struct SetupSteps {
+    span: Span,
+    observer: Option<Arc<dyn SetupObserver>>,
+    start_time: SystemTime,
+    /// Delays each step by a random amount when fuzzing their order
+    fuzz: Option<StdRng>,
+}
+
+impl SetupSteps {
+    fn step<F, Fut, T>(
+        &mut self,
+        name: &'static str,
+        f: F,
+    ) -> impl FnOnce() -> Instrumented<impl Future<Output = Result<T>> + Send> + Send + 'static
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let observer = self.observer.clone();
+        let start_time = self.start_time;
+        let delay = self.fuzz_delay();
+        in_span(&self.span, move || async move {
+            if let Some(delay) = delay {
+                debug!(target: LOG_DEVIMINT, name, ?delay, "Delaying setup step");
+                runtime::sleep(delay).await;
+            }
+            let res = f().await;
+            if let (Ok(_), Some(observer)) = (&res, observer) {
+                observer.component_ready(name, start_time.elapsed().unwrap_or_default());
+            }
+            res
+        })
+    }
+
+    /// Next delay to fuzz a step with, drawn when the steps get created, so
+    /// the same seed delays each step by the same amount however they get
+    /// scheduled
+    fn fuzz_delay(&mut self) -> Option<Duration> {
+        let rng = self.fuzz.as_mut()?;
+        Some(rng.gen_range(Duration::ZERO..=JIT_FUZZ_MAX_DELAY))
+    }
+}
+
 #[derive(Clone)]
 pub struct DevJitFed {
     bitcoind: JitArc<Bitcoind>,
@@ -87,7 +1308,7 @@ pub struct DevJitFed {
     gw_ldk: JitArc<Option<Gatewayd>>,
     electrs: JitArc<Electrs>,
     esplora: JitArc<Esplora>,
-    start_time: std::time::SystemTime,
+    start_time: SystemTime,
     gw_cln_registered: JitArc<()>,
     gw_lnd_registered: JitArc<()>,
     gw_ldk_registered: JitArc<()>,
@@ -106,12 +1327,26 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
         let start_time = fedimint_core::time::now();
 
         debug!("Starting dev federation");
+        let fuzz_seed = jit_fuzz_seed()?;
+        if let Some(seed) = fuzz_seed {
+            info!(
+                target: LOG_DEVIMINT,
+                seed,
+                "Fuzzing the order of setup steps, rerun with {FM_DEVIMINT_JIT_FUZZ_SEED_ENV}={seed} to reproduce"
+            );
+        }
+        let mut setup = SetupSteps {
+            span: debug_span!("devfed_setup"),
+            observer: process_mgr.setup_observer(),
+            start_time,
+            fuzz: fuzz_seed.map(StdRng::seed_from_u64),
+        };
 
-        let bitcoind = JitTry::new_try({
+        let bitcoind = JitTry::new_try(setup.step("bitcoind", {
             let process_mgr = process_mgr.to_owned();
             move || async move { Ok(Arc::new(Bitcoind::new(&process_mgr, skip_setup).await?)) }
-        });
-        let cln = JitTry::new_try({
+        }));
+        let cln = JitTry::new_try(setup.step("cln", {
             let process_mgr = process_mgr.to_owned();
             let bitcoind = bitcoind.clone();
             || async move {
@@ -120,8 +1355,8 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                         .await?,
                 ))
             }
-        });
-        let lnd = JitTry::new_try({
+        }));
+        let lnd = JitTry::new_try(setup.step("lnd", {
             let process_mgr = process_mgr.to_owned();
             let bitcoind = bitcoind.clone();
             || async move {
@@ -129,29 +1364,37 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                     Lnd::new(&process_mgr, bitcoind.get_try().await?.deref().clone()).await?,
                 ))
             }
-        });
-        let electrs = JitTryAnyhow::new_try({
+        }));
+        let electrs = JitTryAnyhow::new_try(setup.step("electrs", {
             let process_mgr = process_mgr.to_owned();
             let bitcoind = bitcoind.clone();
             || async move {
                 let bitcoind = bitcoind.get_try().await?.deref().clone();
                 Ok(Arc::new(Electrs::new(&process_mgr, bitcoind).await?))
             }
-        });
-        let esplora = JitTryAnyhow::new_try({
+        }));
+        let esplora = JitTryAnyhow::new_try(setup.step("esplora", {
             let process_mgr = process_mgr.to_owned();
             let bitcoind = bitcoind.clone();
             || async move {
                 let bitcoind = bitcoind.get_try().await?.deref().clone();
                 Ok(Arc::new(Esplora::new(&process_mgr, bitcoind).await?))
             }
-        });
+        }));
 
-        let fed = JitTryAnyhow::new_try({
+        let fed = JitTryAnyhow::new_try(setup.step("fed", {
             let process_mgr = process_mgr.to_owned();
             let bitcoind = bitcoind.clone();
+            let esplora = esplora.clone();
             move || async move {
                 let bitcoind = bitcoind.get_try().await?.deref().clone();
+                // guardians using esplora as their backend need it from the start
+                if crate::bitcoin_backend::guardian_backends(&process_mgr.globals, fed_size)?
+                    .values()
+                    .any(|backend| backend.kind == "esplora")
+                {
+                    esplora.get_try().await?;
+                }
                 let mut fed = Federation::new(
                     &process_mgr,
                     bitcoind,
@@ -163,12 +1406,13 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
 
                 // Create a degraded federation if there are offline nodes
                 fed.degrade_federation(&process_mgr).await?;
+                info!(target: LOG_DEVIMINT, dkg_duration = ?fed.dkg_duration(), "Federation ready");
 
                 Ok(Arc::new(fed))
             }
-        });
+        }));
 
-        let gw_cln = JitTryAnyhow::new_try({
+        let gw_cln = JitTryAnyhow::new_try(setup.step("gw_cln", {
             let process_mgr = process_mgr.to_owned();
             let cln = cln.clone();
             || async move {
@@ -177,8 +1421,8 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                     Gatewayd::new(&process_mgr, LightningNode::Cln(cln)).await?,
                 ))
             }
-        });
-        let gw_cln_registered = JitTryAnyhow::new_try({
+        }));
+        let gw_cln_registered = JitTryAnyhow::new_try(setup.step("gw_cln_registered", {
             let gw_cln = gw_cln.clone();
             let fed = fed.clone();
             move || async move {
@@ -190,8 +1434,8 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                 }
                 Ok(Arc::new(()))
             }
-        });
-        let gw_lnd = JitTryAnyhow::new_try({
+        }));
+        let gw_lnd = JitTryAnyhow::new_try(setup.step("gw_lnd", {
             let process_mgr = process_mgr.to_owned();
             let lnd = lnd.clone();
             || async move {
@@ -200,8 +1444,8 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                     Gatewayd::new(&process_mgr, LightningNode::Lnd(lnd)).await?,
                 ))
             }
-        });
-        let gw_lnd_registered = JitTryAnyhow::new_try({
+        }));
+        let gw_lnd_registered = JitTryAnyhow::new_try(setup.step("gw_lnd_registered", {
             let gw_lnd = gw_lnd.clone();
             let fed = fed.clone();
             move || async move {
@@ -212,8 +1456,8 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                 }
                 Ok(Arc::new(()))
             }
-        });
-        let gw_ldk = JitTryAnyhow::new_try({
+        }));
+        let gw_ldk = JitTryAnyhow::new_try(setup.step("gw_ldk", {
             let esplora = esplora.clone();
             let process_mgr = process_mgr.to_owned();
             move || async move {
@@ -227,8 +1471,8 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                     Ok(Arc::new(None))
                 }
             }
-        });
-        let gw_ldk_registered = JitTryAnyhow::new_try({
+        }));
+        let gw_ldk_registered = JitTryAnyhow::new_try(setup.step("gw_ldk_registered", {
             let gw_ldk = gw_ldk.clone();
             let fed = fed.clone();
             move || async move {
@@ -241,9 +1485,9 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                 }
                 Ok(Arc::new(()))
             }
-        });
+        }));
 
-        let channel_opened = JitTryAnyhow::new_try({
+        let channel_opened = JitTryAnyhow::new_try(setup.step("channel_opened", {
             let process_mgr = process_mgr.to_owned();
             let lnd = lnd.clone();
             let gw_lnd = gw_lnd.clone();
@@ -284,9 +1528,9 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
 
                 Ok(Arc::new(()))
             }
-        });
+        }));
 
-        let fed_epoch_generated = JitTryAnyhow::new_try({
+        let fed_epoch_generated = JitTryAnyhow::new_try(setup.step("fed_epoch_generated", {
             let fed = fed.clone();
             move || async move {
                 let fed = fed.get_try().await?.deref().clone();
@@ -295,7 +1539,7 @@ pub fn new(process_mgr: &ProcessManager, skip_setup: bool) -> Result<DevJitFed>
                 }
                 Ok(Arc::new(()))
             }
-        });
+        }));
 
         Ok(DevJitFed {
             bitcoind,
@@ -356,6 +1600,52 @@ pub async fn bitcoind(&self) -> anyhow::Result<&Bitcoind> {
         Ok(self.bitcoind.get_try().await?.deref())
     }
 
+    // Readiness of each component: unlike with the getters above, the returned
+    // futures neither borrow `self` nor return the component, so they can be
+    // spawned or held on to while ordering custom setup steps around devimint's.
+    pub fn bitcoind_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.bitcoind)
+    }
+    pub fn electrs_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.electrs)
+    }
+    pub fn esplora_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.esplora)
+    }
+    pub fn cln_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.cln)
+    }
+    pub fn lnd_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.lnd)
+    }
+    pub fn fed_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.fed)
+    }
+    pub fn gw_cln_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.gw_cln)
+    }
+    pub fn gw_cln_registered_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.gw_cln_registered)
+    }
+    pub fn gw_lnd_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.gw_lnd)
+    }
+    pub fn gw_lnd_registered_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.gw_lnd_registered)
+    }
+    pub fn gw_ldk_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.gw_ldk)
+    }
+    pub fn gw_ldk_registered_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.gw_ldk_registered)
+    }
+    pub fn channel_opened_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.channel_opened)
+    }
+    pub fn fed_epoch_generated_ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        ready(&self.fed_epoch_generated)
+    }
+
     pub async fn internal_client(&self) -> anyhow::Result<Client> {
         Ok(self.fed().await?.internal_client().await?.clone())
     }
@@ -376,7 +1666,7 @@ pub async fn finalize(&self, process_mgr: &ProcessManager) -> anyhow::Result<()>
         );
 
         let _ = self.internal_client_gw_registered().await?;
-        let _ = self.channel_opened.get_try().await?;
+        self.channel_opened_ready().await?;
         let _ = self.gw_cln_registered().await?;
         let _ = self.gw_lnd_registered().await?;
         let _ = self.gw_ldk_registered().await?;
@@ -384,7 +1674,7 @@ pub async fn finalize(&self, process_mgr: &ProcessManager) -> anyhow::Result<()>
         let _ = self.lnd().await?;
         let _ = self.electrs().await?;
         let _ = self.esplora().await?;
-        let _ = self.fed_epoch_generated.get_try().await?;
+        self.fed_epoch_generated_ready().await?;
 
         debug!(
             target: LOG_DEVIMINT,
@@ -393,6 +1683,9 @@ pub async fn finalize(&self, process_mgr: &ProcessManager) -> anyhow::Result<()>
             elapsed_ms = %self.start_time.elapsed()?.as_millis(),
             "Dev federation ready",
         );
+        if let Some(observer) = process_mgr.setup_observer() {
+            observer.setup_complete(self.start_time.elapsed()?);
+        }
         Ok(())
     }
 
@@ -435,4 +1728,61 @@ pub async fn fast_terminate(self) {
             spawn_drop(bitcoind),
         );
     }
+
+    /// Stops all daemons, then checks with
+    /// [`ProcessManager::assert_no_leaked_processes`] that none of them
+    /// survived.
+    pub async fn shutdown(self, process_mgr: &ProcessManager) -> Result<()> {
+        self.fast_terminate().await;
+        process_mgr.assert_no_leaked_processes().await
+    }
+}
+
+#[test]
+fn test_fuzz_delay_reproducible() {
+    let setup_steps = |seed| SetupSteps {
+        span: Span::none(),
+        observer: None,
+        start_time: SystemTime::UNIX_EPOCH,
+        fuzz: seed.map(StdRng::seed_from_u64),
+    };
+    let delays = |mut setup: SetupSteps| (0..10).map(|_| setup.fuzz_delay()).collect::<Vec<_>>();
+
+    let fuzzed = delays(setup_steps(Some(42)));
+    assert_eq!(fuzzed, delays(setup_steps(Some(42))));
+    assert!(fuzzed
+        .iter()
+        .all(|delay| delay.is_some_and(|delay| delay <= JIT_FUZZ_MAX_DELAY)));
+    assert_ne!(fuzzed, delays(setup_steps(Some(43))));
+    assert!(delays(setup_steps(None)).iter().all(Option::is_none));
+}
+
+#[tokio::test]
+async fn test_dry_run_launches_nothing() -> Result<()> {
+    let test_dir = std::env::temp_dir().join(format!("devimint-dry-run-{}", std::process::id()));
+    let plan = DevFed::dry_run()
+        .with_test_dir(&test_dir)
+        .with_fed_size(2)
+        .with_base_port(20000)
+        .with_gatewayd_version(VERSION_0_4_0_ALPHA.clone())
+        .plan()
+        .await?;
+
+    assert!(!test_dir.exists());
+    assert_eq!(plan.globals["FM_PORT_BTC_RPC"], "20000");
+    let bitcoind = &plan.daemons[0];
+    assert_eq!(bitcoind.name, "bitcoind");
+    assert_eq!(
+        bitcoind.command.last(),
+        Some(&format!("-datadir={}", test_dir.join("bitcoin").display()))
+    );
+    assert!(plan
+        .daemons
+        .iter()
+        .all(|daemon| daemon.name != "gatewayd-ldk"));
+    assert_eq!(plan.config_gen_params.len(), 2);
+    for params in plan.config_gen_params.values() {
+        assert!(params.consensus.modules.iter_modules().next().is_some());
+    }
+    Ok(())
 }
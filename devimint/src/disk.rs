@@ -0,0 +1,176 @@
+//! Running guardians out of disk space.
+//!
+//! A guardian can get its data dir on a small ext4 filesystem of its own,
+//! backed by a loopback image. Filling that filesystem on demand tests how the
+//! guardian copes with failing writes, and freeing it again whether it
+//! recovers without having corrupted its state.
+
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context as _, Result};
+use fedimint_logging::LOG_DEVIMINT;
+use tracing::{debug, info, warn};
+
+use crate::envs::FM_GUARDIAN_DISK_MB_ENV;
+use crate::util::{cmd, guardian_list_from_env};
+
+/// Smallest filesystem a guardian is given, enough for its config and a
+/// fresh database
+pub const MIN_DISK_MB: u64 = 32;
+
+/// Name of the file [`GuardianDisk::fill`] takes up the free space with
+const FILLER: &str = "disk-filler";
+
+/// Whether loopback filesystems can be mounted here, which requires linux,
+/// root, `mkfs.ext4` and a loop device
+pub fn available() -> bool {
+    cfg!(target_os = "linux")
+        && nix::unistd::geteuid().is_root()
+        && std::process::Command::new("mkfs.ext4")
+            .arg("-V")
+            .output()
+            .is_ok_and(|out| out.status.success())
+        && std::process::Command::new("losetup")
+            .arg("--find")
+            .output()
+            .is_ok_and(|out| out.status.success())
+}
+
+/// Disk sizes of `servers` guardians from `FM_GUARDIAN_DISK_MB` in MiB, by
+/// peer id. Guardians with an empty entry keep their data dir on the host's
+/// filesystem and are left out.
+///
+/// Warns and returns no disks if some were requested but loopback mounts are
+/// unavailable, so only tests relying on them fail.
+pub fn guardian_disks(servers: usize) -> Result<BTreeMap<usize, u64>> {
+    let disks = guardian_list_from_env(FM_GUARDIAN_DISK_MB_ENV, servers, |size_mb| {
+        let size_mb: u64 = size_mb.parse()?;
+        ensure!(
+            MIN_DISK_MB <= size_mb,
+            "disk size {size_mb} MiB is below the minimum of {MIN_DISK_MB} MiB"
+        );
+        Ok(size_mb)
+    })?;
+    if !disks.is_empty() && !available() {
+        warn!(
+            target: LOG_DEVIMINT,
+            "{FM_GUARDIAN_DISK_MB_ENV} is set but loopback mounts are unavailable (requires linux, root and mkfs.ext4), guardians will use the host's disk"
+        );
+        return Ok(BTreeMap::new());
+    }
+    Ok(disks)
+}
+
+/// A size capped filesystem mounted at a guardian's data dir, unmounted when
+/// dropped
+#[derive(Debug)]
+pub struct GuardianDisk {
+    /// Data dir the filesystem is mounted at
+    pub mount_point: PathBuf,
+    /// Loopback image backing the filesystem
+    pub image: PathBuf,
+}
+
+impl GuardianDisk {
+    /// Mounts a new filesystem of `size_mb` MiB at `data_dir`, which must
+    /// still be empty as its content gets hidden by the mount
+    pub async fn create(data_dir: &Path, size_mb: u64) -> Result<Self> {
+        ensure!(
+            tokio::fs::read_dir(data_dir)
+                .await?
+                .next_entry()
+                .await?
+                .is_none(),
+            "data dir {} is not empty",
+            data_dir.display()
+        );
+        let disk = Self {
+            mount_point: data_dir.to_owned(),
+            image: data_dir.with_extension("img"),
+        };
+        debug!(target: LOG_DEVIMINT, mount_point = %disk.mount_point.display(), size_mb, "Creating guardian disk");
+
+        tokio::fs::File::create(&disk.image)
+            .await?
+            .set_len(size_mb * 1024 * 1024)
+            .await?;
+        // no blocks reserved for root, which guardians usually run as here
+        cmd!("mkfs.ext4", "-q", "-F", "-m", "0", disk.image.display())
+            .run()
+            .await?;
+        cmd!(
+            "mount",
+            "-o",
+            "loop",
+            disk.image.display(),
+            disk.mount_point.display()
+        )
+        .run()
+        .await?;
+        Ok(disk)
+    }
+
+    /// Takes up all free space of the filesystem, returning how many bytes
+    /// were taken
+    pub async fn fill(&self) -> Result<u64> {
+        let filler = self.mount_point.join(FILLER);
+        let filled = tokio::task::spawn_blocking(move || -> Result<u64> {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&filler)?;
+            let chunk = vec![0; 1024 * 1024];
+            let mut filled = 0;
+            // shrink the writes to also take up the last partial chunk
+            let mut len = chunk.len();
+            loop {
+                match file.write(&chunk[..len]) {
+                    Ok(written) => filled += written as u64,
+                    Err(err) if err.raw_os_error() == Some(nix::errno::Errno::ENOSPC as i32) => {
+                        if len == 1 {
+                            break;
+                        }
+                        len /= 2;
+                    }
+                    Err(err) => return Err(err).context("filling guardian disk"),
+                }
+            }
+            // space only counts as taken once it's on disk
+            match file.sync_all() {
+                Ok(()) => {}
+                Err(err) if err.raw_os_error() == Some(nix::errno::Errno::ENOSPC as i32) => {}
+                Err(err) => return Err(err).context("syncing guardian disk filler"),
+            }
+            Ok(filled)
+        })
+        .await??;
+        info!(target: LOG_DEVIMINT, mount_point = %self.mount_point.display(), filled, "Filled guardian disk");
+        Ok(filled)
+    }
+
+    /// Frees the space taken by [`Self::fill`]
+    pub async fn free(&self) -> Result<()> {
+        match tokio::fs::remove_file(self.mount_point.join(FILLER)).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).context("removing guardian disk filler"),
+        }
+        info!(target: LOG_DEVIMINT, mount_point = %self.mount_point.display(), "Freed guardian disk");
+        Ok(())
+    }
+}
+
+impl Drop for GuardianDisk {
+    fn drop(&mut self) {
+        // lazily, so a guardian still shutting down doesn't keep it mounted
+        if let Err(err) = std::process::Command::new("umount")
+            .arg("--lazy")
+            .arg(&self.mount_point)
+            .output()
+        {
+            warn!(target: LOG_DEVIMINT, mount_point = %self.mount_point.display(), %err, "Failed to unmount guardian disk");
+        }
+    }
+}
@@ -12,6 +12,14 @@
 // Env variable to TODO
 pub const FM_PORT_GW_LND_ENV: &str = "FM_PORT_GW_LND";
 
+// lnurl-server.rs
+
+// Env variable to set the address the LNURL server listens on
+pub const FM_LNURL_SERVER_BIND_ADDR_ENV: &str = "FM_LNURL_SERVER_BIND_ADDR";
+
+// Env variable to set where the LNURL server writes its https certificate
+pub const FM_LNURL_SERVER_CERT_ENV: &str = "FM_LNURL_SERVER_CERT";
+
 // tests.rs
 
 // Env variable to TODO
@@ -28,6 +36,16 @@
 // Env variable to TODO
 pub const FM_GATEWAY_API_ADDR_ENV: &str = "FM_GATEWAY_API_ADDR";
 
+// Env variable to set how long a gateway's registrations with federations stay
+// valid, in seconds
+pub const FM_GATEWAY_REGISTRATION_TTL_SECS_ENV: &str = "FM_GATEWAY_REGISTRATION_TTL_SECS";
+
+// Env variable to restrict the directions a gateway routes payments in
+pub const FM_GATEWAY_MODE_ENV: &str = "FM_GATEWAY_MODE";
+
+// Env variable to set the bitcoin network of the gateway
+pub const FM_GATEWAY_NETWORK_ENV: &str = "FM_GATEWAY_NETWORK";
+
 // federation.rs
 
 // Env variable to set client's data directory
@@ -37,6 +55,41 @@
 // and db
 pub const FM_CLIENT_DIR_ENV: &str = "FM_CLIENT_DIR";
 
+// Env variable to run each guardian in its own network namespace, if
+// namespaces are available
+pub const FM_GUARDIAN_NETNS_ENV: &str = "FM_GUARDIAN_NETNS";
+
+// Env variable to name the guardians in peer id order, as comma separated
+// labels like `alice,bob`, guardians without one are `guardian-<id>`
+pub const FM_GUARDIAN_LABELS_ENV: &str = "FM_GUARDIAN_LABELS";
+
+// Env variable to skew the clocks of the guardians in peer id order, as comma
+// separated offsets in seconds like `0,0,30,-30`, requires libfaketime
+pub const FM_GUARDIAN_CLOCK_SKEW_SECS_ENV: &str = "FM_GUARDIAN_CLOCK_SKEW_SECS";
+
+// Env variable to bind the guardian apis to other addresses than localhost in
+// peer id order, as comma separated `ADDR` or `ADDR@HOST` entries like
+// `0.0.0.0@192.168.1.5,,10.0.0.2`, where `HOST` is advertised in the invite code
+pub const FM_GUARDIAN_API_BIND_ENV: &str = "FM_GUARDIAN_API_BIND";
+
+// Env variable to point the guardians' wallets at their own bitcoin backends in
+// peer id order, as comma separated `bitcoind`, `esplora` or `KIND=URL` entries
+// like `,,,esplora`, guardians without one use devimint's bitcoind
+pub const FM_GUARDIAN_BITCOIN_RPC_ENV: &str = "FM_GUARDIAN_BITCOIN_RPC";
+
+// Env variable to make the mint charge fees per note, as `ISSUANCE,SPEND` in
+// msats like `10,20`, the mint charges no fees by default
+pub const FM_MINT_FEES_ENV: &str = "FM_MINT_FEES";
+
+// Env variable to put the guardians' data dirs on size capped filesystems in
+// peer id order, as comma separated sizes in MiB like `,64`, guardians without
+// one use the host's disk
+pub const FM_GUARDIAN_DISK_MB_ENV: &str = "FM_GUARDIAN_DISK_MB";
+
+// Env variable to put a proxy that can corrupt api responses in front of the
+// guardians with these comma separated peer ids, like `1`
+pub const FM_GUARDIAN_RESPONSE_PROXY_ENV: &str = "FM_GUARDIAN_RESPONSE_PROXY";
+
 // cli.rs
 
 // Env variable to set the testing directory of the client
@@ -54,6 +107,102 @@
 // Env variable to set a federation's invite code
 pub const FM_INVITE_CODE_ENV: &str = "FM_INVITE_CODE";
 
+// Env variable to cooperatively close the gateways' channels when devfed shuts
+// down
+pub const FM_CLOSE_CHANNELS_ON_SHUTDOWN_ENV: &str = "FM_CLOSE_CHANNELS_ON_SHUTDOWN";
+
+// Env variable to override `RUST_LOG` for individual daemons, as
+// `<daemon>:<directives>` pairs separated by `;`
+pub const FM_DAEMON_RUST_LOG_ENV: &str = "FM_DAEMON_RUST_LOG";
+
+// Env variable to run individual daemons in a cgroup with resource limits, as
+// `<daemon>:<limits>` pairs separated by `;`, with limits like
+// `memory=512M,cpu=50` for 512 MiB and half a CPU
+pub const FM_DAEMON_RESOURCE_LIMITS_ENV: &str = "FM_DAEMON_RESOURCE_LIMITS";
+
+// Env variable to mine a block every N seconds in the background instead of
+// only on demand
+pub const FM_BLOCK_INTERVAL_ENV: &str = "FM_BLOCK_INTERVAL";
+
+// Env variable to set how many federations `devimint dev-fed` launches over
+// the shared bitcoind and lightning nodes
+pub const FM_NUM_FEDS_ENV: &str = "FM_NUM_FEDS";
+
+// Env variable to restart crashed daemons up to this many times each, for
+// soak tests
+pub const FM_MAX_DAEMON_RESTARTS_ENV: &str = "FM_MAX_DAEMON_RESTARTS";
+
+// Env variable to spawn all daemons into one process group that a single
+// signal can stop
+pub const FM_DEVIMINT_PROCESS_GROUP_ENV: &str = "FM_DEVIMINT_PROCESS_GROUP";
+
+// Env variable to let browsers on these origins (e.g. `*`) call esplora's HTTP
+// API cross-origin
+pub const FM_ESPLORA_CORS_ENV: &str = "FM_ESPLORA_CORS";
+
+// Env variable to also launch the esplora web frontend against esplora's API
+pub const FM_ESPLORA_FRONTEND_ENV: &str = "FM_ESPLORA_FRONTEND";
+
+// Env variable to append setup progress as JSON lines to this file, like
+// `/dev/fd/3`
+pub const FM_DEVIMINT_SETUP_EVENTS_ENV: &str = "FM_DEVIMINT_SETUP_EVENTS";
+
+// Env variable to have guardians log JSON lines, for tests to parse with
+// `ProcessManager::tail_logs`
+pub const FM_GUARDIAN_JSON_LOGS_ENV: &str = "FM_GUARDIAN_JSON_LOGS";
+
+// Env variable to export devimint's tracing spans to the OTLP/HTTP collector
+// at this url, like `http://localhost:4318`, e.g. to view the startup timeline
+// in Grafana Tempo or Jaeger. Needs the `telemetry` feature.
+pub const FM_DEVIMINT_OTLP_ENDPOINT_ENV: &str = "FM_DEVIMINT_OTLP_ENDPOINT";
+
+// Env variable to name this devimint run, exported env vars are then also
+// written prefixed with `FM_RUN_<RUN_ID>_` to tell concurrent runs apart
+pub const FM_DEVIMINT_RUN_ID_ENV: &str = "FM_DEVIMINT_RUN_ID";
+
+// Env variable devimint exports with the prefix of the run's env var names,
+// empty without a run id
+pub const FM_DEVIMINT_ENV_PREFIX_ENV: &str = "FM_DEVIMINT_ENV_PREFIX";
+
+// Env variable to run the stack on a bitcoin network other than `regtest`,
+// currently only `signet`
+pub const FM_BITCOIN_NETWORK_ENV: &str = "FM_BITCOIN_NETWORK";
+
+// external.rs
+
+// Env variable to attach to an already running lightningd through this RPC
+// socket instead of spawning one
+pub const FM_EXTERNAL_CLN_SOCKET_ENV: &str = "FM_EXTERNAL_CLN_SOCKET";
+
+// Env variable to attach to an already running lnd at this gRPC address
+// instead of spawning one, requires `FM_EXTERNAL_LND_TLS_CERT` and
+// `FM_EXTERNAL_LND_MACAROON`
+pub const FM_EXTERNAL_LND_RPC_ADDR_ENV: &str = "FM_EXTERNAL_LND_RPC_ADDR";
+
+// Env variable to set the TLS certificate of the external lnd
+pub const FM_EXTERNAL_LND_TLS_CERT_ENV: &str = "FM_EXTERNAL_LND_TLS_CERT";
+
+// Env variable to set the admin macaroon of the external lnd
+pub const FM_EXTERNAL_LND_MACAROON_ENV: &str = "FM_EXTERNAL_LND_MACAROON";
+
+// bitcoind_rpc.rs
+
+// Env variable to append every bitcoind RPC call devimint makes to this file,
+// as JSON lines with the method, params, duration and result or error
+pub const FM_TRACE_BITCOIND_RPC_ENV: &str = "FM_TRACE_BITCOIND_RPC";
+
+// devfed.rs
+
+// Env variable to delay each setup step of a dev federation by a random amount
+// seeded with this u64, or a random seed for `random`, to shake out ordering
+// bugs between them
+pub const FM_DEVIMINT_JIT_FUZZ_SEED_ENV: &str = "FM_DEVIMINT_JIT_FUZZ_SEED";
+
+// lib.rs
+
+// Env variable to collect all logs into this directory when a devfed test fails
+pub const FM_COLLECT_LOGS_DIR_ENV: &str = "FM_COLLECT_LOGS_DIR";
+
 // util.rs
 
 // Env variable to override gatewayd binary set:
@@ -113,12 +262,18 @@
 // Env variable to override esplora binary set:
 pub const FM_ESPLORA_BASE_EXECUTABLE_ENV: &str = "FM_ESPLORA_BASE_EXECUTABLE";
 
+// Env variable to override esplora-frontend binary set:
+pub const FM_ESPLORA_FRONTEND_BASE_EXECUTABLE_ENV: &str = "FM_ESPLORA_FRONTEND_BASE_EXECUTABLE";
+
 // Env variable to override esplora binary set:
 pub const FM_RECOVERYTOOL_BASE_EXECUTABLE_ENV: &str = "FM_RECOVERYTOOL_BASE_EXECUTABLE";
 
 // Env variable to override esplora binary set:
 pub const FM_FAUCET_BASE_EXECUTABLE_ENV: &str = "FM_FAUCET_BASE_EXECUTABLE";
 
+// Env variable to override lnurl-server binary set:
+pub const FM_LNURL_SERVER_BASE_EXECUTABLE_ENV: &str = "FM_LNURL_SERVER_BASE_EXECUTABLE";
+
 // Env variable to override esplora binary set:
 pub const FM_FEDIMINT_DBTOOL_BASE_EXECUTABLE_ENV: &str = "FM_FEDIMINT_DBTOOL_BASE_EXECUTABLE";
 
@@ -134,6 +289,10 @@
 // Env variable to define command for the LND client
 pub const FM_GWCLI_LND_ENV: &str = "FM_GWCLI_LND";
 
+// Env variable devimint sets to its own pid on every daemon it spawns, so
+// processes forked off a daemon can be traced back to devimint
+pub const FM_DEVIMINT_SUPERVISOR_PID_ENV: &str = "FM_DEVIMINT_SUPERVISOR_PID";
+
 /// Make `devimint` print stderr of called commands directly on its own stderr
 pub const FM_DEVIMINT_CMD_INHERIT_STDERR_ENV: &str = "FM_DEVIMINT_CMD_INHERIT_STDERR";
 
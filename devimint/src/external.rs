@@ -1,46 +1,100 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
 use std::ops::ControlFlow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use bitcoincore_rpc::bitcoin::{Address, BlockHash};
-use bitcoincore_rpc::bitcoincore_rpc_json::{GetBalancesResult, GetBlockchainInfoResult};
+use bitcoincore_rpc::bitcoincore_rpc_json::{
+    CreateRawTransactionInput, GetBalancesResult, GetBlockchainInfoResult, GetMempoolEntryResult,
+    ListUnspentResultEntry,
+};
 use bitcoincore_rpc::{bitcoin, RpcApi};
-use cln_rpc::primitives::{Amount as ClnRpcAmount, AmountOrAny};
+use cln_rpc::model::responses::{
+    ListpeerchannelsChannelsHtlcsDirection, ListpeerchannelsChannelsState,
+};
+use cln_rpc::primitives::{Amount as ClnRpcAmount, AmountOrAny, ChannelState};
 use cln_rpc::ClnRpc;
 use fedimint_core::encoding::Encodable;
+use fedimint_core::envs::is_env_var_set;
 use fedimint_core::task::jit::{JitTry, JitTryAnyhow};
-use fedimint_core::task::{block_in_place, block_on, sleep, timeout};
+use fedimint_core::task::{sleep, timeout, TaskGroup};
 use fedimint_core::util::write_overwrite_async;
 use fedimint_core::BitcoinHash;
 use fedimint_logging::LOG_DEVIMINT;
 use fedimint_testing::gateway::LightningNodeType;
 use hex::ToHex;
 use itertools::Itertools;
+use ln_gateway::envs::{
+    FM_GATEWAY_LIGHTNING_ADDR_ENV, FM_LND_MACAROON_ENV, FM_LND_RPC_ADDR_ENV, FM_LND_TLS_CERT_ENV,
+};
 use tokio::fs;
 use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
 use tokio::time::Instant;
-use tonic_lnd::lnrpc::{ChanInfoRequest, GetInfoRequest, ListChannelsRequest};
+use tonic_lnd::lnrpc::channel_point::FundingTxid;
+use tonic_lnd::lnrpc::policy_update_request::Scope;
+use tonic_lnd::lnrpc::{
+    ChanInfoRequest, ChannelGraphRequest, ChannelPoint, GetInfoRequest, ListChannelsRequest,
+    PendingChannelsRequest, PolicyUpdateRequest,
+};
 use tonic_lnd::Client as LndClient;
-use tracing::{debug, info, trace, warn};
-
-use crate::util::{poll, ClnLightningCli, GatewayClnExtension, ProcessHandle, ProcessManager};
-use crate::vars::utf8;
+use tracing::{debug, info, instrument, trace, warn};
+
+use crate::bitcoind_rpc::BitcoindRpc;
+use crate::envs::{
+    FM_BITCOIN_NETWORK_ENV, FM_ESPLORA_CORS_ENV, FM_ESPLORA_FRONTEND_ENV,
+    FM_EXTERNAL_CLN_SOCKET_ENV, FM_EXTERNAL_LND_MACAROON_ENV, FM_EXTERNAL_LND_RPC_ADDR_ENV,
+    FM_EXTERNAL_LND_TLS_CERT_ENV,
+};
+use crate::util::{
+    block_in_place, poll, poll_with_timeout, ClnLightningCli, Command, GatewayClnExtension,
+    LaunchKind, ProcessHandle, ProcessManager,
+};
+use crate::vars::{utf8, Global};
 use crate::version_constants::VERSION_0_4_0_ALPHA;
 use crate::{cmd, poll_eq, Gatewayd};
 
+/// Bitcoin network the stack runs on, `regtest` unless
+/// [`FM_BITCOIN_NETWORK_ENV`] is set
+pub fn bitcoin_network() -> Result<bitcoin::Network> {
+    parse_bitcoin_network(
+        &std::env::var(FM_BITCOIN_NETWORK_ENV).unwrap_or_else(|_| "regtest".to_owned()),
+    )
+}
+
+pub(crate) fn parse_bitcoin_network(network: &str) -> Result<bitcoin::Network> {
+    match network {
+        "regtest" => Ok(bitcoin::Network::Regtest),
+        "signet" => Ok(bitcoin::Network::Signet),
+        "testnet4" => {
+            bail!(
+                "testnet4 is not supported, the bitcoin library fedimint is built with predates it"
+            )
+        }
+        other => bail!("unsupported {FM_BITCOIN_NETWORK_ENV} {other}, expected regtest or signet"),
+    }
+}
+
 #[derive(Clone)]
 pub struct Bitcoind {
-    pub client: Arc<bitcoincore_rpc::Client>,
-    pub(crate) wallet_client: Arc<JitTryAnyhow<Arc<bitcoincore_rpc::Client>>>,
-    pub(crate) _process: ProcessHandle,
+    pub client: Arc<BitcoindRpc>,
+    pub(crate) wallet_client: Arc<JitTryAnyhow<Arc<BitcoindRpc>>>,
+    pub(crate) network: bitcoin::Network,
+    pub(crate) launch_kind: LaunchKind,
+    pub(crate) process: ProcessHandle,
 }
 
 impl Bitcoind {
+    /// Starts bitcoind on the network selected by [`bitcoin_network`].
+    ///
+    /// Only `regtest` chains are mined, on other networks the wallet has to be
+    /// funded from elsewhere and blocks arrive at the network's own pace.
+    #[instrument(name = "bitcoind", level = "debug", skip_all)]
     pub async fn new(processmgr: &ProcessManager, skip_setup: bool) -> Result<Self> {
-        let btc_dir = utf8(&processmgr.globals.FM_BTC_DIR);
+        let chain = &processmgr.globals.FM_BITCOIN_NETWORK;
 
         // TODO(support:v0.3)
         // we need to run with txindex for versions before 0.4.0-alpha to correctly
@@ -52,22 +106,56 @@ pub async fn new(processmgr: &ProcessManager, skip_setup: bool) -> Result<Self>
             "0"
         };
 
-        let conf = format!(
+        let mut conf = format!(
             include_str!("cfg/bitcoin.conf"),
             rpc_port = processmgr.globals.FM_PORT_BTC_RPC,
             p2p_port = processmgr.globals.FM_PORT_BTC_P2P,
             zmq_pub_raw_block = processmgr.globals.FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK,
             zmq_pub_raw_tx = processmgr.globals.FM_PORT_BTC_ZMQ_PUB_RAW_TX,
             tx_index = tx_index,
+            chain = chain,
         );
+        if crate::netns::requested() && crate::netns::available() {
+            // guardians in network namespaces reach the rpc from their subnets, this
+            // ends up in the network's section where rpcbind takes effect
+            conf.push_str("rpcbind=0.0.0.0\nrpcallowip=127.0.0.1\nrpcallowip=10.0.0.0/8\n");
+        }
         write_overwrite_async(processmgr.globals.FM_BTC_DIR.join("bitcoin.conf"), conf).await?;
+        let launch_kind =
+            LaunchKind::detect("bitcoind", &processmgr.globals.FM_BTC_DIR.join(chain)).await?;
         let process = processmgr
-            .spawn_daemon(
-                "bitcoind",
-                cmd!(crate::util::Bitcoind, "-datadir={btc_dir}"),
-            )
+            .spawn_daemon("bitcoind", Self::launch_cmd(&processmgr.globals))
             .await?;
 
+        Self::connect(processmgr, process, launch_kind, skip_setup)
+    }
+
+    /// What [`Self::new`] spawns bitcoind with
+    pub(crate) fn launch_cmd(globals: &Global) -> Command {
+        let btc_dir = utf8(&globals.FM_BTC_DIR);
+        cmd!(crate::util::Bitcoind, "-datadir={btc_dir}")
+    }
+
+    /// Attaches to the bitcoind another devimint spawned with the same
+    /// globals, which keeps owning it, see [`crate::devfed::DevFedHandle`]
+    pub async fn reattach(processmgr: &ProcessManager) -> Result<Self> {
+        let this = Self::connect(
+            processmgr,
+            ProcessHandle::reattached("bitcoind"),
+            LaunchKind::Reattached,
+            true,
+        )?;
+        this.poll_ready().await?;
+        Ok(this)
+    }
+
+    fn connect(
+        processmgr: &ProcessManager,
+        process: ProcessHandle,
+        launch_kind: LaunchKind,
+        skip_setup: bool,
+    ) -> Result<Self> {
+        let network = bitcoin_network()?;
         let url = processmgr.globals.FM_BITCOIN_RPC_URL.parse()?;
         debug!("Parsed FM_BITCOIN_RPC_URL: {:?}", &url);
         let (host, auth) = fedimint_bitcoind::bitcoincore::from_url_to_url_auth(&url)?;
@@ -77,21 +165,41 @@ pub async fn new(processmgr: &ProcessManager, skip_setup: bool) -> Result<Self>
         let wallet_client = JitTry::new_try(move || async move {
             let client =
                 Self::new_bitcoin_rpc(&host, auth).context("Failed to connect to bitcoind")?;
-            Self::init(&client, skip_setup).await?;
+            Self::init(&client, network, skip_setup).await?;
             Ok(Arc::new(client))
         });
 
         Ok(Self {
-            _process: process,
+            process,
             client: Arc::new(client),
             wallet_client: Arc::new(wallet_client),
+            network,
+            launch_kind,
         })
     }
 
-    fn new_bitcoin_rpc(
-        url: &str,
-        auth: bitcoincore_rpc::Auth,
-    ) -> anyhow::Result<bitcoincore_rpc::Client> {
+    pub fn network(&self) -> bitcoin::Network {
+        self.network
+    }
+
+    /// Errors unless blocks can be mined on demand, which is only possible on
+    /// `regtest`
+    fn ensure_mining_supported(&self) -> Result<()> {
+        ensure!(
+            self.network == bitcoin::Network::Regtest,
+            "can't mine blocks on {}, only on regtest",
+            self.network
+        );
+        Ok(())
+    }
+
+    /// Whether bitcoind started on an existing chain, which already has the
+    /// initial blocks mined
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+
+    fn new_bitcoin_rpc(url: &str, auth: bitcoincore_rpc::Auth) -> anyhow::Result<BitcoindRpc> {
         // The default (15s) is too low for some test environments
         const RPC_TIMEOUT: Duration = Duration::from_secs(45);
         let mut builder = bitcoincore_rpc::jsonrpc::simple_http::Builder::new()
@@ -102,16 +210,18 @@ fn new_bitcoin_rpc(
             builder = builder.auth(user, pass);
         }
         let client = bitcoincore_rpc::jsonrpc::Client::with_transport(builder.build());
-        Ok(bitcoincore_rpc::Client::from_jsonrpc(client))
+        BitcoindRpc::new(bitcoincore_rpc::Client::from_jsonrpc(client))
     }
 
-    pub(crate) async fn init(client: &bitcoincore_rpc::Client, skip_setup: bool) -> Result<()> {
+    pub(crate) async fn init(
+        client: &BitcoindRpc,
+        network: bitcoin::Network,
+        skip_setup: bool,
+    ) -> Result<()> {
         debug!("Setting up bitcoind");
         // create RPC wallet
         for attempt in 0.. {
-            match fedimint_core::runtime::block_in_place(|| {
-                client.create_wallet("", None, None, None, None)
-            }) {
+            match block_in_place(|| client.create_wallet("", None, None, None, None)) {
                 Ok(_) => {
                     break;
                 }
@@ -127,6 +237,19 @@ pub(crate) async fn init(client: &bitcoincore_rpc::Client, skip_setup: bool) ->
             }
         }
 
+        if network != bitcoin::Network::Regtest {
+            // nothing to mine, wait for the chain to catch up with the network instead
+            poll("bitcoind initial block download", || async {
+                let info = block_in_place(|| client.get_blockchain_info())
+                    .context("bitcoind getblockchaininfo")
+                    .map_err(ControlFlow::Continue)?;
+                poll_eq!(info.initial_block_download, false)
+            })
+            .await?;
+            debug!("Bitcoind ready");
+            return Ok(());
+        }
+
         if !skip_setup {
             // mine blocks
             let blocks = 101;
@@ -185,7 +308,13 @@ pub fn get_block_count(&self) -> Result<u64> {
         Ok(block_in_place(|| self.client.get_block_count())? + 1)
     }
 
+    /// Transactions in bitcoind's mempool
+    pub fn mempool_txids(&self) -> Result<Vec<bitcoin::Txid>> {
+        Ok(block_in_place(|| self.client.get_raw_mempool())?)
+    }
+
     pub async fn mine_blocks_no_wait(&self, block_num: u64) -> Result<u64> {
+        self.ensure_mining_supported()?;
         let start_time = Instant::now();
         debug!(target: LOG_DEVIMINT, ?block_num, "Mining bitcoin blocks");
         let addr = self.get_new_address().await?;
@@ -199,6 +328,7 @@ pub async fn mine_blocks_no_wait(&self, block_num: u64) -> Result<u64> {
     }
 
     pub async fn mine_blocks(&self, block_num: u64) -> Result<()> {
+        self.ensure_mining_supported()?;
         let start_time = Instant::now();
         debug!(target: LOG_DEVIMINT, ?block_num, "Mining bitcoin blocks");
         let addr = self.get_new_address().await?;
@@ -216,6 +346,28 @@ pub async fn mine_blocks(&self, block_num: u64) -> Result<()> {
         Ok(())
     }
 
+    /// Mine a block every `interval` in the background to mimic the cadence
+    /// of a real chain, on top of any blocks mined on demand.
+    ///
+    /// Runs until `task_group` shuts down; pass a subgroup to be able to stop
+    /// the miner independently.
+    pub fn spawn_block_miner(&self, task_group: &TaskGroup, interval: Duration) {
+        if let Err(err) = self.ensure_mining_supported() {
+            warn!(target: LOG_DEVIMINT, %err, "Not starting background block miner");
+            return;
+        }
+        info!(target: LOG_DEVIMINT, interval_secs = interval.as_secs(), "Starting background block miner");
+        let bitcoind = self.clone();
+        task_group.spawn_cancellable("bitcoind block miner", async move {
+            loop {
+                sleep(interval).await;
+                if let Err(err) = bitcoind.mine_blocks_no_wait(1).await {
+                    warn!(target: LOG_DEVIMINT, %err, "Background block mining failed");
+                }
+            }
+        });
+    }
+
     pub async fn send_to(&self, addr: String, amount: u64) -> Result<bitcoin::Txid> {
         debug!(target: LOG_DEVIMINT, amount, addr, "Sending funds from bitcoind");
         let amount = bitcoin::Amount::from_sat(amount);
@@ -238,6 +390,8 @@ pub fn get_raw_transaction(&self, txid: &bitcoin::Txid) -> Result<String> {
         Ok(bytes.encode_hex())
     }
 
+    /// New receive address of the bitcoind wallet, each call returns a
+    /// different one
     pub async fn get_new_address(&self) -> Result<Address> {
         let client = &self.wallet_client().await?;
         let addr = block_in_place(|| client.client.get_new_address(None, None))?.assume_checked();
@@ -249,6 +403,7 @@ pub async fn generate_to_address(
         block_num: u64,
         address: &Address,
     ) -> Result<Vec<BlockHash>> {
+        self.ensure_mining_supported()?;
         let client = &self.wallet_client().await?;
         Ok(block_in_place(|| {
             client.client.generate_to_address(block_num, address)
@@ -266,10 +421,77 @@ pub async fn get_tx_out_proof(
         })?)
     }
 
+    /// Waits until `txid` is in bitcoind's mempool, returning its mempool
+    /// entry. Confirmed transactions are not in the mempool, so this must be
+    /// called before mining them.
+    pub async fn await_in_mempool(
+        &self,
+        txid: &bitcoin::Txid,
+        timeout: Duration,
+    ) -> Result<GetMempoolEntryResult> {
+        poll_with_timeout("Waiting for transaction in mempool", timeout, || async {
+            block_in_place(|| self.client.get_mempool_entry(txid))
+                .with_context(|| format!("getmempoolentry {txid}"))
+                .map_err(ControlFlow::Continue)
+        })
+        .await
+    }
+
+    /// Funding outpoint of the lightning channel `short_channel_id`, which
+    /// encodes the height of the funding transaction's block, its index in
+    /// the block and the index of the funding output
+    pub async fn channel_funding_outpoint(
+        &self,
+        short_channel_id: u64,
+    ) -> Result<bitcoin::OutPoint> {
+        let height = short_channel_id >> 40;
+        let tx_index = usize::try_from((short_channel_id >> 16) & 0xff_ffff)?;
+        let vout = u32::try_from(short_channel_id & 0xffff)?;
+        let block = block_in_place(|| {
+            let hash = self.client.get_block_hash(height)?;
+            self.client.get_block(&hash)
+        })?;
+        let tx = block.txdata.get(tx_index).with_context(|| {
+            format!("block {height} has no transaction {tx_index} for channel {short_channel_id}")
+        })?;
+        Ok(bitcoin::OutPoint::new(tx.txid(), vout))
+    }
+
+    /// Waits until a transaction spending `outpoint` is in the mempool,
+    /// returning its txid
+    pub async fn await_spent_in_mempool(
+        &self,
+        outpoint: &bitcoin::OutPoint,
+        timeout: Duration,
+    ) -> Result<bitcoin::Txid> {
+        let prevout =
+            serde_json::json!([{ "txid": outpoint.txid.to_string(), "vout": outpoint.vout }]);
+        poll_with_timeout(
+            &format!("Waiting for a transaction spending {outpoint} in mempool"),
+            timeout,
+            || async {
+                let spending: serde_json::Value =
+                    block_in_place(|| self.client.call("gettxspendingprevout", &[prevout.clone()]))
+                        .context("gettxspendingprevout")
+                        .map_err(ControlFlow::Break)?;
+                spending[0]["spendingtxid"]
+                    .as_str()
+                    .with_context(|| format!("{outpoint} is not spent in the mempool yet"))
+                    .map_err(ControlFlow::Continue)?
+                    .parse()
+                    .context("invalid spendingtxid")
+                    .map_err(ControlFlow::Break)
+            },
+        )
+        .await
+    }
+
     pub fn get_blockchain_info(&self) -> anyhow::Result<GetBlockchainInfoResult> {
         Ok(block_in_place(|| self.client.get_blockchain_info())?)
     }
 
+    /// Sends `amount` from the bitcoind wallet to `addr`, returning the txid of
+    /// the unconfirmed transaction
     pub async fn send_to_address(
         &self,
         addr: &Address,
@@ -283,6 +505,153 @@ pub async fn send_to_address(
         })?)
     }
 
+    /// Like [`Self::send_to_address`], but paying the minimum relay fee rate so
+    /// the transaction is stuck until it's bumped with [`Self::bumpfee`],
+    /// which only works if it's `replaceable`
+    pub async fn send_to_address_low_fee(
+        &self,
+        addr: &Address,
+        amount: bitcoin::Amount,
+        replaceable: bool,
+    ) -> Result<bitcoin::Txid> {
+        const LOW_FEE_RATE_SAT_PER_VB: u64 = 1;
+
+        let client = self.wallet_client().await?;
+        Ok(block_in_place(|| {
+            client.client.call(
+                "sendtoaddress",
+                &[
+                    addr.to_string().into(),
+                    amount.to_btc().into(),
+                    serde_json::Value::Null,
+                    serde_json::Value::Null,
+                    false.into(),
+                    replaceable.into(),
+                    serde_json::Value::Null,
+                    "unset".into(),
+                    serde_json::Value::Null,
+                    LOW_FEE_RATE_SAT_PER_VB.into(),
+                ],
+            )
+        })?)
+    }
+
+    /// Replaces the unconfirmed wallet transaction `txid` with one paying a
+    /// higher fee, returning the txid of the replacement
+    pub async fn bumpfee(&self, txid: &bitcoin::Txid) -> Result<bitcoin::Txid> {
+        let entry = block_in_place(|| self.client.get_mempool_entry(txid))
+            .with_context(|| format!("{txid} is not in the mempool"))?;
+        ensure!(
+            entry.bip125_replaceable,
+            "{txid} does not signal replace-by-fee and can't be bumped"
+        );
+
+        let client = self.wallet_client().await?;
+        let res: serde_json::Value =
+            block_in_place(|| client.client.call("bumpfee", &[txid.to_string().into()]))?;
+        let bumped_txid = res["txid"]
+            .as_str()
+            .context("bumpfee result must contain txid")?
+            .parse()?;
+        debug!(target: LOG_DEVIMINT, %txid, %bumped_txid, "Bumped transaction fee");
+        Ok(bumped_txid)
+    }
+
+    /// Invalidates the last `depth` blocks, returning their hashes. Their
+    /// transactions go back to the mempool, where they can be replaced with
+    /// [`Self::double_spend`] before mining a longer chain without them.
+    pub async fn invalidate_blocks(&self, depth: u64) -> Result<Vec<BlockHash>> {
+        let tip = block_in_place(|| self.client.get_block_count())?;
+        ensure!(
+            0 < depth && depth <= tip,
+            "can't invalidate {depth} blocks at height {tip}"
+        );
+        let hashes = (tip - depth + 1..=tip)
+            .map(|height| Ok(block_in_place(|| self.client.get_block_hash(height))?))
+            .collect::<Result<Vec<_>>>()?;
+        info!(target: LOG_DEVIMINT, depth, tip, "Invalidating blocks");
+        block_in_place(|| self.client.invalidate_block(&hashes[0]))?;
+        Ok(hashes)
+    }
+
+    /// Broadcasts a transaction spending all inputs of the unconfirmed wallet
+    /// transaction `txid` to `addr` instead, replacing it in the mempool. The
+    /// original must signal replace-by-fee.
+    pub async fn double_spend(
+        &self,
+        txid: &bitcoin::Txid,
+        addr: &Address,
+    ) -> Result<bitcoin::Txid> {
+        let client = self.wallet_client().await?;
+        let wallet_tx = |txid: &bitcoin::Txid| -> Result<bitcoin::Transaction> {
+            Ok(block_in_place(|| client.client.get_transaction(txid, None))?.transaction()?)
+        };
+
+        let original = wallet_tx(txid)?;
+        let mut input_value = 0;
+        for input in &original.input {
+            let prev_tx = wallet_tx(&input.previous_output.txid)?;
+            input_value += prev_tx
+                .output
+                .get(input.previous_output.vout as usize)
+                .context("input spends a missing output")?
+                .value;
+        }
+        let original_fee = input_value
+            - original
+                .output
+                .iter()
+                .map(|output| output.value)
+                .sum::<u64>();
+        // a replacement has to pay a higher fee and fee rate than the original
+        let fee = 2 * original_fee + 1_000;
+        ensure!(
+            fee < input_value,
+            "inputs of {txid} are too small to double spend"
+        );
+
+        let inputs = original
+            .input
+            .iter()
+            .map(|input| CreateRawTransactionInput {
+                txid: input.previous_output.txid,
+                vout: input.previous_output.vout,
+                sequence: None,
+            })
+            .collect::<Vec<_>>();
+        let outputs = HashMap::from([(
+            addr.to_string(),
+            bitcoin::Amount::from_sat(input_value - fee),
+        )]);
+        let signed = block_in_place(|| {
+            let unsigned =
+                client
+                    .client
+                    .create_raw_transaction_hex(&inputs, &outputs, None, Some(true))?;
+            client
+                .client
+                .sign_raw_transaction_with_wallet(unsigned, None, None)
+        })?;
+        ensure!(
+            signed.complete,
+            "failed to sign double spend: {:?}",
+            signed.errors
+        );
+        let double_spend_txid = block_in_place(|| client.client.send_raw_transaction(&signed.hex))?;
+        info!(target: LOG_DEVIMINT, %txid, %double_spend_txid, "Double spent transaction");
+        Ok(double_spend_txid)
+    }
+
+    /// Confirmed outputs of the bitcoind wallet paying to `addr`
+    pub async fn list_unspent(&self, addr: &Address) -> Result<Vec<ListUnspentResultEntry>> {
+        let client = self.wallet_client().await?;
+        Ok(block_in_place(|| {
+            client
+                .client
+                .list_unspent(Some(1), None, Some(&[addr]), None, None)
+        })?)
+    }
+
     pub(crate) async fn get_balances(&self) -> anyhow::Result<GetBalancesResult> {
         let client = self.wallet_client().await?;
         Ok(block_in_place(|| client.client.get_balances())?)
@@ -293,7 +662,7 @@ pub(crate) fn get_jsonrpc_client(&self) -> &bitcoincore_rpc::jsonrpc::Client {
     }
 }
 
-pub struct LightningdProcessHandle(ProcessHandle);
+pub struct LightningdProcessHandle(pub(crate) ProcessHandle);
 
 impl LightningdProcessHandle {
     async fn terminate(&self) -> Result<()> {
@@ -315,8 +684,22 @@ async fn terminate(&self) -> Result<()> {
 impl Drop for LightningdProcessHandle {
     fn drop(&mut self) {
         // Terminate cln in a controlled way, otherwise it may leave running processes.
+        // Blocking rather than awaiting, so dropping works on any runtime.
         block_in_place(|| {
-            if let Err(e) = block_on(self.terminate()) {
+            if self.0.try_is_running() != Some(false) {
+                let stop_plugins = ClnLightningCli
+                    .cmd()
+                    .arg(&"plugin")
+                    .arg(&"stop")
+                    .arg(&"gateway-cln-extension");
+                if let Err(e) = stop_plugins.run_blocking() {
+                    warn!(
+                        target: LOG_DEVIMINT,
+                        "failed to terminate lightningd plugins: {e:?}"
+                    );
+                }
+            }
+            if let Err(e) = self.0.terminate_blocking() {
                 warn!(target: LOG_DEVIMINT, "failed to terminate lightningd: {e:?}");
             }
         });
@@ -326,25 +709,42 @@ fn drop(&mut self) {
 #[derive(Clone)]
 pub struct Lightningd {
     pub(crate) rpc: Arc<Mutex<ClnRpc>>,
-    pub(crate) process: Arc<LightningdProcessHandle>,
+    /// `None` if devimint attached to a lightningd it didn't launch, which it
+    /// then never stops
+    pub(crate) process: Option<Arc<LightningdProcessHandle>>,
+    pub(crate) launch_kind: LaunchKind,
     pub(crate) bitcoind: Bitcoind,
 }
 
 impl Lightningd {
+    /// Spawns lightningd, or attaches to the one at `FM_EXTERNAL_CLN_SOCKET`
+    /// if set, see [`Self::connect_existing`]
+    #[instrument(name = "lightningd", level = "debug", skip_all)]
     pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Self> {
+        if let Ok(socket) = std::env::var(FM_EXTERNAL_CLN_SOCKET_ENV) {
+            return Self::connect_existing(bitcoind, PathBuf::from(socket)).await;
+        }
         let cln_dir = &process_mgr.globals.FM_CLN_DIR;
         let conf = format!(
             include_str!("cfg/lightningd.conf"),
             port = process_mgr.globals.FM_PORT_CLN,
             bitcoin_rpcport = process_mgr.globals.FM_PORT_BTC_RPC,
+            network = process_mgr.globals.FM_BITCOIN_NETWORK,
         );
         write_overwrite_async(process_mgr.globals.FM_CLN_DIR.join("config"), conf).await?;
         // workaround: will crash on start if it gets a bad response from
         // bitcoind
         bitcoind.poll_ready().await?;
+        let launch_kind = LaunchKind::detect(
+            "lightningd",
+            &cln_dir
+                .join(&process_mgr.globals.FM_BITCOIN_NETWORK)
+                .join("hsm_secret"),
+        )
+        .await?;
         let process = Lightningd::start(process_mgr, cln_dir).await?;
 
-        let socket_cln = cln_dir.join("regtest/lightning-rpc");
+        let socket_cln = process_mgr.globals.FM_CLN_SOCKET.clone();
         poll("lightningd", || async {
             ClnRpc::new(socket_cln.clone())
                 .await
@@ -356,25 +756,96 @@ pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Sel
         Ok(Self {
             bitcoind,
             rpc: Arc::new(Mutex::new(rpc)),
-            process: Arc::new(LightningdProcessHandle(process)),
+            process: Some(Arc::new(LightningdProcessHandle(process))),
+            launch_kind,
         })
     }
 
+    /// Attaches to an externally managed lightningd through its RPC `socket`
+    /// instead of spawning one, e.g. for interop testing. It must use
+    /// `bitcoind`'s chain, and is left running on [`Self::terminate`].
+    pub async fn connect_existing(bitcoind: Bitcoind, socket: PathBuf) -> Result<Self> {
+        let rpc = ClnRpc::new(&socket)
+            .await
+            .with_context(|| format!("connecting to lightningd at {}", socket.display()))?;
+        let this = Self {
+            bitcoind,
+            rpc: Arc::new(Mutex::new(rpc)),
+            process: None,
+            launch_kind: LaunchKind::Reattached,
+        };
+        info!(target: LOG_DEVIMINT, pub_key = %this.pub_key().await?, "Attached to external lightningd");
+        Ok(this)
+    }
+
+    /// Whether devimint attached to a lightningd it didn't launch
+    pub fn is_external(&self) -> bool {
+        self.process.is_none()
+    }
+
+    /// Url gatewayd reaches lightningd's `gateway-cln-extension` plugin at,
+    /// from the plugin's `fm-gateway-listen` option, for a lightningd devimint
+    /// didn't configure itself
+    async fn extension_url(&self) -> Result<String> {
+        let configs: serde_json::Value = self
+            .rpc
+            .lock()
+            .await
+            .call_raw(
+                "listconfigs",
+                &serde_json::json!({ "config": "fm-gateway-listen" }),
+            )
+            .await
+            .map_err(|err| anyhow!("listing lightningd configs: {err:?}"))?;
+        // lightningd v23.08 and later nest each option under `configs`
+        let listen = configs
+            .get("configs")
+            .and_then(|configs| configs.get("fm-gateway-listen"))
+            .and_then(|option| option.get("value_str"))
+            .or_else(|| configs.get("fm-gateway-listen"))
+            .and_then(serde_json::Value::as_str)
+            .context("lightningd doesn't run the gateway-cln-extension plugin")?;
+        let listen: std::net::SocketAddr = listen
+            .parse()
+            .with_context(|| format!("invalid fm-gateway-listen address {listen}"))?;
+        let host = if listen.ip().is_unspecified() {
+            std::net::Ipv4Addr::LOCALHOST.into()
+        } else {
+            listen.ip()
+        };
+        Ok(format!(
+            "http://{}",
+            std::net::SocketAddr::new(host, listen.port())
+        ))
+    }
+
+    /// Whether lightningd started with the node key and channels of an
+    /// earlier run
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+
     pub async fn start(process_mgr: &ProcessManager, cln_dir: &Path) -> Result<ProcessHandle> {
         let extension_path = crate::util::get_gateway_cln_extension_path(
             GatewayClnExtension::default_path().await.as_str(),
         );
-        let btc_dir = utf8(&process_mgr.globals.FM_BTC_DIR);
-        let cmd = cmd!(
+        let cmd = Self::launch_cmd(&process_mgr.globals, cln_dir, &extension_path);
+
+        process_mgr.spawn_daemon("lightningd", cmd).await
+    }
+
+    /// What [`Self::start`] spawns lightningd with, loading the gateway
+    /// extension plugin from `extension_path`
+    pub(crate) fn launch_cmd(globals: &Global, cln_dir: &Path, extension_path: &str) -> Command {
+        let btc_dir = utf8(&globals.FM_BTC_DIR);
+        cmd!(
             crate::util::Lightningd,
             "--dev-fast-gossip",
             "--dev-bitcoind-poll=1",
             format!("--lightning-dir={}", utf8(cln_dir)),
             format!("--bitcoin-datadir={btc_dir}"),
             "--plugin={extension_path}"
-        );
-
-        process_mgr.spawn_daemon("lightningd", cmd).await
+        )
     }
 
     pub async fn request<R>(&self, request: R) -> Result<R::Response>
@@ -389,22 +860,41 @@ pub async fn request<R>(&self, request: R) -> Result<R::Response>
     // TODO(tvolk131): Remove this method and instead use
     // `Gatewayd.wait_for_chain_sync()` once 0.4.0 is released
     pub async fn await_block_processing(&self) -> Result<()> {
+        let btc_height = self
+            .bitcoind
+            .get_blockchain_info()
+            .context("bitcoind getblockchaininfo")?
+            .blocks;
+        self.await_block_height(btc_height).await
+    }
+
+    /// Height of the last block lightningd processed
+    pub async fn block_height(&self) -> Result<u64> {
+        Ok(self
+            .request(cln_rpc::model::requests::GetinfoRequest {})
+            .await?
+            .blockheight
+            .into())
+    }
+
+    /// Waits until lightningd processed all blocks up to `target`, e.g. to see
+    /// a funding transaction confirmed right after mining it
+    pub async fn await_block_height(&self, target: u64) -> Result<()> {
         poll("lightningd block processing", || async {
-            let btc_height = self
-                .bitcoind
-                .get_blockchain_info()
-                .context("bitcoind getblockchaininfo")
-                .map_err(ControlFlow::Continue)?
-                .blocks;
-            let lnd_height = self
+            let cln_height = self
                 .request(cln_rpc::model::requests::GetinfoRequest {})
                 .await
                 .map_err(ControlFlow::Continue)?
                 .blockheight;
-            poll_eq!(u64::from(lnd_height), btc_height)
+            if target <= u64::from(cln_height) {
+                Ok(())
+            } else {
+                Err(ControlFlow::Continue(anyhow!(
+                    "lightningd at height {cln_height}, waiting for {target}"
+                )))
+            }
         })
-        .await?;
-        Ok(())
+        .await
     }
 
     pub async fn pub_key(&self) -> Result<String> {
@@ -416,7 +906,268 @@ pub async fn pub_key(&self) -> Result<String> {
     }
 
     pub async fn terminate(self) -> Result<()> {
-        self.process.terminate().await
+        match &self.process {
+            Some(process) => process.terminate().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Stops lightningd, leaving this handle and its clones to bring it back
+    /// with [`Self::restart`]
+    pub async fn stop(&self) -> Result<()> {
+        self.process
+            .as_ref()
+            .context("can't stop an external lightningd")?
+            .terminate()
+            .await
+    }
+
+    /// Starts lightningd stopped by [`Self::stop`] again with its node key and
+    /// channels, reconnecting this handle and its clones to it
+    pub async fn restart(&self, process_mgr: &ProcessManager) -> Result<()> {
+        let process = self
+            .process
+            .as_ref()
+            .context("can't restart an external lightningd")?;
+        // workaround: will crash on start if it gets a bad response from
+        // bitcoind
+        self.bitcoind.poll_ready().await?;
+        process_mgr.respawn(&process.0).await?;
+        let socket_cln = process_mgr.globals.FM_CLN_SOCKET.clone();
+        let rpc = poll("lightningd", || async {
+            ClnRpc::new(socket_cln.clone())
+                .await
+                .context("connect to lightningd")
+                .map_err(ControlFlow::Continue)
+        })
+        .await?;
+        *self.rpc.lock().await = rpc;
+        Ok(())
+    }
+
+    /// Opens an unannounced channel of `amount_sat` to the already connected
+    /// `peer_pubkey`, returning its channel id
+    pub async fn fund_channel(&self, peer_pubkey: &str, amount_sat: u64) -> Result<String> {
+        let channel_id = self
+            .request(cln_rpc::model::requests::FundchannelRequest {
+                id: peer_pubkey.parse().context("failed to parse peer pubkey")?,
+                amount: cln_rpc::primitives::AmountOrAll::Amount(ClnRpcAmount::from_sat(
+                    amount_sat,
+                )),
+                push_msat: None,
+                feerate: None,
+                announce: Some(false),
+                minconf: None,
+                close_to: None,
+                request_amt: None,
+                compact_lease: None,
+                utxos: None,
+                mindepth: None,
+                reserve: None,
+                channel_type: None,
+            })
+            .await?
+            .channel_id;
+        Ok(channel_id.to_string())
+    }
+
+    /// Cooperatively closes the channel `channel_id`, returning once the
+    /// closing transaction was broadcast
+    pub async fn close_channel(&self, channel_id: &str) -> Result<()> {
+        self.request(cln_rpc::model::requests::CloseRequest {
+            id: channel_id.to_owned(),
+            destination: None,
+            fee_negotiation_step: None,
+            force_lease_closed: None,
+            unilateraltimeout: None,
+            wrong_funding: None,
+            feerange: None,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// State of the channel `channel_id`, `None` once lightningd forgot it
+    pub async fn channel_state(&self, channel_id: &str) -> Result<Option<ChannelState>> {
+        Ok(self
+            .request(cln_rpc::model::requests::ListfundsRequest { spent: None })
+            .await?
+            .channels
+            .into_iter()
+            .find(|channel| {
+                channel
+                    .channel_id
+                    .is_some_and(|id| id.to_string() == channel_id)
+            })
+            .map(|channel| channel.state))
+    }
+
+    /// Number of HTLCs on lightningd's channels that are neither settled nor
+    /// failed yet, i.e. payments in flight through it
+    pub async fn pending_htlc_count(&self) -> Result<usize> {
+        Ok(self.pending_htlcs().await?.len())
+    }
+
+    /// HTLCs on lightningd's channels that are neither settled nor failed yet
+    pub async fn pending_htlcs(&self) -> Result<Vec<HtlcInfo>> {
+        Ok(self
+            .request(cln_rpc::model::requests::ListpeerchannelsRequest { id: None })
+            .await?
+            .channels
+            .into_iter()
+            .flat_map(|channel| {
+                let peer = channel.peer_id.to_string();
+                channel
+                    .htlcs
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |htlc| HtlcInfo {
+                        payment_hash: htlc.payment_hash,
+                        amount: fedimint_core::Amount::from_msats(htlc.amount_msat.msat()),
+                        expiry: htlc.expiry.into(),
+                        htlc_index: htlc.id,
+                        direction: match htlc.direction {
+                            ListpeerchannelsChannelsHtlcsDirection::IN => HtlcDirection::Incoming,
+                            ListpeerchannelsChannelsHtlcsDirection::OUT => HtlcDirection::Outgoing,
+                        },
+                        peer: peer.clone(),
+                    })
+            })
+            .collect())
+    }
+
+    /// Fails lightningd's outgoing HTLC `htlc_index` to `peer` without the
+    /// peer failing or settling it, like when a payment of a gateway gets
+    /// stuck. A node can't take back an HTLC it offered, so this closes the
+    /// channels with `peer` unilaterally and mines blocks until lightningd
+    /// timed the HTLC out on chain and failed its payment.
+    pub async fn force_fail_htlc(&self, peer: &str, htlc_index: u64) -> Result<()> {
+        let htlc = self
+            .pending_htlcs()
+            .await?
+            .into_iter()
+            .find(|htlc| htlc.peer == peer && htlc.htlc_index == htlc_index)
+            .with_context(|| format!("lightningd has no HTLC {htlc_index} to {peer} in flight"))?;
+        ensure!(
+            htlc.direction == HtlcDirection::Outgoing,
+            "HTLC {htlc_index} from {peer} is incoming, lightningd fails those by not settling them"
+        );
+        info!(target: LOG_DEVIMINT, %peer, htlc_index, expiry = htlc.expiry, "Force-failing HTLC on chain");
+        // only available in developer builds, like the ones devimint runs
+        let _: serde_json::Value = self
+            .rpc
+            .lock()
+            .await
+            .call_raw("dev-fail", &serde_json::json!({ "id": peer }))
+            .await
+            .map_err(|err| anyhow!("closing channels with {peer}: {err:?}"))?;
+        let height = self.block_height().await?;
+        self.bitcoind
+            .mine_blocks(htlc.expiry.saturating_sub(height) + 1)
+            .await?;
+        poll_with_timeout(
+            "lightningd timing out HTLC on chain",
+            Duration::from_secs(120),
+            || async {
+                match self
+                    .payment_status(htlc.payment_hash)
+                    .await
+                    .map_err(ControlFlow::Continue)?
+                {
+                    Some(PaymentStatus::Failed) => Ok(()),
+                    Some(PaymentStatus::Succeeded { .. }) => Err(ControlFlow::Break(anyhow!(
+                        "payment of HTLC {htlc_index} succeeded before it timed out"
+                    ))),
+                    Some(PaymentStatus::InFlight) | None => {
+                        // confirms the transaction timing it out
+                        self.bitcoind
+                            .mine_blocks(1)
+                            .await
+                            .map_err(ControlFlow::Continue)?;
+                        Err(ControlFlow::Continue(anyhow!(
+                            "payment of HTLC {htlc_index} still in flight"
+                        )))
+                    }
+                }
+            },
+        )
+        .await
+    }
+
+    /// lightningd's view of the network graph. Gossip only covers announced
+    /// channels, so lightningd's own private ones, which are all there is in
+    /// regtest without announcements, come from its peer channels instead.
+    pub async fn list_channels_graph(&self) -> Result<Vec<GraphChannel>> {
+        let mut channels = BTreeMap::new();
+        for channel in self
+            .request(cln_rpc::model::requests::ListchannelsRequest {
+                destination: None,
+                short_channel_id: None,
+                source: None,
+            })
+            .await?
+            .channels
+        {
+            // listed once per direction
+            let (node1, node2) = GraphChannel::ordered_nodes(
+                channel.source.to_string(),
+                channel.destination.to_string(),
+            );
+            channels
+                .entry(channel.short_channel_id.to_string())
+                .or_insert_with(|| GraphChannel {
+                    short_channel_id: channel.short_channel_id.to_string(),
+                    node1,
+                    node2,
+                    capacity_sat: channel.amount_msat.msat() / 1000,
+                    private: !channel.public,
+                });
+        }
+
+        let pub_key = self.pub_key().await?;
+        for channel in self
+            .request(cln_rpc::model::requests::ListpeerchannelsRequest { id: None })
+            .await?
+            .channels
+        {
+            // channels get their short channel id once the funding confirmed
+            let Some(short_channel_id) = channel.short_channel_id else {
+                continue;
+            };
+            if channel.state != ListpeerchannelsChannelsState::CHANNELD_NORMAL {
+                continue;
+            }
+            let (node1, node2) =
+                GraphChannel::ordered_nodes(pub_key.clone(), channel.peer_id.to_string());
+            channels
+                .entry(short_channel_id.to_string())
+                .or_insert_with(|| GraphChannel {
+                    short_channel_id: short_channel_id.to_string(),
+                    node1,
+                    node2,
+                    capacity_sat: channel.total_msat.map_or(0, |total| total.msat() / 1000),
+                    private: channel.private.unwrap_or(true),
+                });
+        }
+        Ok(channels.into_values().collect())
+    }
+
+    /// Sum of lightningd's confirmed on-chain outputs
+    pub async fn onchain_balance(&self) -> Result<fedimint_core::Amount> {
+        let msats = self
+            .request(cln_rpc::model::requests::ListfundsRequest { spent: None })
+            .await?
+            .outputs
+            .iter()
+            .filter(|output| {
+                matches!(
+                    output.status,
+                    cln_rpc::model::responses::ListfundsOutputsStatus::CONFIRMED
+                )
+            })
+            .map(|output| output.amount_msat.msat())
+            .sum();
+        Ok(fedimint_core::Amount::from_msats(msats))
     }
 
     pub async fn invoice(
@@ -470,6 +1221,58 @@ pub async fn pay_bolt11_invoice(&self, invoice: String) -> anyhow::Result<()> {
         Ok(())
     }
 
+    /// Status of the payment of `payment_hash` sent by this node, `None` if it
+    /// never tried to pay it
+    pub async fn payment_status(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
+    ) -> Result<Option<PaymentStatus>> {
+        let pays = self
+            .request(cln_rpc::model::requests::ListpaysRequest {
+                bolt11: None,
+                payment_hash: Some(payment_hash),
+                status: None,
+            })
+            .await?
+            .pays;
+        // A payment retried after failing is listed once per attempt
+        if let Some(preimage) = pays
+            .iter()
+            .find(|pay| pay.status == cln_rpc::model::responses::ListpaysPaysStatus::COMPLETE)
+            .map(|pay| pay.preimage.context("completed payment has no preimage"))
+            .transpose()?
+        {
+            let preimage = preimage
+                .to_vec()
+                .try_into()
+                .map_err(|_| anyhow!("preimage is not 32 bytes"))?;
+            return Ok(Some(PaymentStatus::Succeeded { preimage }));
+        }
+        if pays
+            .iter()
+            .any(|pay| pay.status == cln_rpc::model::responses::ListpaysPaysStatus::PENDING)
+        {
+            return Ok(Some(PaymentStatus::InFlight));
+        }
+        Ok((!pays.is_empty()).then_some(PaymentStatus::Failed))
+    }
+
+    /// Waits until the payment of `payment_hash` is in flight
+    pub async fn wait_payment_in_flight(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
+    ) -> Result<()> {
+        poll_payment_in_flight(|| self.payment_status(payment_hash)).await
+    }
+
+    /// Waits until the payment of `payment_hash` succeeded or failed
+    pub async fn wait_payment_complete(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
+    ) -> Result<PaymentStatus> {
+        poll_payment_complete(|| self.payment_status(payment_hash)).await
+    }
+
     pub async fn wait_any_bolt11_invoice(&self) -> anyhow::Result<()> {
         let invoice_status = self
             .request(cln_rpc::model::requests::WaitanyinvoiceRequest {
@@ -487,23 +1290,58 @@ pub async fn wait_any_bolt11_invoice(&self) -> anyhow::Result<()> {
     }
 }
 
+/// How gatewayd reaches an lnd devimint attached to instead of the one its
+/// globals point to, see [`Lnd::connect_existing`]
+#[derive(Debug, Clone)]
+pub(crate) struct ExternalLnd {
+    pub(crate) rpc_addr: String,
+    pub(crate) tls_cert: PathBuf,
+    pub(crate) macaroon: PathBuf,
+}
+
 #[derive(Clone)]
 pub struct Lnd {
     pub(crate) client: Arc<Mutex<LndClient>>,
-    pub(crate) process: ProcessHandle,
-    pub(crate) _bitcoind: Bitcoind,
+    /// `None` if devimint attached to an lnd it didn't launch, which it then
+    /// never stops
+    pub(crate) process: Option<ProcessHandle>,
+    /// Where gatewayd finds an lnd devimint didn't launch
+    pub(crate) external: Option<ExternalLnd>,
+    pub(crate) launch_kind: LaunchKind,
+    pub(crate) bitcoind: Bitcoind,
 }
 
 impl Lnd {
+    /// Spawns lnd, or attaches to the one at `FM_EXTERNAL_LND_RPC_ADDR` if
+    /// set, see [`Self::connect_existing`]
+    #[instrument(name = "lnd", level = "debug", skip_all)]
     pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Self> {
+        if let Ok(rpc_addr) = std::env::var(FM_EXTERNAL_LND_RPC_ADDR_ENV) {
+            let tls_cert = std::env::var(FM_EXTERNAL_LND_TLS_CERT_ENV).with_context(|| {
+                format!("{FM_EXTERNAL_LND_TLS_CERT_ENV} must be set for an external lnd")
+            })?;
+            let macaroon = std::env::var(FM_EXTERNAL_LND_MACAROON_ENV).with_context(|| {
+                format!("{FM_EXTERNAL_LND_MACAROON_ENV} must be set for an external lnd")
+            })?;
+            return Self::connect_existing(
+                bitcoind,
+                rpc_addr,
+                PathBuf::from(tls_cert),
+                PathBuf::from(macaroon),
+            )
+            .await;
+        }
         // workaround: will crash on start if it gets a bad response from
         // bitcoind
         bitcoind.poll_ready().await?;
+        let launch_kind = LaunchKind::detect("lnd", &process_mgr.globals.FM_LND_MACAROON).await?;
         let (process, client) = Lnd::start(process_mgr).await?;
         let this = Self {
-            _bitcoind: bitcoind,
+            bitcoind,
             client: Arc::new(Mutex::new(client)),
-            process,
+            process: Some(process),
+            external: None,
+            launch_kind,
         };
         // wait for lnd rpc to be active
         poll("lnd_startup", || async {
@@ -513,6 +1351,51 @@ pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Sel
         Ok(this)
     }
 
+    /// Attaches to an externally managed lnd at `rpc_addr` instead of
+    /// spawning one, e.g. for interop testing. It must use `bitcoind`'s
+    /// chain, and is left running on [`Self::terminate`].
+    pub async fn connect_existing(
+        bitcoind: Bitcoind,
+        rpc_addr: String,
+        tls_cert: PathBuf,
+        macaroon: PathBuf,
+    ) -> Result<Self> {
+        let client = tonic_lnd::connect(rpc_addr.clone(), tls_cert.clone(), macaroon.clone())
+            .await
+            .with_context(|| format!("connecting to lnd at {rpc_addr}"))?;
+        let this = Self {
+            bitcoind,
+            client: Arc::new(Mutex::new(client)),
+            process: None,
+            external: Some(ExternalLnd {
+                rpc_addr,
+                tls_cert,
+                macaroon,
+            }),
+            launch_kind: LaunchKind::Reattached,
+        };
+        info!(target: LOG_DEVIMINT, pub_key = %this.pub_key().await?, "Attached to external lnd");
+        Ok(this)
+    }
+
+    /// Whether devimint attached to an lnd it didn't launch
+    pub fn is_external(&self) -> bool {
+        self.process.is_none()
+    }
+
+    /// Whether lnd started with the wallet and channels of an earlier run
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+
+    /// What [`Self::start`] spawns lnd with
+    pub(crate) fn launch_cmd(globals: &Global) -> Command {
+        cmd!(
+            crate::util::Lnd,
+            format!("--lnddir={}", utf8(&globals.FM_LND_DIR))
+        )
+    }
+
     pub async fn start(process_mgr: &ProcessManager) -> Result<(ProcessHandle, LndClient)> {
         let conf = format!(
             include_str!("cfg/lnd.conf"),
@@ -522,12 +1405,10 @@ pub async fn start(process_mgr: &ProcessManager) -> Result<(ProcessHandle, LndCl
             btc_rpc_port = process_mgr.globals.FM_PORT_BTC_RPC,
             zmq_pub_raw_block = process_mgr.globals.FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK,
             zmq_pub_raw_tx = process_mgr.globals.FM_PORT_BTC_ZMQ_PUB_RAW_TX,
+            network = process_mgr.globals.FM_BITCOIN_NETWORK,
         );
         write_overwrite_async(process_mgr.globals.FM_LND_DIR.join("lnd.conf"), conf).await?;
-        let cmd = cmd!(
-            crate::util::Lnd,
-            format!("--lnddir={}", utf8(&process_mgr.globals.FM_LND_DIR))
-        );
+        let cmd = Self::launch_cmd(&process_mgr.globals);
 
         let process = process_mgr.spawn_daemon("lnd", cmd).await?;
         let lnd_rpc_addr = &process_mgr.globals.FM_LND_RPC_ADDR;
@@ -594,8 +1475,32 @@ pub async fn pub_key(&self) -> Result<String> {
     // TODO(tvolk131): Remove this method and instead use
     // `Gatewayd.wait_for_chain_sync()` once 0.4.0 is released
     pub async fn await_block_processing(&self) -> Result<()> {
+        let btc_height = self
+            .bitcoind
+            .get_blockchain_info()
+            .context("bitcoind getblockchaininfo")?
+            .blocks;
+        self.await_block_height(btc_height).await
+    }
+
+    /// Height of the last block lnd processed
+    pub async fn block_height(&self) -> Result<u64> {
+        Ok(self
+            .lightning_client_lock()
+            .await?
+            .get_info(GetInfoRequest {})
+            .await
+            .context("lnd get_info")?
+            .into_inner()
+            .block_height
+            .into())
+    }
+
+    /// Waits until lnd processed all blocks up to `target` and considers
+    /// itself synced to the chain
+    pub async fn await_block_height(&self, target: u64) -> Result<()> {
         poll("lnd block processing", || async {
-            let synced = self
+            let info = self
                 .lightning_client_lock()
                 .await
                 .map_err(ControlFlow::Break)?
@@ -603,20 +1508,227 @@ pub async fn await_block_processing(&self) -> Result<()> {
                 .await
                 .context("lnd get_info")
                 .map_err(ControlFlow::Continue)?
-                .into_inner()
-                .synced_to_chain;
-            if synced {
+                .into_inner();
+            if target <= u64::from(info.block_height) && info.synced_to_chain {
                 Ok(())
             } else {
-                Err(ControlFlow::Continue(anyhow!("lnd not synced_to_chain")))
+                Err(ControlFlow::Continue(anyhow!(
+                    "lnd at height {} (synced: {}), waiting for {target}",
+                    info.block_height,
+                    info.synced_to_chain
+                )))
             }
         })
+        .await
+    }
+
+    pub async fn terminate(self) -> Result<()> {
+        match &self.process {
+            Some(process) => process.terminate().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Stops lnd, leaving this handle and its clones to bring it back with
+    /// [`Self::restart`]
+    pub async fn stop(&self) -> Result<()> {
+        self.process
+            .as_ref()
+            .context("can't stop an external lnd")?
+            .terminate()
+            .await
+    }
+
+    /// Starts lnd stopped by [`Self::stop`] again with its wallet and
+    /// channels, reconnecting this handle and its clones to it
+    pub async fn restart(&self, process_mgr: &ProcessManager) -> Result<()> {
+        let process = self
+            .process
+            .as_ref()
+            .context("can't restart an external lnd")?;
+        // workaround: will crash on start if it gets a bad response from
+        // bitcoind
+        self.bitcoind.poll_ready().await?;
+        process_mgr.respawn(process).await?;
+        let globals = &process_mgr.globals;
+        let client = poll("lnd_connect", || async {
+            tonic_lnd::connect(
+                globals.FM_LND_RPC_ADDR.clone(),
+                globals.FM_LND_TLS_CERT.clone(),
+                globals.FM_LND_MACAROON.clone(),
+            )
+            .await
+            .context("lnd connect")
+            .map_err(ControlFlow::Continue)
+        })
+        .await?;
+        *self.client.lock().await = client;
+        poll("lnd_startup", || async {
+            self.pub_key().await.map_err(ControlFlow::Continue)
+        })
         .await?;
         Ok(())
     }
 
-    pub async fn terminate(self) -> Result<()> {
-        self.process.terminate().await
+    /// Number of HTLCs on lnd's channels that are neither settled nor failed
+    /// yet, i.e. payments in flight through it
+    pub async fn pending_htlc_count(&self) -> Result<usize> {
+        Ok(self.pending_htlcs().await?.len())
+    }
+
+    /// HTLCs on lnd's channels that are neither settled nor failed yet
+    pub async fn pending_htlcs(&self) -> Result<Vec<HtlcInfo>> {
+        // lnd lists the HTLCs on its channels in whole sats only, the msats
+        // come from the invoices they pay or the payments they belong to
+        let mut client = self.lightning_client_lock().await?;
+        let mut incoming_msats = HashMap::new();
+        for invoice in client
+            .list_invoices(tonic_lnd::lnrpc::ListInvoiceRequest {
+                pending_only: true,
+                ..Default::default()
+            })
+            .await?
+            .into_inner()
+            .invoices
+        {
+            for htlc in &invoice.htlcs {
+                if htlc.state() == tonic_lnd::lnrpc::InvoiceHtlcState::Accepted {
+                    incoming_msats.insert((htlc.chan_id, htlc.htlc_index), htlc.amt_msat);
+                }
+            }
+        }
+        let mut outgoing_msats = HashMap::new();
+        // in-flight payments are among the most recent ones
+        for payment in client
+            .list_payments(tonic_lnd::lnrpc::ListPaymentsRequest {
+                include_incomplete: true,
+                reversed: true,
+                ..Default::default()
+            })
+            .await?
+            .into_inner()
+            .payments
+        {
+            for attempt in &payment.htlcs {
+                if attempt.status() != tonic_lnd::lnrpc::htlc_attempt::HtlcStatus::InFlight {
+                    continue;
+                }
+                let Some(route) = attempt.route.as_ref() else {
+                    continue;
+                };
+                if let Some(hop) = route.hops.first() {
+                    outgoing_msats.insert(
+                        (hop.chan_id, payment.payment_hash.clone()),
+                        route.total_amt_msat,
+                    );
+                }
+            }
+        }
+
+        let mut htlcs = vec![];
+        for channel in client
+            .list_channels(ListChannelsRequest::default())
+            .await?
+            .into_inner()
+            .channels
+        {
+            for htlc in channel.pending_htlcs {
+                let msats = if htlc.incoming {
+                    incoming_msats
+                        .get(&(channel.chan_id, htlc.htlc_index))
+                        .copied()
+                } else {
+                    outgoing_msats
+                        .get(&(channel.chan_id, htlc.hash_lock.encode_hex::<String>()))
+                        .map(|&msats| u64::try_from(msats))
+                        .transpose()
+                        .context("lnd payment with negative amount")?
+                };
+                // forwarded HTLCs, like the ones gatewayd intercepts, belong
+                // to neither, leaving their whole sats
+                let msats = match msats {
+                    Some(msats) => msats,
+                    None => u64::try_from(htlc.amount)
+                        .context("lnd HTLC with negative amount")?
+                        .checked_mul(1000)
+                        .context("lnd HTLC amount overflows")?,
+                };
+                htlcs.push(HtlcInfo {
+                    payment_hash: cln_rpc::primitives::Sha256::from_slice(&htlc.hash_lock)
+                        .context("lnd HTLC with invalid hash lock")?,
+                    amount: fedimint_core::Amount::from_msats(msats),
+                    expiry: htlc.expiration_height.into(),
+                    htlc_index: htlc.htlc_index,
+                    direction: if htlc.incoming {
+                        HtlcDirection::Incoming
+                    } else {
+                        HtlcDirection::Outgoing
+                    },
+                    peer: channel.remote_pubkey.clone(),
+                });
+            }
+        }
+        Ok(htlcs)
+    }
+
+    /// Fails the HTLC of `payment_hash` that lnd holds for one of its hold
+    /// invoices back to the payer, see [`Self::hold_invoice`]. If none was
+    /// accepted yet, payments of the invoice get rejected.
+    pub async fn fail_held_htlc(&self, payment_hash: cln_rpc::primitives::Sha256) -> Result<()> {
+        self.invoices_client_lock()
+            .await?
+            .cancel_invoice(tonic_lnd::invoicesrpc::CancelInvoiceMsg {
+                payment_hash: payment_hash.to_byte_array().to_vec(),
+            })
+            .await
+            .with_context(|| format!("failing HTLC of {payment_hash}"))?;
+        Ok(())
+    }
+
+    /// lnd's view of the network graph, including the unannounced channels lnd
+    /// is part of, which are all there is in regtest without announcements
+    pub async fn describe_graph(&self) -> Result<Vec<GraphChannel>> {
+        let mut client = self.lightning_client_lock().await?;
+        let private: HashSet<u64> = client
+            .list_channels(ListChannelsRequest::default())
+            .await?
+            .into_inner()
+            .channels
+            .into_iter()
+            .filter(|channel| channel.private)
+            .map(|channel| channel.chan_id)
+            .collect();
+        client
+            .describe_graph(ChannelGraphRequest {
+                include_unannounced: true,
+            })
+            .await?
+            .into_inner()
+            .edges
+            .into_iter()
+            .map(|edge| {
+                Ok(GraphChannel {
+                    short_channel_id: format_short_channel_id(edge.channel_id),
+                    node1: edge.node1_pub,
+                    node2: edge.node2_pub,
+                    capacity_sat: u64::try_from(edge.capacity)?,
+                    private: private.contains(&edge.channel_id),
+                })
+            })
+            .collect()
+    }
+
+    /// Number of lnd's channels that are still being opened or closed
+    pub async fn pending_channel_count(&self) -> Result<usize> {
+        let pending = self
+            .lightning_client_lock()
+            .await?
+            .pending_channels(PendingChannelsRequest {})
+            .await?
+            .into_inner();
+        Ok(pending.pending_open_channels.len()
+            + pending.waiting_close_channels.len()
+            + pending.pending_force_closing_channels.len())
     }
 
     pub async fn invoice(&self, amount: u64) -> anyhow::Result<(String, Vec<u8>)> {
@@ -645,17 +1757,8 @@ pub async fn pay_bolt11_invoice(&self, invoice: String) -> anyhow::Result<()> {
             .await?
             .into_inner();
         let payment_status = self
-            .lightning_client_lock()
-            .await?
-            .list_payments(tonic_lnd::lnrpc::ListPaymentsRequest {
-                include_incomplete: true,
-                ..Default::default()
-            })
+            .find_payment(&payment.payment_hash.encode_hex::<String>())
             .await?
-            .into_inner()
-            .payments
-            .into_iter()
-            .find(|p| p.payment_hash == payment.payment_hash.encode_hex::<String>())
             .context("payment not in list")?
             .status();
         anyhow::ensure!(payment_status == tonic_lnd::lnrpc::payment::PaymentStatus::Succeeded);
@@ -663,6 +1766,79 @@ pub async fn pay_bolt11_invoice(&self, invoice: String) -> anyhow::Result<()> {
         Ok(())
     }
 
+    /// Status of the payment of `payment_hash` sent by this node, `None` if it
+    /// never tried to pay it
+    pub async fn payment_status(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
+    ) -> Result<Option<PaymentStatus>> {
+        let Some(payment) = self
+            .find_payment(&payment_hash.encode_hex::<String>())
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(match payment.status() {
+            tonic_lnd::lnrpc::payment::PaymentStatus::Succeeded => {
+                let preimage = hex::decode(&payment.payment_preimage)?
+                    .try_into()
+                    .map_err(|_| anyhow!("preimage is not 32 bytes"))?;
+                PaymentStatus::Succeeded { preimage }
+            }
+            tonic_lnd::lnrpc::payment::PaymentStatus::Failed => PaymentStatus::Failed,
+            tonic_lnd::lnrpc::payment::PaymentStatus::Unknown
+            | tonic_lnd::lnrpc::payment::PaymentStatus::InFlight => PaymentStatus::InFlight,
+        }))
+    }
+
+    /// Finds the payment of hex encoded `payment_hash`, paging backwards from
+    /// the most recent one as `ListPayments` returns one page per call
+    async fn find_payment(&self, payment_hash: &str) -> Result<Option<tonic_lnd::lnrpc::Payment>> {
+        let mut index_offset = 0;
+        loop {
+            let page = self
+                .lightning_client_lock()
+                .await?
+                .list_payments(tonic_lnd::lnrpc::ListPaymentsRequest {
+                    include_incomplete: true,
+                    index_offset,
+                    reversed: true,
+                    ..Default::default()
+                })
+                .await?
+                .into_inner();
+            if let Some(payment) = page
+                .payments
+                .iter()
+                .rev()
+                .find(|p| p.payment_hash == payment_hash)
+            {
+                return Ok(Some(payment.clone()));
+            }
+            // index offsets are exclusive and start at 1
+            if page.payments.is_empty() || page.first_index_offset <= 1 {
+                return Ok(None);
+            }
+            index_offset = page.first_index_offset;
+        }
+    }
+
+    /// Waits until the payment of `payment_hash` is in flight
+    pub async fn wait_payment_in_flight(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
+    ) -> Result<()> {
+        poll_payment_in_flight(|| self.payment_status(payment_hash)).await
+    }
+
+    /// Waits until the payment of `payment_hash` succeeded or failed
+    pub async fn wait_payment_complete(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
+    ) -> Result<PaymentStatus> {
+        poll_payment_complete(|| self.payment_status(payment_hash)).await
+    }
+
     pub async fn wait_bolt11_invoice(&self, payment_hash: Vec<u8>) -> anyhow::Result<()> {
         let invoice_status = self
             .lightning_client_lock()
@@ -703,10 +1879,41 @@ pub async fn create_hold_invoice(
         Ok((preimage, payment_request, hash))
     }
 
+    /// Creates a hold invoice of `amount` msats, which accepts the payer's
+    /// HTLC but only settles or cancels it when told to through the returned
+    /// handle, to test how gateways handle pending HTLCs
+    pub async fn hold_invoice(&self, amount: u64) -> Result<HoldInvoiceHandle> {
+        let (preimage, invoice, payment_hash) = self.create_hold_invoice(amount).await?;
+        Ok(HoldInvoiceHandle {
+            lnd: self.clone(),
+            preimage,
+            payment_hash,
+            invoice,
+        })
+    }
+
     pub async fn settle_hold_invoice(
         &self,
         preimage: [u8; 32],
         payment_hash: cln_rpc::primitives::Sha256,
+    ) -> anyhow::Result<()> {
+        self.await_hold_invoice_accepted(payment_hash).await?;
+
+        self.invoices_client_lock()
+            .await?
+            .settle_invoice(tonic_lnd::invoicesrpc::SettleInvoiceMsg {
+                preimage: preimage.to_vec(),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Waits until lnd accepted an HTLC paying the hold invoice of
+    /// `payment_hash`, which it then holds until settling or cancelling it
+    pub async fn await_hold_invoice_accepted(
+        &self,
+        payment_hash: cln_rpc::primitives::Sha256,
     ) -> anyhow::Result<()> {
         let mut hold_invoice_subscription = self
             .invoices_client_lock()
@@ -742,17 +1949,117 @@ pub async fn settle_hold_invoice(
             }
         }
 
-        self.invoices_client_lock()
-            .await?
-            .settle_invoice(tonic_lnd::invoicesrpc::SettleInvoiceMsg {
-                preimage: preimage.to_vec(),
-            })
-            .await?;
-
         Ok(())
     }
 }
 
+/// A hold invoice of an [`Lnd`], see [`Lnd::hold_invoice`]
+pub struct HoldInvoiceHandle {
+    lnd: Lnd,
+    preimage: [u8; 32],
+    payment_hash: cln_rpc::primitives::Sha256,
+    invoice: String,
+}
+
+impl HoldInvoiceHandle {
+    /// Bolt11 invoice to pay
+    pub fn invoice(&self) -> &str {
+        &self.invoice
+    }
+
+    /// Preimage the payer learns once the invoice got settled
+    pub fn preimage(&self) -> [u8; 32] {
+        self.preimage
+    }
+
+    pub fn payment_hash(&self) -> cln_rpc::primitives::Sha256 {
+        self.payment_hash
+    }
+
+    /// Waits until lnd accepted the payer's HTLC and holds it
+    pub async fn await_accepted(&self) -> Result<()> {
+        self.lnd
+            .await_hold_invoice_accepted(self.payment_hash)
+            .await
+    }
+
+    /// Waits until the payer's HTLC is held and settles it, releasing the
+    /// preimage
+    pub async fn settle(self) -> Result<()> {
+        self.lnd
+            .settle_hold_invoice(self.preimage, self.payment_hash)
+            .await
+    }
+
+    /// Fails a held HTLC back to the payer, or rejects payments if none was
+    /// accepted yet
+    pub async fn cancel(self) -> Result<()> {
+        self.lnd.fail_held_htlc(self.payment_hash).await
+    }
+}
+
+/// An HTLC on one of a node's channels that is neither settled nor failed yet,
+/// see [`Gatewayd::inflight_htlcs`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtlcInfo {
+    pub payment_hash: cln_rpc::primitives::Sha256,
+    pub amount: fedimint_core::Amount,
+    /// Block height at which the HTLC times out
+    pub expiry: u64,
+    /// Identifies the HTLC among those of its channel
+    pub htlc_index: u64,
+    pub direction: HtlcDirection,
+    /// Pubkey of the node at the other end of the channel
+    pub peer: String,
+}
+
+/// Whether an [`HtlcInfo`] pays the node or is paid by it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HtlcDirection {
+    Incoming,
+    Outgoing,
+}
+
+/// Where an outgoing lightning payment is, as seen by the paying node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentStatus {
+    InFlight,
+    Succeeded { preimage: [u8; 32] },
+    Failed,
+}
+
+async fn poll_payment_complete<Fut>(payment_status: impl Fn() -> Fut) -> Result<PaymentStatus>
+where
+    Fut: Future<Output = Result<Option<PaymentStatus>>>,
+{
+    poll("payment complete", || async {
+        match payment_status().await.map_err(ControlFlow::Break)? {
+            Some(PaymentStatus::InFlight) => {
+                Err(ControlFlow::Continue(anyhow!("payment is in flight")))
+            }
+            Some(status) => Ok(status),
+            None => Err(ControlFlow::Continue(anyhow!("payment not started"))),
+        }
+    })
+    .await
+}
+
+async fn poll_payment_in_flight<Fut>(payment_status: impl Fn() -> Fut) -> Result<()>
+where
+    Fut: Future<Output = Result<Option<PaymentStatus>>>,
+{
+    poll("payment in flight", || async {
+        match payment_status().await.map_err(ControlFlow::Break)? {
+            Some(PaymentStatus::InFlight) => Ok(()),
+            None => Err(ControlFlow::Continue(anyhow!("payment not started"))),
+            Some(status) => Err(ControlFlow::Break(anyhow!(
+                "payment completed as {status:?} instead of staying in flight"
+            ))),
+        }
+    })
+    .await
+}
+
 // TODO(tvolk131): Remove this method and instead use
 // `open_channel_between_gateways()` below once 0.4.0 is released
 pub async fn open_channel(
@@ -775,6 +2082,8 @@ pub async fn open_channel(
 
     bitcoind.send_to(cln_addr, 100_000_000).await?;
     bitcoind.mine_blocks(10).await?;
+    cln.await_block_height(bitcoind.get_blockchain_info()?.blocks)
+        .await?;
 
     let lnd_pubkey = lnd.pub_key().await?;
     let cln_pubkey = cln.pub_key().await?;
@@ -817,6 +2126,11 @@ pub async fn open_channel(
     .await?;
 
     bitcoind.mine_blocks(10).await?;
+    let funded_height = bitcoind.get_blockchain_info()?.blocks;
+    tokio::try_join!(
+        cln.await_block_height(funded_height),
+        lnd.await_block_height(funded_height)
+    )?;
 
     poll("Wait for channel update", || async {
         let mut lnd_client = lnd.client.lock().await;
@@ -964,34 +2278,332 @@ pub fn name(&self) -> LightningNodeType {
             LightningNode::Ldk => LightningNodeType::Ldk,
         }
     }
+
+    pub async fn pub_key(&self) -> Result<String> {
+        match self {
+            LightningNode::Cln(cln) => cln.pub_key().await,
+            LightningNode::Lnd(lnd) => lnd.pub_key().await,
+            LightningNode::Ldk => bail!("ldk runs inside gatewayd and has no standalone rpc"),
+        }
+    }
+
+    /// Env variables gatewayd needs on top of devimint's globals to reach the
+    /// node, which for a node devimint didn't launch replace the rpc address,
+    /// TLS cert and macaroon the globals point to
+    pub(crate) async fn gateway_env(&self) -> Result<Vec<(&'static str, String)>> {
+        match self {
+            LightningNode::Cln(cln) if cln.is_external() => Ok(vec![(
+                FM_GATEWAY_LIGHTNING_ADDR_ENV,
+                cln.extension_url().await?,
+            )]),
+            LightningNode::Lnd(Lnd {
+                external: Some(external),
+                ..
+            }) => Ok(vec![
+                (FM_LND_RPC_ADDR_ENV, external.rpc_addr.clone()),
+                (FM_LND_TLS_CERT_ENV, utf8(&external.tls_cert).to_owned()),
+                (FM_LND_MACAROON_ENV, utf8(&external.macaroon).to_owned()),
+            ]),
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// The node's view of the network graph, see [`Lnd::describe_graph`] and
+    /// [`Lightningd::list_channels_graph`]
+    pub async fn graph_channels(&self) -> Result<Vec<GraphChannel>> {
+        match self {
+            LightningNode::Cln(cln) => cln.list_channels_graph().await,
+            LightningNode::Lnd(lnd) => lnd.describe_graph().await,
+            LightningNode::Ldk => bail!("ldk runs inside gatewayd and has no standalone rpc"),
+        }
+    }
+
+    /// The policy we apply to payments forwarded over our channel with
+    /// `peer_pubkey`, as the node itself reports it
+    pub async fn channel_policy(&self, peer_pubkey: &str) -> Result<ChannelPolicy> {
+        self.channel_side_policy(peer_pubkey, ChannelSide::Local)
+            .await
+    }
+
+    /// The policy `peer_pubkey` applies to payments it forwards to us over our
+    /// channel, as we learned it from the peer's channel updates
+    pub async fn peer_channel_policy(&self, peer_pubkey: &str) -> Result<ChannelPolicy> {
+        self.channel_side_policy(peer_pubkey, ChannelSide::Remote)
+            .await
+    }
+
+    async fn channel_side_policy(
+        &self,
+        peer_pubkey: &str,
+        side: ChannelSide,
+    ) -> Result<ChannelPolicy> {
+        match self {
+            LightningNode::Cln(cln) => {
+                let peer: cln_rpc::primitives::PublicKey =
+                    peer_pubkey.parse().context("failed to parse peer pubkey")?;
+                let short_channel_id = cln
+                    .request(cln_rpc::model::requests::ListpeerchannelsRequest { id: Some(peer) })
+                    .await?
+                    .channels
+                    .into_iter()
+                    .find_map(|channel| channel.short_channel_id)
+                    .with_context(|| format!("cln has no confirmed channel with {peer_pubkey}"))?;
+                // `listpeerchannels` only reports the policies from v24.02 on,
+                // `listchannels` has a direction of the channel per source
+                let source = match side {
+                    ChannelSide::Local => cln
+                        .pub_key()
+                        .await?
+                        .parse()
+                        .context("failed to parse cln pubkey")?,
+                    ChannelSide::Remote => peer,
+                };
+                let direction = cln
+                    .request(cln_rpc::model::requests::ListchannelsRequest {
+                        short_channel_id: Some(short_channel_id),
+                        source: None,
+                        destination: None,
+                    })
+                    .await?
+                    .channels
+                    .into_iter()
+                    .find(|direction| direction.source == source)
+                    .with_context(|| match side {
+                        ChannelSide::Local => {
+                            format!("cln has no policy for its side of {short_channel_id}")
+                        }
+                        ChannelSide::Remote => {
+                            format!("cln has no channel update from {peer_pubkey} yet")
+                        }
+                    })?;
+                Ok(ChannelPolicy {
+                    base_fee_msat: direction.base_fee_millisatoshi.into(),
+                    fee_rate_ppm: direction.fee_per_millionth,
+                    cltv_delta: direction.delay,
+                })
+            }
+            LightningNode::Lnd(lnd) => {
+                let pub_key = lnd.pub_key().await?;
+                let mut client = lnd.lightning_client_lock().await?;
+                let chan_id = client
+                    .list_channels(ListChannelsRequest::default())
+                    .await?
+                    .into_inner()
+                    .channels
+                    .into_iter()
+                    .find(|channel| channel.remote_pubkey == peer_pubkey)
+                    .with_context(|| format!("lnd has no channel with {peer_pubkey}"))?
+                    .chan_id;
+                let edge = client
+                    .get_chan_info(ChanInfoRequest { chan_id })
+                    .await?
+                    .into_inner();
+                let (local, remote) = if edge.node1_pub == pub_key {
+                    (edge.node1_policy, edge.node2_policy)
+                } else {
+                    (edge.node2_policy, edge.node1_policy)
+                };
+                let policy = match side {
+                    ChannelSide::Local => {
+                        local.context("lnd has no policy for its side of the channel")?
+                    }
+                    ChannelSide::Remote => {
+                        remote.context("lnd has no channel update from the peer yet")?
+                    }
+                };
+                Ok(ChannelPolicy {
+                    base_fee_msat: u64::try_from(policy.fee_base_msat)?,
+                    fee_rate_ppm: u32::try_from(policy.fee_rate_milli_msat)?,
+                    cltv_delta: policy.time_lock_delta,
+                })
+            }
+            LightningNode::Ldk => bail!("ldk runs inside gatewayd and has no standalone rpc"),
+        }
+    }
+}
+
+/// A channel in a node's view of the network graph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphChannel {
+    /// Like `103x1x0`, the block, transaction index and output of the funding
+    pub short_channel_id: String,
+    /// Pubkey of the endpoint sorting first, as in channel announcements
+    pub node1: String,
+    pub node2: String,
+    pub capacity_sat: u64,
+    /// Whether the channel is kept from being announced, in which case only
+    /// its endpoints see it
+    pub private: bool,
+}
+
+impl GraphChannel {
+    /// Whether the channel is between `a` and `b`, given by pubkey
+    pub fn connects(&self, a: &str, b: &str) -> bool {
+        (self.node1 == a && self.node2 == b) || (self.node1 == b && self.node2 == a)
+    }
+
+    fn ordered_nodes(a: String, b: String) -> (String, String) {
+        // hex pubkeys sort like the pubkeys themselves
+        if a <= b {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+}
+
+/// Formats lnd's numeric channel id like lightningd's short channel ids
+fn format_short_channel_id(channel_id: u64) -> String {
+    format!(
+        "{}x{}x{}",
+        channel_id >> 40,
+        (channel_id >> 16) & 0xff_ffff,
+        channel_id & 0xffff
+    )
+}
+
+/// Which end of a channel a [`ChannelPolicy`] is of, see
+/// [`LightningNode::channel_policy`]
+#[derive(Debug, Clone, Copy)]
+enum ChannelSide {
+    Local,
+    Remote,
+}
+
+/// Fees and CLTV delta a node charges for forwarding over one of its channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelPolicy {
+    pub base_fee_msat: u64,
+    /// Proportional fee in millionths of the forwarded amount
+    pub fee_rate_ppm: u32,
+    pub cltv_delta: u32,
+}
+
+/// Changes the routing policy of `node` on its channel with `peer`, returning
+/// once `node` reports the new policy back and `peer` received it, so `peer`
+/// routes with it.
+///
+/// Only `node`'s side of the channel changes, which is the side that matters
+/// for payments `node` forwards to `peer`. lightningd can't set the CLTV
+/// delta per channel, so for it `cltv_delta` has to match its node wide
+/// setting.
+pub async fn set_channel_policy(
+    node: &LightningNode,
+    peer: &LightningNode,
+    base_fee_msat: u64,
+    fee_rate_ppm: u32,
+    cltv_delta: u32,
+) -> Result<()> {
+    let peer_pubkey = peer.pub_key().await?;
+    let policy = ChannelPolicy {
+        base_fee_msat,
+        fee_rate_ppm,
+        cltv_delta,
+    };
+    info!(
+        target: LOG_DEVIMINT,
+        node = %node.name(),
+        peer = %peer.name(),
+        ?policy,
+        "Setting channel policy"
+    );
+
+    match node {
+        LightningNode::Cln(cln) => {
+            let current = node.channel_policy(&peer_pubkey).await?;
+            ensure!(
+                current.cltv_delta == cltv_delta,
+                "cln can't set the cltv delta of a single channel, it is {}",
+                current.cltv_delta
+            );
+            cln.request(cln_rpc::model::requests::SetchannelRequest {
+                id: peer_pubkey.clone(),
+                feebase: Some(ClnRpcAmount::from_msat(base_fee_msat)),
+                feeppm: Some(fee_rate_ppm),
+                htlcmin: None,
+                htlcmax: None,
+                enforcedelay: None,
+                ignorefeelimits: None,
+            })
+            .await?;
+        }
+        LightningNode::Lnd(lnd) => {
+            let mut client = lnd.lightning_client_lock().await?;
+            let channel_point = client
+                .list_channels(ListChannelsRequest::default())
+                .await?
+                .into_inner()
+                .channels
+                .into_iter()
+                .find(|channel| channel.remote_pubkey == peer_pubkey)
+                .with_context(|| format!("lnd has no channel with {peer_pubkey}"))?
+                .channel_point;
+            let (txid, output_index) = channel_point
+                .split_once(':')
+                .with_context(|| format!("invalid channel point {channel_point}"))?;
+            let failed_updates = client
+                .update_channel_policy(PolicyUpdateRequest {
+                    scope: Some(Scope::ChanPoint(ChannelPoint {
+                        funding_txid: Some(FundingTxid::FundingTxidStr(txid.to_owned())),
+                        output_index: output_index.parse()?,
+                    })),
+                    base_fee_msat: i64::try_from(base_fee_msat)?,
+                    fee_rate_ppm,
+                    time_lock_delta: cltv_delta,
+                    ..Default::default()
+                })
+                .await?
+                .into_inner()
+                .failed_updates;
+            ensure!(
+                failed_updates.is_empty(),
+                "lnd failed to update the channel policy: {failed_updates:?}"
+            );
+        }
+        LightningNode::Ldk => bail!("ldk runs inside gatewayd and has no standalone rpc"),
+    }
+
+    poll("channel policy updated", || async {
+        let current = node
+            .channel_policy(&peer_pubkey)
+            .await
+            .map_err(ControlFlow::Continue)?;
+        poll_eq!(current, policy)
+    })
+    .await?;
+
+    let node_pubkey = node.pub_key().await?;
+    poll("peer received channel policy", || async {
+        let seen = peer
+            .peer_channel_policy(&node_pubkey)
+            .await
+            .map_err(ControlFlow::Continue)?;
+        poll_eq!(seen, policy)
+    })
+    .await
 }
 
 #[derive(Clone)]
 pub struct Electrs {
-    _process: ProcessHandle,
+    launch_kind: LaunchKind,
+    pub(crate) process: ProcessHandle,
     _bitcoind: Bitcoind,
 }
 
 impl Electrs {
+    #[instrument(name = "electrs", level = "debug", skip_all)]
     pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Self> {
         // workaround: will crash on start if it gets a bad response from
         // bitcoind
         bitcoind.poll_ready().await?;
         debug!(target: LOG_DEVIMINT, "Starting electrs");
-        let electrs_dir = process_mgr
-            .globals
-            .FM_ELECTRS_DIR
-            .to_str()
-            .context("non utf8 path")?;
-
-        let daemon_dir = &process_mgr.globals.FM_BTC_DIR.display();
-
         let conf = format!(
             include_str!("cfg/electrs.toml"),
             rpc_port = process_mgr.globals.FM_PORT_BTC_RPC,
             p2p_port = process_mgr.globals.FM_PORT_BTC_P2P,
             electrs_port = process_mgr.globals.FM_PORT_ELECTRS,
             monitoring_port = process_mgr.globals.FM_PORT_ELECTRS_MONITORING,
+            network = process_mgr.globals.FM_BITCOIN_NETWORK,
         );
         debug!("electrs conf: {:?}", conf);
         write_overwrite_async(
@@ -999,70 +2611,246 @@ pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Sel
             conf,
         )
         .await?;
-        let cmd = cmd!(
+        let cmd = Self::launch_cmd(&process_mgr.globals);
+        let launch_kind = LaunchKind::detect(
+            "electrs",
+            &process_mgr
+                .globals
+                .FM_ELECTRS_DIR
+                .join(&process_mgr.globals.FM_BITCOIN_NETWORK),
+        )
+        .await?;
+        let process = process_mgr.spawn_daemon("electrs", cmd).await?;
+        debug!(target: LOG_DEVIMINT, "Electrs ready");
+
+        Ok(Self {
+            _bitcoind: bitcoind,
+            process,
+            launch_kind,
+        })
+    }
+
+    /// What [`Self::new`] spawns electrs with
+    pub(crate) fn launch_cmd(globals: &Global) -> Command {
+        let electrs_dir = utf8(&globals.FM_ELECTRS_DIR);
+        let daemon_dir = utf8(&globals.FM_BTC_DIR);
+        cmd!(
             crate::util::Electrs,
             "--conf-dir={electrs_dir}",
             "--db-dir={electrs_dir}",
             "--daemon-dir={daemon_dir}"
-        );
-        let process = process_mgr.spawn_daemon("electrs", cmd).await?;
-        debug!(target: LOG_DEVIMINT, "Electrs ready");
+        )
+    }
 
-        Ok(Self {
+    /// Attaches to the electrs another devimint spawned over `bitcoind`,
+    /// which keeps owning it
+    pub fn reattach(bitcoind: Bitcoind) -> Self {
+        Self {
             _bitcoind: bitcoind,
-            _process: process,
+            process: ProcessHandle::reattached("electrs"),
+            launch_kind: LaunchKind::Reattached,
+        }
+    }
+
+    /// Whether electrs started on an index built by an earlier run
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+}
+
+/// How [`Esplora::new_with_options`] launches esplora, anything left unset
+/// is taken from the environment like [`Esplora::new`] does
+#[derive(Debug, Clone, Default)]
+pub struct EsploraOptions {
+    daemon_rpc_addr: Option<String>,
+    cors: Option<String>,
+    frontend: Option<bool>,
+}
+
+impl EsploraOptions {
+    /// Esplora fetches blocks from bitcoind's rpc at `addr`, e.g. a
+    /// [`crate::throttle::ThrottledProxy`] in front of it
+    pub fn with_daemon_rpc_addr(mut self, addr: &str) -> Self {
+        self.daemon_rpc_addr = Some(addr.to_owned());
+        self
+    }
+
+    /// Esplora answers cross-origin requests from `origins` (e.g. `*`), so
+    /// web e2e suites can hit its HTTP API straight from a browser. Without
+    /// it esplora sends no CORS headers and browsers block such requests,
+    /// unless the frontend is launched, whose origin is then allowed.
+    pub fn with_cors(mut self, origins: &str) -> Self {
+        self.cors = Some(origins.to_owned());
+        self
+    }
+
+    /// Launches the esplora web frontend along with esplora, to browse the
+    /// regtest chain from a browser, see [`Esplora::frontend_url`]. Off by
+    /// default since it slows down startup.
+    pub fn with_frontend(mut self, frontend: bool) -> Self {
+        self.frontend = Some(frontend);
+        self
+    }
+
+    fn cors(&self) -> Option<String> {
+        self.cors.clone().or_else(|| {
+            std::env::var(FM_ESPLORA_CORS_ENV)
+                .ok()
+                .filter(|origins| !origins.is_empty())
         })
     }
+
+    pub(crate) fn frontend(&self) -> bool {
+        self.frontend
+            .unwrap_or_else(|| is_env_var_set(FM_ESPLORA_FRONTEND_ENV))
+    }
 }
 
 #[derive(Clone)]
 pub struct Esplora {
-    _process: ProcessHandle,
+    launch_kind: LaunchKind,
+    frontend_url: Option<String>,
+    pub(crate) frontend: Option<ProcessHandle>,
+    pub(crate) process: ProcessHandle,
     _bitcoind: Bitcoind,
 }
 
 impl Esplora {
     pub async fn new(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Self> {
+        Self::new_with_options(process_mgr, bitcoind, EsploraOptions::default()).await
+    }
+
+    /// Like [`Self::new`], but launched as `options` says
+    #[instrument(name = "esplora", level = "debug", skip_all)]
+    pub async fn new_with_options(
+        process_mgr: &ProcessManager,
+        bitcoind: Bitcoind,
+        options: EsploraOptions,
+    ) -> Result<Self> {
         // workaround: will crash(?) on start if it gets a bad response from
         // bitcoind
         bitcoind.poll_ready().await?;
         debug!("Starting esplora");
-        let daemon_dir = process_mgr
-            .globals
-            .FM_BTC_DIR
-            .to_str()
-            .context("non utf8 path")?;
-        let esplora_dir = process_mgr
-            .globals
-            .FM_ESPLORA_DIR
-            .to_str()
-            .context("non utf8 path")?;
-
-        let btc_rpc_port = process_mgr.globals.FM_PORT_BTC_RPC;
-        let esplora_port = process_mgr.globals.FM_PORT_ESPLORA;
+        let network = &process_mgr.globals.FM_BITCOIN_NETWORK;
         // spawn esplora
+        let cmd = Self::launch_cmd(&process_mgr.globals, &options);
+        let frontend_url = Self::frontend_url_for(&process_mgr.globals, options.frontend());
+        let launch_kind =
+            LaunchKind::detect("esplora", &process_mgr.globals.FM_ESPLORA_DIR.join(network))
+                .await?;
+        let process = process_mgr.spawn_daemon("esplora", cmd).await?;
+
+        Self::wait_for_ready(process_mgr).await?;
+        debug!(target: LOG_DEVIMINT, "Esplora ready");
+        let frontend = match &frontend_url {
+            Some(frontend_url) => Some(Self::start_frontend(process_mgr, frontend_url).await?),
+            None => None,
+        };
+
+        Ok(Self {
+            _bitcoind: bitcoind,
+            process,
+            frontend,
+            frontend_url,
+            launch_kind,
+        })
+    }
+
+    /// Url of the esplora web frontend, if launched, see
+    /// [`EsploraOptions::with_frontend`]
+    pub fn frontend_url(&self) -> Option<&str> {
+        self.frontend_url.as_deref()
+    }
+
+    /// What [`Self::new_with_options`] spawns esplora with
+    pub(crate) fn launch_cmd(globals: &Global, options: &EsploraOptions) -> Command {
+        let daemon_dir = utf8(&globals.FM_BTC_DIR);
+        let esplora_dir = utf8(&globals.FM_ESPLORA_DIR);
+        let esplora_port = globals.FM_PORT_ESPLORA;
+        let network = &globals.FM_BITCOIN_NETWORK;
+        let daemon_rpc_addr = options
+            .daemon_rpc_addr
+            .clone()
+            .unwrap_or_else(|| format!("127.0.0.1:{}", globals.FM_PORT_BTC_RPC));
         let cmd = cmd!(
             crate::util::Esplora,
             "--daemon-dir={daemon_dir}",
             "--db-dir={esplora_dir}",
             "--cookie=bitcoin:bitcoin",
-            "--network=regtest",
-            "--daemon-rpc-addr=127.0.0.1:{btc_rpc_port}",
+            "--network={network}",
+            "--daemon-rpc-addr={daemon_rpc_addr}",
             "--http-addr=127.0.0.1:{esplora_port}",
             "--monitoring-addr=127.0.0.1:0",
             "--jsonrpc-import", // Workaround for incompatible on-disk format
         );
-        let process = process_mgr.spawn_daemon("esplora", cmd).await?;
+        // the frontend calls the API from the browser
+        match options
+            .cors()
+            .or_else(|| Self::frontend_url_for(globals, options.frontend()))
+        {
+            Some(origins) => cmd.arg(&format!("--cors={origins}")),
+            None => cmd,
+        }
+    }
 
-        Self::wait_for_ready(process_mgr).await?;
-        debug!(target: LOG_DEVIMINT, "Esplora ready");
+    fn frontend_url_for(globals: &Global, frontend: bool) -> Option<String> {
+        frontend.then(|| format!("http://127.0.0.1:{}", globals.FM_PORT_ESPLORA_FRONTEND))
+    }
+
+    async fn start_frontend(process_mgr: &ProcessManager, url: &str) -> Result<ProcessHandle> {
+        debug!(target: LOG_DEVIMINT, %url, "Starting esplora frontend");
+        let cmd = Self::frontend_launch_cmd(&process_mgr.globals);
+        let process = process_mgr.spawn_daemon("esplora-frontend", cmd).await?;
+        let addr = format!("127.0.0.1:{}", process_mgr.globals.FM_PORT_ESPLORA_FRONTEND);
+        // building the frontend's assets takes a while on first start
+        poll_with_timeout(
+            "esplora frontend ready",
+            Duration::from_secs(300),
+            || async {
+                tokio::net::TcpStream::connect(&addr)
+                    .await
+                    .context("connect to esplora frontend")
+                    .map_err(ControlFlow::Continue)
+            },
+        )
+        .await?;
+        debug!(target: LOG_DEVIMINT, %url, "Esplora frontend ready");
+        Ok(process)
+    }
+
+    /// What [`Self::new_with_options`] spawns the esplora frontend with
+    pub(crate) fn frontend_launch_cmd(globals: &Global) -> Command {
+        crate::util::EsploraFrontend
+            .cmd()
+            .env(
+                "API_URL",
+                format!("http://127.0.0.1:{}/", globals.FM_PORT_ESPLORA),
+            )
+            .env("PORT", globals.FM_PORT_ESPLORA_FRONTEND.to_string())
+    }
 
+    /// Attaches to the esplora another devimint spawned over `bitcoind`,
+    /// which keeps owning it
+    pub async fn reattach(process_mgr: &ProcessManager, bitcoind: Bitcoind) -> Result<Self> {
+        Self::wait_for_ready(process_mgr).await?;
+        let frontend_url =
+            Self::frontend_url_for(&process_mgr.globals, EsploraOptions::default().frontend());
         Ok(Self {
             _bitcoind: bitcoind,
-            _process: process,
+            process: ProcessHandle::reattached("esplora"),
+            frontend: frontend_url
+                .is_some()
+                .then(|| ProcessHandle::reattached("esplora-frontend")),
+            frontend_url,
+            launch_kind: LaunchKind::Reattached,
         })
     }
 
+    /// Whether esplora started on an index built by an earlier run
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+
     /// Wait until the server is able to respond to requests.
     async fn wait_for_ready(process_mgr: &ProcessManager) -> Result<()> {
         let client = esplora_client::Builder::new(&format!(
@@ -1120,3 +2908,38 @@ pub async fn external_daemons(process_mgr: &ProcessManager) -> Result<ExternalDa
         esplora,
     })
 }
+
+/// Just the lightning nodes and the bitcoind they run on, for developing
+/// against the nodes directly
+pub struct LightningDaemons {
+    pub bitcoind: Bitcoind,
+    pub cln: Lightningd,
+    pub lnd: Lnd,
+}
+
+/// Spins up bitcoind, cln and lnd, and opens a channel between the two
+/// lightning nodes, without electrs, esplora, any federation or gateway
+pub async fn lightning_daemons(process_mgr: &ProcessManager) -> Result<LightningDaemons> {
+    let start_time = fedimint_core::time::now();
+    let bitcoind = Bitcoind::new(process_mgr, false).await?;
+    let (cln, lnd) = tokio::try_join!(
+        Lightningd::new(process_mgr, bitcoind.clone()),
+        Lnd::new(process_mgr, bitcoind.clone()),
+    )?;
+    open_channel(process_mgr, &bitcoind, &cln, &lnd).await?;
+    info!(
+        target: LOG_DEVIMINT,
+        "starting lightning daemons took {:?}",
+        start_time.elapsed()?
+    );
+    Ok(LightningDaemons { bitcoind, cln, lnd })
+}
+
+#[test]
+fn test_format_short_channel_id() {
+    assert_eq!(format_short_channel_id((103 << 40) | (1 << 16)), "103x1x0");
+    assert_eq!(
+        format_short_channel_id((0xff_ffff << 40) | (0xff_ffff << 16) | 0xffff),
+        "16777215x16777215x65535"
+    );
+}
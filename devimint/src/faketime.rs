@@ -0,0 +1,78 @@
+//! Running guardians with skewed clocks.
+//!
+//! Guardians are wrapped in `faketime` from libfaketime, which offsets the
+//! wall clock the guardian sees. Monotonic clocks are left alone, so timers
+//! and timeouts inside the guardian keep working normally.
+
+use std::collections::BTreeMap;
+use std::env;
+
+use anyhow::{ensure, Result};
+
+use crate::envs::FM_GUARDIAN_CLOCK_SKEW_SECS_ENV;
+use crate::util::{parse_guardian_list, Command, ToCmdExt};
+
+/// Whether the `faketime` wrapper of libfaketime can be used here
+pub fn available() -> bool {
+    std::process::Command::new("faketime")
+        .args(["-f", "+0s", "true"])
+        .output()
+        .is_ok_and(|out| out.status.success())
+}
+
+/// Clock skews of `servers` guardians from `FM_GUARDIAN_CLOCK_SKEW_SECS`, in
+/// seconds by peer id. Guardians without a skew, or a skew of 0, are left
+/// out.
+pub fn guardian_skews(servers: usize) -> Result<BTreeMap<usize, i64>> {
+    parse_skews(
+        &env::var(FM_GUARDIAN_CLOCK_SKEW_SECS_ENV).unwrap_or_default(),
+        servers,
+    )
+}
+
+fn parse_skews(value: &str, servers: usize) -> Result<BTreeMap<usize, i64>> {
+    let mut skews = parse_guardian_list(FM_GUARDIAN_CLOCK_SKEW_SECS_ENV, value, servers, |skew| {
+        Ok(skew.parse()?)
+    })?;
+    skews.retain(|_, skew| *skew != 0);
+    Ok(skews)
+}
+
+/// Errors unless guardians can run with `skews`
+pub fn ensure_available(skews: &BTreeMap<usize, i64>) -> Result<()> {
+    ensure!(
+        skews.is_empty() || available(),
+        "guardians need clock skews {skews:?}, but `faketime` from libfaketime is not installed"
+    );
+    Ok(())
+}
+
+/// Wraps `command` so it sees the wall clock offset by `skew_secs`.
+///
+/// Like [`crate::netns::GuardianNetns::exec`], must be called before setting
+/// any env variables on `command`. The offset is inherited by what `command`
+/// executes, so this can wrap a command already run in a namespace.
+pub fn wrap(skew_secs: i64, command: Command) -> Command {
+    [
+        "faketime".to_owned(),
+        "-f".to_owned(),
+        format!("{skew_secs:+}s"),
+    ]
+    .into_iter()
+    .chain(command.args_debug)
+    .collect::<Vec<_>>()
+    .cmd()
+    .env("FAKETIME_DONT_FAKE_MONOTONIC", "1")
+}
+
+#[test]
+fn test_guardian_skews() -> Result<()> {
+    assert_eq!(
+        parse_skews("0, 30, -15", 4)?,
+        BTreeMap::from([(1, 30), (2, -15)])
+    );
+    // an empty entry leaves the guardian's clock alone
+    assert_eq!(parse_skews(",,5", 4)?, BTreeMap::from([(2, 5)]));
+    assert!(parse_skews("1,2,3,4,5", 4).is_err());
+    Ok(())
+}
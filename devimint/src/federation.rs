@@ -1,47 +1,83 @@
-mod config;
+pub(crate) mod config;
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ops::ControlFlow;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
-use std::{env, fs, iter};
+use std::{env, fmt, fs, iter};
 
-use anyhow::{anyhow, bail, Context, Result};
-use bitcoincore_rpc::bitcoin::Network;
-use fedimint_api_client::api::DynGlobalApi;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use bitcoincore_rpc::bitcoin;
+use fedimint_aead::{encrypted_read, encrypted_write, get_encryption_key};
+use fedimint_api_client::api::{DynGlobalApi, StatusResponse};
 use fedimint_client::module::ClientModule;
 use fedimint_core::admin_client::{
     ConfigGenConnectionsRequest, ConfigGenParamsRequest, ServerStatus,
 };
-use fedimint_core::config::{load_from_file, ClientConfig, ServerModuleConfigGenParamsRegistry};
-use fedimint_core::core::LEGACY_HARDCODED_INSTANCE_ID_WALLET;
-use fedimint_core::envs::BitcoinRpcConfig;
+use fedimint_core::config::{
+    load_from_file, ClientConfig, FederationId, ServerModuleConfigGenParamsRegistry,
+    ServerModuleConsensusConfig,
+};
+use fedimint_core::core::{
+    ModuleInstanceId, ModuleKind, LEGACY_HARDCODED_INSTANCE_ID_LN,
+    LEGACY_HARDCODED_INSTANCE_ID_WALLET,
+};
+use fedimint_core::encoding::Encodable as _;
+use fedimint_core::envs::{is_env_var_set, BitcoinRpcConfig};
+use fedimint_core::epoch::ConsensusItem;
+use fedimint_core::invite_code::InviteCode;
 use fedimint_core::module::registry::ModuleDecoderRegistry;
-use fedimint_core::module::{ApiAuth, ModuleCommon};
-use fedimint_core::runtime::block_in_place;
-use fedimint_core::task::block_on;
+use fedimint_core::module::{ApiAuth, ModuleCommon, SerdeModuleEncoding, ServerModuleInit as _};
+use fedimint_core::secp256k1::PublicKey;
+use fedimint_core::session_outcome::{AcceptedItem, SessionStatus};
 use fedimint_core::task::jit::JitTryAnyhow;
+use fedimint_core::transaction::{Transaction, TransactionSignature};
 use fedimint_core::util::SafeUrl;
-use fedimint_core::{Amount, PeerId};
-use fedimint_logging::LOG_DEVIMINT;
+use fedimint_core::{Amount, NumPeers, PeerId, TieredMulti, TransactionId};
+use fedimint_ln_client::{LightningOperationMeta, LightningOperationMetaVariant};
+use fedimint_ln_server::common::contracts::{ContractId, FundedContract};
+use fedimint_ln_server::common::{ContractAccount, LightningGateway, LightningGatewayAnnouncement};
+use fedimint_lnv2_client::{FinalReceiveState, FinalSendState};
+use fedimint_logging::{LOG_DB, LOG_DEVIMINT};
+use fedimint_meta_server::MetaInit;
+use fedimint_mint_client::{OOBNotes, SpendableNote};
+use fedimint_mint_server::common::config::{FeeConsensus, MintClientConfig};
 use fedimint_portalloc::port_alloc;
+use fedimint_server::config::io::{
+    CONSENSUS_CONFIG, DB_FILE, ENCRYPTED_EXT, JSON_EXT, PLAINTEXT_PASSWORD, PRIVATE_CONFIG,
+    SALT_FILE,
+};
 use fedimint_server::config::ConfigGenParams;
 use fedimint_testing::federation::local_config_gen_params;
 use fedimint_wallet_client::config::WalletClientConfig;
-use fedimint_wallet_client::WalletClientModule;
-use fedimintd::envs::FM_EXTRA_DKG_META_ENV;
+use fedimint_wallet_client::{WalletClientModule, WalletConsensusItem};
+use fedimintd::envs::{FM_EXTRA_DKG_META_ENV, FM_LOG_JSON_ENV};
 use fs_lock::FileLock;
 use futures::future::join_all;
 use rand::Rng;
-use tokio::task::JoinSet;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use tokio::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, info, instrument, warn};
 
+use self::config::ModuleOptions;
 use super::external::Bitcoind;
-use super::util::{cmd, parse_map, Command, ProcessHandle, ProcessManager};
+use super::util::{cmd, parse_map, Command, KillSignal, LaunchKind, ProcessHandle, ProcessManager};
 use super::vars::utf8;
-use crate::envs::{FM_CLIENT_DIR_ENV, FM_DATA_DIR_ENV};
-use crate::util::{poll, poll_with_timeout, FedimintdCmd};
+use crate::api_bind::ApiBind;
+use crate::byzantine::{CorruptMode, CorruptingProxy};
+use crate::config_diff::{ConsensusConfigExport, ModuleConfigExport};
+use crate::disk::GuardianDisk;
+use crate::envs::{
+    FM_CLIENT_DIR_ENV, FM_DATA_DIR_ENV, FM_FEDIMINTD_BASE_EXECUTABLE_ENV, FM_GUARDIAN_LABELS_ENV,
+    FM_LOGS_DIR_ENV,
+};
+use crate::netns::GuardianNetns;
+use crate::util::{
+    block_in_place, parse_guardian_list, poll, poll_with_timeout, FedimintdCmd, JsonValueExt,
+};
 use crate::version_constants::{VERSION_0_3_0, VERSION_0_3_0_ALPHA, VERSION_0_5_0_ALPHA};
 use crate::{poll_eq, vars};
 
@@ -54,22 +90,245 @@ pub struct Federation {
 
     /// Built in [`Client`], already joined
     client: JitTryAnyhow<Client>,
+    /// Network namespaces of the guardians, if they run in their own, see
+    /// [`crate::netns`]
+    netns: BTreeMap<usize, Arc<GuardianNetns>>,
+    /// Size capped filesystems of the guardians that have their data dir on
+    /// one, see [`crate::disk`]
+    disks: BTreeMap<usize, Arc<GuardianDisk>>,
+    /// Proxies in front of the APIs of the guardians that have one, see
+    /// [`crate::byzantine`]
+    response_proxies: BTreeMap<usize, CorruptingProxy>,
+    /// Wall clock offset of each guardian with a skewed clock in seconds, see
+    /// [`crate::faketime`]
+    clock_skews: BTreeMap<usize, i64>,
+    /// Current admin credentials of each guardian
+    api_auth: BTreeMap<usize, ApiAuth>,
+    /// Human readable name of each guardian, see [`Self::guardian_by_label`]
+    labels: BTreeMap<usize, String>,
+    /// Name the federation was created with, which tells apart federations
+    /// sharing one devimint
+    name: String,
+    /// How long DKG took, see [`Self::dkg_duration`]
+    dkg_duration: Duration,
 }
 
 impl Drop for Federation {
     fn drop(&mut self) {
+        // stops the guardians in parallel, blocking rather than awaiting them so
+        // dropping works on any runtime
         block_in_place(|| {
-            block_on(async {
-                let mut set = JoinSet::new();
-
+            std::thread::scope(|scope| {
                 while let Some((_id, fedimintd)) = self.members.pop_first() {
-                    set.spawn(async { drop(fedimintd) });
+                    scope.spawn(move || drop(fedimintd));
                 }
-                while (set.join_next().await).is_some() {}
             });
         });
     }
 }
+
+/// Everything needed to attach to a running [`Federation`] from another
+/// process, see [`Federation::to_handle`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederationHandle {
+    pub name: String,
+    /// Peer ids of the guardians that were running
+    pub members: BTreeSet<usize>,
+    /// Env vars of each guardian, which carry its ports and data dir
+    pub vars: BTreeMap<usize, BTreeMap<String, String>>,
+    /// Current admin password of each guardian
+    pub api_auth: BTreeMap<usize, String>,
+    pub labels: BTreeMap<usize, String>,
+    pub clock_skews: BTreeMap<usize, i64>,
+    pub invite_code: String,
+    pub dkg_duration: Duration,
+}
+
+/// Balance sheet of a [`Federation`] as reported by [`Federation::audit`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditSummary {
+    /// Total value of the outstanding ecash (a liability of the federation)
+    pub issued_ecash: Amount,
+    /// Value of the UTXOs held by the wallet module
+    pub onchain_balance: Amount,
+    /// Assets minus liabilities across all modules, negative if the
+    /// federation is insolvent
+    pub net_assets_msat: i64,
+}
+
+impl AuditSummary {
+    pub fn is_solvent(&self) -> bool {
+        0 <= self.net_assets_msat
+    }
+}
+
+/// Guardians that signed a peg-out, see [`Federation::pegout_signers`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PegOutSigners {
+    /// Id of the signed peg-out transaction
+    pub txid: bitcoin::Txid,
+    /// Guardians whose signatures consensus accepted
+    pub signers: BTreeSet<PeerId>,
+}
+
+/// Generation of the lightning module a [`Federation`] runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LightningVersion {
+    /// The legacy `ln` module
+    V1,
+    /// The `lnv2` module
+    V2,
+}
+
+/// Outcome of a lightning payment made by a [`Client`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayResult {
+    /// The paid invoice
+    pub invoice: String,
+    /// Amount of the paid invoice
+    pub amount: Amount,
+    /// Fee taken by the gateway on top of `amount`
+    pub fee: Amount,
+    /// Gateway the payment was routed through, not known for older clients
+    pub gateway_id: Option<PublicKey>,
+    /// Whether the payment was settled internally between two users of the
+    /// federation instead of over lightning
+    pub is_internal: bool,
+    /// Contract funding the payment, see [`Client::outgoing_contract_state`]
+    pub contract_id: ContractId,
+}
+
+/// What became of the outgoing contract funding a lightning payment, as the
+/// federation sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutgoingContractState {
+    /// Holds the payment's ecash until the gateway claims it with the
+    /// preimage or cancels it
+    Funded,
+    /// Claimed by the gateway, which paid the invoice. Also what a contract
+    /// the payer got refunded after its timelock looks like.
+    Claimed,
+    /// Cancelled by the gateway, which failed to pay the invoice, so the
+    /// payer can take its ecash back
+    Cancelled,
+}
+
+/// Fewest bytes spending an ecash note adds to a transaction: the note's
+/// nonce, the mint's signature on it and the signature spending it
+const MIN_BYTES_PER_SPENT_NOTE: usize = 33 + 48 + 64;
+
+/// Denominations `1 << 0` to `1 << (TX_SIZE_PROBE_TIERS - 1)` msat the notes
+/// of [`Client::probe_transaction_size_limit`] are spread over
+const TX_SIZE_PROBE_TIERS: usize = 20;
+
+/// Part of the client's error for transactions exceeding
+/// [`Transaction::MAX_TX_SIZE`]
+const TX_TOO_LARGE_ERROR: &str = "too large";
+
+/// How many notes fit into a single transaction, see
+/// [`Client::probe_transaction_size_limit`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxSizeLimit {
+    /// Notes reissued by the largest transaction that was accepted
+    pub max_notes: usize,
+    /// Why reissuing one note more in a single transaction was rejected
+    pub rejection: String,
+}
+
+/// A gateway registration as a [`Client`] sees it when choosing a gateway to
+/// route through
+#[derive(Debug, Clone)]
+pub struct GatewayRegistration {
+    /// Federation the client got the registration from
+    pub federation_id: FederationId,
+    /// What the gateway announced, including its fees and route hints
+    pub info: LightningGateway,
+    /// Whether the gateway was vetted by the federation
+    pub vetted: bool,
+    /// How long the registration remains valid for
+    pub ttl: Duration,
+    /// Whether the gateway announced it pays invoices for clients
+    pub pays_invoices: bool,
+    /// Whether the gateway announced it accepts incoming payments to clients
+    pub receives_payments: bool,
+}
+
+/// Database log lines a guardian wrote while starting, see
+/// [`Federation::restart_server_with_bin`]
+#[derive(Debug, Clone, Default)]
+pub struct DbMigrationLog {
+    pub lines: Vec<String>,
+}
+
+impl DbMigrationLog {
+    /// Migration steps that actually ran, one per module and version bump
+    pub fn migrations(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .filter(|line| line.contains("Migrating module"))
+    }
+
+    /// Lines reporting a failed or missing migration
+    pub fn errors(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .filter(|line| line.contains("ERROR") || line.contains("Missing server db migration"))
+    }
+
+    /// Database version each module (and the server itself) reported to be at
+    /// once done migrating, by module kind
+    pub fn db_versions(&self) -> BTreeMap<String, u64> {
+        self.versions("Migration complete", "db_version")
+    }
+
+    /// Database version each migrated module was to be migrated to, by
+    /// module kind
+    pub fn target_db_versions(&self) -> BTreeMap<String, u64> {
+        self.versions("Migrating module", "target_db_version")
+    }
+
+    fn versions(&self, message: &str, field: &str) -> BTreeMap<String, u64> {
+        self.lines
+            .iter()
+            .filter(|line| line.contains(message))
+            .filter_map(|line| {
+                let kind = log_field(line, "kind")?;
+                let version = log_field(line, field)?
+                    .strip_prefix("DatabaseVersion(")?
+                    .strip_suffix(')')?
+                    .parse()
+                    .ok()?;
+                Some((kind.trim_matches('"').to_owned(), version))
+            })
+            .collect()
+    }
+}
+
+/// Value of the field `key` of a log line, either in the text format
+/// (`key=value`) or the JSON one (see [`crate::logs`])
+fn log_field(line: &str, key: &str) -> Option<String> {
+    if line.starts_with('{') {
+        let entry: serde_json::Value = serde_json::from_str(line).ok()?;
+        return entry["fields"][key].as_str().map(ToOwned::to_owned);
+    }
+    line.split_whitespace()
+        .find_map(|token| token.strip_prefix(key)?.strip_prefix('='))
+        .map(ToOwned::to_owned)
+}
+
+/// Meta fields of a federation as a [`Client`] fetches them, from the meta
+/// module once the guardians agreed on a value, from the legacy meta of the
+/// client config before
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MetaFields {
+    /// Revision of the meta module's consensus value, `0` for both the legacy
+    /// meta and the first value agreed on
+    pub revision: u64,
+    pub values: BTreeMap<String, String>,
+}
+
 /// `fedimint-cli` instance (basically path with client state: config + db)
 #[derive(Clone)]
 pub struct Client {
@@ -120,6 +379,24 @@ pub fn create(name: &str) -> Result<Client> {
         })
     }
 
+    /// Every client that joined a federation, whoever created it, e.g. to
+    /// wait for all of them to be idle
+    pub fn list_joined() -> Result<Vec<Client>> {
+        let mut clients = vec![];
+        for entry in std::fs::read_dir(Self::clients_dir())? {
+            let entry = entry?;
+            if entry.path().join("client.db").exists() {
+                clients.push(Client {
+                    name: entry
+                        .file_name()
+                        .into_string()
+                        .map_err(|name| anyhow!("non utf8 client name {name:?}"))?,
+                });
+            }
+        }
+        Ok(clients)
+    }
+
     /// Open or create a [`Client`] that starts with a fresh state.
     pub fn open_or_create(name: &str) -> Result<Client> {
         block_in_place(|| {
@@ -201,9 +478,274 @@ pub async fn new_forked(&self, name: &str) -> Result<Client> {
     }
 
     pub async fn balance(&self) -> Result<u64> {
-        Ok(cmd!(self, "info").out_json().await?["total_amount_msat"]
-            .as_u64()
-            .unwrap())
+        #[derive(Deserialize)]
+        struct Info {
+            total_amount_msat: u64,
+        }
+
+        Ok(self.cli::<Info>(&["info"]).await?.total_amount_msat)
+    }
+
+    /// Number of spendable ecash notes the client holds per denomination,
+    /// notes already selected for a spend are not included
+    pub async fn notes(&self) -> Result<BTreeMap<Amount, usize>> {
+        let denominations: BTreeMap<u64, usize> = cmd!(self, "info").out_json().await?
+            ["denominations_msat"]
+            .take()
+            .to_typed()?;
+        Ok(denominations
+            .into_iter()
+            .map(|(msats, count)| (Amount::from_msats(msats), count))
+            .collect())
+    }
+
+    /// Reissues all of the client's notes at once, letting the mint module
+    /// pick its preferred denominations for the whole balance again
+    pub async fn consolidate_notes(&self) -> Result<()> {
+        let balance = self.balance().await?;
+        if balance == 0 {
+            return Ok(());
+        }
+        let notes = cmd!(self, "spend", balance).out_json().await?["notes"]
+            .as_str()
+            .context("spend output must contain notes")?
+            .to_owned();
+        cmd!(self, "reissue", notes).run().await?;
+        Ok(())
+    }
+
+    /// Spends notes of exactly the denominations in `counts`, e.g. to build
+    /// transactions with as many notes as possible. Notes the client lacks
+    /// are broken out of larger ones by reissuing the rest of its balance,
+    /// which refills the client's small denominations.
+    ///
+    /// Errors if the mint doesn't issue one of the denominations or the
+    /// client's balance can't be broken into the requested notes.
+    pub async fn mint_exact_denominations(
+        &self,
+        counts: BTreeMap<Amount, usize>,
+    ) -> Result<OOBNotes> {
+        let tiers = self.mint_tiers().await?;
+        if let Some(amount) = counts.keys().find(|amount| !tiers.contains(amount)) {
+            bail!("the mint doesn't issue notes of {amount}, only of {tiers:?}");
+        }
+        let requested: Amount = counts
+            .iter()
+            .map(|(amount, count)| *amount * *count as u64)
+            .sum();
+        let balance = Amount::from_msats(self.balance().await?);
+        ensure!(
+            requested <= balance,
+            "{requested} of notes requested, but the client only holds {balance}"
+        );
+
+        let mut missing = counts;
+        missing.retain(|_, count| 0 < *count);
+        let mut picked = TieredMulti::default();
+        let mut federation_id_prefix = None;
+        // a round without progress reissues the client's balance into small
+        // denominations, so giving up takes two of them
+        let mut stalled_rounds = 0;
+        while !missing.is_empty() {
+            ensure!(
+                stalled_rounds < 2,
+                "can't break the client's balance into the missing notes {missing:?}"
+            );
+            let balance = self.balance().await?;
+            ensure!(
+                0 < balance,
+                "the client ran out of notes, still missing {missing:?}"
+            );
+            let notes: OOBNotes = cmd!(self, "spend", balance).out_json().await?["notes"]
+                .as_str()
+                .context("spend output must contain notes")?
+                .parse()?;
+            federation_id_prefix = Some(notes.federation_id_prefix());
+
+            let mut rest = TieredMulti::default();
+            let mut progress = false;
+            for (amount, note) in notes.notes().iter_items() {
+                match missing.get_mut(&amount) {
+                    Some(count) if 0 < *count => {
+                        *count -= 1;
+                        picked.push(amount, *note);
+                        progress = true;
+                    }
+                    _ => rest.push(amount, *note),
+                }
+            }
+            missing.retain(|_, count| 0 < *count);
+            stalled_rounds = if progress { 0 } else { stalled_rounds + 1 };
+
+            if 0 < rest.count_items() {
+                let rest = OOBNotes::new(notes.federation_id_prefix(), rest);
+                cmd!(self, "reissue", rest).run().await?;
+            }
+        }
+
+        Ok(OOBNotes::new(
+            federation_id_prefix.context("no notes requested")?,
+            picked,
+        ))
+    }
+
+    /// Reissues as many notes as fit into a single transaction, after
+    /// checking a transaction with one note more is rejected, and returns the
+    /// limit along with the rejection reason. Needs a balance of about 20k
+    /// sats.
+    ///
+    /// Transactions are capped at [`Transaction::MAX_TX_SIZE`] bytes rather
+    /// than by number of inputs and outputs, which clients check before
+    /// submitting. To find the most notes that fit without reissuing each
+    /// attempt for good, every probe includes a note that was spent already,
+    /// so the federation rejects the probes that fit.
+    pub async fn probe_transaction_size_limit(&self) -> Result<TxSizeLimit> {
+        // more notes than could possibly fit, plus two of the smallest
+        // denomination to spend beforehand and to replace the spent one with
+        let pool_size = Transaction::MAX_TX_SIZE / MIN_BYTES_PER_SPENT_NOTE + 1;
+        let mut counts: BTreeMap<Amount, usize> = (0..TX_SIZE_PROBE_TIERS)
+            .map(|tier| {
+                (
+                    Amount::from_msats(1 << tier),
+                    pool_size.div_ceil(TX_SIZE_PROBE_TIERS),
+                )
+            })
+            .collect();
+        *counts.entry(Amount::from_msats(1)).or_default() += 2;
+        let notes = self.mint_exact_denominations(counts).await?;
+        let federation_id_prefix = notes.federation_id_prefix();
+        // ordered by amount, so the first two are of the smallest denomination
+        let mut pool: Vec<(Amount, SpendableNote)> = notes
+            .notes()
+            .iter_items()
+            .map(|(amount, note)| (amount, *note))
+            .collect();
+        let spent = pool.remove(0);
+        let spare = pool.remove(0);
+        cmd!(
+            self,
+            "reissue",
+            OOBNotes::new(federation_id_prefix, [spent].into_iter().collect())
+        )
+        .run()
+        .await?;
+
+        // `first` and the first `count` notes of the pool, the spent and the
+        // spare note encode to the same size
+        let attempt = |first: (Amount, SpendableNote), count: usize| {
+            let notes = std::iter::once(first)
+                .chain(pool[..count].iter().copied())
+                .collect();
+            let notes = OOBNotes::new(federation_id_prefix, notes);
+            async move {
+                cmd!(self, "reissue", notes)
+                    .out_or_err_json()
+                    .await
+                    .map(|result| {
+                        result.map_err(|err| err["error"].as_str().unwrap_or_default().to_owned())
+                    })
+            }
+        };
+
+        // the spent note along with `lo` notes of the pool fits, with `hi` it
+        // doesn't
+        let (mut lo, mut hi) = (0, pool.len());
+        match attempt(spent, hi).await? {
+            Err(err) if err.contains(TX_TOO_LARGE_ERROR) => {}
+            result => bail!("{} notes fit into one transaction: {result:?}", hi + 1),
+        }
+        while lo + 1 < hi {
+            let mid = (lo + hi) / 2;
+            match attempt(spent, mid).await? {
+                Ok(_) => bail!("a transaction spending a spent note was accepted"),
+                Err(err) if err.contains(TX_TOO_LARGE_ERROR) => hi = mid,
+                Err(_) => lo = mid,
+            }
+        }
+
+        let rejection = match attempt(spare, hi).await? {
+            Err(err) if err.contains(TX_TOO_LARGE_ERROR) => err,
+            result => bail!(
+                "reissuing {} notes wasn't rejected for its size: {result:?}",
+                hi + 1
+            ),
+        };
+        attempt(spare, lo)
+            .await?
+            .map_err(|err| anyhow!("reissuing {} notes was rejected: {err}", lo + 1))?;
+        Ok(TxSizeLimit {
+            max_notes: lo + 1,
+            rejection,
+        })
+    }
+
+    /// Submits a transaction just over [`Transaction::MAX_TX_SIZE`] straight
+    /// to guardian `peer_id`, past the client's own size check, and returns
+    /// the guardian's error. Signature bytes of an unknown scheme pad out the
+    /// otherwise empty transaction.
+    pub async fn submit_oversized_transaction(&self, peer_id: usize) -> Result<String> {
+        let padded = |len| Transaction {
+            inputs: vec![],
+            outputs: vec![],
+            nonce: [0; 8],
+            signatures: TransactionSignature::Default {
+                variant: u64::MAX,
+                bytes: vec![0; len],
+            },
+        };
+        let overhead = padded(0).consensus_encode_to_vec().len();
+        let transaction = padded(Transaction::MAX_TX_SIZE + 1 - overhead);
+        match cmd!(
+            self,
+            "dev",
+            "api",
+            "--peer-id",
+            peer_id,
+            "submit_transaction",
+            transaction.consensus_encode_to_hex()
+        )
+        .out_or_err_json()
+        .await?
+        {
+            Ok(outcome) => {
+                bail!("guardian {peer_id} accepted an oversized transaction: {outcome}")
+            }
+            Err(err) => Ok(err["error"].as_str().unwrap_or_default().to_owned()),
+        }
+    }
+
+    /// Denominations the mint issues notes of
+    async fn mint_tiers(&self) -> Result<BTreeSet<Amount>> {
+        let modules = cmd!(self, "config").out_json().await?["modules"].take();
+        let mint = modules
+            .as_object()
+            .context("config without modules")?
+            .values()
+            .find(|module| module["kind"] == fedimint_mint_server::common::KIND.as_str())
+            .context("mint module not found")?;
+        let tbs_pks: BTreeMap<Amount, serde_json::Value> = mint["tbs_pks"].clone().to_typed()?;
+        Ok(tbs_pks.into_keys().collect())
+    }
+
+    /// Gateways in the client's gateway cache after updating it from the
+    /// federation, which are the ones the client picks from to route payments
+    pub async fn list_gateways(&self) -> Result<Vec<GatewayRegistration>> {
+        let federation_id = cmd!(self, "info").out_json().await?["federation_id"]
+            .take()
+            .to_typed()?;
+        let announcements: Vec<LightningGatewayAnnouncement> =
+            cmd!(self, "list-gateways").out_json().await?.to_typed()?;
+        Ok(announcements
+            .into_iter()
+            .map(|announcement| GatewayRegistration {
+                federation_id,
+                info: announcement.info,
+                vetted: announcement.vetted,
+                ttl: announcement.ttl,
+                pays_invoices: announcement.pays_invoices,
+                receives_payments: announcement.receives_payments,
+            })
+            .collect())
     }
 
     // TODO(support:v0.2): remove
@@ -223,6 +765,230 @@ pub async fn use_gateway(&self, gw: &super::gatewayd::Gatewayd) -> Result<()> {
         Ok(())
     }
 
+    /// Pay `invoice` via the gateway `gateway_id` and wait for the payment to
+    /// complete, returning the fee the gateway charged for it
+    pub async fn ln_pay(&self, invoice: String, gateway_id: String) -> Result<PayResult> {
+        let operation_id = cmd!(self, "ln-pay", invoice, "--gateway-id", gateway_id)
+            .out_json()
+            .await?["operation_id"]
+            .as_str()
+            .context("operation_id must be a string")?
+            .to_owned();
+
+        self.pay_result(&operation_id).await
+    }
+
+    /// Like [`Self::ln_pay`], but also checks the client routed the payment
+    /// over lightning through `gateway_id` and no other gateway, which the
+    /// returned [`PayResult`] records
+    pub async fn ln_pay_via(&self, invoice: String, gateway_id: &str) -> Result<PayResult> {
+        let expected: PublicKey = gateway_id.parse().context("invalid gateway id")?;
+        let pay = self.ln_pay(invoice, gateway_id.to_owned()).await?;
+        ensure!(
+            !pay.is_internal,
+            "payment was settled internally instead of through gateway {expected}"
+        );
+        ensure!(
+            pay.gateway_id == Some(expected),
+            "payment was routed through gateway {:?} instead of {expected}",
+            pay.gateway_id
+        );
+        Ok(pay)
+    }
+
+    /// Checks paying `invoice` restricted to the gateway `gateway_id` fails
+    /// because that gateway couldn't route it, e.g. as it has no route to the
+    /// payee, and that the client got refunded rather than falling back to
+    /// another gateway. Returns the error the gateway reported.
+    pub async fn assert_ln_pay_via_fails(
+        &self,
+        invoice: String,
+        gateway_id: &str,
+    ) -> Result<String> {
+        /// Start of [`fedimint_ln_client::pay::GatewayPayError::GatewayInternalError`]
+        const ROUTING_ERROR: &str = "Lightning Gateway failed to pay invoice";
+
+        let pay = cmd!(self, "ln-pay", invoice, "--gateway-id", gateway_id)
+            .out_json()
+            .await
+            .with_context(|| format!("paying via gateway {gateway_id}"))?;
+        ensure!(
+            pay["status"] == "refunded",
+            "payment via gateway {gateway_id} wasn't refunded: {pay}"
+        );
+        let gateway_error = pay["gateway_error"]
+            .as_str()
+            .context("refund must report the gateway error")?;
+        ensure!(
+            gateway_error.starts_with(ROUTING_ERROR),
+            "payment via gateway {gateway_id} failed with {gateway_error} instead of a routing error"
+        );
+        debug!(target: LOG_DEVIMINT, %gateway_id, %gateway_error, "Payment via gateway failed as expected");
+        Ok(gateway_error.to_owned())
+    }
+
+    /// Pay `amount` to the LNURL-pay endpoint or lightning address `lnurl` via
+    /// `gw` and wait for the payment to complete. fedimint-cli resolves
+    /// `lnurl` to an invoice, with `env` set, like
+    /// [`crate::lnurl::LnurlServer::client_env`]. The invoice ends up in the
+    /// returned [`PayResult`].
+    pub async fn pay_lnurl(
+        &self,
+        lnurl: &str,
+        amount: Amount,
+        gw: &super::gatewayd::Gatewayd,
+        env: &[(String, String)],
+    ) -> Result<PayResult> {
+        ensure!(
+            lnurl.to_lowercase().starts_with("lnurl") || lnurl.contains('@'),
+            "{lnurl} is neither an LNURL nor a lightning address"
+        );
+        let gateway_id = gw.gateway_id().await?;
+        let operation_id = cmd!(
+            self,
+            "ln-pay",
+            lnurl,
+            "--amount",
+            amount.msats,
+            "--gateway-id",
+            gateway_id
+        )
+        .envs(env.iter().map(|(var, value)| (var, value)))
+        .out_json()
+        .await
+        .with_context(|| format!("resolving and paying {lnurl}"))?["operation_id"]
+            .as_str()
+            .context("operation_id must be a string")?
+            .to_owned();
+
+        self.pay_result(&operation_id).await
+    }
+
+    /// Read the [`PayResult`] of a previous lightning payment from the
+    /// client's operation log
+    pub async fn pay_result(&self, operation_id: &str) -> Result<PayResult> {
+        let operations = cmd!(self, "list-operations", "--limit", 100)
+            .out_json()
+            .await?["operations"]
+            .as_array()
+            .context("operations must be an array")?
+            .clone();
+        let operation = operations
+            .into_iter()
+            .find(|op| op["id"].as_str() == Some(operation_id))
+            .with_context(|| format!("operation {operation_id} not found in operation log"))?;
+
+        let meta: LightningOperationMeta = operation["operation_meta"].clone().to_typed()?;
+        let LightningOperationMetaVariant::Pay(pay) = meta.variant else {
+            bail!("operation {operation_id} is not a lightning payment");
+        };
+
+        Ok(PayResult {
+            invoice: pay.invoice.to_string(),
+            amount: Amount::from_msats(
+                pay.invoice
+                    .amount_milli_satoshis()
+                    .context("invoice must have an amount")?,
+            ),
+            fee: pay.fee,
+            gateway_id: pay.gateway_id,
+            is_internal: pay.is_internal_payment,
+            contract_id: pay.contract_id,
+        })
+    }
+
+    /// State of the outgoing contract `contract_id` of a lightning payment,
+    /// which errors until the payment funded it
+    pub async fn outgoing_contract_state(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<OutgoingContractState> {
+        let account: Option<ContractAccount> = cmd!(
+            self,
+            "dev",
+            "api",
+            "module_{LEGACY_HARDCODED_INSTANCE_ID_LN}_account",
+            contract_id
+        )
+        .out_json()
+        .await?["value"]
+            .take()
+            .to_typed()?;
+        let account = account.with_context(|| format!("contract {contract_id} isn't funded"))?;
+        let FundedContract::Outgoing(contract) = account.contract else {
+            bail!("contract {contract_id} isn't an outgoing contract");
+        };
+        Ok(if contract.cancelled {
+            OutgoingContractState::Cancelled
+        } else if account.amount == Amount::ZERO {
+            OutgoingContractState::Claimed
+        } else {
+            OutgoingContractState::Funded
+        })
+    }
+
+    /// Waits until the outgoing contract `contract_id` is in the state
+    /// `expected`, see [`Self::outgoing_contract_state`]
+    pub async fn await_outgoing_contract_state(
+        &self,
+        contract_id: &ContractId,
+        expected: OutgoingContractState,
+        timeout: Duration,
+    ) -> Result<()> {
+        poll_with_timeout(
+            &format!("contract {contract_id} {expected:?}"),
+            timeout,
+            || async {
+                let state = self
+                    .outgoing_contract_state(contract_id)
+                    .await
+                    .map_err(ControlFlow::Continue)?;
+                if state == expected {
+                    Ok(())
+                } else {
+                    Err(ControlFlow::Continue(anyhow!(
+                        "contract {contract_id} is {state:?}"
+                    )))
+                }
+            },
+        )
+        .await
+    }
+
+    /// Requests an invoice for `amount` from the gateway with api `gateway`
+    /// using the lnv2 contract flow, returning the invoice and the receive
+    /// operation id
+    pub async fn lnv2_receive(&self, gateway: &str, amount: Amount) -> Result<(String, String)> {
+        let (invoice, operation_id): (String, String) =
+            cmd!(self, "module", "lnv2", "receive", gateway, amount.msats)
+                .out_json()
+                .await?
+                .to_typed()?;
+        Ok((invoice, operation_id))
+    }
+
+    /// Waits for an lnv2 receive started by [`Self::lnv2_receive`] to
+    /// complete
+    pub async fn lnv2_await_receive(&self, operation_id: &str) -> Result<FinalReceiveState> {
+        cmd!(self, "module", "lnv2", "await-receive", operation_id)
+            .out_json()
+            .await?
+            .to_typed()
+    }
+
+    /// Pays `invoice` through the gateway with api `gateway` using the lnv2
+    /// contract flow, returning once the payment succeeded or was refunded
+    pub async fn lnv2_pay(&self, gateway: &str, invoice: String) -> Result<FinalSendState> {
+        let operation_id: String = cmd!(self, "module", "lnv2", "send", gateway, invoice)
+            .out_json()
+            .await?
+            .to_typed()?;
+        cmd!(self, "module", "lnv2", "await-send", operation_id)
+            .out_json()
+            .await?
+            .to_typed()
+    }
+
     pub async fn get_deposit_addr(&self) -> Result<(String, String)> {
         let deposit = cmd!(self, "deposit-address").out_json().await?;
         Ok((
@@ -242,15 +1008,61 @@ pub fn cmd(&self) -> Command {
         )
     }
 
+    /// Runs `fedimint-cli` with `args` on this client and deserializes its
+    /// JSON output, for commands without a helper of their own. If the
+    /// command fails the error includes its stderr.
+    pub async fn cli<T: DeserializeOwned>(&self, args: &[&str]) -> Result<T> {
+        let output = self.cmd().args(args).out_string().await?;
+        serde_json::from_str(&output).with_context(|| {
+            format!(
+                "parsing output of fedimint-cli {} as {}: {output}",
+                args.join(" "),
+                std::any::type_name::<T>()
+            )
+        })
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
 
     /// Returns the current consensus session count
     pub async fn get_session_count(&self) -> Result<u64> {
-        cmd!(self, "dev", "session-count").out_json().await?["count"]
-            .as_u64()
-            .context("count field wasn't a number")
+        #[derive(Deserialize)]
+        struct SessionCount {
+            count: u64,
+        }
+
+        Ok(self
+            .cli::<SessionCount>(&["dev", "session-count"])
+            .await?
+            .count)
+    }
+
+    /// Meta fields of the federation as the client cached them. `fedimint-cli`
+    /// refreshes the cache each time it opens the client, instead of every
+    /// ten minutes like a long-running client.
+    pub async fn cached_meta_fields(&self) -> Result<MetaFields> {
+        self.cli(&["dev", "cached-meta-fields"]).await
+    }
+
+    /// Waits until the client cached `expected`, like after
+    /// [`Federation::submit_meta`] changed them
+    pub async fn await_cached_meta_fields(&self, expected: &MetaFields) -> Result<MetaFields> {
+        poll("client meta fields update", || async {
+            let fields = self
+                .cached_meta_fields()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            if &fields == expected {
+                Ok(fields)
+            } else {
+                Err(ControlFlow::Continue(anyhow!(
+                    "client has meta fields {fields:?}, expected {expected:?}"
+                )))
+            }
+        })
+        .await
     }
 
     /// Returns once all active state machines complete
@@ -258,6 +1070,49 @@ pub async fn wait_complete(&self) -> Result<()> {
         cmd!(self, "dev", "wait-complete").run().await
     }
 
+    /// Like [`Self::wait_complete`], but gives up after `timeout` with an
+    /// error listing the operations that have no outcome yet. Call it before
+    /// asserting on balances, state machines of operations that already
+    /// completed can still be claiming change in the background.
+    pub async fn await_idle(&self, timeout: Duration) -> Result<()> {
+        // the client db stays locked while fedimint-cli runs
+        let mut wait_complete = cmd!(self, "dev", "wait-complete").kill_on_drop(true);
+        match tokio::time::timeout(timeout, wait_complete.run()).await {
+            Ok(res) => res,
+            Err(_) => {
+                let pending = self.pending_operations().await?;
+                bail!(
+                    "client {} still active after {timeout:?}, operations without an outcome: {}",
+                    self.name,
+                    pending.join(", ")
+                )
+            }
+        }
+    }
+
+    /// Recent operations that have no outcome yet, as `id (kind)`
+    async fn pending_operations(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Operation {
+            id: String,
+            operation_kind: String,
+            outcome: Option<serde_json::Value>,
+        }
+        #[derive(Deserialize)]
+        struct Operations {
+            operations: Vec<Operation>,
+        }
+
+        Ok(self
+            .cli::<Operations>(&["list-operations", "--limit", "100"])
+            .await?
+            .operations
+            .into_iter()
+            .filter(|operation| operation.outcome.is_none())
+            .map(|operation| format!("{} ({})", operation.id, operation.operation_kind))
+            .collect())
+    }
+
     /// Returns once the current session completes
     pub async fn wait_session(&self) -> anyhow::Result<()> {
         info!("Waiting for a new session");
@@ -294,6 +1149,58 @@ pub async fn wait_session_outcome(&self, session_count: u64) -> anyhow::Result<(
     }
 }
 
+/// How [`Federation::new_with_options`] sets up a federation, anything left
+/// unset is taken from the environment like [`Federation::new`] does
+#[derive(Debug, Clone, Default)]
+pub struct FederationOptions {
+    modules: Option<BTreeSet<ModuleKind>>,
+    mint_fees: Option<FeeConsensus>,
+    clock_skews: Option<BTreeMap<usize, i64>>,
+    api_binds: Option<BTreeMap<usize, ApiBind>>,
+    bitcoin_backends: Option<BTreeMap<usize, BitcoinRpcConfig>>,
+}
+
+impl FederationOptions {
+    /// Config gen only instantiates `modules` instead of all default ones,
+    /// which makes DKG faster. The mint is always required and lightning
+    /// modules require the wallet.
+    pub fn with_modules(mut self, modules: BTreeSet<ModuleKind>) -> Self {
+        self.modules = Some(modules);
+        self
+    }
+
+    /// The mint charges `fees` for every note it issues and every note spent,
+    /// at most 1 sat each
+    pub fn with_mint_fees(mut self, fees: FeeConsensus) -> Self {
+        self.mint_fees = Some(fees);
+        self
+    }
+
+    /// Each guardian in `clock_skews` sees its wall clock offset by that many
+    /// seconds, for testing consensus under clock drift. Requires `faketime`
+    /// from libfaketime.
+    pub fn with_clock_skews(mut self, clock_skews: BTreeMap<usize, i64>) -> Self {
+        self.clock_skews = Some(clock_skews);
+        self
+    }
+
+    /// Each guardian in `binds` listens on its address instead of localhost,
+    /// and the federation config and invite code advertise the host it's
+    /// reachable at, so the federation can be used from other hosts or
+    /// containers
+    pub fn with_api_binds(mut self, binds: BTreeMap<usize, ApiBind>) -> Self {
+        self.api_binds = Some(binds);
+        self
+    }
+
+    /// The wallet of each guardian in `backends` uses that bitcoin backend
+    /// instead of devimint's bitcoind, see [`crate::bitcoin_backend`]
+    pub fn with_bitcoin_backends(mut self, backends: BTreeMap<usize, BitcoinRpcConfig>) -> Self {
+        self.bitcoin_backends = Some(backends);
+        self
+    }
+}
+
 impl Federation {
     pub async fn new(
         process_mgr: &ProcessManager,
@@ -302,35 +1209,159 @@ pub async fn new(
         skip_setup: bool,
         federation_name: String,
     ) -> Result<Self> {
+        Self::new_with_options(
+            process_mgr,
+            bitcoind,
+            servers,
+            skip_setup,
+            federation_name,
+            FederationOptions::default(),
+        )
+        .await
+    }
+
+    /// Like [`Self::new`], but set up as `options` says
+    #[instrument(name = "federation", level = "debug", skip_all, fields(federation_name = %federation_name))]
+    pub async fn new_with_options(
+        process_mgr: &ProcessManager,
+        bitcoind: Bitcoind,
+        servers: usize,
+        skip_setup: bool,
+        federation_name: String,
+        options: FederationOptions,
+    ) -> Result<Self> {
+        if let Some(modules) = &options.modules {
+            self::config::validate_module_selection(modules)?;
+        }
+        let module_options = ModuleOptions {
+            kinds: options.modules,
+            mint_fees: options
+                .mint_fees
+                .map_or_else(self::config::mint_fees_from_env, Ok)?,
+        };
+        let clock_skews = options
+            .clock_skews
+            .map_or_else(|| crate::faketime::guardian_skews(servers), Ok)?;
+        let api_binds = options
+            .api_binds
+            .map_or_else(|| crate::api_bind::guardian_api_binds(servers), Ok)?;
+        let bitcoin_backends = options.bitcoin_backends.map_or_else(
+            || crate::bitcoin_backend::guardian_backends(&process_mgr.globals, servers),
+            Ok,
+        )?;
+        self::config::validate_mint_fees(&module_options.mint_fees)?;
+        crate::faketime::ensure_available(&clock_skews)?;
+        ensure!(
+            clock_skews.keys().all(|peer_id| *peer_id < servers),
+            "clock skews {clock_skews:?} for a federation of {servers}"
+        );
+        ensure!(
+            api_binds.keys().all(|peer_id| *peer_id < servers),
+            "api binds {api_binds:?} for a federation of {servers}"
+        );
+        ensure!(
+            bitcoin_backends.keys().all(|peer_id| *peer_id < servers),
+            "bitcoin backends {bitcoin_backends:?} for a federation of {servers}"
+        );
         let mut members = BTreeMap::new();
         let mut peer_to_env_vars_map = BTreeMap::new();
+        let mut api_auth = BTreeMap::new();
+        let labels = guardian_labels(process_mgr, servers)?;
 
         let peers: Vec<_> = (0..servers).map(|id| PeerId::from(id as u16)).collect();
         let base_port = port_alloc((3 * servers).try_into().unwrap())?;
-        let params: HashMap<PeerId, ConfigGenParams> = local_config_gen_params(
+        let mut params: HashMap<PeerId, ConfigGenParams> = local_config_gen_params(
             &peers,
             base_port,
             &ServerModuleConfigGenParamsRegistry::default(),
         )?;
 
+        let mut netns = BTreeMap::new();
+        if crate::netns::enabled() {
+            for peer in &peers {
+                netns.insert(
+                    peer.to_usize(),
+                    Arc::new(GuardianNetns::create(base_port, peer.to_usize()).await?),
+                );
+            }
+            crate::netns::rewrite_config_gen_params(&mut params, &netns)?;
+        }
+        if !api_binds.is_empty() {
+            ensure!(
+                netns.is_empty(),
+                "guardians in network namespaces can't bind their api to other addresses"
+            );
+            crate::api_bind::rewrite_config_gen_params(&mut params, &api_binds)?;
+        }
+        ensure!(
+            bitcoin_backends.is_empty() || netns.is_empty(),
+            "guardians in network namespaces can't use other bitcoin backends"
+        );
+        let proxied = crate::byzantine::guardian_response_proxies(servers)?;
+        ensure!(
+            proxied.is_empty() || netns.is_empty(),
+            "guardians in network namespaces can't have a proxy in front of their api"
+        );
+        let mut response_proxies = BTreeMap::new();
+        for peer_id in proxied {
+            let peer_params = params
+                .get_mut(&PeerId::from(peer_id as u16))
+                .expect("params for every peer");
+            // the proxy takes the guardian's place, which moves to a port of its own
+            let addr = peer_params.local.api_bind;
+            peer_params.local.api_bind.set_port(port_alloc(1)?);
+            response_proxies.insert(
+                peer_id,
+                CorruptingProxy::start(addr, peer_params.local.api_bind).await?,
+            );
+        }
+
+        let disk_sizes = crate::disk::guardian_disks(servers)?;
+        let mut disks = BTreeMap::new();
+
         let mut admin_clients: BTreeMap<PeerId, DynGlobalApi> = BTreeMap::new();
         let mut endpoints: BTreeMap<PeerId, _> = BTreeMap::new();
         for (peer, peer_params) in &params {
-            let peer_env_vars = vars::Fedimintd::init(
+            let mut peer_env_vars = vars::Fedimintd::init(
                 &process_mgr.globals,
                 peer_params.to_owned(),
                 federation_name.clone(),
                 base_port,
+                &vars::Reserve::Launch,
             )
             .await?;
+            if let Some(size_mb) = disk_sizes.get(&peer.to_usize()) {
+                // while the data dir is still empty
+                disks.insert(
+                    peer.to_usize(),
+                    Arc::new(GuardianDisk::create(&peer_env_vars.FM_DATA_DIR, *size_mb).await?),
+                );
+            }
+            let peer_netns = netns.get(&peer.to_usize()).map(AsRef::as_ref);
+            if let Some(peer_netns) = peer_netns {
+                peer_env_vars.FM_FORCE_BITCOIN_RPC_URL = format!(
+                    "http://bitcoin:bitcoin@{}:{}",
+                    peer_netns.host_addr, process_mgr.globals.FM_PORT_BTC_RPC
+                );
+            }
+            if let Some(backend) = bitcoin_backends.get(&peer.to_usize()) {
+                debug!(target: LOG_DEVIMINT, %peer, kind = %backend.kind, url = %backend.url, "Guardian uses its own bitcoin backend");
+                peer_env_vars.FM_FORCE_BITCOIN_RPC_KIND = backend.kind.clone();
+                peer_env_vars.FM_FORCE_BITCOIN_RPC_URL = backend.url.as_str().to_owned();
+            }
             members.insert(
                 peer.to_usize(),
-                Fedimintd::new(
+                Fedimintd::new_inner(
                     process_mgr,
                     bitcoind.clone(),
                     peer.to_usize(),
                     &peer_env_vars,
                     federation_name.clone(),
+                    GuardianLaunch {
+                        label: &labels[&peer.to_usize()],
+                        netns: peer_netns,
+                        clock_skew_secs: clock_skews.get(&peer.to_usize()).copied(),
+                    },
                 )
                 .await?,
             );
@@ -340,28 +1371,34 @@ pub async fn new(
             );
             endpoints.insert(*peer, peer_env_vars.FM_API_URL.clone());
             admin_clients.insert(*peer, admin_client);
+            api_auth.insert(peer.to_usize(), peer_params.local.api_auth.clone());
             peer_to_env_vars_map.insert(peer.to_usize(), peer_env_vars);
         }
 
+        let mut dkg_duration = Duration::ZERO;
         if !skip_setup {
             let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+            let dkg_start = Instant::now();
             if fedimint_cli_version >= *VERSION_0_3_0_ALPHA {
-                run_cli_dkg(params, endpoints).await?;
+                run_cli_dkg(params, endpoints, &module_options).await?;
             } else {
                 // TODO(support:v0.2): old fedimint-cli can't do DKG commands. keep this old DKG
                 // setup while fedimint-cli <= v0.2.x is supported
-                run_client_dkg(admin_clients, params).await?;
+                run_client_dkg(admin_clients, params, &module_options).await?;
             }
+            dkg_duration = dkg_start.elapsed();
+            debug!(?dkg_duration, "DKG complete");
 
             // move configs to config directory
             let client_dir = utf8(&process_mgr.globals.FM_CLIENT_DIR);
             let invite_code_filename_original = "invite-code";
+            let invite_code_filename = invite_code_filename(&federation_name);
 
             // copy over invite-code file to client directory
             let peer_data_dir = utf8(&peer_to_env_vars_map[&0].FM_DATA_DIR);
             tokio::fs::copy(
                 format!("{peer_data_dir}/{invite_code_filename_original}"),
-                format!("{client_dir}/{invite_code_filename_original}"),
+                format!("{client_dir}/{invite_code_filename}"),
             )
             .await
             .context("copying invite-code file")?;
@@ -371,8 +1408,7 @@ pub async fn new(
             for (index, peer_env_vars) in &peer_to_env_vars_map {
                 let peer_data_dir = utf8(&peer_env_vars.FM_DATA_DIR);
 
-                let invite_code_filename_indexed =
-                    format!("{invite_code_filename_original}-{index}");
+                let invite_code_filename_indexed = format!("{invite_code_filename}-{index}");
                 tokio::fs::rename(
                     format!("{peer_data_dir}/{invite_code_filename_original}"),
                     format!("{client_dir}/{invite_code_filename_indexed}"),
@@ -384,9 +1420,10 @@ pub async fn new(
         }
 
         let client = JitTryAnyhow::new_try({
+            let federation_name = federation_name.clone();
             move || async move {
                 let client = Client::open_or_create(federation_name.as_str())?;
-                let invite_code = Self::invite_code_static()?;
+                let invite_code = read_invite_code(&federation_name)?;
                 if !skip_setup {
                     cmd!(client, "join-federation", invite_code).run().await?;
                 }
@@ -399,6 +1436,271 @@ pub async fn new(
             vars: peer_to_env_vars_map,
             bitcoind,
             client,
+            netns,
+            disks,
+            response_proxies,
+            clock_skews,
+            api_auth,
+            labels,
+            name: federation_name,
+            dkg_duration,
+        })
+    }
+
+    /// Describes the running federation so [`Self::from_handle`] can attach
+    /// to it from another process
+    pub fn to_handle(&self) -> Result<FederationHandle> {
+        Ok(FederationHandle {
+            name: self.name.clone(),
+            members: self.members.keys().copied().collect(),
+            vars: self
+                .vars
+                .iter()
+                .map(|(peer_id, vars)| {
+                    let vars = vars
+                        .vars()
+                        .map(|(name, value)| (name.to_owned(), value))
+                        .collect();
+                    (*peer_id, vars)
+                })
+                .collect(),
+            api_auth: self
+                .api_auth
+                .iter()
+                .map(|(peer_id, auth)| (*peer_id, auth.0.clone()))
+                .collect(),
+            labels: self.labels.clone(),
+            clock_skews: self.clock_skews.clone(),
+            invite_code: self.invite_code()?,
+            dkg_duration: self.dkg_duration,
+        })
+    }
+
+    /// Attaches to the federation described by `handle`. The guardians stay
+    /// owned by the process that spawned them, so they keep running when this
+    /// is dropped and can't be restarted through it. Network namespaces
+    /// aren't carried over, so guardians can't be cut off from each other.
+    pub async fn from_handle(
+        process_mgr: &ProcessManager,
+        bitcoind: Bitcoind,
+        handle: &FederationHandle,
+    ) -> Result<Self> {
+        let vars = handle
+            .vars
+            .iter()
+            .map(|(peer_id, vars)| Ok((*peer_id, vars::Fedimintd::from_vars(vars)?)))
+            .collect::<Result<BTreeMap<_, _>>>()
+            .context("reading guardian vars from handle")?;
+        let members = handle
+            .members
+            .iter()
+            .map(|peer_id| {
+                let fedimintd = Fedimintd {
+                    _bitcoind: bitcoind.clone(),
+                    process: ProcessHandle::reattached(&format!(
+                        "fedimintd-{}-{peer_id}",
+                        handle.name
+                    )),
+                    launch_kind: LaunchKind::Reattached,
+                };
+                (*peer_id, fedimintd)
+            })
+            .collect();
+
+        let invite_code_path = process_mgr
+            .globals
+            .FM_CLIENT_DIR
+            .join(invite_code_filename(&handle.name));
+        if !invite_code_path.exists() {
+            tokio::fs::write(&invite_code_path, &handle.invite_code)
+                .await
+                .context("writing invite-code file")?;
+        }
+
+        let client = JitTryAnyhow::new_try({
+            let federation_name = handle.name.clone();
+            move || async move { Client::open_or_create(federation_name.as_str()) }
+        });
+
+        let federation = Self {
+            members,
+            vars,
+            bitcoind,
+            client,
+            netns: BTreeMap::new(),
+            disks: BTreeMap::new(),
+            response_proxies: BTreeMap::new(),
+            clock_skews: handle.clock_skews.clone(),
+            api_auth: handle
+                .api_auth
+                .iter()
+                .map(|(peer_id, auth)| (*peer_id, ApiAuth(auth.clone())))
+                .collect(),
+            labels: handle.labels.clone(),
+            name: handle.name.clone(),
+            dkg_duration: handle.dkg_duration,
+        };
+        federation.await_all_peers().await?;
+        Ok(federation)
+    }
+
+    /// Name the federation was created with
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// How long DKG took when the federation was set up, from the first
+    /// config gen request until every guardian had its config. Zero when
+    /// the federation was started from existing configs with `skip_setup`.
+    pub fn dkg_duration(&self) -> Duration {
+        self.dkg_duration
+    }
+
+    /// Kinds of the modules the federation runs, by instance id
+    pub fn modules(&self) -> Result<BTreeMap<ModuleInstanceId, ModuleKind>> {
+        Ok(self
+            .client_config()?
+            .modules
+            .into_iter()
+            .map(|(instance_id, module)| (instance_id, module.kind))
+            .collect())
+    }
+
+    /// Fees the mint charges per issued and spent note, according to its
+    /// client config
+    pub fn mint_fees(&self) -> Result<FeeConsensus> {
+        let client_config = self.client_config()?;
+        let (instance_id, _) = client_config
+            .modules
+            .iter()
+            .find(|(_, module)| module.kind == fedimint_mint_server::common::KIND)
+            .context("mint module not found")?;
+        let mint_cfg = client_config.modules[instance_id].clone().redecode_raw(
+            &ModuleDecoderRegistry::new([(
+                *instance_id,
+                fedimint_mint_server::common::KIND,
+                fedimint_mint_server::common::MintModuleTypes::decoder(),
+            )]),
+        )?;
+        let mint_cfg: &MintClientConfig = mint_cfg.cast()?;
+        Ok(mint_cfg.fee_consensus.clone())
+    }
+
+    /// Consensus config of the federation, with the module params decoded,
+    /// to compare it with other configs using [`crate::config_diff`]
+    pub async fn export_config(&self) -> Result<ConsensusConfigExport> {
+        let vars = self
+            .vars
+            .values()
+            .next()
+            .context("federation without guardians")?;
+        let mut core: serde_json::Value = load_from_file(
+            &vars
+                .FM_DATA_DIR
+                .join(format!("{CONSENSUS_CONFIG}.{JSON_EXT}")),
+        )?;
+        let server_modules: BTreeMap<ModuleInstanceId, ServerModuleConsensusConfig> =
+            serde_json::from_value(
+                core.as_object_mut()
+                    .and_then(|core| core.remove("modules"))
+                    .context("consensus config without modules")?,
+            )?;
+
+        let mut client_modules = cmd!(self.internal_client().await?, "config")
+            .out_json()
+            .await?["modules"]
+            .take();
+        let mut modules = BTreeMap::new();
+        for (instance_id, module) in server_modules {
+            let mut params = client_modules
+                .get_mut(instance_id.to_string())
+                .with_context(|| format!("client config lacks module {instance_id}"))?
+                .take();
+            if let Some(params) = params.as_object_mut() {
+                params.remove("kind");
+            }
+            modules.insert(
+                instance_id,
+                ModuleConfigExport {
+                    kind: module.kind,
+                    version: module.version,
+                    params,
+                },
+            );
+        }
+        Ok(ConsensusConfigExport { core, modules })
+    }
+
+    /// Whether the federation runs the meta module, which it does unless
+    /// `FM_DISABLE_META_MODULE` was set when it was set up
+    pub fn runs_meta_module(&self) -> Result<bool> {
+        Ok(self
+            .modules()?
+            .values()
+            .any(|kind| kind == &MetaInit::kind()))
+    }
+
+    /// Revision of the meta module's consensus value, `None` until the
+    /// guardians agreed on a first value
+    pub async fn meta_revision(&self) -> Result<Option<u64>> {
+        #[derive(Deserialize)]
+        struct Revision {
+            revision: u64,
+        }
+
+        let revision: Option<Revision> = self
+            .internal_client()
+            .await?
+            .cli(&["module", "meta", "get-rev"])
+            .await?;
+        Ok(revision.map(|revision| revision.revision))
+    }
+
+    /// Has every running guardian propose `values` as the federation's meta
+    /// fields through the meta module, and waits until they agreed on them.
+    ///
+    /// Returns the meta fields clients fetch from then on, with the revision
+    /// of the new consensus value.
+    pub async fn submit_meta(&self, values: &BTreeMap<String, String>) -> Result<MetaFields> {
+        ensure!(
+            self.runs_meta_module()?,
+            "federation does not run the meta module"
+        );
+        let previous = self.meta_revision().await?;
+        let value = serde_json::to_string(values)?;
+        let client = self.internal_client().await?;
+        for peer_id in self.members.keys() {
+            cmd!(
+                client,
+                "--password",
+                self.guardian_auth(*peer_id).0,
+                "--our-id",
+                peer_id,
+                "module",
+                "meta",
+                "submit",
+                value
+            )
+            .run()
+            .await
+            .with_context(|| format!("submitting meta fields from guardian {peer_id}"))?;
+        }
+
+        let revision = poll("meta consensus update", || async {
+            match self.meta_revision().await.map_err(ControlFlow::Continue)? {
+                Some(revision) if previous.map_or(true, |previous| previous < revision) => {
+                    Ok(revision)
+                }
+                revision => Err(ControlFlow::Continue(anyhow!(
+                    "meta revision still at {revision:?}"
+                ))),
+            }
+        })
+        .await?;
+        info!(target: LOG_DEVIMINT, ?previous, revision, "Federation agreed on new meta fields");
+        Ok(MetaFields {
+            revision,
+            values: values.clone(),
         })
     }
 
@@ -407,6 +1709,28 @@ pub fn client_config(&self) -> Result<ClientConfig> {
         load_from_file(&cfg_path)
     }
 
+    /// Lightning modules the federation runs, according to its client config.
+    ///
+    /// lnv2 is attached unless `FM_ENABLE_MODULE_LNV2` is set to `0` or
+    /// fedimintd is older than v0.4.
+    pub fn lightning_versions(&self) -> Result<Vec<LightningVersion>> {
+        let versions: BTreeSet<_> = self
+            .client_config()?
+            .modules
+            .values()
+            .filter_map(|module| {
+                if module.kind == fedimint_ln_server::common::KIND {
+                    Some(LightningVersion::V1)
+                } else if module.kind == fedimint_lnv2_common::KIND {
+                    Some(LightningVersion::V2)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Ok(versions.into_iter().collect())
+    }
+
     pub fn module_client_config<M: ClientModule>(
         &self,
     ) -> Result<Option<<M::Common as ModuleCommon>::ClientConfig>> {
@@ -439,78 +1763,701 @@ pub fn module_client_config<M: ClientModule>(
             .transpose()
     }
 
-    pub fn deposit_fees(&self) -> Result<Amount> {
-        Ok(self
-            .module_client_config::<WalletClientModule>()?
-            .context("No wallet module found")?
-            .fee_consensus
-            .peg_in_abs)
+    pub fn deposit_fees(&self) -> Result<Amount> {
+        Ok(self
+            .module_client_config::<WalletClientModule>()?
+            .context("No wallet module found")?
+            .fee_consensus
+            .peg_in_abs)
+    }
+
+    /// Pegs out `amount` from `client` to `address` and returns the guardians
+    /// whose signatures consensus accepted for the peg-out transaction.
+    ///
+    /// Waits until completed sessions hold the signatures of a threshold of
+    /// guardians. Consensus stops accepting signatures once it has a threshold
+    /// of them, so with more guardians online than the threshold the slowest
+    /// ones may be missing. Errors if no threshold signed before the poll
+    /// timeout, or if any online guardian didn't sign while exactly a
+    /// threshold is online.
+    pub async fn pegout_signers(
+        &self,
+        client: &Client,
+        address: &bitcoin::Address,
+        amount: bitcoin::Amount,
+    ) -> Result<PegOutSigners> {
+        let first_session = client.get_session_count().await?;
+        let withdraw = cmd!(
+            client,
+            "withdraw",
+            "--address",
+            address,
+            "--amount",
+            format!("{} sat", amount.to_sat())
+        )
+        .out_json()
+        .await?;
+        let txid: bitcoin::Txid = withdraw["txid"]
+            .as_str()
+            .context("withdraw output must contain txid")?
+            .parse()?;
+        let online: BTreeSet<_> = self
+            .members
+            .keys()
+            .map(|peer_id| PeerId::from(*peer_id as u16))
+            .collect();
+        let threshold = NumPeers::from(self.vars.len()).threshold();
+
+        // the client gets the txid once the unsigned transaction is accepted, the
+        // guardians submit their signatures afterwards, so rescan the completed
+        // sessions until a threshold of them made it into consensus
+        let peer_id = *self.members.keys().next().context("no guardian running")?;
+        let signers = poll("peg-out signatures", || async {
+            let session_count = client.get_session_count().await.map_err(ControlFlow::Break)?;
+            let mut signers = BTreeSet::new();
+            for session_index in first_session..session_count {
+                let items = self
+                    .session_items(peer_id, session_index)
+                    .await
+                    .map_err(ControlFlow::Break)?;
+                for accepted in items {
+                    let ConsensusItem::Module(item) = accepted.item else {
+                        continue;
+                    };
+                    if let Some(WalletConsensusItem::PegOutSignature(signature)) =
+                        item.as_any().downcast_ref::<WalletConsensusItem>()
+                    {
+                        if signature.txid == txid {
+                            signers.insert(accepted.peer);
+                        }
+                    }
+                }
+            }
+            if signers.len() < threshold {
+                return Err(ControlFlow::Continue(anyhow!(
+                    "peg-out {txid} signed by {signers:?} in sessions {first_session}..{session_count}"
+                )));
+            }
+            Ok(signers)
+        })
+        .await?;
+        info!(%txid, ?signers, ?online, "Peg-out signed");
+        ensure!(
+            threshold <= signers.len(),
+            "peg-out {txid} was signed by {signers:?}, fewer than the threshold of {threshold}"
+        );
+        ensure!(
+            signers.is_subset(&online),
+            "peg-out {txid} was signed by {signers:?}, not all of which are online {online:?}"
+        );
+        ensure!(
+            online.len() != threshold || signers == online,
+            "guardians {:?} are online but didn't sign peg-out {txid}",
+            online.difference(&signers).collect::<Vec<_>>()
+        );
+        Ok(PegOutSigners { txid, signers })
+    }
+
+    /// Items `peer_id` accepted in the completed session `session_index`,
+    /// with the wallet's items decoded
+    async fn session_items(&self, peer_id: usize, session_index: u64) -> Result<Vec<AcceptedItem>> {
+        let client = self.internal_client().await?;
+        let mut status = cmd!(
+            client,
+            "dev",
+            "api",
+            "--peer-id",
+            peer_id,
+            "session_status",
+            session_index
+        )
+        .out_json()
+        .await?;
+        let status: SerdeModuleEncoding<SessionStatus> = status["value"].take().to_typed()?;
+        let decoders = ModuleDecoderRegistry::new([(
+            LEGACY_HARDCODED_INSTANCE_ID_WALLET,
+            fedimint_wallet_client::KIND,
+            fedimint_wallet_client::WalletModuleTypes::decoder(),
+        )])
+        .with_fallback();
+        match status.try_into_inner(&decoders)? {
+            SessionStatus::Complete(outcome) => Ok(outcome.items),
+            SessionStatus::Initial | SessionStatus::Pending(_) => {
+                bail!("session {session_index} not complete")
+            }
+        }
+    }
+
+    /// Ids of the transactions the first online peer accepted into the
+    /// current session, which are not finalized by a session outcome yet
+    pub async fn pending_transactions(&self) -> Result<Vec<TransactionId>> {
+        let peer_id = *self.members.keys().next().context("no guardian running")?;
+        let client = self.internal_client().await?;
+        let session_count = client.get_session_count().await?;
+        let mut status = cmd!(
+            client,
+            "dev",
+            "api",
+            "--peer-id",
+            peer_id,
+            "session_status",
+            session_count
+        )
+        .out_json()
+        .await?;
+        let status: SerdeModuleEncoding<SessionStatus> = status["value"].take().to_typed()?;
+
+        let items =
+            match status.try_into_inner(&ModuleDecoderRegistry::default().with_fallback())? {
+                SessionStatus::Pending(items) => items,
+                // the session got finalized in the meantime
+                SessionStatus::Initial | SessionStatus::Complete(_) => vec![],
+            };
+        Ok(items
+            .into_iter()
+            .filter_map(|accepted| match accepted.item {
+                ConsensusItem::Transaction(tx) => Some(tx.tx_hash()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Hex encoded header of the latest session the federation completed, as
+    /// reported by every running guardian, waiting for lagging ones to catch
+    /// up. A header commits to all items accepted in its session, so
+    /// guardians agree on the consensus state iff their hashes are equal, see
+    /// [`Self::assert_consensus_consistent`].
+    pub async fn consensus_state_hash(&self) -> Result<BTreeMap<PeerId, String>> {
+        let client = self.internal_client().await?;
+        let session_index = client
+            .get_session_count()
+            .await?
+            .checked_sub(1)
+            .context("no session completed yet")?;
+        let mut hashes = BTreeMap::new();
+        for peer_id in self.members.keys() {
+            let outcome = poll(
+                &format!("fedimintd-{peer_id} completing session {session_index}"),
+                || async {
+                    let mut status = cmd!(
+                        client,
+                        "dev",
+                        "api",
+                        "--peer-id",
+                        peer_id,
+                        "session_status",
+                        session_index
+                    )
+                    .out_json()
+                    .await
+                    .map_err(ControlFlow::Continue)?;
+                    let status: SerdeModuleEncoding<SessionStatus> = status["value"]
+                        .take()
+                        .to_typed()
+                        .map_err(ControlFlow::Break)?;
+                    match status
+                        .try_into_inner(&ModuleDecoderRegistry::default().with_fallback())
+                        .map_err(|e| ControlFlow::Break(e.into()))?
+                    {
+                        SessionStatus::Complete(outcome) => Ok(outcome),
+                        SessionStatus::Initial | SessionStatus::Pending(_) => Err(
+                            ControlFlow::Continue(anyhow!("session {session_index} not complete")),
+                        ),
+                    }
+                },
+            )
+            .await?;
+            hashes.insert(
+                PeerId::from(*peer_id as u16),
+                hex::encode(outcome.header(session_index)),
+            );
+        }
+        Ok(hashes)
+    }
+
+    /// Errors if the running guardians' [`Self::consensus_state_hash`]es
+    /// diverge, returning them either way to diagnose mismatches
+    pub async fn assert_consensus_consistent(&self) -> Result<BTreeMap<PeerId, String>> {
+        let hashes = self.consensus_state_hash().await?;
+        ensure!(
+            hashes.values().collect::<BTreeSet<_>>().len() <= 1,
+            "guardians diverged on the consensus state: {hashes:?}"
+        );
+        Ok(hashes)
+    }
+
+    /// How guardian `peer_id` sees its connections to each other guardian,
+    /// ordered by peer id
+    pub async fn peer_connections(&self, peer_id: usize) -> Result<Vec<PeerConnectionStatus>> {
+        let client = self.internal_client().await?;
+        let status: StatusResponse = cmd!(client, "dev", "api", "--peer-id", peer_id, "status")
+            .out_json()
+            .await?["value"]
+            .take()
+            .to_typed()?;
+        let federation = status.federation.with_context(|| {
+            format!(
+                "fedimintd-{peer_id} is not running consensus, it is {:?}",
+                status.server
+            )
+        })?;
+        Ok(federation
+            .status_by_peer
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(peer, status)| PeerConnectionStatus {
+                peer_id: peer,
+                connected: status.connection_status
+                    == fedimint_api_client::api::PeerConnectionStatus::Connected,
+                last_contribution: status.last_contribution,
+                flagged: status.flagged,
+            })
+            .collect())
+    }
+
+    /// Fetch the guardians' audit (balance sheet) via the first online peer
+    pub async fn audit(&self) -> Result<AuditSummary> {
+        let peer_id = *self.members.keys().next().context("no guardian running")?;
+        let audit: fedimint_core::module::audit::AuditSummary = cmd!(
+            self.internal_client().await?,
+            "--password",
+            self.guardian_auth(peer_id).0,
+            "--our-id",
+            peer_id,
+            "admin",
+            "audit"
+        )
+        .out_json()
+        .await?
+        .to_typed()?;
+
+        let module_net_assets = |kind: &str| -> i64 {
+            audit
+                .module_summaries
+                .values()
+                .filter(|summary| summary.kind == kind)
+                .map(|summary| summary.net_assets)
+                .sum()
+        };
+
+        Ok(AuditSummary {
+            issued_ecash: Amount::from_msats(
+                module_net_assets(fedimint_mint_server::common::KIND.as_str()).unsigned_abs(),
+            ),
+            onchain_balance: Amount::from_msats(
+                module_net_assets(fedimint_wallet_client::KIND.as_str()).unsigned_abs(),
+            ),
+            net_assets_msat: audit.net_assets,
+        })
+    }
+
+    /// Read the invite code from the client data dir
+    pub fn invite_code(&self) -> Result<String> {
+        read_invite_code(&self.name)
+    }
+
+    /// Read the invite code of the `default` federation from the client
+    /// data dir
+    pub fn invite_code_static() -> Result<String> {
+        read_invite_code("default")
+    }
+
+    /// Invite code referencing only the guardians `peers`, to test joining
+    /// while the others are unreachable. At least one of `peers` has to be
+    /// honest for a client to bootstrap from it, so there must be more than
+    /// the federation's maximum number of faulty guardians.
+    pub fn invite_code_for(&self, peers: &[PeerId]) -> Result<InviteCode> {
+        let peer_urls = peers
+            .iter()
+            .map(|peer| {
+                let vars = self
+                    .vars
+                    .get(&peer.to_usize())
+                    .with_context(|| format!("fedimintd-{peer} does not exist"))?;
+                Ok((*peer, SafeUrl::parse(&vars.FM_API_URL)?))
+            })
+            .collect::<Result<BTreeMap<_, _>>>()?;
+        let one_honest = NumPeers::from(self.vars.len()).one_honest();
+        ensure!(
+            one_honest <= peer_urls.len(),
+            "an invite code needs at least {one_honest} distinct guardians, got {peers:?}"
+        );
+
+        let api_secret = InviteCode::from_str(self.invite_code()?.trim())?.api_secret();
+        Ok(InviteCode::from_map_all_peers(
+            &peer_urls,
+            self.client_config()?.global.calculate_federation_id(),
+            api_secret,
+        ))
+    }
+
+    /// Joins a throwaway client with [`Self::invite_code`] and checks it ends
+    /// up in this federation, guarding against broken invite codes.
+    ///
+    /// The client's data dir is removed again afterwards, failing to do so is
+    /// only logged.
+    pub async fn verify_invite_code(&self) -> Result<()> {
+        let invite_code = self.invite_code()?;
+        let expected_federation_id = self.calculate_federation_id();
+        let invite_federation_id = InviteCode::from_str(invite_code.trim())?
+            .federation_id()
+            .to_string();
+        ensure!(
+            invite_federation_id == expected_federation_id,
+            "invite code is for federation {invite_federation_id}, expected {expected_federation_id}"
+        );
+
+        let client = Client::create(&self.client_name("invite-code-check"))?;
+        let result = async {
+            client.join_federation(invite_code).await?;
+            let joined_federation_id = cmd!(client, "info").out_json().await?["federation_id"]
+                .as_str()
+                .context("federation_id must be a string")?
+                .to_owned();
+            ensure!(
+                joined_federation_id == expected_federation_id,
+                "client joined federation {joined_federation_id}, expected {expected_federation_id}"
+            );
+            Ok(())
+        }
+        .await;
+        // a leftover data dir must not mask the verification result
+        if let Err(err) = tokio::fs::remove_dir_all(client.client_dir()).await {
+            warn!(target: LOG_DEVIMINT, %err, "Failed to remove invite code check client data dir");
+        }
+        result
+    }
+
+    /// Built-in, default, internal [`Client`]
+    ///
+    /// We should be moving away from using it for anything.
+    pub async fn internal_client(&self) -> Result<&Client> {
+        self.client
+            .get_try()
+            .await
+            .context("Internal client joining Federation")
+    }
+
+    /// Fork the built-in client of `Federation` and give it a name
+    pub async fn fork_client(&self, name: &str) -> Result<Client> {
+        Client::new_forked(self.internal_client().await?, &self.client_name(name)).await
+    }
+
+    /// Name of the client `name` of `self` in the shared client dir, prefixed
+    /// with the federation's name so clients of several federations don't
+    /// share a database. The `default` federation keeps plain names.
+    fn client_name(&self, name: &str) -> String {
+        if self.name == "default" {
+            name.to_owned()
+        } else {
+            format!("{}-{name}", self.name)
+        }
+    }
+
+    /// New [`Client`] that already joined `self`
+    pub async fn new_joined_client(&self, name: &str) -> Result<Client> {
+        let client = Client::create(&self.client_name(name))?;
+        client.join_federation(self.invite_code()?).await?;
+        Ok(client)
+    }
+
+    /// New [`Client`] of `self` with the secret of the BIP39 mnemonic
+    /// `words`, so the keys it derives are known in advance and the same for
+    /// every client created from `words`.
+    ///
+    /// fedimint-cli only takes a mnemonic when restoring, so the client joins
+    /// through recovery and starts out with whatever a client of the same
+    /// secret backed up or left in the federation's history.
+    pub async fn new_client_with_mnemonic(&self, words: &str) -> Result<Client> {
+        let mnemonic =
+            bip39::Mnemonic::parse_normalized(words.trim()).context("invalid mnemonic")?;
+        let client = Client::create(&self.client_name("mnemonic"))?;
+        client
+            .restore_federation(self.invite_code()?, mnemonic.to_string())
+            .await?;
+        Ok(client)
+    }
+
+    pub async fn start_server(&mut self, process_mgr: &ProcessManager, peer: usize) -> Result<()> {
+        if self.members.contains_key(&peer) {
+            bail!("fedimintd-{peer} already running");
+        }
+        let label = self
+            .guardian_label(peer)
+            .with_context(|| format!("fedimintd-{peer} does not exist"))?;
+        let fedimintd = Fedimintd::new_inner(
+            process_mgr,
+            self.bitcoind.clone(),
+            peer,
+            &self.vars[&peer],
+            self.name.clone(),
+            GuardianLaunch {
+                label,
+                netns: self.netns.get(&peer).map(AsRef::as_ref),
+                clock_skew_secs: self.clock_skews.get(&peer).copied(),
+            },
+        )
+        .await?;
+        self.members.insert(peer, fedimintd);
+        Ok(())
+    }
+
+    /// Cuts all network traffic of guardian `peer_id`, which must be running
+    /// in its own network namespace, see [`crate::netns`]
+    pub async fn cut_guardian_network(&self, peer_id: usize) -> Result<()> {
+        self.guardian_netns(peer_id)?.cut().await
+    }
+
+    /// Restores network traffic cut by [`Self::cut_guardian_network`]
+    pub async fn restore_guardian_network(&self, peer_id: usize) -> Result<()> {
+        self.guardian_netns(peer_id)?.restore().await
+    }
+
+    /// Whether guardians run in their own network namespaces
+    pub fn has_guardian_netns(&self) -> bool {
+        !self.netns.is_empty()
+    }
+
+    /// Takes up all free space on the data dir of guardian `peer_id`, which
+    /// must have its own filesystem, see [`crate::disk`]. Returns how many
+    /// bytes were taken.
+    pub async fn fill_guardian_disk(&self, peer_id: usize) -> Result<u64> {
+        self.guardian_disk(peer_id)?.fill().await
+    }
+
+    /// Frees the space taken by [`Self::fill_guardian_disk`]
+    pub async fn free_guardian_disk(&self, peer_id: usize) -> Result<()> {
+        self.guardian_disk(peer_id)?.free().await
+    }
+
+    /// Whether guardian `peer_id` has its data dir on a size capped filesystem
+    pub fn has_guardian_disk(&self, peer_id: usize) -> bool {
+        self.disks.contains_key(&peer_id)
+    }
+
+    /// Wall clock offset of guardian `peer_id` in seconds, 0 unless skewed
+    pub fn clock_skew_secs(&self, peer_id: usize) -> i64 {
+        self.clock_skews.get(&peer_id).copied().unwrap_or_default()
+    }
+
+    /// Profiles the running guardian `peer_id` for `duration` and returns the
+    /// path of the resulting flamegraph SVG in `$FM_LOGS_DIR/profiles`.
+    ///
+    /// Warns and returns `None` without `perf` and `inferno` installed, see
+    /// [`crate::profiler`]. As the profiler attaches to the guardian's pid,
+    /// the kernel has to allow that, e.g. through
+    /// `kernel.perf_event_paranoid`.
+    pub async fn profile_guardian(
+        &self,
+        peer_id: usize,
+        duration: Duration,
+    ) -> Result<Option<PathBuf>> {
+        if !crate::profiler::available() {
+            warn!(
+                target: LOG_DEVIMINT,
+                peer_id, "Not profiling guardian, `perf` and `inferno` need to be installed"
+            );
+            return Ok(None);
+        }
+        let pid = self
+            .members
+            .get(&peer_id)
+            .with_context(|| format!("Guardian {peer_id} is not running"))?
+            .pid()
+            .await
+            .with_context(|| format!("Guardian {peer_id} is not running"))?;
+        // the spawned pid may be a wrapper, e.g. `faketime` for clock skews
+        let program = crate::util::FedimintdCmd.cmd().args_debug;
+        let program = program
+            .first()
+            .and_then(|program| Path::new(program).file_name())
+            .context("fedimintd command is empty")?
+            .to_string_lossy();
+        let pid = crate::profiler::find_program_pid(pid, &program)?;
+
+        let profiles_dir = PathBuf::from(env::var(FM_LOGS_DIR_ENV)?).join("profiles");
+        tokio::fs::create_dir_all(&profiles_dir).await?;
+        let started_at = fedimint_core::time::duration_since_epoch().as_secs();
+        let svg = profiles_dir.join(format!(
+            "fedimintd-{}-{peer_id}-{started_at}.svg",
+            self.name
+        ));
+        info!(target: LOG_DEVIMINT, peer_id, pid, ?duration, "Profiling guardian");
+        crate::profiler::record_flamegraph(pid, duration, &svg).await?;
+        info!(target: LOG_DEVIMINT, peer_id, path = %svg.display(), "Wrote guardian flamegraph");
+        Ok(Some(svg))
+    }
+
+    /// Has the proxy in front of the API of guardian `peer_id` corrupt its
+    /// responses in `mode` until [`Self::restore_guardian_responses`]. The
+    /// guardian needs a proxy, see [`crate::byzantine`].
+    pub fn corrupt_guardian_responses(&self, peer_id: usize, mode: CorruptMode) -> Result<()> {
+        self.guardian_response_proxy(peer_id)?.set_mode(Some(mode));
+        Ok(())
+    }
+
+    /// Passes the responses corrupted since
+    /// [`Self::corrupt_guardian_responses`] through untouched again
+    pub fn restore_guardian_responses(&self, peer_id: usize) -> Result<()> {
+        self.guardian_response_proxy(peer_id)?.set_mode(None);
+        Ok(())
+    }
+
+    /// Total responses of guardian `peer_id` its proxy corrupted so far
+    pub fn guardian_responses_corrupted(&self, peer_id: usize) -> Result<u64> {
+        Ok(self.guardian_response_proxy(peer_id)?.responses_corrupted())
     }
 
-    /// Read the invite code from the client data dir
-    pub fn invite_code(&self) -> Result<String> {
-        let data_dir: PathBuf = env::var(FM_CLIENT_DIR_ENV)?.parse()?;
-        let invite_code = fs::read_to_string(data_dir.join("invite-code"))?;
-        Ok(invite_code)
+    /// Whether guardian `peer_id` has a proxy in front of its API
+    pub fn has_guardian_response_proxy(&self, peer_id: usize) -> bool {
+        self.response_proxies.contains_key(&peer_id)
     }
 
-    pub fn invite_code_static() -> Result<String> {
-        let data_dir: PathBuf = env::var(FM_CLIENT_DIR_ENV)?.parse()?;
-        let invite_code = fs::read_to_string(data_dir.join("invite-code"))?;
-        Ok(invite_code)
+    fn guardian_response_proxy(&self, peer_id: usize) -> Result<&CorruptingProxy> {
+        self.response_proxies
+            .get(&peer_id)
+            .with_context(|| format!("fedimintd-{peer_id} has no proxy in front of its api"))
+    }
+
+    fn guardian_disk(&self, peer_id: usize) -> Result<&GuardianDisk> {
+        self.disks
+            .get(&peer_id)
+            .map(AsRef::as_ref)
+            .with_context(|| format!("fedimintd-{peer_id} has no disk of its own"))
     }
-    pub fn invite_code_for(peer_id: PeerId) -> Result<String> {
-        let data_dir: PathBuf = env::var(FM_CLIENT_DIR_ENV)?.parse()?;
-        let name = format!("invite-code-{peer_id}");
-        let invite_code = fs::read_to_string(data_dir.join(name))?;
-        Ok(invite_code)
+
+    fn guardian_netns(&self, peer_id: usize) -> Result<&GuardianNetns> {
+        self.netns
+            .get(&peer_id)
+            .map(AsRef::as_ref)
+            .with_context(|| format!("fedimintd-{peer_id} does not run in a network namespace"))
     }
 
-    /// Built-in, default, internal [`Client`]
+    pub async fn terminate_server(&mut self, peer_id: usize) -> Result<()> {
+        let Some((_, fedimintd)) = self.members.remove_entry(&peer_id) else {
+            bail!("fedimintd-{peer_id} does not exist");
+        };
+        fedimintd.terminate().await?;
+        Ok(())
+    }
+
+    /// Like [`Self::terminate_server`], but stops the guardian with `signal`
+    /// only, so [`KillSignal::Sigkill`] simulates a power loss
+    pub async fn kill_server(&mut self, peer_id: usize, signal: KillSignal) -> Result<()> {
+        let Some((_, fedimintd)) = self.members.remove_entry(&peer_id) else {
+            bail!("fedimintd-{peer_id} does not exist");
+        };
+        fedimintd.kill(signal).await?;
+        Ok(())
+    }
+
+    /// Data directory of guardian `peer_id`, whether it is running or not.
     ///
-    /// We should be moving away from using it for anything.
-    pub async fn internal_client(&self) -> Result<&Client> {
-        self.client
-            .get_try()
-            .await
-            .context("Internal client joining Federation")
+    /// It's `$FM_DATA_DIR/fedimintd-<federation name>-{peer_id}` and contains the
+    /// rocksdb `database` directory, the `local`, `consensus` and encrypted
+    /// `private` configs with their `private.salt`, and `password.private`.
+    pub fn guardian_data_dir(&self, peer_id: usize) -> Result<PathBuf> {
+        Ok(self
+            .vars
+            .get(&peer_id)
+            .with_context(|| format!("fedimintd-{peer_id} does not exist"))?
+            .FM_DATA_DIR
+            .clone())
     }
 
-    /// Fork the built-in client of `Federation` and give it a name
-    pub async fn fork_client(&self, name: &str) -> Result<Client> {
-        Client::new_forked(self.internal_client().await?, name).await
+    /// Label of guardian `peer_id`, set with `FM_GUARDIAN_LABELS`, if the
+    /// federation has such a guardian
+    pub fn guardian_label(&self, peer_id: usize) -> Option<&str> {
+        self.labels.get(&peer_id).map(String::as_str)
     }
 
-    /// New [`Client`] that already joined `self`
-    pub async fn new_joined_client(&self, name: &str) -> Result<Client> {
-        let client = Client::create(name)?;
-        client.join_federation(self.invite_code()?).await?;
-        Ok(client)
+    /// Peer id of the guardian labeled `label`, to write tests like
+    /// `fed.terminate_server(fed.guardian_by_label("alice")?)`
+    pub fn guardian_by_label(&self, label: &str) -> Result<usize> {
+        self.labels
+            .iter()
+            .find_map(|(peer_id, peer_label)| (peer_label == label).then_some(*peer_id))
+            .with_context(|| format!("no guardian labeled {label}"))
     }
 
-    pub async fn start_server(&mut self, process_mgr: &ProcessManager, peer: usize) -> Result<()> {
-        if self.members.contains_key(&peer) {
-            bail!("fedimintd-{peer} already running");
-        }
-        self.members.insert(
-            peer,
-            Fedimintd::new(
-                process_mgr,
-                self.bitcoind.clone(),
-                peer,
-                &self.vars[&peer],
-                "default".to_string(),
-            )
-            .await?,
+    /// Peer ids and labels of all guardians, whether running or not
+    pub fn guardian_labels(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.labels
+            .iter()
+            .map(|(peer_id, label)| (*peer_id, label.as_str()))
+    }
+
+    /// Current admin credentials of guardian `peer_id`
+    pub fn guardian_auth(&self, peer_id: usize) -> &ApiAuth {
+        self.api_auth
+            .get(&peer_id)
+            .unwrap_or_else(|| panic!("fedimintd-{peer_id} does not exist"))
+    }
+
+    /// Changes the admin password of guardian `peer_id`, which is also the
+    /// password its private config is encrypted with, and makes all helpers
+    /// use it from now on.
+    ///
+    /// fedimintd has no API for changing the password, so a running guardian
+    /// is terminated, its private config re-encrypted and
+    /// `password.private` updated, and then it's started again.
+    pub async fn set_guardian_password(
+        &mut self,
+        process_mgr: &ProcessManager,
+        peer_id: usize,
+        new_password: &str,
+    ) -> Result<()> {
+        let data_dir = self.guardian_data_dir(peer_id)?;
+        let private_config = data_dir.join(PRIVATE_CONFIG).with_extension(ENCRYPTED_EXT);
+        ensure!(
+            private_config.exists() && data_dir.join(PLAINTEXT_PASSWORD).exists(),
+            "can't rotate the password of fedimintd-{peer_id}: it has no generated config \
+             or was not started from {PLAINTEXT_PASSWORD}"
         );
+
+        let was_running = self.members.contains_key(&peer_id);
+        if was_running {
+            self.terminate_server(peer_id).await?;
+        }
+        info!(target: LOG_DEVIMINT, %peer_id, "Rotating guardian password");
+
+        let salt = fs::read_to_string(data_dir.join(SALT_FILE))?;
+        let old_key = get_encryption_key(&self.guardian_auth(peer_id).0, &salt)?;
+        let mut private: serde_json::Value =
+            serde_json::from_slice(&encrypted_read(&old_key, private_config.clone())?)?;
+        let new_auth = ApiAuth(new_password.to_owned());
+        private["api_auth"] = serde_json::to_value(&new_auth)?;
+
+        let new_key = get_encryption_key(new_password, &salt)?;
+        fs::remove_file(&private_config)?;
+        encrypted_write(
+            serde_json::to_vec(&private)?,
+            &new_key,
+            private_config.clone(),
+        )?;
+        fs::write(data_dir.join(PLAINTEXT_PASSWORD), new_password)?;
+        self.api_auth.insert(peer_id, new_auth);
+
+        if was_running {
+            self.start_server(process_mgr, peer_id).await?;
+        }
         Ok(())
     }
 
-    pub async fn terminate_server(&mut self, peer_id: usize) -> Result<()> {
-        let Some((_, fedimintd)) = self.members.remove_entry(&peer_id) else {
-            bail!("fedimintd-{peer_id} does not exist");
-        };
-        fedimintd.terminate().await?;
+    /// Deletes the database of the stopped guardian `peer_id`, keeping its
+    /// configs, so that once started again it has to recover the consensus
+    /// history from its peers.
+    pub async fn wipe_guardian_db(&self, peer_id: usize) -> Result<()> {
+        ensure!(
+            !self.members.contains_key(&peer_id),
+            "fedimintd-{peer_id} must be terminated before wiping its database"
+        );
+        let db_path = self.guardian_data_dir(peer_id)?.join(DB_FILE);
+        info!(target: LOG_DEVIMINT, %peer_id, path = %db_path.display(), "Wiping guardian database");
+        tokio::fs::remove_dir_all(&db_path)
+            .await
+            .with_context(|| format!("removing {}", db_path.display()))?;
         Ok(())
     }
 
@@ -605,9 +2552,12 @@ pub async fn restart_all_with_bin_after_session(
 
         // schedule shutdown for all peers
         for peer_id in 0..self.num_members() {
-            let auth = ApiAuth("pass".to_string());
             crate::util::FedimintCli
-                .shutdown(&auth, peer_id.try_into()?, shutdown_after_session)
+                .shutdown(
+                    self.guardian_auth(peer_id),
+                    peer_id.try_into()?,
+                    shutdown_after_session,
+                )
                 .await?;
         }
 
@@ -619,9 +2569,11 @@ pub async fn restart_all_with_bin_after_session(
             Duration::from_secs(70),
             || async {
                 for peer_id in 0..self.num_members() {
-                    let auth = ApiAuth("pass".to_string());
                     if crate::util::FedimintCli
-                        .status(&auth, peer_id.try_into().expect("conversion to u64 works"))
+                        .status(
+                            self.guardian_auth(peer_id),
+                            peer_id.try_into().expect("conversion to u64 works"),
+                        )
                         .await
                         .is_ok()
                     {
@@ -680,6 +2632,78 @@ pub async fn restart_all_with_bin(
         }
     }
 
+    /// Restarts only guardian `peer_id` on the fedimintd at `bin_path`,
+    /// leaving the rest of the federation on its current version, and returns
+    /// the database migration lines it logged while starting.
+    ///
+    /// Fails if the guardian logged a migration error or a missing migration.
+    pub async fn restart_server_with_bin(
+        &mut self,
+        process_mgr: &ProcessManager,
+        peer_id: usize,
+        bin_path: &Path,
+    ) -> Result<DbMigrationLog> {
+        if self.members.contains_key(&peer_id) {
+            self.terminate_server(peer_id).await?;
+        }
+
+        let log_path = process_mgr
+            .globals
+            .FM_LOGS_DIR
+            .join(format!("fedimintd-{}-{peer_id}.log", self.name));
+        let log_offset = tokio::fs::metadata(&log_path)
+            .await
+            .map_or(0, |metadata| metadata.len());
+
+        let current_fedimintd_path = env::var(FM_FEDIMINTD_BASE_EXECUTABLE_ENV).ok();
+        env::set_var(FM_FEDIMINTD_BASE_EXECUTABLE_ENV, bin_path);
+        let started = self.start_server(process_mgr, peer_id).await;
+        match current_fedimintd_path {
+            Some(path) => env::set_var(FM_FEDIMINTD_BASE_EXECUTABLE_ENV, path),
+            None => env::remove_var(FM_FEDIMINTD_BASE_EXECUTABLE_ENV),
+        }
+        started?;
+
+        // migrations run before the api is started
+        poll("waiting for restarted peer api", || async {
+            crate::util::FedimintCli
+                .status(self.guardian_auth(peer_id), peer_id as u64)
+                .await
+                .map_err(ControlFlow::Continue)
+        })
+        .await?;
+
+        let log = tokio::fs::read(&log_path).await?;
+        let migration_log = DbMigrationLog {
+            lines: String::from_utf8_lossy(&log[log_offset as usize..])
+                .lines()
+                .filter(|line| line.contains(LOG_DB))
+                .map(ToOwned::to_owned)
+                .collect(),
+        };
+        let errors = migration_log.errors().collect::<Vec<_>>();
+        ensure!(
+            errors.is_empty(),
+            "fedimintd-{peer_id} failed to migrate its database:\n{}",
+            errors.join("\n")
+        );
+        let db_versions = migration_log.db_versions();
+        for (kind, target) in migration_log.target_db_versions() {
+            let version = db_versions.get(&kind).copied();
+            ensure!(
+                version == Some(target),
+                "fedimintd-{peer_id} migrated {kind} to database version {version:?}, expected {target}"
+            );
+        }
+        info!(
+            target: LOG_DEVIMINT,
+            %peer_id,
+            migrated = migration_log.migrations().count(),
+            "Guardian restarted on new binary"
+        );
+        Ok(migration_log)
+    }
+
     fn version_requires_coordinated_shutdown(version: &semver::Version) -> bool {
         matches!((version.major, version.minor), (0, 4 | 5))
     }
@@ -765,6 +2789,20 @@ pub fn calculate_federation_id(&self) -> String {
             .to_string()
     }
 
+    /// Errors unless the federation's id is `expected`, to pin it in golden
+    /// tests of federations set up from the same configs, which catches
+    /// unintended changes to what config gen outputs. The error shows both ids
+    /// and where they start to differ.
+    pub fn assert_federation_id(&self, expected: &str) -> Result<()> {
+        let actual = self.calculate_federation_id();
+        ensure!(
+            actual == expected,
+            "{}",
+            golden_mismatch("federation id", expected, &actual)
+        );
+        Ok(())
+    }
+
     pub async fn await_block_sync(&self) -> Result<u64> {
         let finality_delay = self.get_finality_delay()?;
         let block_count = self.bitcoind.get_block_count()?;
@@ -780,7 +2818,22 @@ pub async fn await_block_sync(&self) -> Result<u64> {
         Ok(expected)
     }
 
-    fn get_finality_delay(&self) -> Result<u32, anyhow::Error> {
+    /// Block count the guardians agreed on, which trails bitcoind's by the
+    /// finality delay
+    pub async fn consensus_block_count(&self) -> Result<u64> {
+        cmd!(
+            self.internal_client().await?,
+            "dev",
+            "api",
+            "module_{LEGACY_HARDCODED_INSTANCE_ID_WALLET}_block_count",
+        )
+        .out_json()
+        .await?["value"]
+            .as_u64()
+            .context("No block count returned")
+    }
+
+    pub fn get_finality_delay(&self) -> Result<u32, anyhow::Error> {
         let client_config = &self.client_config()?;
         let wallet_cfg = client_config
             .modules
@@ -839,6 +2892,15 @@ pub async fn await_gateways_registered(&self) -> Result<()> {
         Ok(())
     }
 
+    /// Gateways currently registered with the federation, as seen by a freshly
+    /// updated client gateway cache. Expired registrations are not included.
+    pub async fn registered_gateways(&self) -> Result<Vec<LightningGatewayAnnouncement>> {
+        cmd!(self.internal_client().await?, "list-gateways")
+            .out_json()
+            .await?
+            .to_typed()
+    }
+
     pub async fn await_all_peers(&self) -> Result<()> {
         poll("Waiting for all peers to be online", || async {
             cmd!(
@@ -891,10 +2953,21 @@ pub fn member_ids(&self) -> impl Iterator<Item = PeerId> + '_ {
     }
 }
 
+/// How [`Fedimintd::new_inner`] runs a guardian of a [`Federation`]
+pub(crate) struct GuardianLaunch<'a> {
+    /// Label the guardian shows up as in logs and tables
+    pub label: &'a str,
+    /// Network namespace the guardian runs in, see [`crate::netns`]
+    pub netns: Option<&'a GuardianNetns>,
+    /// Offset of the guardian's wall clock, see [`crate::faketime`]
+    pub clock_skew_secs: Option<i64>,
+}
+
 #[derive(Clone)]
 pub struct Fedimintd {
     _bitcoind: Bitcoind,
-    process: ProcessHandle,
+    pub(crate) process: ProcessHandle,
+    launch_kind: LaunchKind,
 }
 
 impl Fedimintd {
@@ -905,28 +2978,274 @@ pub async fn new(
         env: &vars::Fedimintd,
         fed_name: String,
     ) -> Result<Self> {
-        debug!(target: LOG_DEVIMINT, "Starting fedimintd-{fed_name}-{peer_id}");
+        Self::new_inner(
+            process_mgr,
+            bitcoind,
+            peer_id,
+            env,
+            fed_name,
+            GuardianLaunch {
+                label: &default_guardian_label(peer_id),
+                netns: None,
+                clock_skew_secs: None,
+            },
+        )
+        .await
+    }
+
+    #[instrument(
+        name = "fedimintd",
+        level = "debug",
+        skip_all,
+        fields(peer_id = peer_id, label = launch.label)
+    )]
+    pub(crate) async fn new_inner(
+        process_mgr: &ProcessManager,
+        bitcoind: Bitcoind,
+        peer_id: usize,
+        env: &vars::Fedimintd,
+        fed_name: String,
+        launch: GuardianLaunch<'_>,
+    ) -> Result<Self> {
+        let GuardianLaunch {
+            label,
+            netns,
+            clock_skew_secs,
+        } = launch;
+        debug!(target: LOG_DEVIMINT, %label, ?clock_skew_secs, "Starting fedimintd-{fed_name}-{peer_id}");
+        let launch_kind = LaunchKind::detect(
+            &format!("fedimintd-{fed_name}-{peer_id}"),
+            &env.FM_DATA_DIR.join(DB_FILE),
+        )
+        .await?;
+        let cmd = match netns {
+            Some(netns) => netns.exec(cmd!(FedimintdCmd)),
+            None => cmd!(FedimintdCmd),
+        };
+        let cmd = match clock_skew_secs {
+            Some(skew_secs) => crate::faketime::wrap(skew_secs, cmd),
+            None => cmd,
+        };
+        let cmd = if !process_mgr.guardian_json_logs() {
+            cmd
+        } else if crate::util::FedimintdCmd::supports_json_logs().await {
+            cmd.envs([(FM_LOG_JSON_ENV, "true")])
+        } else {
+            warn!(target: LOG_DEVIMINT, "fedimintd can't log JSON, guardians log text");
+            cmd
+        };
         let process = process_mgr
             .spawn_daemon(
                 &format!("fedimintd-{fed_name}-{peer_id}"),
-                cmd!(FedimintdCmd).envs(env.vars()),
+                cmd.envs(env.vars()),
             )
             .await?;
 
         Ok(Self {
             _bitcoind: bitcoind,
             process,
+            launch_kind,
         })
     }
 
+    /// Whether the guardian started on the database of an earlier run, like
+    /// after [`Federation::start_server`] restarts it
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+
     pub async fn terminate(self) -> Result<()> {
         self.process.terminate().await
     }
+
+    pub async fn kill(self, signal: KillSignal) -> Result<()> {
+        self.process.kill(signal).await
+    }
+
+    /// Pid of the running guardian
+    pub async fn pid(&self) -> Option<u32> {
+        self.process.pid().await
+    }
+}
+
+/// A guardian's connection to another guardian, see
+/// [`Federation::peer_connections`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerConnectionStatus {
+    pub peer_id: PeerId,
+    pub connected: bool,
+    /// Latest consensus session the peer contributed to, guardians don't
+    /// track when they last heard from a peer in wall clock time
+    pub last_contribution: Option<u64>,
+    /// Whether the peer hasn't contributed for long enough that its operator
+    /// should look into it
+    pub flagged: bool,
+}
+
+fn default_guardian_label(peer_id: usize) -> String {
+    format!("guardian-{peer_id}")
+}
+
+/// Labels of `servers` guardians from `--guardian-labels` or
+/// `FM_GUARDIAN_LABELS`, defaulting to `guardian-<id>`
+fn guardian_labels(
+    process_mgr: &ProcessManager,
+    servers: usize,
+) -> Result<BTreeMap<usize, String>> {
+    let configured = match process_mgr.guardian_labels() {
+        Some(labels) => labels.to_owned(),
+        None => env::var(FM_GUARDIAN_LABELS_ENV).unwrap_or_default(),
+    };
+    parse_guardian_labels(&configured, servers)
+}
+
+/// Parses labels like `alice,,bob`, a guardian without one in its position is
+/// labeled `guardian-<id>`
+fn parse_guardian_labels(value: &str, servers: usize) -> Result<BTreeMap<usize, String>> {
+    let mut labels = parse_guardian_list(FM_GUARDIAN_LABELS_ENV, value, servers, |label| {
+        Ok(label.to_owned())
+    })?;
+    for peer_id in 0..servers {
+        labels
+            .entry(peer_id)
+            .or_insert_with(|| default_guardian_label(peer_id));
+    }
+    ensure!(
+        labels.values().collect::<BTreeSet<_>>().len() == servers,
+        "{FM_GUARDIAN_LABELS_ENV} labels must be unique"
+    );
+    Ok(labels)
+}
+
+/// How long to wait for a guardian's status when describing a failed DKG
+const DKG_FAILURE_STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Step of the DKG ceremony devimint drives the guardians through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DkgStage {
+    ConnectingToPeers,
+    SettingPasswords,
+    SharingConfigGenParams,
+    RunningDkg,
+    VerifyingConfigs,
+    StartingConsensus,
+}
+
+/// Error returned by [`Federation::new`] when DKG fails, describing how far
+/// the ceremony got.
+///
+/// Get it from the returned [`anyhow::Error`] with `downcast_ref`.
+#[derive(Debug)]
+pub struct DkgError {
+    /// Stage the ceremony failed in
+    pub stage: DkgStage,
+    /// Status of every guardian after the failure, `None` if it couldn't be
+    /// queried
+    pub peer_status: BTreeMap<PeerId, Option<ServerStatus>>,
+    pub source: anyhow::Error,
+}
+
+impl DkgError {
+    /// Guardians that didn't generate their config, including unreachable ones
+    pub fn incomplete_peers(&self) -> Vec<PeerId> {
+        self.peer_status
+            .iter()
+            .filter(|(_, status)| {
+                !matches!(
+                    status,
+                    Some(
+                        ServerStatus::VerifyingConfigs
+                            | ServerStatus::VerifiedConfigs
+                            | ServerStatus::ConsensusRunning
+                    )
+                )
+            })
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+}
+
+impl fmt::Display for DkgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DKG failed while {:?}, incomplete peers: [{}] (",
+            self.stage,
+            self.incomplete_peers()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        for (idx, (peer_id, status)) in self.peer_status.iter().enumerate() {
+            if idx != 0 {
+                f.write_str(", ")?;
+            }
+            match status {
+                Some(status) => write!(f, "{peer_id}: {status:?}")?,
+                None => write!(f, "{peer_id}: unreachable")?,
+            }
+        }
+        f.write_str(")")
+    }
+}
+
+impl std::error::Error for DkgError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Name of the file in the client data dir holding the invite code of the
+/// federation `federation_name`, the `default` one keeps the plain
+/// `invite-code` scripts expect
+fn invite_code_filename(federation_name: &str) -> String {
+    if federation_name == "default" {
+        "invite-code".to_owned()
+    } else {
+        format!("invite-code-{federation_name}")
+    }
 }
 
+fn read_invite_code(federation_name: &str) -> Result<String> {
+    let data_dir: PathBuf = env::var(FM_CLIENT_DIR_ENV)?.parse()?;
+    let invite_code = fs::read_to_string(data_dir.join(invite_code_filename(federation_name)))?;
+    Ok(invite_code)
+}
+
+#[instrument(name = "dkg", level = "debug", skip_all)]
 pub async fn run_cli_dkg(
     params: HashMap<PeerId, ConfigGenParams>,
     endpoints: BTreeMap<PeerId, String>,
+    module_options: &ModuleOptions,
+) -> Result<()> {
+    let mut stage = DkgStage::ConnectingToPeers;
+    let Err(source) = run_cli_dkg_stages(&params, &endpoints, module_options, &mut stage).await
+    else {
+        return Ok(());
+    };
+    let mut peer_status = BTreeMap::new();
+    for (peer_id, endpoint) in &endpoints {
+        let status = tokio::time::timeout(
+            DKG_FAILURE_STATUS_TIMEOUT,
+            crate::util::FedimintCli.ws_status(endpoint),
+        )
+        .await;
+        peer_status.insert(*peer_id, status.ok().and_then(Result::ok).map(|s| s.server));
+    }
+    Err(DkgError {
+        stage,
+        peer_status,
+        source,
+    }
+    .into())
+}
+
+async fn run_cli_dkg_stages(
+    params: &HashMap<PeerId, ConfigGenParams>,
+    endpoints: &BTreeMap<PeerId, String>,
+    module_options: &ModuleOptions,
+    stage: &mut DkgStage,
 ) -> Result<()> {
     let auth_for = |peer: &PeerId| -> &ApiAuth { &params[peer].local.api_auth };
 
@@ -944,17 +3263,17 @@ pub async fn run_cli_dkg(
 
     debug!(target: LOG_DEVIMINT, "Connected to all peers");
 
-    for (peer_id, endpoint) in &endpoints {
+    for (peer_id, endpoint) in endpoints {
         let status = crate::util::FedimintCli.ws_status(endpoint).await?;
-        assert_eq!(
-            status.server,
-            ServerStatus::AwaitingPassword,
+        ensure!(
+            status.server == ServerStatus::AwaitingPassword,
             "peer_id isn't waiting for password: {peer_id}"
         );
     }
 
     debug!(target: LOG_DEVIMINT, "Setting passwords");
-    for (peer_id, endpoint) in &endpoints {
+    *stage = DkgStage::SettingPasswords;
+    for (peer_id, endpoint) in endpoints {
         crate::util::FedimintCli
             .set_password(auth_for(peer_id), endpoint)
             .await?;
@@ -966,6 +3285,7 @@ pub async fn run_cli_dkg(
         .collect::<BTreeMap<_, _>>();
 
     debug!(target: LOG_DEVIMINT, "calling set_config_gen_connections for leader");
+    *stage = DkgStage::SharingConfigGenParams;
     let leader_name = "leader".to_string();
     crate::util::FedimintCli
         .set_config_gen_connections(auth_for(leader_id), leader_endpoint, &leader_name, None)
@@ -982,6 +3302,7 @@ pub async fn run_cli_dkg(
         leader_endpoint,
         auth_for(leader_id),
         server_gen_params.clone(),
+        module_options,
     )
     .await?;
 
@@ -1009,7 +3330,13 @@ pub async fn run_cli_dkg(
             .set_config_gen_connections(auth_for(peer_id), endpoint, name, Some(leader_endpoint))
             .await?;
 
-        cli_set_config_gen_params(endpoint, auth_for(peer_id), server_gen_params.clone()).await?;
+        cli_set_config_gen_params(
+            endpoint,
+            auth_for(peer_id),
+            server_gen_params.clone(),
+            module_options,
+        )
+        .await?;
     }
 
     debug!(target: LOG_DEVIMINT, "calling get_config_gen_peers for leader");
@@ -1026,7 +3353,10 @@ pub async fn run_cli_dkg(
         .cloned()
         .chain(iter::once(leader_name))
         .collect::<HashSet<_>>();
-    assert_eq!(found_names, all_names);
+    ensure!(
+        found_names == all_names,
+        "leader only knows peers {found_names:?}, expected {all_names:?}"
+    );
 
     debug!(target: LOG_DEVIMINT, "Waiting for SharingConfigGenParams");
     cli_wait_server_status(leader_endpoint, ServerStatus::SharingConfigGenParams).await?;
@@ -1042,17 +3372,21 @@ pub async fn run_cli_dkg(
     // Confirm all consensus configs are the same
     let mut consensus: Vec<_> = configs.iter().map(|p| p.consensus.clone()).collect();
     consensus.dedup();
-    assert_eq!(consensus.len(), 1);
+    ensure!(
+        consensus.len() == 1,
+        "peers disagree on the consensus params"
+    );
     // Confirm all peer ids are unique
     let ids = configs
         .iter()
         .map(|p| p.our_current_id)
         .collect::<HashSet<_>>();
-    assert_eq!(ids.len(), endpoints.len());
+    ensure!(ids.len() == endpoints.len(), "peer ids are not unique");
     let dkg_results = endpoints
         .iter()
         .map(|(peer_id, endpoint)| crate::util::FedimintCli.run_dkg(auth_for(peer_id), endpoint));
     debug!(target: LOG_DEVIMINT, "Running DKG");
+    *stage = DkgStage::RunningDkg;
     let (dkg_results, leader_wait_result) = tokio::join!(
         join_all(dkg_results),
         cli_wait_server_status(leader_endpoint, ServerStatus::VerifyingConfigs)
@@ -1064,18 +3398,20 @@ pub async fn run_cli_dkg(
 
     // verify config hashes equal for all peers
     debug!(target: LOG_DEVIMINT, "Verifying config hashes");
+    *stage = DkgStage::VerifyingConfigs;
     let mut hashes = HashSet::new();
-    for (peer_id, endpoint) in &endpoints {
+    for (peer_id, endpoint) in endpoints {
         cli_wait_server_status(endpoint, ServerStatus::VerifyingConfigs).await?;
         let hash = crate::util::FedimintCli
             .get_verify_config_hash(auth_for(peer_id), endpoint)
             .await?;
         hashes.insert(hash);
     }
-    assert_eq!(hashes.len(), 1);
+    ensure!(hashes.len() == 1, "peers generated different configs");
     info!(target: LOG_DEVIMINT, "DKG completed");
     debug!(target: LOG_DEVIMINT, "Starting consensus");
-    for (peer_id, endpoint) in &endpoints {
+    *stage = DkgStage::StartingConsensus;
+    for (peer_id, endpoint) in endpoints {
         let result = crate::util::FedimintCli
             .start_consensus(auth_for(peer_id), endpoint)
             .await;
@@ -1088,12 +3424,39 @@ pub async fn run_cli_dkg(
     Ok(())
 }
 
+#[instrument(name = "dkg", level = "debug", skip_all)]
 pub async fn run_client_dkg(
     admin_clients: BTreeMap<PeerId, DynGlobalApi>,
     params: HashMap<PeerId, ConfigGenParams>,
+    module_options: &ModuleOptions,
 ) -> Result<()> {
-    let auth_for = |peer: &PeerId| -> ApiAuth { params[peer].local.api_auth.clone() };
+    let mut stage = DkgStage::ConnectingToPeers;
+    let Err(source) =
+        run_client_dkg_stages(&admin_clients, &params, module_options, &mut stage).await
+    else {
+        return Ok(());
+    };
+    let mut peer_status = BTreeMap::new();
     for (peer_id, client) in &admin_clients {
+        let status = tokio::time::timeout(DKG_FAILURE_STATUS_TIMEOUT, client.status()).await;
+        peer_status.insert(*peer_id, status.ok().and_then(Result::ok).map(|s| s.server));
+    }
+    Err(DkgError {
+        stage,
+        peer_status,
+        source,
+    }
+    .into())
+}
+
+async fn run_client_dkg_stages(
+    admin_clients: &BTreeMap<PeerId, DynGlobalApi>,
+    params: &HashMap<PeerId, ConfigGenParams>,
+    module_options: &ModuleOptions,
+    stage: &mut DkgStage,
+) -> Result<()> {
+    let auth_for = |peer: &PeerId| -> ApiAuth { params[peer].local.api_auth.clone() };
+    for (peer_id, client) in admin_clients {
         poll("trying-to-connect-to-peers", || async {
             client
                 .status()
@@ -1105,15 +3468,15 @@ pub async fn run_client_dkg(
         debug!(target: LOG_DEVIMINT, "Connected to {peer_id}");
     }
 
-    for (peer_id, client) in &admin_clients {
-        assert_eq!(
-            client.status().await?.server,
-            ServerStatus::AwaitingPassword,
+    for (peer_id, client) in admin_clients {
+        ensure!(
+            client.status().await?.server == ServerStatus::AwaitingPassword,
             "peer_id isn't waiting for password: {peer_id}"
         );
     }
 
-    for (peer_id, client) in &admin_clients {
+    *stage = DkgStage::SettingPasswords;
+    for (peer_id, client) in admin_clients {
         client.set_password(auth_for(peer_id)).await?;
     }
 
@@ -1123,6 +3486,7 @@ pub async fn run_client_dkg(
         .filter(|(id, _)| *id != leader_id)
         .collect::<BTreeMap<_, _>>();
 
+    *stage = DkgStage::SharingConfigGenParams;
     // Note: names prefixed by peerid, as DKG sort peers by submitted name
     // by default.
     let leader_name = format!("{leader_id}-leader");
@@ -1140,7 +3504,13 @@ pub async fn run_client_dkg(
         .get_default_config_gen_params(auth_for(leader_id))
         .await?; // sanity check
     let server_gen_params = params[leader_id].consensus.modules.clone();
-    set_config_gen_params(leader, auth_for(leader_id), server_gen_params.clone()).await?;
+    set_config_gen_params(
+        leader,
+        auth_for(leader_id),
+        server_gen_params.clone(),
+        module_options,
+    )
+    .await?;
     let followers_names = followers
         .keys()
         .map(|peer_id| {
@@ -1179,7 +3549,13 @@ pub async fn run_client_dkg(
                 auth_for(peer_id),
             )
             .await?;
-        set_config_gen_params(client, auth_for(peer_id), server_gen_params.clone()).await?;
+        set_config_gen_params(
+            client,
+            auth_for(peer_id),
+            server_gen_params.clone(),
+            module_options,
+        )
+        .await?;
     }
     let found_names = leader
         .get_config_gen_peers()
@@ -1192,7 +3568,10 @@ pub async fn run_client_dkg(
         names.insert(leader_name);
         names
     };
-    assert_eq!(found_names, all_names);
+    ensure!(
+        found_names == all_names,
+        "leader only knows peers {found_names:?}, expected {all_names:?}"
+    );
     wait_server_status(leader, ServerStatus::SharingConfigGenParams).await?;
 
     let mut configs = vec![];
@@ -1202,17 +3581,21 @@ pub async fn run_client_dkg(
     // Confirm all consensus configs are the same
     let mut consensus: Vec<_> = configs.iter().map(|p| p.consensus.clone()).collect();
     consensus.dedup();
-    assert_eq!(consensus.len(), 1);
+    ensure!(
+        consensus.len() == 1,
+        "peers disagree on the consensus params"
+    );
     // Confirm all peer ids are unique
     let ids = configs
         .iter()
         .map(|p| p.our_current_id)
         .collect::<HashSet<_>>();
-    assert_eq!(ids.len(), admin_clients.len());
+    ensure!(ids.len() == admin_clients.len(), "peer ids are not unique");
     let dkg_results = admin_clients
         .iter()
         .map(|(peer_id, client)| client.run_dkg(auth_for(peer_id)));
     debug!(target: LOG_DEVIMINT, "Running DKG");
+    *stage = DkgStage::RunningDkg;
     let (dkg_results, leader_wait_result) = tokio::join!(
         join_all(dkg_results),
         wait_server_status(leader, ServerStatus::VerifyingConfigs)
@@ -1223,15 +3606,17 @@ pub async fn run_client_dkg(
     leader_wait_result?;
 
     // verify config hashes equal for all peers
+    *stage = DkgStage::VerifyingConfigs;
     let mut hashes = HashSet::new();
-    for (peer_id, client) in &admin_clients {
+    for (peer_id, client) in admin_clients {
         wait_server_status(client, ServerStatus::VerifyingConfigs).await?;
         hashes.insert(client.get_verify_config_hash(auth_for(peer_id)).await?);
     }
-    assert_eq!(hashes.len(), 1);
+    ensure!(hashes.len() == 1, "peers generated different configs");
     info!(target: LOG_DEVIMINT, "DKG completed");
     debug!(target: LOG_DEVIMINT, "Starting consensus");
-    for (peer_id, client) in &admin_clients {
+    *stage = DkgStage::StartingConsensus;
+    for (peer_id, client) in admin_clients {
         if let Err(e) = client.start_consensus(auth_for(peer_id)).await {
             tracing::debug!(target: LOG_DEVIMINT, "Error calling start_consensus: {e:?}, trying to continue...");
         }
@@ -1245,31 +3630,21 @@ async fn set_config_gen_params(
     client: &DynGlobalApi,
     auth: ApiAuth,
     mut server_gen_params: ServerModuleConfigGenParamsRegistry,
+    module_options: &ModuleOptions,
 ) -> Result<()> {
     // TODO(support:v0.3): v0.4 introduced lnv2 modules, so we need to skip
     // attaching the module for old fedimintd versions
     let fedimintd_version = crate::util::FedimintdCmd::version_or_default().await;
-    self::config::attach_default_module_init_params(
+    module_options.apply(
         &BitcoinRpcConfig::get_defaults_from_env_vars()?,
         &mut server_gen_params,
-        Network::Regtest,
+        crate::external::bitcoin_network()?,
         10,
         &fedimintd_version,
+        is_env_var_set,
     );
-    // Since we are not actually calling `fedimintd` binary, parse and handle
-    // `FM_EXTRA_META_DATA` like it would do.
-    let mut extra_meta_data = parse_map(
-        &std::env::var(FM_EXTRA_DKG_META_ENV)
-            .ok()
-            .unwrap_or_default(),
-    )
-    .with_context(|| format!("Failed to parse {FM_EXTRA_DKG_META_ENV}"))
-    .expect("Failed");
-    let mut meta = BTreeMap::from([("federation_name".to_string(), "testfed".to_string())]);
-    meta.append(&mut extra_meta_data);
-
     let request = ConfigGenParamsRequest {
-        meta,
+        meta: dkg_meta(),
         modules: server_gen_params,
     };
     client.set_config_gen_params(request, auth.clone()).await?;
@@ -1280,17 +3655,28 @@ async fn cli_set_config_gen_params(
     endpoint: &str,
     auth: &ApiAuth,
     mut server_gen_params: ServerModuleConfigGenParamsRegistry,
+    module_options: &ModuleOptions,
 ) -> Result<()> {
     // TODO(support:v0.3): v0.4 introduced lnv2 modules, so we need to skip
     // attaching the module for old fedimintd versions
     let fedimintd_version = crate::util::FedimintdCmd::version_or_default().await;
-    self::config::attach_default_module_init_params(
+    module_options.apply(
         &BitcoinRpcConfig::get_defaults_from_env_vars()?,
         &mut server_gen_params,
-        Network::Regtest,
+        crate::external::bitcoin_network()?,
         10,
         &fedimintd_version,
+        is_env_var_set,
     );
+    crate::util::FedimintCli
+        .set_config_gen_params(auth, endpoint, dkg_meta(), server_gen_params)
+        .await?;
+    Ok(())
+}
+
+/// Meta the guardians run DKG with, the federation name and
+/// `FM_EXTRA_DKG_META`
+pub(crate) fn dkg_meta() -> BTreeMap<String, String> {
     // Since we are not actually calling `fedimintd` binary, parse and handle
     // `FM_EXTRA_META_DATA` like it would do.
     let extra_meta_data = parse_map(
@@ -1300,15 +3686,9 @@ async fn cli_set_config_gen_params(
     )
     .with_context(|| format!("Failed to parse {FM_EXTRA_DKG_META_ENV}"))
     .expect("Failed");
-    let meta: BTreeMap<String, String> =
-        iter::once(("federation_name".to_string(), "testfed".to_string()))
-            .chain(extra_meta_data)
-            .collect();
-
-    crate::util::FedimintCli
-        .set_config_gen_params(auth, endpoint, meta, server_gen_params)
-        .await?;
-    Ok(())
+    iter::once(("federation_name".to_string(), "testfed".to_string()))
+        .chain(extra_meta_data)
+        .collect()
 }
 
 async fn wait_server_status(client: &DynGlobalApi, expected_status: ServerStatus) -> Result<()> {
@@ -1356,3 +3736,106 @@ async fn cli_wait_server_status(endpoint: &str, expected_status: ServerStatus) -
     .await?;
     Ok(())
 }
+
+/// Describes how `actual` differs from the golden `expected` value of `what`,
+/// pointing at the first character that differs
+fn golden_mismatch(what: &str, expected: &str, actual: &str) -> String {
+    let first_diff = expected
+        .chars()
+        .zip(actual.chars())
+        .take_while(|(e, a)| e == a)
+        .count();
+    format!(
+        "{what} doesn't match the golden value, first difference at character {first_diff}\n\
+         expected: {expected}\n  \
+         actual: {actual}\n          {:>width$}",
+        "^",
+        width = first_diff + 1
+    )
+}
+
+#[test]
+fn test_golden_mismatch() {
+    assert_eq!(
+        golden_mismatch("federation id", "15cd", "15ef"),
+        "federation id doesn't match the golden value, first difference at character 2\n\
+         expected: 15cd\n  \
+         actual: 15ef\n            ^"
+    );
+    assert_eq!(
+        golden_mismatch("federation id", "15cd", "15"),
+        "federation id doesn't match the golden value, first difference at character 2\n\
+         expected: 15cd\n  \
+         actual: 15\n            ^"
+    );
+}
+
+#[test]
+fn test_dkg_error_incomplete_peers() {
+    let err = DkgError {
+        stage: DkgStage::RunningDkg,
+        peer_status: BTreeMap::from([
+            (PeerId::from(0), Some(ServerStatus::VerifyingConfigs)),
+            (PeerId::from(1), Some(ServerStatus::ConfigGenFailed)),
+            (PeerId::from(2), None),
+        ]),
+        source: anyhow!("dkg timed out"),
+    };
+    assert_eq!(
+        err.incomplete_peers(),
+        vec![PeerId::from(1), PeerId::from(2)]
+    );
+
+    let err = anyhow::Error::from(err);
+    assert_eq!(
+        err.to_string(),
+        "DKG failed while RunningDkg, incomplete peers: [1, 2] (0: VerifyingConfigs, 1: ConfigGenFailed, 2: unreachable)"
+    );
+    // the cause is only part of the error chain
+    assert_eq!(
+        format!("{err:#}"),
+        "DKG failed while RunningDkg, incomplete peers: [1, 2] (0: VerifyingConfigs, 1: ConfigGenFailed, 2: unreachable): dkg timed out"
+    );
+    assert!(err.downcast_ref::<DkgError>().is_some());
+}
+
+#[test]
+fn test_db_migration_log_versions() {
+    let log = DbMigrationLog {
+        lines: vec![
+            r#"2024-09-01T10:00:00.000000Z  INFO fm::db: Migrating module... kind="mint" current_db_version=DatabaseVersion(0) target_db_version=DatabaseVersion(1)"#.to_owned(),
+            r#"2024-09-01T10:00:00.100000Z  INFO fm::db: Migration complete kind="mint" db_version=DatabaseVersion(1)"#.to_owned(),
+            r#"{"timestamp":"2024-09-01T10:00:00.200000Z","level":"INFO","fields":{"message":"Migration complete","kind":"\"wallet\"","db_version":"DatabaseVersion(2)"},"target":"fm::db","spans":[]}"#.to_owned(),
+        ],
+    };
+    assert_eq!(
+        log.db_versions(),
+        BTreeMap::from([("mint".to_owned(), 1), ("wallet".to_owned(), 2)])
+    );
+    assert_eq!(
+        log.target_db_versions(),
+        BTreeMap::from([("mint".to_owned(), 1)])
+    );
+}
+
+#[test]
+fn test_parse_guardian_labels() -> Result<()> {
+    assert_eq!(
+        parse_guardian_labels("alice,,bob", 4)?,
+        BTreeMap::from([
+            (0, "alice".to_owned()),
+            (1, "guardian-1".to_owned()),
+            (2, "bob".to_owned()),
+            (3, "guardian-3".to_owned()),
+        ])
+    );
+    assert_eq!(
+        parse_guardian_labels("", 2)?,
+        BTreeMap::from([(0, "guardian-0".to_owned()), (1, "guardian-1".to_owned())])
+    );
+    assert!(parse_guardian_labels("a,b,c", 2).is_err());
+    assert!(parse_guardian_labels("alice,alice", 2).is_err());
+    // a label can't take the default label of another guardian
+    assert!(parse_guardian_labels("guardian-1", 2).is_err());
+    Ok(())
+}
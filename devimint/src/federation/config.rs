@@ -1,7 +1,16 @@
+use std::collections::BTreeSet;
+use std::env;
+
+use anyhow::{ensure, Context, Result};
 use bitcoincore_rpc::bitcoin::Network;
 use fedimint_core::config::{EmptyGenParams, ServerModuleConfigGenParamsRegistry};
-use fedimint_core::envs::{is_env_var_set, BitcoinRpcConfig, FM_USE_UNKNOWN_MODULE_ENV};
+use fedimint_core::core::ModuleKind;
+use fedimint_core::envs::{
+    BitcoinRpcConfig, FM_ENABLE_MODULE_LNV2_ENV, FM_USE_UNKNOWN_MODULE_ENV,
+};
+use fedimint_core::module::registry::ModuleRegistry;
 use fedimint_core::module::ServerModuleInit as _;
+use fedimint_core::Amount;
 use fedimint_ln_server::common::config::{
     LightningGenParams, LightningGenParamsConsensus, LightningGenParamsLocal,
 };
@@ -18,8 +27,58 @@
 use fedimintd::default_esplora_server;
 use fedimintd::envs::FM_DISABLE_META_MODULE_ENV;
 
+use crate::envs::FM_MINT_FEES_ENV;
 use crate::version_constants::VERSION_0_4_0_ALPHA;
 
+/// Highest fee the mint may charge for issuing or spending a single note:
+/// the default fee of the lnv2 module per input and output, which is what
+/// fedimint itself configures for a single item of a transaction.
+///
+/// Fees are charged per note, and a note worth no more than its spend fee is
+/// worthless, so higher fees would make most of the small notes devimint
+/// tests deal in unspendable.
+pub fn max_mint_fee() -> Amount {
+    let lnv2_fees = fedimint_lnv2_common::config::FeeConsensus::default();
+    lnv2_fees.input.max(lnv2_fees.output)
+}
+
+/// How config gen sets up the modules, beyond the default module params
+#[derive(Debug, Clone, Default)]
+pub struct ModuleOptions {
+    /// Only instantiate these modules, see [`retain_modules`]
+    pub kinds: Option<BTreeSet<ModuleKind>>,
+    /// Fees the mint charges per issued and spent note
+    pub mint_fees: FeeConsensus,
+}
+
+impl ModuleOptions {
+    /// Attaches the params of the default modules and drops the ones not
+    /// selected, with the optional modules enabled by `env_flag` like by
+    /// [`fedimint_core::envs::is_env_var_set`]
+    pub fn apply(
+        &self,
+        bitcoin_rpc: &BitcoinRpcConfig,
+        module_init_params: &mut ServerModuleConfigGenParamsRegistry,
+        network: Network,
+        finality_delay: u32,
+        fedimintd_version: &semver::Version,
+        env_flag: impl Fn(&str) -> bool,
+    ) {
+        attach_default_module_init_params(
+            bitcoin_rpc,
+            module_init_params,
+            network,
+            finality_delay,
+            fedimintd_version,
+            self.mint_fees.clone(),
+            env_flag,
+        );
+        if let Some(kinds) = &self.kinds {
+            retain_modules(module_init_params, kinds);
+        }
+    }
+}
+
 /// Duplicate default fedimint module setup
 pub fn attach_default_module_init_params(
     bitcoin_rpc: &BitcoinRpcConfig,
@@ -27,6 +86,8 @@ pub fn attach_default_module_init_params(
     network: Network,
     finality_delay: u32,
     fedimintd_version: &semver::Version,
+    mint_fees: FeeConsensus,
+    env_flag: impl Fn(&str) -> bool,
 ) {
     module_init_params
         .attach_config_gen_params(
@@ -42,7 +103,7 @@ pub fn attach_default_module_init_params(
             MintInit::kind(),
             MintGenParams {
                 local: EmptyGenParams::default(),
-                consensus: MintGenParamsConsensus::new(2, FeeConsensus::default()),
+                consensus: MintGenParamsConsensus::new(2, mint_fees),
             },
         )
         .attach_config_gen_params(
@@ -64,7 +125,7 @@ pub fn attach_default_module_init_params(
 
     // TODO(support:v0.3): v0.4 introduced lnv2 modules, so we need to skip
     // attaching the module for old fedimintd versions
-    if fedimintd_version >= &VERSION_0_4_0_ALPHA {
+    if fedimintd_version >= &VERSION_0_4_0_ALPHA && env_flag(FM_ENABLE_MODULE_LNV2_ENV) {
         module_init_params.attach_config_gen_params(
             fedimint_lnv2_server::LightningInit::kind(),
             fedimint_lnv2_common::config::LightningGenParams {
@@ -76,12 +137,121 @@ pub fn attach_default_module_init_params(
         );
     }
 
-    if !is_env_var_set(FM_DISABLE_META_MODULE_ENV) {
+    if !env_flag(FM_DISABLE_META_MODULE_ENV) {
         module_init_params.attach_config_gen_params(MetaInit::kind(), MetaGenParams::default());
     }
 
-    if is_env_var_set(FM_USE_UNKNOWN_MODULE_ENV) {
+    if env_flag(FM_USE_UNKNOWN_MODULE_ENV) {
         module_init_params
             .attach_config_gen_params(UnknownInit::kind(), UnknownGenParams::default());
     }
 }
+
+/// Checks a module selection for [`crate::federation::Federation::new_with_modules`]
+/// can form a working federation
+pub fn validate_module_selection(kinds: &BTreeSet<ModuleKind>) -> Result<()> {
+    ensure!(
+        kinds.contains(&MintInit::kind()),
+        "the {} module is always required",
+        MintInit::kind()
+    );
+    for lightning in [
+        LightningInit::kind(),
+        fedimint_lnv2_server::LightningInit::kind(),
+    ] {
+        ensure!(
+            !kinds.contains(&lightning) || kinds.contains(&WalletInit::kind()),
+            "the {lightning} module requires the {} module",
+            WalletInit::kind()
+        );
+    }
+    Ok(())
+}
+
+/// Checks the mint fees don't exceed [`max_mint_fee`]
+pub fn validate_mint_fees(fees: &FeeConsensus) -> Result<()> {
+    let max_fee = max_mint_fee();
+    ensure!(
+        fees.note_issuance_abs <= max_fee && fees.note_spend_abs <= max_fee,
+        "mint fees of {} to issue and {} to spend a note exceed the maximum of {max_fee}",
+        fees.note_issuance_abs,
+        fees.note_spend_abs
+    );
+    Ok(())
+}
+
+/// Parses mint fees from `ISSUANCE,SPEND` in msats
+pub fn parse_mint_fees(fees: &str) -> Result<FeeConsensus> {
+    let (issuance, spend) = fees
+        .split_once(',')
+        .context("mint fees must be ISSUANCE,SPEND in msats")?;
+    let fees = FeeConsensus {
+        note_issuance_abs: Amount::from_msats(
+            issuance
+                .trim()
+                .parse()
+                .context("invalid note issuance fee")?,
+        ),
+        note_spend_abs: Amount::from_msats(spend.trim().parse().context("invalid note spend fee")?),
+    };
+    validate_mint_fees(&fees)?;
+    Ok(fees)
+}
+
+/// Mint fees from `FM_MINT_FEES`, no fees if unset
+pub fn mint_fees_from_env() -> Result<FeeConsensus> {
+    match env::var(FM_MINT_FEES_ENV) {
+        Ok(fees) if !fees.trim().is_empty() => {
+            parse_mint_fees(&fees).with_context(|| format!("{FM_MINT_FEES_ENV} is invalid"))
+        }
+        _ => Ok(FeeConsensus::default()),
+    }
+}
+
+/// Removes all modules not in `kinds` from `module_init_params`. The
+/// remaining modules keep their instance ids, which devimint hardcodes for the
+/// default modules.
+pub fn retain_modules(
+    module_init_params: &mut ServerModuleConfigGenParamsRegistry,
+    kinds: &BTreeSet<ModuleKind>,
+) {
+    *module_init_params = ModuleRegistry::new(
+        std::mem::take(module_init_params)
+            .into_iter_modules()
+            .filter(|(_, kind, _)| kinds.contains(kind)),
+    );
+}
+
+#[test]
+fn test_validate_module_selection() {
+    let selection = |kinds: &[ModuleKind]| kinds.iter().cloned().collect::<BTreeSet<_>>();
+
+    assert!(validate_module_selection(&selection(&[MintInit::kind()])).is_ok());
+    assert!(validate_module_selection(&selection(&[
+        MintInit::kind(),
+        WalletInit::kind(),
+        LightningInit::kind()
+    ]))
+    .is_ok());
+    assert!(validate_module_selection(&selection(&[WalletInit::kind()])).is_err());
+    assert!(
+        validate_module_selection(&selection(&[MintInit::kind(), LightningInit::kind()])).is_err()
+    );
+}
+
+#[test]
+fn test_parse_mint_fees() -> Result<()> {
+    assert_eq!(
+        parse_mint_fees("10, 20")?,
+        FeeConsensus {
+            note_issuance_abs: Amount::from_msats(10),
+            note_spend_abs: Amount::from_msats(20),
+        }
+    );
+    assert_eq!(parse_mint_fees("0,0")?, FeeConsensus::default());
+    assert!(parse_mint_fees("10").is_err());
+    assert!(parse_mint_fees("10,-1").is_err());
+    assert!(parse_mint_fees(&format!("0,{}", max_mint_fee().msats)).is_ok());
+    assert!(parse_mint_fees(&format!("0,{}", max_mint_fee().msats + 1)).is_err());
+    Ok(())
+}
@@ -1,41 +1,113 @@
 use std::collections::HashMap;
 use std::ops::ControlFlow;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+use clap::ValueEnum;
+use fedimint_core::invite_code::InviteCode;
 use fedimint_core::secp256k1::PublicKey;
 use fedimint_core::util::{backoff_util, retry};
+use fedimint_core::Amount;
+use fedimint_logging::LOG_DEVIMINT;
 use fedimint_testing::gateway::LightningNodeType;
 use ln_gateway::lightning::ChannelInfo;
-use ln_gateway::rpc::V1_API_ENDPOINT;
-use tracing::info;
+use ln_gateway::rpc::{FederationStatus, GatewayMode, V1_API_ENDPOINT};
+use tracing::{info, instrument};
 
-use crate::envs::{FM_GATEWAY_API_ADDR_ENV, FM_GATEWAY_DATA_DIR_ENV, FM_GATEWAY_LISTEN_ADDR_ENV};
-use crate::external::{Bitcoind, LightningNode};
+use crate::envs::{
+    FM_GATEWAY_API_ADDR_ENV, FM_GATEWAY_DATA_DIR_ENV, FM_GATEWAY_LISTEN_ADDR_ENV,
+    FM_GATEWAY_MODE_ENV, FM_GATEWAY_NETWORK_ENV, FM_GATEWAY_REGISTRATION_TTL_SECS_ENV,
+};
+use crate::external::{Bitcoind, HtlcInfo, LightningNode};
 use crate::federation::Federation;
-use crate::util::{poll, Command, ProcessHandle, ProcessManager};
+use crate::util::{poll, poll_with_timeout, Command, LaunchKind, ProcessHandle, ProcessManager};
 use crate::vars::utf8;
 use crate::version_constants::VERSION_0_5_0_ALPHA;
 use crate::{cmd, Lightningd};
 
+/// Upper bound of what moving on-chain funds into a channel costs the gateway
+/// besides the moved amount: the funding transaction's fee, and the commitment
+/// fee and reserve that don't count towards its lightning balance
+const MAX_REBALANCE_COST_SATS: u64 = 20_000;
+
+/// How long [`Gatewayd::connect_invite_code`] keeps retrying to reach the
+/// federation
+pub const CONNECT_FED_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// On-chain and lightning balances of a gateway's node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeBalances {
+    pub onchain_sats: u64,
+    /// Sum of the gateway's side of its channels
+    pub lightning_msats: u64,
+}
+
+/// Which side of a gateway's channels [`Gatewayd::await_liquidity`] checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiquidityDirection {
+    /// The gateway can receive, needed for incoming payments
+    Inbound,
+    /// The gateway can send, needed for outgoing payments
+    Outbound,
+}
+
 #[derive(Clone)]
 pub struct Gatewayd {
     pub(crate) process: ProcessHandle,
     pub ln: Option<LightningNode>,
     pub addr: String,
     pub(crate) lightning_node_addr: String,
+    pub(crate) registration_ttl: Option<Duration>,
+    pub(crate) mode: GatewayMode,
+    pub(crate) launch_kind: LaunchKind,
 }
 
 impl Gatewayd {
     pub async fn new(process_mgr: &ProcessManager, ln: LightningNode) -> Result<Self> {
-        let ln_name = ln.name();
-        let test_dir = &process_mgr.globals.FM_TEST_DIR;
+        Self::new_inner(process_mgr, ln, None).await
+    }
 
-        let port = match ln {
-            LightningNode::Cln(_) => process_mgr.globals.FM_PORT_GW_CLN,
-            LightningNode::Lnd(_) => process_mgr.globals.FM_PORT_GW_LND,
-            LightningNode::Ldk => process_mgr.globals.FM_PORT_GW_LDK,
-        };
+    /// Starts a gateway on `node` without any federation, for working on the
+    /// gateway in isolation. It's connected to a federation later with
+    /// [`Self::connect_invite_code`].
+    pub async fn standalone(process_mgr: &ProcessManager, node: LightningNode) -> Result<Self> {
+        let gatewayd = Self::new(process_mgr, node).await?;
+        info!(
+            target: LOG_DEVIMINT,
+            addr = %gatewayd.addr,
+            "Started standalone gateway, not connected to any federation"
+        );
+        Ok(gatewayd)
+    }
+
+    /// Like [`Self::new`], but the gateway's registrations with federations
+    /// expire after `registration_ttl` instead of gatewayd's default, which
+    /// is useful for testing registration expiry.
+    pub async fn new_with_registration_ttl(
+        process_mgr: &ProcessManager,
+        ln: LightningNode,
+        registration_ttl: Duration,
+    ) -> Result<Self> {
+        Self::new_inner(process_mgr, ln, Some(registration_ttl)).await
+    }
+
+    #[instrument(name = "gatewayd", level = "debug", skip_all, fields(ln = %ln.name()))]
+    async fn new_inner(
+        process_mgr: &ProcessManager,
+        ln: LightningNode,
+        registration_ttl: Option<Duration>,
+    ) -> Result<Self> {
+        if let Some(registration_ttl) = registration_ttl {
+            // gatewayd takes whole seconds and re-registers at 85% of the TTL,
+            // so anything shorter would make it re-register in a busy loop
+            ensure!(
+                registration_ttl >= Duration::from_secs(1),
+                "Gateway registration TTL must be at least a second, got {registration_ttl:?}"
+            );
+        }
+        let port = Self::port(process_mgr, &ln);
         let addr = format!("http://127.0.0.1:{port}/{V1_API_ENDPOINT}");
 
         let lightning_node_port = match ln {
@@ -45,7 +117,91 @@ pub async fn new(process_mgr: &ProcessManager, ln: LightningNode) -> Result<Self
         };
         let lightning_node_addr = format!("127.0.0.1:{lightning_node_port}");
 
-        let gateway_env: HashMap<String, String> = HashMap::from_iter([
+        let launch_kind = Self::detect_launch_kind(process_mgr, &ln).await?;
+        let mode = GatewayMode::Full;
+        let process = Self::spawn(process_mgr, &ln, &addr, registration_ttl, mode).await?;
+
+        let gatewayd = Self {
+            ln: Some(ln),
+            process,
+            addr,
+            lightning_node_addr,
+            registration_ttl,
+            mode,
+            launch_kind,
+        };
+        gatewayd.wait_for_rpc().await?;
+        Ok(gatewayd)
+    }
+
+    /// Attaches to the gateway on `ln` that another devimint spawned with the
+    /// same globals, which keeps owning it, see
+    /// [`crate::devfed::DevFedHandle`]. [`Self::stop`] leaves it running.
+    pub async fn reattach(
+        process_mgr: &ProcessManager,
+        ln: LightningNode,
+        registration_ttl: Option<Duration>,
+        mode: GatewayMode,
+    ) -> Result<Self> {
+        let port = Self::port(process_mgr, &ln);
+        let lightning_node_port = match ln {
+            LightningNode::Cln(_) => process_mgr.globals.FM_PORT_CLN,
+            LightningNode::Lnd(_) => process_mgr.globals.FM_PORT_LND_LISTEN,
+            LightningNode::Ldk => process_mgr.globals.FM_PORT_LDK,
+        };
+        let gatewayd = Self {
+            process: ProcessHandle::reattached(&format!("gatewayd-{}", ln.name())),
+            ln: Some(ln),
+            addr: format!("http://127.0.0.1:{port}/{V1_API_ENDPOINT}"),
+            lightning_node_addr: format!("127.0.0.1:{lightning_node_port}"),
+            registration_ttl,
+            mode,
+            launch_kind: LaunchKind::Reattached,
+        };
+        gatewayd.wait_for_rpc().await?;
+        Ok(gatewayd)
+    }
+
+    fn port(process_mgr: &ProcessManager, ln: &LightningNode) -> u16 {
+        match ln {
+            LightningNode::Cln(_) => process_mgr.globals.FM_PORT_GW_CLN,
+            LightningNode::Lnd(_) => process_mgr.globals.FM_PORT_GW_LND,
+            LightningNode::Ldk => process_mgr.globals.FM_PORT_GW_LDK,
+        }
+    }
+
+    async fn detect_launch_kind(
+        process_mgr: &ProcessManager,
+        ln: &LightningNode,
+    ) -> Result<LaunchKind> {
+        let ln_name = ln.name();
+        // same data dir as in `Self::spawn`
+        let db = process_mgr
+            .globals
+            .FM_TEST_DIR
+            .join(ln_name.to_string())
+            .join("gatewayd.db");
+        LaunchKind::detect(&format!("gatewayd-{ln_name}"), &db).await
+    }
+
+    /// Whether the gateway started on the database of an earlier run, which
+    /// is always the case after [`Self::stop`] and [`Self::start`]
+    pub fn launch_kind(&self) -> LaunchKind {
+        self.launch_kind
+    }
+
+    async fn spawn(
+        process_mgr: &ProcessManager,
+        ln: &LightningNode,
+        addr: &str,
+        registration_ttl: Option<Duration>,
+        mode: GatewayMode,
+    ) -> Result<ProcessHandle> {
+        let ln_name = ln.name();
+        let test_dir = &process_mgr.globals.FM_TEST_DIR;
+        let port = Self::port(process_mgr, ln);
+
+        let mut gateway_env: HashMap<String, String> = HashMap::from_iter([
             (
                 FM_GATEWAY_DATA_DIR_ENV.to_owned(),
                 format!("{}/{ln_name}", utf8(test_dir)),
@@ -54,27 +210,93 @@ pub async fn new(process_mgr: &ProcessManager, ln: LightningNode) -> Result<Self
                 FM_GATEWAY_LISTEN_ADDR_ENV.to_owned(),
                 format!("127.0.0.1:{port}"),
             ),
-            (FM_GATEWAY_API_ADDR_ENV.to_owned(), addr.clone()),
+            (FM_GATEWAY_API_ADDR_ENV.to_owned(), addr.to_owned()),
+            (
+                FM_GATEWAY_NETWORK_ENV.to_owned(),
+                process_mgr.globals.FM_BITCOIN_NETWORK.clone(),
+            ),
         ]);
-        let process = process_mgr
+        if let Some(registration_ttl) = registration_ttl {
+            gateway_env.insert(
+                FM_GATEWAY_REGISTRATION_TTL_SECS_ENV.to_owned(),
+                registration_ttl.as_secs().to_string(),
+            );
+        }
+        if mode != GatewayMode::Full {
+            let mode = mode
+                .to_possible_value()
+                .context("gateway mode is skipped")?;
+            gateway_env.insert(FM_GATEWAY_MODE_ENV.to_owned(), mode.get_name().to_owned());
+        }
+        for (var, value) in ln.gateway_env().await? {
+            gateway_env.insert(var.to_owned(), value);
+        }
+        process_mgr
             .spawn_daemon(
                 &format!("gatewayd-{ln_name}"),
                 cmd!(crate::util::Gatewayd, ln_name).envs(gateway_env),
             )
-            .await?;
+            .await
+    }
 
-        let gatewayd = Self {
-            ln: Some(ln),
-            process,
-            addr,
-            lightning_node_addr,
-        };
+    async fn wait_for_rpc(&self) -> Result<()> {
         poll(
             "waiting for gateway to be ready to respond to rpc",
-            || async { gatewayd.gateway_id().await.map_err(ControlFlow::Continue) },
+            || async { self.gateway_id().await.map_err(ControlFlow::Continue) },
         )
         .await?;
-        Ok(gatewayd)
+        Ok(())
+    }
+
+    /// Takes the gateway offline, keeping its data dir and ports so it can be
+    /// brought back with [`Self::start`]. The federation keeps listing the
+    /// gateway until its registration expires.
+    pub async fn stop(&self) -> Result<()> {
+        info!(target: LOG_DEVIMINT, addr = %self.addr, "Stopping gateway");
+        self.process.terminate().await
+    }
+
+    /// Brings a gateway stopped with [`Self::stop`] back online. Once running,
+    /// gatewayd re-registers with its federations on its own.
+    pub async fn start(&mut self, process_mgr: &ProcessManager) -> Result<()> {
+        ensure!(
+            !self.process.is_running().await,
+            "Gateway is already running"
+        );
+        let ln = self.ln.as_ref().context("Lightning Node should exist")?;
+        info!(target: LOG_DEVIMINT, addr = %self.addr, "Starting gateway");
+        self.launch_kind = Self::detect_launch_kind(process_mgr, ln).await?;
+        self.process = Self::spawn(
+            process_mgr,
+            ln,
+            &self.addr,
+            self.registration_ttl,
+            self.mode,
+        )
+        .await?;
+        self.wait_for_rpc().await
+    }
+
+    /// Restarts the gateway so it only routes payments in the directions
+    /// allowed by `mode`, clients then fail to route through it in the others.
+    /// Unlike the registration TTL, the mode is kept across [`Self::stop`] and
+    /// [`Self::start`].
+    pub async fn set_mode(
+        &mut self,
+        process_mgr: &ProcessManager,
+        mode: GatewayMode,
+    ) -> Result<()> {
+        info!(target: LOG_DEVIMINT, addr = %self.addr, ?mode, "Setting gateway mode");
+        self.stop().await?;
+        self.mode = mode;
+        self.start(process_mgr).await
+    }
+
+    /// The directions the gateway routes payments in, as reported by its info
+    pub async fn mode(&self) -> Result<GatewayMode> {
+        Ok(serde_json::from_value(
+            self.get_info().await?["gateway_mode"].clone(),
+        )?)
     }
 
     pub fn set_lightning_node(&mut self, ln_node: LightningNode) {
@@ -97,6 +319,60 @@ pub async fn stop_lightning_node(&mut self) -> Result<()> {
         }
     }
 
+    /// Kills the gateway's lightning node while leaving gatewayd running, to
+    /// test how the gateway copes with losing it, and waits until `info`
+    /// reports the node as unreachable. Unlike [`Self::stop_lightning_node`]
+    /// the node can be brought back with [`Self::restart_lightning_node`].
+    pub async fn kill_lightning_node(&self) -> Result<()> {
+        info!(target: LOG_DEVIMINT, "Killing lightning node");
+        match &self.ln {
+            Some(LightningNode::Cln(cln)) => cln.stop().await?,
+            Some(LightningNode::Lnd(lnd)) => lnd.stop().await?,
+            Some(LightningNode::Ldk) => {
+                bail!("The LDK node lives in the gateway process and can't be killed on its own")
+            }
+            None => bail!("Cannot kill an already stopped Lightning Node"),
+        }
+        poll("gateway reports lightning node down", || async {
+            let info = self.get_info().await.map_err(ControlFlow::Continue)?;
+            if info["lightning_pub_key"].is_null() {
+                Ok(())
+            } else {
+                Err(ControlFlow::Continue(anyhow::anyhow!(
+                    "gateway still reports lightning node {}",
+                    info["lightning_pub_key"]
+                )))
+            }
+        })
+        .await
+    }
+
+    /// Restarts the lightning node killed by [`Self::kill_lightning_node`]
+    /// and waits until the gateway reconnected to it
+    pub async fn restart_lightning_node(&self, process_mgr: &ProcessManager) -> Result<()> {
+        info!(target: LOG_DEVIMINT, "Restarting lightning node");
+        match &self.ln {
+            Some(LightningNode::Cln(cln)) => cln.restart(process_mgr).await?,
+            Some(LightningNode::Lnd(lnd)) => lnd.restart(process_mgr).await?,
+            Some(LightningNode::Ldk) => {
+                bail!("The LDK node lives in the gateway process and can't be restarted on its own")
+            }
+            None => bail!("Cannot restart a stopped Lightning Node"),
+        }
+        poll("gateway reconnects to lightning node", || async {
+            let info = self.get_info().await.map_err(ControlFlow::Continue)?;
+            if info["lightning_pub_key"].is_string() {
+                Ok(())
+            } else {
+                Err(ControlFlow::Continue(anyhow::anyhow!(
+                    "gateway doesn't report its lightning node yet, state {}",
+                    info["gateway_state"]
+                )))
+            }
+        })
+        .await
+    }
+
     /// Restarts the gateway using the provided `bin_path`, which is useful for
     /// testing upgrades.
     pub async fn restart_with_bin(
@@ -134,9 +410,9 @@ pub async fn restart_with_bin(
             }
             _ => ln,
         };
-        let new_gw = Self::new(process_mgr, new_ln.clone()).await?;
-        self.process = new_gw.process;
+        // keeps the gateway's mode and registration TTL
         self.set_lightning_node(new_ln);
+        self.start(process_mgr).await?;
         let gatewayd_version = crate::util::Gatewayd::version_or_default().await;
         let gateway_cli_version = crate::util::GatewayCli::version_or_default().await;
         let gateway_cln_extension_version =
@@ -201,16 +477,41 @@ pub async fn lightning_pubkey(&self) -> Result<PublicKey> {
     }
 
     pub async fn connect_fed(&self, fed: &Federation) -> Result<()> {
-        let invite_code = fed.invite_code()?;
-        poll("gateway connect-fed", || async {
-            cmd!(self, "connect-fed", invite_code.clone())
-                .run()
-                .await
-                .map_err(ControlFlow::Continue)?;
-            Ok(())
-        })
-        .await?;
-        Ok(())
+        self.connect_invite_code(&fed.invite_code()?).await
+    }
+
+    /// Connects the gateway to the federation of `invite_code`, which doesn't
+    /// have to be one started by devimint.
+    ///
+    /// A malformed invite code fails right away, an unreachable federation
+    /// after [`CONNECT_FED_TIMEOUT`].
+    pub async fn connect_invite_code(&self, invite_code: &str) -> Result<()> {
+        let invite_code = invite_code.trim();
+        let federation_id = InviteCode::from_str(invite_code)
+            .context("invalid invite code")?
+            .federation_id();
+        poll_with_timeout(
+            &format!("gateway connect-fed {federation_id}"),
+            CONNECT_FED_TIMEOUT,
+            || async {
+                cmd!(self, "connect-fed", invite_code)
+                    .run()
+                    .await
+                    .map_err(ControlFlow::Continue)?;
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    /// Whether the gateway can currently reach the connected federation
+    /// `fed_id`, as reported by the gateway itself. Returns within the
+    /// gateway's own timeout even if the federation doesn't answer.
+    pub async fn federation_status(&self, fed_id: &str) -> Result<FederationStatus> {
+        let status = cmd!(self, "federation-status", "--federation-id={fed_id}")
+            .out_json()
+            .await?;
+        serde_json::from_value(status).context("invalid federation status")
     }
 
     pub async fn get_pegin_addr(&self, fed_id: &str) -> Result<String> {
@@ -262,6 +563,163 @@ pub async fn open_channel(
         Ok(())
     }
 
+    /// On-chain and lightning balances of the gateway's node
+    pub async fn node_balances(&self) -> Result<NodeBalances> {
+        let balances = cmd!(self, "get-balances").out_json().await?;
+        Ok(NodeBalances {
+            onchain_sats: balances["onchain_balance_sats"]
+                .as_u64()
+                .context("onchain_balance_sats must be a u64")?,
+            lightning_msats: balances["lightning_balance_msats"]
+                .as_u64()
+                .context("lightning_balance_msats must be a u64")?,
+        })
+    }
+
+    /// Ecash balance of the gateway in the federation `federation_id`
+    pub async fn ecash_balance(&self, federation_id: &str) -> Result<Amount> {
+        let balance = cmd!(self, "balance", "--federation-id={federation_id}")
+            .out_json()
+            .await?
+            .as_u64()
+            .context("gateway balance must be a number")?;
+        Ok(Amount::from_msats(balance))
+    }
+
+    /// Waits until the gateway's ecash balance in the federation
+    /// `federation_id` matches `predicate`, e.g. once it received or settled
+    /// the ecash of a payment, and returns that balance.
+    ///
+    /// Errors with the last balance observed once `timeout` is hit.
+    pub async fn await_ecash_balance(
+        &self,
+        federation_id: &str,
+        predicate: impl Fn(Amount) -> bool,
+        timeout: Duration,
+    ) -> Result<Amount> {
+        let last_observed = std::sync::Mutex::new(None);
+        poll_with_timeout("gateway ecash balance", timeout, || async {
+            let balance = self.ecash_balance(federation_id).await.map_err(|err| {
+                let last = *last_observed.lock().expect("locking can't fail");
+                ControlFlow::Continue(err.context(match last {
+                    Some(last) => format!("last observed balance {last}"),
+                    None => "no balance observed yet".to_owned(),
+                }))
+            })?;
+            *last_observed.lock().expect("locking can't fail") = Some(balance);
+            if predicate(balance) {
+                Ok(balance)
+            } else {
+                Err(ControlFlow::Continue(anyhow::anyhow!(
+                    "gateway balance in federation {federation_id} is {balance}, which doesn't match yet"
+                )))
+            }
+        })
+        .await
+    }
+
+    /// Moves `amount_sats` of the gateway's on-chain funds into lightning
+    /// liquidity by opening a new channel of that size to `peer`, mining the
+    /// confirmations it needs. Checks the on-chain balance dropped by at least
+    /// `amount_sats` and the lightning balance rose by about as much, and
+    /// returns the balances from before and after.
+    ///
+    /// The gateway has no swap service of its own, so funding channels from
+    /// its on-chain wallet is how it turns on-chain funds into liquidity.
+    /// Returns `None` without doing anything if the gateway can't report its
+    /// balances, which it can since v0.5.0.
+    pub async fn rebalance_onchain(
+        &self,
+        peer: &Gatewayd,
+        bitcoind: &Bitcoind,
+        amount_sats: u64,
+    ) -> Result<Option<(NodeBalances, NodeBalances)>> {
+        let gateway_cli_version = crate::util::GatewayCli::version_or_default().await;
+        let gatewayd_version = crate::util::Gatewayd::version_or_default().await;
+        if gateway_cli_version < *VERSION_0_5_0_ALPHA || gatewayd_version < *VERSION_0_5_0_ALPHA {
+            info!(target: LOG_DEVIMINT, %gatewayd_version, "Gateway can't report its balances, skipping on-chain rebalance");
+            return Ok(None);
+        }
+
+        let before = self.node_balances().await?;
+        ensure!(
+            amount_sats + MAX_REBALANCE_COST_SATS <= before.onchain_sats,
+            "gateway has {} sats on-chain, too little to move {amount_sats} sats into a channel",
+            before.onchain_sats
+        );
+        let peer_pubkey = peer.lightning_pubkey().await?;
+        let channels_before = self
+            .list_active_channels()
+            .await?
+            .into_iter()
+            .filter(|channel| channel.remote_pubkey == peer_pubkey)
+            .count();
+        info!(target: LOG_DEVIMINT, ?before, amount_sats, %peer_pubkey, "Moving gateway on-chain funds into a channel");
+
+        self.open_channel(peer, amount_sats, None).await?;
+        // the funding transaction may not be in the mempool right away
+        poll("gateway channel funded", || async {
+            bitcoind
+                .mine_blocks(10)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            self.wait_for_chain_sync(bitcoind)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            let channels = self
+                .list_active_channels()
+                .await
+                .map_err(ControlFlow::Continue)?
+                .into_iter()
+                .filter(|channel| channel.remote_pubkey == peer_pubkey)
+                .count();
+            if channels <= channels_before {
+                return Err(ControlFlow::Continue(anyhow::anyhow!(
+                    "gateway has {channels} active channels with {peer_pubkey}, the new channel isn't active yet"
+                )));
+            }
+            Ok(())
+        })
+        .await?;
+
+        let after = poll("gateway balances after rebalance", || async {
+            let after = self
+                .node_balances()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            let lightning_gain_sats = after.lightning_msats.saturating_sub(before.lightning_msats) / 1000;
+            if lightning_gain_sats + MAX_REBALANCE_COST_SATS < amount_sats {
+                return Err(ControlFlow::Continue(anyhow::anyhow!(
+                    "lightning balance rose by {lightning_gain_sats} sats, expected about {amount_sats}"
+                )));
+            }
+            Ok(after)
+        })
+        .await?;
+        ensure!(
+            after.onchain_sats + amount_sats <= before.onchain_sats,
+            "on-chain balance went from {} to {} sats, expected it to drop by at least {amount_sats}",
+            before.onchain_sats,
+            after.onchain_sats
+        );
+        info!(target: LOG_DEVIMINT, ?before, ?after, "Moved gateway on-chain funds into a channel");
+        Ok(Some((before, after)))
+    }
+
+    /// Cooperatively closes all of the gateway's channels with `pubkey`
+    pub async fn close_channels_with_peer(&self, pubkey: PublicKey) -> Result<()> {
+        cmd!(
+            self,
+            "lightning",
+            "close-channels-with-peer",
+            "--pubkey",
+            pubkey
+        )
+        .run()
+        .await?;
+        Ok(())
+    }
+
     pub async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>> {
         let channels = cmd!(self, "lightning", "list-active-channels")
             .out_json()
@@ -301,6 +759,81 @@ pub async fn list_active_channels(&self) -> Result<Vec<ChannelInfo>> {
         Ok(channels)
     }
 
+    /// HTLCs on the channels of the gateway's lightning node that are neither
+    /// settled nor failed yet, i.e. the payments it has in flight. An
+    /// outgoing one stuck paying a hold invoice of lnd can be failed with
+    /// [`Self::force_fail_htlc`] to test contract cancellation.
+    pub async fn inflight_htlcs(&self) -> Result<Vec<HtlcInfo>> {
+        match &self.ln {
+            Some(LightningNode::Cln(cln)) => cln.pending_htlcs().await,
+            Some(LightningNode::Lnd(lnd)) => lnd.pending_htlcs().await,
+            Some(LightningNode::Ldk) => {
+                bail!("HTLCs of the LDK node inside the gateway can't be listed")
+            }
+            None => bail!("Gateway has no lightning node"),
+        }
+    }
+
+    /// Fails the gateway's outgoing HTLC `htlc`, one of
+    /// [`Self::inflight_htlcs`], on its lightning node, see
+    /// [`crate::Lightningd::force_fail_htlc`]. This closes the node's
+    /// channels with the HTLC's peer.
+    pub async fn force_fail_htlc(&self, htlc: &HtlcInfo) -> Result<()> {
+        match &self.ln {
+            Some(LightningNode::Cln(cln)) => cln.force_fail_htlc(&htlc.peer, htlc.htlc_index).await,
+            Some(LightningNode::Lnd(_)) => {
+                bail!("Force-failing HTLCs of lnd isn't supported")
+            }
+            Some(LightningNode::Ldk) => {
+                bail!("HTLCs of the LDK node inside the gateway can't be failed")
+            }
+            None => bail!("Gateway has no lightning node"),
+        }
+    }
+
+    /// Waits until one of the gateway's active channels can route `amount` in
+    /// `direction`, since a single payment can't be split across channels.
+    ///
+    /// Errors with the liquidity of every channel once `timeout` is hit.
+    pub async fn await_liquidity(
+        &self,
+        direction: LiquidityDirection,
+        amount: Amount,
+        timeout: Duration,
+    ) -> Result<()> {
+        poll_with_timeout("gateway liquidity", timeout, || async {
+            let channels = self
+                .list_active_channels()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            let liquidity_sats = |channel: &ChannelInfo| match direction {
+                LiquidityDirection::Inbound => channel.inbound_liquidity_sats,
+                LiquidityDirection::Outbound => channel.outbound_liquidity_sats,
+            };
+            if channels
+                .iter()
+                .any(|channel| Amount::from_sats(liquidity_sats(channel)) >= amount)
+            {
+                return Ok(());
+            }
+            let current = channels
+                .iter()
+                .map(|channel| {
+                    format!(
+                        "{} ({} sats)",
+                        channel.remote_pubkey,
+                        liquidity_sats(channel)
+                    )
+                })
+                .collect::<Vec<_>>();
+            Err(ControlFlow::Continue(anyhow::anyhow!(
+                "no channel with {direction:?} liquidity of {amount}, channels: [{}]",
+                current.join(", ")
+            )))
+        })
+        .await
+    }
+
     pub async fn wait_for_chain_sync(&self, bitcoind: &Bitcoind) -> Result<()> {
         poll("lightning node block processing", || async {
             let block_height = bitcoind.get_block_count().map_err(ControlFlow::Continue)? - 1;
@@ -16,30 +16,60 @@
 #![allow(clippy::too_many_lines)]
 
 use std::ffi;
+use std::path::Path;
 
+use anyhow::Context as _;
 use clap::Parser as _;
 use cli::cleanup_on_exit;
 use devfed::DevJitFed;
 pub use devfed::{dev_fed, DevFed};
 pub use external::{
-    external_daemons, ExternalDaemons, LightningNode, Lightningd, LightningdProcessHandle, Lnd,
+    external_daemons, lightning_daemons, ExternalDaemons, HoldInvoiceHandle, HtlcDirection,
+    HtlcInfo, LightningDaemons, LightningNode, Lightningd, LightningdProcessHandle, Lnd,
 };
+use fedimint_logging::LOG_DEVIMINT;
 use futures::Future;
 pub use gatewayd::Gatewayd;
 use tests::log_binary_versions;
+use tokio::runtime::Handle;
+use tracing::warn;
 use util::ProcessManager;
 
+pub mod api_bind;
+pub mod bitcoin_backend;
+pub mod bitcoind_rpc;
+pub mod byzantine;
+pub mod cgroup;
 pub mod cli;
+pub mod config_diff;
 pub mod devfed;
+pub mod disk;
 pub mod envs;
 pub mod external;
+pub mod faketime;
 pub mod federation;
 pub mod gatewayd;
+pub mod lnurl;
+pub mod logs;
+pub mod manifest;
+pub mod memory;
+pub mod netns;
+pub mod payments;
+pub mod profiler;
+pub mod proxy;
+pub mod quiescence;
+pub mod replay;
+pub mod setup_events;
 pub mod tests;
+pub mod throttle;
 pub mod util;
 pub mod vars;
 pub mod version_constants;
 
+/// Sets up a dev federation, runs `f` against it and shuts everything down
+/// again, failing if any spawned process survives.
+///
+/// Runs on any tokio runtime, see [`run_devfed_test_on`].
 pub async fn run_devfed_test<F, FF>(f: F) -> anyhow::Result<()>
 where
     F: FnOnce(DevJitFed, ProcessManager) -> FF,
@@ -54,9 +84,55 @@ pub async fn run_devfed_test<F, FF>(f: F) -> anyhow::Result<()>
     // by waiting on all jits to complete, we make it less likely
     // that something is not finished yet and will block in `on_block`
     let _ = dev_fed.finalize(&process_mgr).await;
+    if let Some(interval) = cli::block_interval()? {
+        dev_fed
+            .bitcoind()
+            .await?
+            .spawn_block_miner(&task_group, interval);
+    }
     let res = cleanup_on_exit(f(dev_fed.clone(), process_mgr.clone()), task_group).await;
-    dev_fed.fast_terminate().await;
+    if res.is_err() {
+        if let Ok(dest) = std::env::var(envs::FM_COLLECT_LOGS_DIR_ENV) {
+            let collected = async {
+                dev_fed
+                    .clone()
+                    .to_dev_fed(&process_mgr)
+                    .await?
+                    .collect_logs(Path::new(&dest))
+                    .await
+            };
+            if let Err(err) = collected.await {
+                warn!(target: LOG_DEVIMINT, %err, "Failed to collect logs");
+            }
+        }
+    }
+    let shutdown = dev_fed.shutdown(&process_mgr).await;
     res?;
 
-    Ok(())
+    shutdown
+}
+
+/// Runs [`run_devfed_test`] on the runtime of `handle`, for embedding
+/// devimint into test harnesses with a runtime of their own.
+///
+/// `handle` may belong to a current-thread or a multi-threaded runtime, which
+/// the caller keeps alive until the returned future completes. Everything
+/// devimint spawns and drops stays on that runtime, while the calling task
+/// only awaits the outcome, so it can run in a [`tokio::task::LocalSet`] too.
+///
+/// Devimint never blocks on a future from within async code. It does make
+/// blocking calls to bitcoind's RPC client, and stops daemons by blocking
+/// until they exited when their handles get dropped. On a multi-threaded
+/// runtime those go through [`tokio::task::block_in_place`]. On a
+/// current-thread runtime they block its only thread for as long as the
+/// daemon takes to answer or exit, see [`util::block_in_place`].
+pub async fn run_devfed_test_on<F, FF>(handle: &Handle, f: F) -> anyhow::Result<()>
+where
+    F: FnOnce(DevJitFed, ProcessManager) -> FF + Send + 'static,
+    FF: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    handle
+        .spawn(run_devfed_test(f))
+        .await
+        .context("devimint test task failed")?
 }
@@ -0,0 +1,103 @@
+//! A local LNURL-pay server, to test paying LNURLs and lightning addresses
+//! end to end without reaching out to the internet.
+//!
+//! The server runs as the `lnurl-server` daemon and issues its invoices from
+//! cln, so paying them from the federation goes out through the lnd gateway.
+//! It serves the `.well-known/lnurlp` endpoints of lightning addresses over
+//! plain http, for the bech32 LNURLs of [`LnurlServer::lnurl`], and over
+//! https for the lightning addresses of [`LnurlServer::address`]. Clients
+//! always fetch those from port 443 of their domain, so they reach the
+//! server as their https proxy instead, see [`LnurlServer::client_env`].
+
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use bitcoincore_rpc::bitcoin::bech32::{self, ToBase32, Variant};
+use fedimint_ln_client::envs::FM_LNURL_TLS_ROOT_CERT_ENV;
+use serde_json::json;
+use tokio::net::TcpStream;
+
+use crate::cmd;
+use crate::util::{poll, ProcessHandle, ProcessManager};
+
+/// Smallest amount the server issues invoices for, in msats
+pub const MIN_SENDABLE_MSAT: u64 = 1_000;
+/// Largest amount the server issues invoices for, in msats
+pub const MAX_SENDABLE_MSAT: u64 = 100_000_000;
+/// Domain of the lightning addresses the server serves over https
+pub const TLS_DOMAIN: &str = "localhost";
+
+/// LNURL-pay metadata of the pay endpoint of `name`, whose hash its invoices
+/// commit to
+pub fn metadata(name: &str) -> String {
+    json!([["text/plain", format!("devimint payment to {name}")]]).to_string()
+}
+
+/// The running `lnurl-server` daemon, stopped when dropped like every daemon
+/// of the [`ProcessManager`]
+#[derive(Clone)]
+pub struct LnurlServer {
+    base_url: String,
+    cert_file: PathBuf,
+    _process: ProcessHandle,
+}
+
+impl LnurlServer {
+    /// Spawns the server, which needs cln to be running
+    pub async fn start(process_mgr: &ProcessManager) -> Result<Self> {
+        let process = process_mgr
+            .spawn_daemon("lnurl-server", cmd!(crate::util::LnurlServer))
+            .await?;
+        let bind_addr = &process_mgr.globals.FM_LNURL_SERVER_BIND_ADDR;
+        poll("waiting for lnurl-server startup", || async {
+            TcpStream::connect(bind_addr)
+                .await
+                .context("connect to lnurl-server")
+                .map_err(ControlFlow::Continue)
+        })
+        .await?;
+
+        Ok(Self {
+            base_url: format!("http://{bind_addr}"),
+            cert_file: process_mgr.globals.FM_LNURL_SERVER_CERT.clone(),
+            _process: process,
+        })
+    }
+
+    /// Url the server is reachable at, like `http://127.0.0.1:1234`
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Url of the LNURL-pay endpoint of `name`, any name is accepted
+    pub fn pay_url(&self, name: &str) -> String {
+        format!("{}/.well-known/lnurlp/{name}", self.base_url)
+    }
+
+    /// bech32 encoded LNURL of [`Self::pay_url`]
+    pub fn lnurl(&self, name: &str) -> Result<String> {
+        Ok(bech32::encode(
+            "lnurl",
+            self.pay_url(name).as_bytes().to_base32(),
+            Variant::Bech32,
+        )?)
+    }
+
+    /// Lightning address of `name`, any name is accepted
+    pub fn address(&self, name: &str) -> String {
+        format!("{name}@{TLS_DOMAIN}")
+    }
+
+    /// Env variables clients need to resolve [`Self::address`]: the server
+    /// as https proxy, and its certificate as trusted root
+    pub fn client_env(&self) -> Vec<(String, String)> {
+        vec![
+            ("HTTPS_PROXY".to_owned(), self.base_url.clone()),
+            (
+                FM_LNURL_TLS_ROOT_CERT_ENV.to_owned(),
+                self.cert_file.display().to_string(),
+            ),
+        ]
+    }
+}
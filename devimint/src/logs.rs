@@ -0,0 +1,175 @@
+//! Parsing the JSON logs of daemons for log based test assertions.
+//!
+//! With [`ProcessManager::with_guardian_json_logs`] the guardians write a JSON
+//! object per line to their log file in `$FM_LOGS_DIR`, which
+//! [`ProcessManager::tail_logs`] parses and filters by level, target and
+//! message. Lines that aren't JSON, like a panic message or the output of a
+//! fedimintd too old to log JSON, are skipped.
+
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::Level;
+
+use crate::util::{poll_with_timeout, ProcessManager};
+
+/// A log line in the JSON format of `tracing_subscriber`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    /// Like `INFO`, see [`Self::level`]
+    pub level: String,
+    pub target: String,
+    /// The message and fields of the event
+    #[serde(default)]
+    pub fields: Map<String, Value>,
+    /// Spans the event happened in, outermost first
+    #[serde(default)]
+    pub spans: Vec<Value>,
+}
+
+impl LogEntry {
+    pub fn level(&self) -> Option<Level> {
+        Level::from_str(&self.level).ok()
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.fields.get("message").and_then(Value::as_str)
+    }
+}
+
+/// Which entries [`ProcessManager::tail_logs`] returns, all of them by default
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// Entries of this level or more severe, so `WARN` includes `ERROR`
+    pub min_level: Option<Level>,
+    /// Entries of this target or targets below it, so `fm::net` includes
+    /// `fm::net::peer`
+    pub target: Option<String>,
+    pub message_contains: Option<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(min_level) = self.min_level {
+            // more verbose levels compare greater
+            if !entry.level().is_some_and(|level| level <= min_level) {
+                return false;
+            }
+        }
+        if let Some(target) = self.target.as_deref() {
+            let below = entry
+                .target
+                .strip_prefix(target)
+                .is_some_and(|rest| rest.starts_with("::"));
+            if entry.target != target && !below {
+                return false;
+            }
+        }
+        if let Some(needle) = self.message_contains.as_deref() {
+            if !entry
+                .message()
+                .is_some_and(|message| message.contains(needle))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl ProcessManager {
+    /// The last `limit` entries of the JSON log of the daemon spawned as
+    /// `name`, like `fedimintd-default-0`, that pass `filter`, oldest first
+    pub async fn tail_logs(
+        &self,
+        name: &str,
+        filter: &LogFilter,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        let path = self.globals.FM_LOGS_DIR.join(format!("{name}.log"));
+        let logs = tokio::fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("reading logs of {name} at {}", path.display()))?;
+        Ok(parse_logs(&logs, filter, limit))
+    }
+
+    /// Waits until the daemon `name` logged an entry passing `filter`, and
+    /// returns the latest such entry
+    pub async fn await_log(
+        &self,
+        name: &str,
+        filter: &LogFilter,
+        timeout: Duration,
+    ) -> Result<LogEntry> {
+        poll_with_timeout(&format!("log entry of {name}"), timeout, || async {
+            self.tail_logs(name, filter, 1)
+                .await
+                .map_err(ControlFlow::Continue)?
+                .pop()
+                .ok_or_else(|| {
+                    ControlFlow::Continue(anyhow!("{name} logged nothing matching {filter:?}"))
+                })
+        })
+        .await
+    }
+}
+
+/// The last `limit` entries of JSON `logs` that pass `filter`
+fn parse_logs(logs: &str, filter: &LogFilter, limit: usize) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = logs
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter(|entry| filter.matches(entry))
+        .collect();
+    entries.drain(..entries.len().saturating_sub(limit));
+    entries
+}
+
+#[test]
+fn test_parse_logs() {
+    let logs = concat!(
+        r#"{"timestamp":"2024-06-01T12:00:00.000001Z","level":"INFO","fields":{"message":"Starting fedimintd"},"target":"fedimintd::fedimintd"}"#,
+        "\n",
+        "thread 'main' panicked at src/main.rs:1:1\n",
+        r#"{"timestamp":"2024-06-01T12:00:01.000001Z","level":"WARN","fields":{"message":"Peer unreachable","peer":"1"},"target":"fm::net::peer","spans":[{"name":"connect"}]}"#,
+        "\n",
+        r#"{"timestamp":"2024-06-01T12:00:02.000001Z","level":"DEBUG","fields":{"message":"Peer reconnected"},"target":"fm::net::peer"}"#,
+        "\n",
+        r#"{"timestamp":"2024-06-01T12:00:03.000001Z","level":"ERROR","fields":{"message":"Peer banned"},"target":"fm::network"}"#,
+        "\n",
+    );
+
+    assert_eq!(parse_logs(logs, &LogFilter::default(), 10).len(), 4);
+    let last_two = parse_logs(logs, &LogFilter::default(), 2);
+    assert_eq!(last_two[0].message(), Some("Peer reconnected"));
+    assert_eq!(last_two[1].level(), Some(Level::ERROR));
+
+    let warnings = parse_logs(
+        logs,
+        &LogFilter {
+            min_level: Some(Level::WARN),
+            ..LogFilter::default()
+        },
+        10,
+    );
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|entry| entry.message().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["Peer unreachable", "Peer banned"]
+    );
+    assert_eq!(warnings[0].fields["peer"], "1");
+
+    let net = LogFilter {
+        target: Some("fm::net".to_owned()),
+        message_contains: Some("Peer".to_owned()),
+        ..LogFilter::default()
+    };
+    assert_eq!(parse_logs(logs, &net, 10).len(), 2);
+}
@@ -54,5 +54,6 @@ async fn main() -> anyhow::Result<()> {
         }
     };
     debug!(target: LOG_DEVIMINT, elapsed_ms = %start_time.elapsed().as_millis(), "Finished");
+    fedimint_logging::shutdown();
     res
 }
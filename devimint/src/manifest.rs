@@ -0,0 +1,329 @@
+//! Exporting a dev federation to run without devimint.
+//!
+//! [`crate::DevFed::export_manifest`] writes a docker-compose file or a nix
+//! expression with a service per daemon, spawned with the exact program,
+//! arguments and env variables devimint used, together with the versions of
+//! the fedimint binaries and the ports, to share the setup of a failing test
+//! in a bug report. The daemons' data dirs, which hold the generated configs,
+//! are referenced under the test dir rather than copied, so that dir has to be
+//! shared along with the manifest. The programs are the host's `/nix/store`
+//! paths, so the manifest runs on a machine with the same dev shell.
+//! `devimint dev-fed --export-manifest <path>` writes one once the dev
+//! federation is ready.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+/// Image the docker-compose services run the host's nix store binaries in
+const COMPOSE_IMAGE: &str = "nixos/nix";
+
+/// Services a service with the given name needs running before it starts,
+/// matching names ending in `-` as prefixes of the names of the services
+pub(crate) fn service_dependencies(name: &str) -> &'static [&'static str] {
+    match name {
+        "bitcoind" => &[],
+        "esplora-frontend" => &["esplora"],
+        "gatewayd-cln" => &["lightningd", "fedimintd-"],
+        "gatewayd-lnd" => &["lnd", "fedimintd-"],
+        name if name.starts_with("gatewayd-") => &["bitcoind", "fedimintd-"],
+        name if name.starts_with("fedimintd-") => &["bitcoind", "esplora"],
+        _ => &["bitcoind"],
+    }
+}
+
+/// Format of [`crate::DevFed::export_manifest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ManifestFormat {
+    /// `docker-compose.yaml` with a container per daemon on the host network,
+    /// with `/nix/store` and the test dir mounted
+    DockerCompose,
+    /// Nix expression of an attribute set with the services under `services`
+    /// and everything else under `devimint`
+    Nix,
+}
+
+/// What a [`crate::DevFed`] was launched with, see [`crate::DevFed::manifest`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub federation_id: String,
+    pub invite_code: String,
+    pub test_dir: PathBuf,
+    /// Versions of the fedimint binaries, by binary
+    pub versions: BTreeMap<&'static str, String>,
+    /// Ports devimint allocated, by env variable like `FM_PORT_BTC_RPC`
+    pub ports: BTreeMap<&'static str, String>,
+    /// Daemons in the order they were launched in
+    pub services: Vec<ServiceManifest>,
+}
+
+/// A daemon of a [`Manifest`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceManifest {
+    pub name: String,
+    /// Program followed by its arguments
+    pub command: Vec<String>,
+    /// Devimint's globals the daemon inherited and the env variables it was
+    /// spawned with on top
+    pub environment: BTreeMap<String, String>,
+    pub working_dir: Option<PathBuf>,
+    /// Services that have to be running before this one starts, by name
+    pub depends_on: Vec<String>,
+}
+
+/// `docker-compose.yaml` of a [`Manifest`], written as JSON, which YAML is a
+/// superset of
+#[derive(Serialize)]
+struct ComposeFile<'a> {
+    #[serde(rename = "x-devimint")]
+    devimint: ComposeExtension<'a>,
+    services: BTreeMap<&'a str, ComposeService<'a>>,
+}
+
+/// What the dev federation was launched with, under an extension field
+/// docker-compose ignores
+#[derive(Serialize)]
+struct ComposeExtension<'a> {
+    federation_id: &'a str,
+    invite_code: &'a str,
+    versions: &'a BTreeMap<&'static str, String>,
+    ports: &'a BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+struct ComposeService<'a> {
+    image: &'static str,
+    network_mode: &'static str,
+    volumes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    working_dir: Option<String>,
+    environment: BTreeMap<&'a str, String>,
+    entrypoint: Vec<String>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    depends_on: &'a [String],
+}
+
+impl Manifest {
+    pub fn render(&self, format: ManifestFormat) -> String {
+        match format {
+            ManifestFormat::DockerCompose => self.to_docker_compose(),
+            ManifestFormat::Nix => self.to_nix(),
+        }
+    }
+
+    fn header(&self) -> String {
+        format!(
+            "# Generated by devimint for federation {}, with {}\n",
+            self.federation_id,
+            self.versions
+                .iter()
+                .map(|(binary, version)| format!("{binary} {version}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    fn to_docker_compose(&self) -> String {
+        let test_dir = self.test_dir.display().to_string();
+        let compose = ComposeFile {
+            devimint: ComposeExtension {
+                federation_id: &self.federation_id,
+                invite_code: &self.invite_code,
+                versions: &self.versions,
+                ports: &self.ports,
+            },
+            services: self
+                .services
+                .iter()
+                .map(|service| {
+                    let compose_service = ComposeService {
+                        image: COMPOSE_IMAGE,
+                        network_mode: "host",
+                        volumes: vec![
+                            "/nix/store:/nix/store:ro".to_owned(),
+                            compose_escape(&format!("{test_dir}:{test_dir}")),
+                        ],
+                        working_dir: service
+                            .working_dir
+                            .as_ref()
+                            .map(|dir| compose_escape(&dir.display().to_string())),
+                        environment: service
+                            .environment
+                            .iter()
+                            .map(|(var, value)| (var.as_str(), compose_escape(value)))
+                            .collect(),
+                        entrypoint: service
+                            .command
+                            .iter()
+                            .map(|arg| compose_escape(arg))
+                            .collect(),
+                        depends_on: &service.depends_on,
+                    };
+                    (service.name.as_str(), compose_service)
+                })
+                .collect(),
+        };
+        let mut out = self.header();
+        out.push_str(&serde_json::to_string_pretty(&compose).expect("manifests serialize"));
+        out.push('\n');
+        out
+    }
+
+    fn to_nix(&self) -> String {
+        let mut out = self.header();
+        out.push_str("{\n  devimint = {\n");
+        writeln!(
+            out,
+            "    federationId = {};",
+            nix_string(&self.federation_id)
+        )
+        .unwrap();
+        writeln!(out, "    inviteCode = {};", nix_string(&self.invite_code)).unwrap();
+        let test_dir = self.test_dir.display().to_string();
+        writeln!(out, "    testDir = {};", nix_string(&test_dir)).unwrap();
+        out.push_str("    versions = {\n");
+        for (binary, version) in &self.versions {
+            writeln!(
+                out,
+                "      {} = {};",
+                nix_string(binary),
+                nix_string(version)
+            )
+            .unwrap();
+        }
+        out.push_str("    };\n    ports = {\n");
+        for (var, port) in &self.ports {
+            writeln!(out, "      {var} = {port};").unwrap();
+        }
+        out.push_str("    };\n  };\n  services = {\n");
+        for service in &self.services {
+            writeln!(out, "    {} = {{", nix_string(&service.name)).unwrap();
+            let command = service
+                .command
+                .iter()
+                .map(|arg| nix_string(arg))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(out, "      command = [ {command} ];").unwrap();
+            out.push_str("      environment = {\n");
+            for (var, value) in &service.environment {
+                writeln!(out, "        {} = {};", nix_string(var), nix_string(value)).unwrap();
+            }
+            out.push_str("      };\n");
+            if !service.depends_on.is_empty() {
+                let depends_on = service
+                    .depends_on
+                    .iter()
+                    .map(|name| nix_string(name))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(out, "      dependsOn = [ {depends_on} ];").unwrap();
+            }
+            if let Some(working_dir) = &service.working_dir {
+                let working_dir = working_dir.display().to_string();
+                writeln!(
+                    out,
+                    "      workingDirectory = {};",
+                    nix_string(&working_dir)
+                )
+                .unwrap();
+            }
+            out.push_str("    };\n");
+        }
+        out.push_str("  };\n}\n");
+        out
+    }
+}
+
+/// `s` with the `$` docker-compose would interpolate variables at escaped
+fn compose_escape(s: &str) -> String {
+    s.replace('$', "$$")
+}
+
+/// `s` as a double-quoted nix string, escaping interpolation too
+fn nix_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn test_render_manifest() {
+    let service = |name: &str, command: &str, depends_on: &[&str]| ServiceManifest {
+        name: name.to_owned(),
+        command: vec![command.to_owned(), "-regtest".to_owned()],
+        environment: BTreeMap::from([("FM_LABEL".to_owned(), "say \"${hi}\"".to_owned())]),
+        working_dir: None,
+        depends_on: depends_on.iter().map(|name| (*name).to_owned()).collect(),
+    };
+    let manifest = Manifest {
+        federation_id: "fed1d".to_owned(),
+        invite_code: "fed11qgq".to_owned(),
+        test_dir: PathBuf::from("/tmp/devimint"),
+        versions: BTreeMap::from([("fedimintd", "0.5.0-alpha".to_owned())]),
+        ports: BTreeMap::from([("FM_PORT_BTC_RPC", "10000".to_owned())]),
+        services: vec![
+            service("bitcoind", "/nix/store/x-bitcoind/bin/bitcoind", &[]),
+            service("lnd", "/nix/store/x-lnd/bin/lnd", &["bitcoind"]),
+        ],
+    };
+
+    let compose = manifest.render(ManifestFormat::DockerCompose);
+    let (header, body) = compose.split_once('\n').unwrap();
+    assert_eq!(
+        header,
+        "# Generated by devimint for federation fed1d, with fedimintd 0.5.0-alpha"
+    );
+    let compose: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(compose["x-devimint"]["ports"]["FM_PORT_BTC_RPC"], "10000");
+    let bitcoind = &compose["services"]["bitcoind"];
+    assert_eq!(
+        *bitcoind,
+        serde_json::json!({
+            "image": "nixos/nix",
+            "network_mode": "host",
+            "volumes": ["/nix/store:/nix/store:ro", "/tmp/devimint:/tmp/devimint"],
+            "environment": {"FM_LABEL": "say \"$${hi}\""},
+            "entrypoint": ["/nix/store/x-bitcoind/bin/bitcoind", "-regtest"],
+        })
+    );
+    assert_eq!(
+        compose["services"]["lnd"]["depends_on"],
+        serde_json::json!(["bitcoind"])
+    );
+
+    let nix = manifest.render(ManifestFormat::Nix);
+    assert!(nix.contains("      FM_PORT_BTC_RPC = 10000;\n"));
+    assert!(nix.contains(
+        "    \"bitcoind\" = {\n      command = [ \"/nix/store/x-bitcoind/bin/bitcoind\" \"-regtest\" ];\n"
+    ));
+    assert!(nix.contains("        \"FM_LABEL\" = \"say \\\"\\${hi}\\\"\";\n"));
+    assert!(nix.contains("      dependsOn = [ \"bitcoind\" ];\n"));
+    assert!(nix.ends_with("    };\n  };\n}\n"));
+}
+
+#[test]
+fn test_service_dependencies() {
+    assert!(service_dependencies("bitcoind").is_empty());
+    assert_eq!(service_dependencies("esplora-frontend"), ["esplora"]);
+    assert_eq!(service_dependencies("gatewayd-lnd"), ["lnd", "fedimintd-"]);
+    assert_eq!(
+        service_dependencies("fedimintd-default-0"),
+        ["bitcoind", "esplora"]
+    );
+}
@@ -0,0 +1,63 @@
+//! Catching daemons that leak memory.
+//!
+//! [`crate::DevFed::memory_baseline`] samples the resident set size of every
+//! daemon before a workload, and [`crate::DevFed::assert_memory_growth_under`]
+//! checks a daemon didn't grow by more than expected running it, e.g. a
+//! guardian over many rounds of a soak test. Reads `/proc`, so only works on
+//! Linux.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use tokio::time::Instant;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Resident set sizes of the daemons of a [`crate::DevFed`] at some point
+#[derive(Debug, Clone)]
+pub struct MemoryBaseline {
+    /// Pid and resident set size in bytes of each running daemon, by name
+    pub daemons: BTreeMap<String, (u32, u64)>,
+    pub taken_at: Instant,
+}
+
+impl MemoryBaseline {
+    /// Resident set size in bytes of daemon `name` when the baseline was
+    /// taken
+    pub fn rss_bytes(&self, name: &str) -> Option<u64> {
+        self.daemons.get(name).map(|(_, rss)| *rss)
+    }
+}
+
+/// Resident set size of process `pid` in bytes
+pub fn rss_bytes(pid: u32) -> Result<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{pid}/status"))
+        .with_context(|| format!("reading memory usage of process {pid}"))?;
+    parse_vm_rss(&status).with_context(|| format!("process {pid} reports no VmRSS"))
+}
+
+/// `VmRSS` of a `/proc/<pid>/status` in bytes
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+pub(crate) fn format_mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / MIB as f64)
+}
+
+#[test]
+fn test_parse_vm_rss() {
+    let status =
+        "Name:\tfedimintd\nVmPeak:\t  204800 kB\nVmRSS:\t   51200 kB\nRssAnon:\t   40000 kB\n";
+    assert_eq!(parse_vm_rss(status), Some(50 * MIB));
+    assert_eq!(parse_vm_rss("Name:\tkthreadd\n"), None);
+    assert_eq!(format_mib(50 * MIB + MIB / 2), "50.5 MiB");
+}
@@ -0,0 +1,211 @@
+//! Running guardians in their own linux network namespaces.
+//!
+//! Each guardian gets a namespace connected to the host through a veth pair
+//! on its own /30 subnet. Guardians reach each other through the host, which
+//! forwards between the subnets, so taking a guardian's host side link down
+//! cuts it off from the rest of the federation until it is brought back up.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use fedimint_core::envs::is_env_var_set;
+use fedimint_core::util::SafeUrl;
+use fedimint_core::PeerId;
+use fedimint_logging::LOG_DEVIMINT;
+use fedimint_server::config::ConfigGenParams;
+use tracing::{debug, warn};
+
+use crate::envs::FM_GUARDIAN_NETNS_ENV;
+use crate::util::{cmd, Command, ToCmdExt};
+
+/// Whether the user asked for guardians to run in network namespaces
+pub fn requested() -> bool {
+    is_env_var_set(FM_GUARDIAN_NETNS_ENV)
+}
+
+/// Whether network namespaces can be created here, which requires linux,
+/// root and the `ip` tool
+pub fn available() -> bool {
+    cfg!(target_os = "linux")
+        && nix::unistd::geteuid().is_root()
+        && std::process::Command::new("ip")
+            .args(["netns", "list"])
+            .output()
+            .is_ok_and(|out| out.status.success())
+}
+
+/// Whether guardians will run in network namespaces, warning if they were
+/// requested but can't be used
+pub fn enabled() -> bool {
+    if !requested() {
+        return false;
+    }
+    if !available() {
+        warn!(
+            target: LOG_DEVIMINT,
+            "{FM_GUARDIAN_NETNS_ENV} is set but network namespaces are unavailable (requires linux and root), guardians will share the host network"
+        );
+        return false;
+    }
+    true
+}
+
+/// A network namespace a single guardian runs in
+#[derive(Debug)]
+pub struct GuardianNetns {
+    pub name: String,
+    /// Host side of the veth pair
+    pub host_veth: String,
+    /// Address of the guardian inside its namespace
+    pub addr: Ipv4Addr,
+    /// Address of the host on the guardian's subnet
+    pub host_addr: Ipv4Addr,
+}
+
+impl GuardianNetns {
+    /// Creates the namespace of `peer_id`.
+    ///
+    /// `base_port` is the federation's unique port allocation and is used to
+    /// keep names and subnets of concurrently running federations apart.
+    pub async fn create(base_port: u16, peer_id: usize) -> Result<Self> {
+        let peer = u8::try_from(peer_id)
+            .ok()
+            .filter(|peer| *peer < 64)
+            .context("network namespaces support at most 64 guardians")?;
+        let [hi, lo] = base_port.to_be_bytes();
+        let netns = Self {
+            name: format!("fm-{base_port}-{peer}"),
+            host_veth: format!("fmh{base_port}p{peer}"),
+            addr: Ipv4Addr::new(10, hi, lo, peer * 4 + 2),
+            host_addr: Ipv4Addr::new(10, hi, lo, peer * 4 + 1),
+        };
+        let ns_veth = format!("fmn{base_port}p{peer}");
+        let name = &netns.name;
+        let host_veth = &netns.host_veth;
+        debug!(target: LOG_DEVIMINT, %name, addr = %netns.addr, "Creating guardian network namespace");
+
+        cmd!("ip", "netns", "add", name).run().await?;
+        cmd!("ip", "link", "add", host_veth, "type", "veth", "peer", "name", ns_veth)
+            .run()
+            .await?;
+        cmd!("ip", "link", "set", ns_veth, "netns", name)
+            .run()
+            .await?;
+        cmd!(
+            "ip",
+            "addr",
+            "add",
+            format!("{}/30", netns.host_addr),
+            "dev",
+            host_veth
+        )
+        .run()
+        .await?;
+        cmd!("ip", "link", "set", host_veth, "up").run().await?;
+        netns
+            .exec(cmd!(
+                "ip",
+                "addr",
+                "add",
+                format!("{}/30", netns.addr),
+                "dev",
+                ns_veth
+            ))
+            .run()
+            .await?;
+        netns
+            .exec(cmd!("ip", "link", "set", ns_veth, "up"))
+            .run()
+            .await?;
+        netns
+            .exec(cmd!("ip", "link", "set", "lo", "up"))
+            .run()
+            .await?;
+        netns
+            .exec(cmd!(
+                "ip",
+                "route",
+                "add",
+                "default",
+                "via",
+                netns.host_addr
+            ))
+            .run()
+            .await?;
+        // guardians talk to each other through the host
+        cmd!("sysctl", "-q", "-w", "net.ipv4.ip_forward=1")
+            .run()
+            .await?;
+
+        Ok(netns)
+    }
+
+    /// Wraps `command` so it runs inside the namespace.
+    ///
+    /// Must be called before setting any env variables on `command`, they are
+    /// not carried over.
+    pub fn exec(&self, command: Command) -> Command {
+        ["ip", "netns", "exec", &self.name]
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .chain(command.args_debug)
+            .collect::<Vec<_>>()
+            .cmd()
+    }
+
+    /// Cuts all traffic between the guardian and everything outside its
+    /// namespace
+    pub async fn cut(&self) -> Result<()> {
+        cmd!("ip", "link", "set", self.host_veth, "down")
+            .run()
+            .await
+    }
+
+    /// Restores traffic cut by [`Self::cut`]
+    pub async fn restore(&self) -> Result<()> {
+        cmd!("ip", "link", "set", self.host_veth, "up").run().await
+    }
+}
+
+impl Drop for GuardianNetns {
+    fn drop(&mut self) {
+        // deleting the namespace also deletes the veth pair
+        if let Err(err) = std::process::Command::new("ip")
+            .args(["netns", "del", &self.name])
+            .output()
+        {
+            warn!(target: LOG_DEVIMINT, name = %self.name, %err, "Failed to delete guardian network namespace");
+        }
+    }
+}
+
+/// Makes every guardian that has a namespace in `netns` listen on, and be
+/// reached at, its namespace address instead of 127.0.0.1
+pub fn rewrite_config_gen_params(
+    params: &mut HashMap<PeerId, ConfigGenParams>,
+    netns: &BTreeMap<usize, Arc<GuardianNetns>>,
+) -> Result<()> {
+    for peer_params in params.values_mut() {
+        if let Some(ns) = netns.get(&peer_params.local.our_id.to_usize()) {
+            peer_params.local.p2p_bind.set_ip(ns.addr.into());
+            peer_params.local.api_bind.set_ip(ns.addr.into());
+        }
+        for (peer, peer_server_params) in &mut peer_params.consensus.peers {
+            if let Some(ns) = netns.get(&peer.to_usize()) {
+                peer_server_params.p2p_url = with_host(&peer_server_params.p2p_url, ns.addr)?;
+                peer_server_params.api_url = with_host(&peer_server_params.api_url, ns.addr)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn with_host(url: &SafeUrl, addr: Ipv4Addr) -> Result<SafeUrl> {
+    let port = url.port().context("guardian url without port")?;
+    Ok(SafeUrl::parse(&format!(
+        "{}://{addr}:{port}",
+        url.scheme()
+    ))?)
+}
@@ -0,0 +1,96 @@
+//! Lightning payments of devimint's clients, through the `fedimint-cli`
+//! commands of every version it supports
+
+use anyhow::anyhow;
+use fedimint_core::Amount;
+use fedimint_ln_client::cli::LnInvoiceResponse;
+
+use crate::cmd;
+use crate::federation::Client;
+use crate::version_constants::VERSION_0_3_0_ALPHA;
+
+/// Pays `invoice` from `client` through the gateway `gw_id` with the
+/// `ln-pay` of whichever `fedimint-cli` version it runs, returning the
+/// operation id
+pub async fn ln_pay(
+    client: &Client,
+    invoice: String,
+    gw_id: String,
+    finish_in_background: bool,
+) -> anyhow::Result<String> {
+    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+
+    // TODO(support:v0.2): 0.3 removed the active gateway concept and requires a
+    // `gateway-id` parameter for lightning sends
+    let value = if fedimint_cli_version < *VERSION_0_3_0_ALPHA {
+        if finish_in_background {
+            cmd!(client, "ln-pay", invoice, "--finish-in-background",)
+                .out_json()
+                .await?
+        } else {
+            cmd!(client, "ln-pay", invoice,).out_json().await?
+        }
+    } else if finish_in_background {
+        cmd!(
+            client,
+            "ln-pay",
+            invoice,
+            "--finish-in-background",
+            "--gateway-id",
+            gw_id,
+        )
+        .out_json()
+        .await?
+    } else {
+        cmd!(client, "ln-pay", invoice, "--gateway-id", gw_id,)
+            .out_json()
+            .await?
+    };
+
+    let operation_id = value["operation_id"]
+        .as_str()
+        .ok_or(anyhow!("Failed to pay invoice"))?
+        .to_string();
+    Ok(operation_id)
+}
+
+/// Creates an invoice of `amount` for `client` to receive through the
+/// gateway `gw_id` with the `ln-invoice` of whichever `fedimint-cli` version
+/// it runs
+pub async fn ln_invoice(
+    client: &Client,
+    amount: Amount,
+    description: String,
+    gw_id: String,
+) -> anyhow::Result<LnInvoiceResponse> {
+    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+    // TODO(support:v0.2): 0.3 removed the active gateway concept and requires a
+    // `gateway-id` parameter for lightning receives
+    let ln_response_val = if fedimint_cli_version < *VERSION_0_3_0_ALPHA {
+        cmd!(
+            client,
+            "ln-invoice",
+            "--amount",
+            amount.msats,
+            format!("--description='{description}'"),
+        )
+        .out_json()
+        .await?
+    } else {
+        cmd!(
+            client,
+            "ln-invoice",
+            "--amount",
+            amount.msats,
+            format!("--description='{description}'"),
+            "--gateway-id",
+            gw_id,
+        )
+        .out_json()
+        .await?
+    };
+
+    let ln_invoice_response: LnInvoiceResponse = serde_json::from_value(ln_response_val)?;
+
+    Ok(ln_invoice_response)
+}
@@ -0,0 +1,136 @@
+//! Profiling running guardians.
+//!
+//! Samples are recorded with `perf` attached to the guardian's pid and
+//! rendered into a flamegraph SVG with `inferno`, so neither the guardian
+//! binary nor its launch has to change.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+
+use crate::util::cmd;
+
+/// Sampling frequency in Hz, off from round numbers so sampling doesn't run
+/// in lockstep with periodic work
+const SAMPLE_FREQUENCY: u32 = 997;
+
+/// Whether `perf` and the `inferno` flamegraph tools are installed
+pub fn available() -> bool {
+    ["perf", "inferno-collapse-perf", "inferno-flamegraph"]
+        .into_iter()
+        .all(|tool| {
+            std::process::Command::new(tool)
+                .arg("--version")
+                .output()
+                .is_ok_and(|out| out.status.success())
+        })
+}
+
+/// Length `/proc/<pid>/comm` truncates command names to
+const MAX_COMM_LEN: usize = 15;
+
+/// `pid` itself or its first descendant, breadth first, running the program
+/// `name`. Guardians may be spawned through wrappers like `faketime` that fork
+/// the guardian instead of executing it in their place.
+pub fn find_program_pid(pid: u32, name: &str) -> Result<u32> {
+    let name = &name.as_bytes()[..name.len().min(MAX_COMM_LEN)];
+    let mut children = BTreeMap::<u32, Vec<u32>>::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let Some(child) = entry?.file_name().to_str().and_then(|pid| pid.parse().ok()) else {
+            continue;
+        };
+        if let Some(parent) = parent_pid(child) {
+            children.entry(parent).or_default().push(child);
+        }
+    }
+
+    let mut queue = VecDeque::from([pid]);
+    while let Some(candidate) = queue.pop_front() {
+        let comm = std::fs::read(format!("/proc/{candidate}/comm")).unwrap_or_default();
+        if comm.strip_suffix(b"\n").unwrap_or(&comm[..]) == name {
+            return Ok(candidate);
+        }
+        queue.extend(children.remove(&candidate).unwrap_or_default());
+    }
+    anyhow::bail!(
+        "No process of {} runs under pid {pid}",
+        String::from_utf8_lossy(name)
+    )
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the parenthesized command name may itself contain parentheses, the
+    // state and then the parent pid follow it
+    stat.rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+/// Samples `pid` for `duration` and writes a flamegraph of it to `svg`, next
+/// to the raw `perf` samples
+pub async fn record_flamegraph(pid: u32, duration: Duration, svg: &Path) -> Result<()> {
+    let perf_data = svg.with_extension("perf.data");
+    cmd!(
+        "perf",
+        "record",
+        "-F",
+        SAMPLE_FREQUENCY,
+        "-g",
+        "-p",
+        pid,
+        "-o",
+        perf_data.display(),
+        "--",
+        "sleep",
+        duration.as_secs_f64()
+    )
+    .run()
+    .await?;
+    // perf script output of a busy guardian gets large, so it's streamed
+    // through the pipeline instead of collected
+    cmd!(
+        "sh",
+        "-c",
+        "perf script -i \"$1\" | inferno-collapse-perf | inferno-flamegraph > \"$2\"",
+        "sh",
+        perf_data.display(),
+        svg.display()
+    )
+    .run()
+    .await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_program_pid_behind_forking_wrapper() -> Result<()> {
+    use tokio::io::AsyncBufReadExt as _;
+
+    // forks the program and waits for it, like `faketime` does
+    let mut wrapper = tokio::process::Command::new("sh")
+        .args(["-c", "sleep 60 & echo $!; wait"])
+        .stdout(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let wrapper_pid = wrapper.id().context("wrapper exited")?;
+    let mut program_pid = String::new();
+    tokio::io::BufReader::new(wrapper.stdout.take().context("stdout is piped")?)
+        .read_line(&mut program_pid)
+        .await?;
+    let program_pid: u32 = program_pid.trim().parse()?;
+
+    let found = find_program_pid(wrapper_pid, "sleep");
+    let _ = nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(i32::try_from(program_pid)?),
+        nix::sys::signal::Signal::SIGKILL,
+    );
+    assert_eq!(found?, program_pid);
+    assert_eq!(find_program_pid(wrapper_pid, "sh")?, wrapper_pid);
+    assert!(find_program_pid(wrapper_pid, "fedimintd").is_err());
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Forwarding TCP connections to a daemon.
+//!
+//! A [`TcpProxy`] sits in front of a daemon and passes each direction of
+//! every connection through a [`Transform`], which the misbehaving proxies
+//! like [`crate::throttle::ThrottledProxy`] and
+//! [`crate::byzantine::CorruptingProxy`] implement to slow down or rewrite
+//! what flows through.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use fedimint_logging::LOG_DEVIMINT;
+use futures::future::BoxFuture;
+use tokio::io::{AsyncBufRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, warn};
+
+/// What a [`TcpProxy`] does with the bytes flowing in one direction of a
+/// connection
+pub trait Transform: Send + Sync + 'static {
+    /// Forwards `reader` to `writer` until `reader` is exhausted, changing
+    /// what passes through as it likes
+    fn forward<'a>(
+        &'a self,
+        reader: &'a mut (dyn AsyncBufRead + Unpin + Send),
+        writer: &'a mut (dyn AsyncWrite + Unpin + Send),
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Forwards the bytes untouched
+pub struct Passthrough;
+
+impl Transform for Passthrough {
+    fn forward<'a>(
+        &'a self,
+        reader: &'a mut (dyn AsyncBufRead + Unpin + Send),
+        writer: &'a mut (dyn AsyncWrite + Unpin + Send),
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            tokio::io::copy_buf(reader, writer).await?;
+            Ok(())
+        })
+    }
+}
+
+/// TCP proxy on `addr` to `target`, passing what clients send through one
+/// [`Transform`] and what `target` sends back through another.
+///
+/// Stops accepting and drops all connections once the last clone is dropped.
+#[derive(Clone)]
+pub struct TcpProxy {
+    addr: SocketAddr,
+    target: SocketAddr,
+    _accept: Arc<AbortOnDrop>,
+}
+
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+impl TcpProxy {
+    /// Starts proxying from `addr`, port 0 picking a free one, to `target`,
+    /// with `upstream` transforming what clients send and `downstream` what
+    /// `target` sends back. `name` tells proxies apart in the logs.
+    pub async fn start(
+        name: &'static str,
+        addr: SocketAddr,
+        target: SocketAddr,
+        upstream: impl Transform,
+        downstream: impl Transform,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("binding {name} to {addr}"))?;
+        let addr = listener.local_addr()?;
+        let upstream: Arc<dyn Transform> = Arc::new(upstream);
+        let downstream: Arc<dyn Transform> = Arc::new(downstream);
+        let accept = fedimint_core::runtime::spawn(&format!("{name} {addr}"), async move {
+            // dropped with the accept task, which aborts all connections
+            let mut connections = JoinSet::new();
+            loop {
+                let inbound = match listener.accept().await {
+                    Ok((inbound, _)) => inbound,
+                    Err(err) => {
+                        warn!(target: LOG_DEVIMINT, proxy = name, %addr, %err, "Proxy failed to accept");
+                        fedimint_core::runtime::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let upstream = upstream.clone();
+                let downstream = downstream.clone();
+                connections.spawn(async move {
+                    if let Err(err) =
+                        proxy_connection(inbound, target, &*upstream, &*downstream).await
+                    {
+                        debug!(target: LOG_DEVIMINT, proxy = name, %addr, %target, %err, "Proxy connection closed");
+                    }
+                });
+                // reap finished connections
+                while connections.try_join_next().is_some() {}
+            }
+        });
+        debug!(target: LOG_DEVIMINT, proxy = name, %addr, %target, "Proxy started");
+        Ok(Self {
+            addr,
+            target,
+            _accept: Arc::new(AbortOnDrop(accept)),
+        })
+    }
+
+    /// Address clients connect to instead of the target
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.target
+    }
+}
+
+async fn proxy_connection(
+    mut inbound: TcpStream,
+    target: SocketAddr,
+    upstream: &dyn Transform,
+    downstream: &dyn Transform,
+) -> Result<()> {
+    let mut outbound = TcpStream::connect(target)
+        .await
+        .with_context(|| format!("connecting to {target}"))?;
+    let (inbound_read, mut inbound_write) = inbound.split();
+    let (outbound_read, mut outbound_write) = outbound.split();
+    tokio::try_join!(
+        async {
+            upstream
+                .forward(&mut BufReader::new(inbound_read), &mut outbound_write)
+                .await?;
+            outbound_write.shutdown().await?;
+            anyhow::Ok(())
+        },
+        async {
+            downstream
+                .forward(&mut BufReader::new(outbound_read), &mut inbound_write)
+                .await?;
+            inbound_write.shutdown().await?;
+            anyhow::Ok(())
+        },
+    )?;
+    Ok(())
+}
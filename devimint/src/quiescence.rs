@@ -0,0 +1,107 @@
+//! Waiting for a dev federation to settle before asserting on it.
+//!
+//! Assertions at the end of a test are flaky while something is still in
+//! flight, like a transaction waiting to be mined, an indexer behind the tip
+//! or a client claiming change. [`crate::DevFed::await_quiescent`] waits for
+//! all of it at once, instead of each test picking the waits it remembers.
+//! [`crate::DevFed::assert_synced_height`] is the same for the chain only,
+//! checking everything ended up at the height a test expects after mining.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+/// How long each part of a dev federation took to settle in
+/// [`crate::DevFed::await_quiescent`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuiescenceReport {
+    /// Time until each subsystem settled in the last round, by name
+    pub settled_after: BTreeMap<&'static str, Duration>,
+    /// Rounds needed until no new transaction showed up while settling
+    pub rounds: usize,
+    pub total: Duration,
+}
+
+impl QuiescenceReport {
+    /// The subsystem that took longest to settle
+    pub fn slowest(&self) -> Option<(&'static str, Duration)> {
+        self.settled_after
+            .iter()
+            .max_by_key(|(_, duration)| **duration)
+            .map(|(name, duration)| (*name, *duration))
+    }
+}
+
+/// Awaits `f`, returning `name` and how long it took
+pub(crate) async fn timed(
+    name: &'static str,
+    f: impl Future<Output = Result<()>>,
+) -> Result<(&'static str, Duration)> {
+    let start = Instant::now();
+    f.await.with_context(|| format!("{name} didn't settle"))?;
+    Ok((name, start.elapsed()))
+}
+
+/// Height of the block electrs indexed last, over the electrum protocol
+pub(crate) async fn electrs_tip_height(port: u16) -> Result<u64> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))
+        .await
+        .context("connecting to electrs")?;
+    stream
+        .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":0,\"method\":\"blockchain.headers.subscribe\",\"params\":[]}\n")
+        .await?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    let response: serde_json::Value = serde_json::from_str(&line)?;
+    response["result"]["height"]
+        .as_u64()
+        .with_context(|| format!("unexpected electrs response {line}"))
+}
+
+/// Components not at the height expected of them, like `cln at 110, expected
+/// 111`, from their height and the expected one by name
+pub(crate) fn height_mismatches(heights: &BTreeMap<&'static str, (u64, u64)>) -> Option<String> {
+    let mismatches = heights
+        .iter()
+        .filter(|(_, (height, expected))| height != expected)
+        .map(|(name, (height, expected))| format!("{name} at {height}, expected {expected}"))
+        .collect::<Vec<_>>();
+    (!mismatches.is_empty()).then(|| mismatches.join("; "))
+}
+
+#[test]
+fn test_height_mismatches() {
+    let heights = BTreeMap::from([
+        ("cln", (110, 111)),
+        ("electrs", (111, 111)),
+        ("federation block count", (102, 102)),
+        ("lnd", (109, 111)),
+    ]);
+    assert_eq!(
+        height_mismatches(&heights).as_deref(),
+        Some("cln at 110, expected 111; lnd at 109, expected 111")
+    );
+    assert_eq!(
+        height_mismatches(&BTreeMap::from([("esplora", (111, 111))])),
+        None
+    );
+}
+
+#[test]
+fn test_quiescence_report_slowest() {
+    let report = QuiescenceReport {
+        settled_after: BTreeMap::from([
+            ("clients", Duration::from_secs(3)),
+            ("electrs", Duration::from_secs(1)),
+            ("mempool", Duration::ZERO),
+        ]),
+        rounds: 1,
+        total: Duration::from_secs(3),
+    };
+    assert_eq!(report.slowest(), Some(("clients", Duration::from_secs(3))));
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use fedimint_logging::LOG_DEVIMINT;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::devfed::DevFed;
+use crate::federation::Client;
+use crate::util::ProcessManager;
+
+/// A high level operation performed against a [`DevFed`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Op {
+    MineBlocks {
+        blocks: u64,
+    },
+    JoinClient {
+        client: String,
+    },
+    PeginClient {
+        client: String,
+        amount_sats: u64,
+    },
+    /// Pay an LND invoice from `client` through the CLN gateway
+    PayLndInvoice {
+        client: String,
+        amount_msat: u64,
+    },
+    TerminateServer {
+        peer_id: usize,
+    },
+    StartServer {
+        peer_id: usize,
+    },
+}
+
+/// A recorded sequence of [`Op`]s, stored as JSON
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Script {
+    pub ops: Vec<Op>,
+}
+
+impl Script {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let json = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("reading script {}", path.display()))?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        tokio::fs::write(path, serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+/// Runs [`Op`]s against a [`DevFed`] through its typed helpers, recording
+/// them into a [`Script`] that [`DevFed::replay`] can re-execute later.
+#[derive(Default)]
+pub struct Recorder {
+    script: Script,
+    clients: BTreeMap<String, Client>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Executes `op` and appends it to the script if it succeeded
+    pub async fn run(
+        &mut self,
+        dev_fed: &mut DevFed,
+        process_mgr: &ProcessManager,
+        op: Op,
+    ) -> Result<()> {
+        info!(target: LOG_DEVIMINT, ?op, "Running op");
+        match &op {
+            Op::MineBlocks { blocks } => dev_fed.bitcoind.mine_blocks(*blocks).await?,
+            Op::JoinClient { client } => {
+                let joined = dev_fed.fed.new_joined_client(client).await?;
+                self.clients.insert(client.clone(), joined);
+            }
+            Op::PeginClient {
+                client,
+                amount_sats,
+            } => {
+                dev_fed
+                    .fed
+                    .pegin_client(*amount_sats, self.client(client)?)
+                    .await?;
+            }
+            Op::PayLndInvoice {
+                client,
+                amount_msat,
+            } => {
+                let (invoice, _) = dev_fed.lnd.invoice(*amount_msat).await?;
+                let gateway_id = dev_fed.gw_cln.gateway_id().await?;
+                self.client(client)?.ln_pay(invoice, gateway_id).await?;
+            }
+            Op::TerminateServer { peer_id } => dev_fed.fed.terminate_server(*peer_id).await?,
+            Op::StartServer { peer_id } => dev_fed.fed.start_server(process_mgr, *peer_id).await?,
+        }
+        self.script.ops.push(op);
+        Ok(())
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    fn client(&self, name: &str) -> Result<&Client> {
+        self.clients
+            .get(name)
+            .with_context(|| format!("client {name} was not joined by an earlier op"))
+    }
+}
+
+#[test]
+fn test_script_round_trip() -> Result<()> {
+    let script = Script {
+        ops: vec![
+            Op::MineBlocks { blocks: 10 },
+            Op::JoinClient {
+                client: "replay".to_owned(),
+            },
+            Op::PeginClient {
+                client: "replay".to_owned(),
+                amount_sats: 10_000,
+            },
+            Op::PayLndInvoice {
+                client: "replay".to_owned(),
+                amount_msat: 1_000_000,
+            },
+            Op::TerminateServer { peer_id: 3 },
+            Op::StartServer { peer_id: 3 },
+        ],
+    };
+
+    let json = serde_json::to_value(&script)?;
+    assert_eq!(
+        json["ops"][0],
+        serde_json::json!({"op": "mine_blocks", "blocks": 10})
+    );
+    assert_eq!(script, serde_json::from_value(json)?);
+
+    Ok(())
+}
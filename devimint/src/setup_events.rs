@@ -0,0 +1,69 @@
+//! Progress of the dev federation setup, for harnesses driving devimint.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write as _;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use fedimint_logging::LOG_DEVIMINT;
+use serde_json::json;
+use tracing::warn;
+
+/// Gets told as the pieces of a dev federation become ready, see
+/// [`crate::util::ProcessManager::with_setup_observer`]
+pub trait SetupObserver: Send + Sync {
+    /// `name` is set up, `elapsed` after the setup started
+    fn component_ready(&self, name: &str, elapsed: Duration);
+
+    /// Every component is set up
+    fn setup_complete(&self, elapsed: Duration);
+}
+
+/// Writes each event as a line of JSON to a file of its own as soon as it
+/// happens, e.g. `{"event":"component_ready","name":"lnd","elapsed_ms":1234}`.
+///
+/// Stdout is left to the user command, use `/dev/fd/3` to get the events on a
+/// dedicated fd instead of a file.
+pub struct JsonLinesObserver {
+    out: Mutex<File>,
+}
+
+impl JsonLinesObserver {
+    /// Appends the events to `path`, creating it if needed
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let out = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("opening setup events file {}", path.display()))?;
+        Ok(Self {
+            out: Mutex::new(out),
+        })
+    }
+
+    fn emit(&self, event: &serde_json::Value) {
+        let mut out = self.out.lock().expect("locking can't fail");
+        if let Err(err) = writeln!(out, "{event}").and_then(|()| out.flush()) {
+            warn!(target: LOG_DEVIMINT, %err, "Failed to write setup event");
+        }
+    }
+}
+
+impl SetupObserver for JsonLinesObserver {
+    fn component_ready(&self, name: &str, elapsed: Duration) {
+        self.emit(&json!({
+            "event": "component_ready",
+            "name": name,
+            "elapsed_ms": elapsed.as_millis() as u64,
+        }));
+    }
+
+    fn setup_complete(&self, elapsed: Duration) {
+        self.emit(&json!({
+            "event": "setup_complete",
+            "elapsed_ms": elapsed.as_millis() as u64,
+        }));
+    }
+}
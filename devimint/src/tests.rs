@@ -9,70 +9,49 @@
 use bitcoincore_rpc::bitcoin;
 use bitcoincore_rpc::bitcoin::Txid;
 use clap::Subcommand;
+use cln_rpc::primitives::ChannelState;
 use fedimint_core::core::LEGACY_HARDCODED_INSTANCE_ID_WALLET;
 use fedimint_core::encoding::Decodable;
 use fedimint_core::envs::is_env_var_set;
 use fedimint_core::module::registry::ModuleRegistry;
 use fedimint_core::net::api_announcement::SignedApiAnnouncement;
-use fedimint_core::task::block_in_place;
-use fedimint_core::{Amount, PeerId};
-use fedimint_ln_client::cli::LnInvoiceResponse;
+use fedimint_core::{Amount, NumPeers, PeerId};
+use fedimint_ln_client::envs::FM_LNURL_TLS_ROOT_CERT_ENV;
 use fedimint_logging::LOG_DEVIMINT;
 use hex::ToHex;
-use ln_gateway::rpc::GatewayInfo;
+use ln_gateway::rpc::{GatewayInfo, GatewayMode};
 use serde_json::json;
 use tokio::net::TcpStream;
 use tokio::{fs, try_join};
 use tracing::{debug, info};
 
+use crate::byzantine::CorruptMode;
+use crate::cgroup::ResourceLimits;
 use crate::cli::{cleanup_on_exit, exec_user_command, setup, write_ready_file, CommonArgs};
-use crate::envs::{FM_DATA_DIR_ENV, FM_DEVIMINT_RUN_DEPRECATED_TESTS_ENV, FM_PASSWORD_ENV};
-use crate::federation::{Client, Federation};
-use crate::util::{poll, LoadTestTool, ProcessManager};
-use crate::version_constants::{VERSION_0_3_0, VERSION_0_3_0_ALPHA, VERSION_0_4_0_ALPHA};
+use crate::config_diff::diff_exports;
+use crate::devfed::DevFedHandle;
+use crate::envs::{
+    FM_DATA_DIR_ENV, FM_DEVIMINT_RUN_DEPRECATED_TESTS_ENV, FM_FEDIMINTD_BASE_EXECUTABLE_ENV,
+    FM_GUARDIAN_DISK_MB_ENV, FM_MINT_FEES_ENV, FM_PASSWORD_ENV,
+};
+use crate::external::{
+    set_channel_policy, ChannelPolicy, Esplora, EsploraOptions, HtlcDirection, PaymentStatus,
+};
+use crate::federation::{Client, Federation, OutgoingContractState};
+use crate::gatewayd::LiquidityDirection;
+use crate::lnurl::LnurlServer;
+use crate::logs::LogFilter;
+use crate::payments::{ln_invoice, ln_pay};
+use crate::throttle::ThrottledProxy;
+use crate::util::{
+    block_in_place, poll, poll_with_timeout, stats_for, FedimintdCmd, KillSignal, LoadTestTool,
+    ProcessManager,
+};
+use crate::version_constants::{
+    VERSION_0_3_0, VERSION_0_3_0_ALPHA, VERSION_0_4_0_ALPHA, VERSION_0_5_0_ALPHA,
+};
 use crate::{cmd, dev_fed, poll_eq, DevFed, Gatewayd, LightningNode, Lightningd, Lnd};
 
-pub struct Stats {
-    pub min: Duration,
-    pub avg: Duration,
-    pub median: Duration,
-    pub p90: Duration,
-    pub max: Duration,
-    pub sum: Duration,
-}
-
-impl std::fmt::Display for Stats {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "min: {:.1}s", self.min.as_secs_f32())?;
-        write!(f, ", avg: {:.1}s", self.avg.as_secs_f32())?;
-        write!(f, ", median: {:.1}s", self.median.as_secs_f32())?;
-        write!(f, ", p90: {:.1}s", self.p90.as_secs_f32())?;
-        write!(f, ", max: {:.1}s", self.max.as_secs_f32())?;
-        write!(f, ", sum: {:.1}s", self.sum.as_secs_f32())?;
-        Ok(())
-    }
-}
-
-pub fn stats_for(mut v: Vec<Duration>) -> Stats {
-    assert!(!v.is_empty());
-    v.sort();
-    let n = v.len();
-    let min = v.first().unwrap().to_owned();
-    let max = v.iter().last().unwrap().to_owned();
-    let median = v[n / 2];
-    let sum: Duration = v.iter().sum();
-    let avg = sum / n as u32;
-    let p90 = v[(n as f32 * 0.9) as usize];
-    Stats {
-        min,
-        avg,
-        median,
-        p90,
-        max,
-        sum,
-    }
-}
-
 pub async fn log_binary_versions() -> Result<()> {
     let fedimint_cli_version = cmd!(crate::util::get_fedimint_cli_path(), "--version")
         .out_string()
@@ -456,9 +435,17 @@ pub async fn upgrade_tests(process_mgr: &ProcessManager, binary: UpgradeTest) ->
             let mut dev_fed = dev_fed(process_mgr).await?;
             let client = dev_fed.fed.new_joined_client("test-client").await?;
             try_join!(stress_test_fed(&dev_fed, None), client.wait_session())?;
+            let original_config = dev_fed.fed.export_config().await?;
 
             for path in paths.iter().skip(1) {
                 dev_fed.fed.restart_all_with_bin(process_mgr, path).await?;
+                let drift = diff_exports(&original_config, &dev_fed.fed.export_config().await?);
+                anyhow::ensure!(
+                    drift.is_empty(),
+                    "config drifted after upgrading to {}: {}",
+                    path.display(),
+                    serde_json::to_string_pretty(&drift)?
+                );
 
                 // stress test with all peers online
                 try_join!(stress_test_fed(&dev_fed, None), client.wait_session())?;
@@ -529,6 +516,61 @@ pub async fn upgrade_tests(process_mgr: &ProcessManager, binary: UpgradeTest) ->
             }
             info!("## fedimint-cli upgraded all binaries successfully");
         }
+        UpgradeTest::FedimintdDbMigration { paths } => {
+            const MIGRATED_PEER: usize = 0;
+            const OTHER_PEER: usize = 1;
+
+            let Some((oldest_fedimintd, newer_fedimintds)) = paths.split_first() else {
+                bail!("Must provide at least 2 binary paths");
+            };
+            anyhow::ensure!(
+                !newer_fedimintds.is_empty(),
+                "Must provide at least 2 binary paths"
+            );
+            std::env::set_var(FM_FEDIMINTD_BASE_EXECUTABLE_ENV, oldest_fedimintd);
+
+            let mut dev_fed = dev_fed(process_mgr).await?;
+            dev_fed.fed.start_all_servers(process_mgr).await?;
+            let client = dev_fed.fed.new_joined_client("db-migration-client").await?;
+            let mut db_versions = BTreeMap::new();
+            // populate every module's database before migrating
+            try_join!(stress_test_fed(&dev_fed, None), client.wait_session())?;
+
+            for path in newer_fedimintds {
+                let migration_log = dev_fed
+                    .fed
+                    .restart_server_with_bin(process_mgr, MIGRATED_PEER, path)
+                    .await?;
+                let new_db_versions = migration_log.db_versions();
+                anyhow::ensure!(
+                    !new_db_versions.is_empty(),
+                    "fedimintd-{MIGRATED_PEER} did not report its database versions"
+                );
+                for (kind, version) in &db_versions {
+                    let new_version = new_db_versions.get(kind).copied();
+                    anyhow::ensure!(
+                        new_version >= Some(*version),
+                        "fedimintd-{MIGRATED_PEER} went from database version {version} of {kind} to {new_version:?}"
+                    );
+                }
+                db_versions = new_db_versions;
+                for line in migration_log.migrations() {
+                    info!(target: LOG_DEVIMINT, "{line}");
+                }
+
+                // with another peer offline consensus can't make progress without the
+                // migrated peer
+                dev_fed.fed.terminate_server(OTHER_PEER).await?;
+                try_join!(stress_test_fed(&dev_fed, None), client.wait_session())?;
+                dev_fed.fed.start_server(process_mgr, OTHER_PEER).await?;
+
+                info!(
+                    "### fedimintd-{MIGRATED_PEER} migrated its database to {}",
+                    path.display()
+                );
+            }
+            info!("## fedimintd migrated its database across all binaries successfully");
+        }
         UpgradeTest::Gatewayd {
             gatewayd_paths,
             gateway_cli_paths,
@@ -641,8 +683,36 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         return Ok(());
     }
 
+    cli_tests_bitcoind_wallet(&bitcoind).await?;
+    fed.verify_invite_code().await?;
+
+    info!("Testing joining with an invite code for a subset of guardians");
+    let num_peers = fed.members.len();
+    let one_honest = NumPeers::from(num_peers).one_honest();
+    let subset = fed
+        .members
+        .keys()
+        .rev()
+        .take(one_honest)
+        .map(|peer_id| PeerId::from(*peer_id as u16))
+        .collect::<Vec<_>>();
+    if fed.invite_code_for(&subset[1..]).is_ok() {
+        bail!("invite code for less than {one_honest} guardians must be rejected");
+    }
+    let subset_client = Client::create("invite-code-subset")?;
+    subset_client
+        .join_federation(fed.invite_code_for(&subset)?.to_string())
+        .await?;
+    assert_eq!(
+        cmd!(subset_client, "info").out_json().await?["federation_id"]
+            .as_str()
+            .context("federation_id must be a string")?,
+        fed.calculate_federation_id()
+    );
+
     let client = fed.new_joined_client("cli-tests-client").await?;
     client.use_gateway(&gw_cln).await?;
+    let reencrypt_password = format!("{}-foo", fed.guardian_auth(0).0);
     let cln_gw_id = gw_cln.gateway_id().await?;
     let lnd_gw_id = gw_lnd.gateway_id().await?;
 
@@ -653,7 +723,7 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         "--in-file={data_dir}/fedimintd-default-0/private.encrypt",
         "--out-file={data_dir}/fedimintd-default-0/config-plaintext.json"
     )
-    .env(FM_PASSWORD_ENV, "pass")
+    .env(FM_PASSWORD_ENV, &fed.guardian_auth(0).0)
     .run()
     .await?;
 
@@ -664,7 +734,7 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         "--in-file={data_dir}/fedimintd-default-0/config-plaintext.json",
         "--out-file={data_dir}/fedimintd-default-0/config-2"
     )
-    .env(FM_PASSWORD_ENV, "pass-foo")
+    .env(FM_PASSWORD_ENV, &reencrypt_password)
     .run()
     .await?;
 
@@ -675,7 +745,7 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         "--in-file={data_dir}/fedimintd-default-0/config-2",
         "--out-file={data_dir}/fedimintd-default-0/config-plaintext-2.json"
     )
-    .env(FM_PASSWORD_ENV, "pass-foo")
+    .env(FM_PASSWORD_ENV, &reencrypt_password)
     .run()
     .await?;
 
@@ -818,6 +888,30 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         client_post_spend_balance,
         CLIENT_START_AMOUNT - CLIENT_SPEND_AMOUNT
     );
+    let notes_total: u64 = client
+        .notes()
+        .await?
+        .into_iter()
+        .map(|(denomination, count)| denomination.msats * count as u64)
+        .sum();
+    assert_eq!(notes_total, client_post_spend_balance);
+
+    info!("Testing consolidating notes");
+    let notes_before_consolidation = client.notes().await?;
+    client.consolidate_notes().await?;
+    assert_eq!(client.balance().await?, client_post_spend_balance);
+    let notes_after_consolidation = client.notes().await?;
+    debug!(
+        target: LOG_DEVIMINT,
+        ?notes_before_consolidation,
+        ?notes_after_consolidation,
+        "Consolidated notes"
+    );
+    let consolidated_total: u64 = notes_after_consolidation
+        .iter()
+        .map(|(denomination, count)| denomination.msats * *count as u64)
+        .sum();
+    assert_eq!(consolidated_total, client_post_spend_balance);
 
     // Test we can reissue our own notes
     cmd!(client, "reissue", notes).out_json().await?;
@@ -891,9 +985,15 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         .as_u64()
         .unwrap();
     let (invoice, payment_hash) = lnd.invoice(1_200_000).await?;
-    ln_pay(&client, invoice.clone(), cln_gw_id.clone(), false).await?;
+    let operation_id = ln_pay(&client, invoice.clone(), cln_gw_id.clone(), false).await?;
     lnd.wait_bolt11_invoice(payment_hash).await?;
 
+    // devimint gateways are configured with `FM_GATEWAY_FEES=0,0`
+    let pay_result = client.pay_result(&operation_id).await?;
+    assert_eq!(pay_result.amount, Amount::from_msats(1_200_000));
+    assert_eq!(pay_result.fee, Amount::ZERO);
+    assert!(!pay_result.is_internal);
+
     // Try to pay the same invoice with a different client
     let new_client = fed.new_joined_client("pay_invoice").await?;
     fed.pegin_client(CLIENT_START_AMOUNT / 1000, &new_client)
@@ -958,6 +1058,11 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         (final_cln_outgoing_gateway_balance - final_cln_incoming_gateway_balance)
     );
 
+    info!("Testing fee accounting of an ecash -> LN -> ecash cycle over the CLN gateway");
+    let breakdown =
+        payment_cycle_fees(&fed, &client, &gw_cln, &lnd, Amount::from_sats(1_000)).await?;
+    info!(?breakdown, "Payment cycle fees");
+
     // LND gateway tests
     info!("Testing LND gateway");
     client.use_gateway(&gw_lnd).await?;
@@ -1047,6 +1152,9 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
     );
 
     info!("Will finish the payment of the LND HOLD invoice via CLN gateway");
+    cln.wait_payment_in_flight(hold_invoice_hash)
+        .await
+        .context("CLN should be waiting on the LND HOLD invoice")?;
     finish_hold_invoice_payment(
         &client,
         hold_invoice_operation_id,
@@ -1055,6 +1163,13 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         hold_invoice_preimage,
     )
     .await?;
+    anyhow::ensure!(
+        cln.wait_payment_complete(hold_invoice_hash).await?
+            == PaymentStatus::Succeeded {
+                preimage: hold_invoice_preimage
+            },
+        "CLN should have paid the LND HOLD invoice"
+    );
 
     // TODO: test cancel/timeout
 
@@ -1092,15 +1207,10 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
     let txid: Txid = withdraw_res["txid"].as_str().unwrap().parse().unwrap();
     let fees_sat = withdraw_res["fees_sat"].as_u64().unwrap();
 
-    let tx_hex = poll("Waiting for transaction in mempool", || async {
-        // TODO: distinguish errors from not found
-        bitcoind
-            .get_raw_transaction(&txid)
-            .context("getrawtransaction")
-            .map_err(ControlFlow::Continue)
-    })
-    .await
-    .expect("cannot fail, gets stuck");
+    bitcoind
+        .await_in_mempool(&txid, Duration::from_secs(60))
+        .await?;
+    let tx_hex = bitcoind.get_raw_transaction(&txid)?;
 
     let tx =
         bitcoin::Transaction::consensus_decode_hex(&tx_hex, &ModuleRegistry::default()).unwrap();
@@ -1141,7 +1251,7 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
                 "--our-id",
                 "0",
                 "--password",
-                "pass",
+                fed.guardian_auth(0).0,
                 "admin",
                 "sign-api-announcement",
                 NEW_API_URL
@@ -1191,6 +1301,12 @@ pub async fn cli_tests(dev_fed: DevFed) -> Result<()> {
         );
     }
 
+    // After all the peg-ins, peg-outs and payments above the federation must
+    // still be able to back all the ecash issued
+    let audit = fed.audit().await?;
+    info!(?audit, "Federation audit");
+    anyhow::ensure!(audit.is_solvent(), "Federation is insolvent: {audit:?}");
+
     Ok(())
 }
 
@@ -1233,8 +1349,10 @@ pub async fn cli_load_test_tool_test(dev_fed: DevFed) -> Result<()> {
     let data_dir = env::var(FM_DATA_DIR_ENV)?;
     let load_test_temp = PathBuf::from(data_dir).join("load-test-temp");
     dev_fed
-        .fed
-        .pegin_client(10_000, dev_fed.fed.internal_client().await?)
+        .prefund_client(
+            dev_fed.fed.internal_client().await?,
+            Amount::from_sats(10_000),
+        )
         .await?;
     let invite_code = dev_fed.fed.invite_code()?;
     run_standard_load_test(&load_test_temp, &invite_code).await?;
@@ -1396,6 +1514,70 @@ pub async fn run_ln_circular_load_test(
     Ok(())
 }
 
+/// Funds fresh bitcoind wallet addresses and checks the confirmed outputs show
+/// up on each of them
+async fn cli_tests_bitcoind_wallet(bitcoind: &crate::external::Bitcoind) -> Result<()> {
+    let amounts = [
+        bitcoin::Amount::from_sat(50_000),
+        bitcoin::Amount::from_sat(70_000),
+    ];
+    let mut sent = Vec::new();
+    for amount in amounts {
+        let address = bitcoind.get_new_address().await?;
+        let txid = bitcoind.send_to_address(&address, amount).await?;
+        sent.push((address, txid, amount));
+    }
+    bitcoind.mine_blocks(1).await?;
+
+    for (address, txid, amount) in sent {
+        let utxos = bitcoind.list_unspent(&address).await?;
+        anyhow::ensure!(
+            utxos
+                .iter()
+                .any(|utxo| utxo.txid == txid && utxo.amount == amount),
+            "output of {txid} to {address} not found in {utxos:?}"
+        );
+    }
+
+    let address = bitcoind.get_new_address().await?;
+    let amount = bitcoin::Amount::from_sat(30_000);
+    let stuck_txid = bitcoind
+        .send_to_address_low_fee(&address, amount, true)
+        .await?;
+    let stuck_entry = bitcoind
+        .await_in_mempool(&stuck_txid, Duration::from_secs(30))
+        .await?;
+    let bumped_txid = bitcoind.bumpfee(&stuck_txid).await?;
+    let bumped_entry = bitcoind
+        .await_in_mempool(&bumped_txid, Duration::from_secs(30))
+        .await?;
+    anyhow::ensure!(
+        stuck_entry.fees.base < bumped_entry.fees.base,
+        "bumped fee {} is not higher than {}",
+        bumped_entry.fees.base,
+        stuck_entry.fees.base
+    );
+
+    let unreplaceable_txid = bitcoind
+        .send_to_address_low_fee(&address, amount, false)
+        .await?;
+    bitcoind
+        .await_in_mempool(&unreplaceable_txid, Duration::from_secs(30))
+        .await?;
+    anyhow::ensure!(
+        bitcoind.bumpfee(&unreplaceable_txid).await.is_err(),
+        "bumping a transaction not signaling replace-by-fee must fail"
+    );
+
+    bitcoind.mine_blocks(1).await?;
+    let utxos = bitcoind.list_unspent(&address).await?;
+    anyhow::ensure!(
+        utxos.iter().any(|utxo| utxo.txid == bumped_txid),
+        "bumped transaction {bumped_txid} was not confirmed"
+    );
+    Ok(())
+}
+
 pub async fn cli_tests_backup_and_restore(
     fed: &Federation,
     reference_client: &Client,
@@ -1693,7 +1875,7 @@ pub async fn gw_reboot_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Re
 
     // Reboot gateways with the same Lightning node instances
     info!("Rebooting gateways...");
-    let (new_gw_cln, new_gw_lnd) = try_join!(
+    let (new_gw_cln, mut new_gw_lnd) = try_join!(
         Gatewayd::new(process_mgr, LightningNode::Cln(cln.clone())),
         Gatewayd::new(process_mgr, LightningNode::Lnd(lnd.clone()))
     )?;
@@ -1738,6 +1920,202 @@ pub async fn gw_reboot_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Re
     )
     .await?;
 
+    // Clients route through the gateways they see, the rebooted gateways must be
+    // visible to the client under this federation
+    let federation_id = fed.calculate_federation_id();
+    let client_gateways = client.list_gateways().await?;
+    for gateway_id in [
+        new_gw_cln.gateway_id().await?,
+        new_gw_lnd.gateway_id().await?,
+    ] {
+        anyhow::ensure!(
+            client_gateways.iter().any(|gw| {
+                gw.info.gateway_id.to_string() == gateway_id
+                    && gw.federation_id.to_string() == federation_id
+            }),
+            "client does not see gateway {gateway_id} after reboot"
+        );
+    }
+
+    // Take the LND gateway offline in place and verify payments fail without
+    // losing funds until it is started again
+    info!("Stopping LND gateway");
+    client.use_gateway(&new_gw_lnd).await?;
+    let lnd_gateway_id = new_gw_lnd.gateway_id().await?;
+    new_gw_lnd.stop().await?;
+    let initial_client_balance = client.balance().await?;
+    let invoice = cln
+        .invoice(
+            1_000_000,
+            "gw-stop-test".to_owned(),
+            "gw-stop-test".to_owned(),
+        )
+        .await?;
+    ln_pay(&client, invoice, lnd_gateway_id.clone(), false)
+        .await
+        .expect_err("Expected ln-pay to return error because the gateway is stopped");
+    anyhow::ensure!(initial_client_balance == client.balance().await?);
+
+    info!("Starting LND gateway");
+    new_gw_lnd.start(process_mgr).await?;
+    poll(
+        "Waiting for LND Gateway Running state after start",
+        || async {
+            let info: GatewayInfo =
+                serde_json::from_value(new_gw_lnd.get_info().await.map_err(ControlFlow::Continue)?)
+                    .context("json invalid")
+                    .map_err(ControlFlow::Break)?;
+            poll_eq!(info.gateway_state, "Running")
+        },
+    )
+    .await?;
+    let invoice = cln
+        .invoice(
+            1_000_000,
+            "gw-start-test".to_owned(),
+            "gw-start-test".to_owned(),
+        )
+        .await?;
+    ln_pay(&client, invoice, lnd_gateway_id.clone(), false).await?;
+
+    // Restrict the LND gateway to one direction at a time and verify payments in
+    // the other fail without losing funds
+    if gatewayd_version >= *VERSION_0_5_0_ALPHA {
+        // TODO(support:v0.4): gateways announce the directions they route payments
+        // in, and clients honour them, since v0.5.0
+        let fedimintd_version = FedimintdCmd::version_or_default().await;
+        let clients_see_mode = fedimint_cli_version >= *VERSION_0_5_0_ALPHA
+            && fedimintd_version >= *VERSION_0_5_0_ALPHA;
+        let await_lnd_registration = |pays_invoices: bool, receives_payments: bool| {
+            let client = &client;
+            let lnd_gateway_id = &lnd_gateway_id;
+            async move {
+                poll("Waiting for LND Gateway to announce its mode", || async {
+                    let gateways = client
+                        .list_gateways()
+                        .await
+                        .map_err(ControlFlow::Continue)?;
+                    let announced = gateways.iter().any(|gw| {
+                        gw.info.gateway_id.to_string() == *lnd_gateway_id
+                            && gw.pays_invoices == pays_invoices
+                            && gw.receives_payments == receives_payments
+                    });
+                    poll_eq!(announced, true)
+                })
+                .await
+            }
+        };
+
+        info!("Restricting LND gateway to receiving");
+        new_gw_lnd
+            .set_mode(process_mgr, GatewayMode::ReceiveOnly)
+            .await?;
+        anyhow::ensure!(new_gw_lnd.mode().await? == GatewayMode::ReceiveOnly);
+        if clients_see_mode {
+            await_lnd_registration(false, true).await?;
+        }
+        let initial_client_balance = client.balance().await?;
+        let invoice = cln
+            .invoice(
+                1_000_000,
+                "gw-receive-only-test".to_owned(),
+                "gw-receive-only-test".to_owned(),
+            )
+            .await?;
+        ln_pay(&client, invoice, lnd_gateway_id.clone(), false)
+            .await
+            .expect_err("Expected ln-pay to fail because the gateway only receives");
+        poll("Waiting for refund from receive-only gateway", || async {
+            let balance = client.balance().await.map_err(ControlFlow::Continue)?;
+            poll_eq!(balance, initial_client_balance)
+        })
+        .await?;
+
+        // Without a gateway given, clients pick a random one to pay through, which
+        // must never be the receive-only one
+        if clients_see_mode {
+            for attempt in 0..3 {
+                let (invoice, _) = lnd.invoice(1_000).await?;
+                let operation_id = cmd!(client, "ln-pay", invoice).out_json().await?
+                    ["operation_id"]
+                    .as_str()
+                    .context("operation_id must be a string")?
+                    .to_owned();
+                let gateway_id = client.pay_result(&operation_id).await?.gateway_id;
+                anyhow::ensure!(
+                    gateway_id.map(|id| id.to_string()) != Some(lnd_gateway_id.clone()),
+                    "Client paid through the receive-only gateway on attempt {attempt}"
+                );
+            }
+        }
+
+        info!("Restricting LND gateway to sending");
+        new_gw_lnd
+            .set_mode(process_mgr, GatewayMode::SendOnly)
+            .await?;
+        anyhow::ensure!(new_gw_lnd.mode().await? == GatewayMode::SendOnly);
+        let create_invoice = ln_invoice(
+            &client,
+            Amount::from_msats(1_000_000),
+            "gw-send-only-test".to_owned(),
+            lnd_gateway_id.clone(),
+        );
+        if clients_see_mode {
+            await_lnd_registration(true, false).await?;
+            create_invoice
+                .await
+                .expect_err("Expected creating an invoice to fail because the gateway only sends");
+        } else {
+            cln.pay_bolt11_invoice(create_invoice.await?.invoice)
+                .await
+                .expect_err("Expected paying the client to fail because the gateway only sends");
+        }
+
+        new_gw_lnd.set_mode(process_mgr, GatewayMode::Full).await?;
+    }
+
+    // Bring the LND gateway back with a short registration TTL, take it offline
+    // and verify its registration expires from the client's view
+    if gatewayd_version >= *VERSION_0_5_0_ALPHA {
+        const REGISTRATION_TTL: Duration = Duration::from_secs(10);
+        let is_registered = |ttl_limit: Duration| {
+            let fed = &fed;
+            let lnd_gateway_id = &lnd_gateway_id;
+            async move {
+                Ok::<_, anyhow::Error>(fed.registered_gateways().await?.iter().any(|gw| {
+                    gw.info.gateway_id.to_string() == *lnd_gateway_id && gw.ttl <= ttl_limit
+                }))
+            }
+        };
+
+        new_gw_lnd.stop().await?;
+        let gw_lnd = Gatewayd::new_with_registration_ttl(
+            process_mgr,
+            LightningNode::Lnd(lnd.clone()),
+            REGISTRATION_TTL,
+        )
+        .await?;
+        poll(
+            "Waiting for LND Gateway to register with short TTL",
+            || async {
+                let registered = is_registered(REGISTRATION_TTL)
+                    .await
+                    .map_err(ControlFlow::Continue)?;
+                poll_eq!(registered, true)
+            },
+        )
+        .await?;
+
+        gw_lnd.stop().await?;
+        poll("Waiting for LND Gateway registration to expire", || async {
+            let registered = is_registered(Duration::MAX)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            poll_eq!(registered, false)
+        })
+        .await?;
+    }
+
     info!(LOG_DEVIMINT, "gateway_reboot_test: success");
     Ok(())
 }
@@ -1791,84 +2169,82 @@ pub async fn do_try_create_and_pay_invoice(
     Ok(())
 }
 
-async fn ln_pay(
-    client: &Client,
-    invoice: String,
-    gw_id: String,
-    finish_in_background: bool,
-) -> anyhow::Result<String> {
-    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
-
-    // TODO(support:v0.2): 0.3 removed the active gateway concept and requires a
-    // `gateway-id` parameter for lightning sends
-    let value = if fedimint_cli_version < *VERSION_0_3_0_ALPHA {
-        if finish_in_background {
-            cmd!(client, "ln-pay", invoice, "--finish-in-background",)
-                .out_json()
-                .await?
-        } else {
-            cmd!(client, "ln-pay", invoice,).out_json().await?
-        }
-    } else if finish_in_background {
-        cmd!(
-            client,
-            "ln-pay",
-            invoice,
-            "--finish-in-background",
-            "--gateway-id",
-            gw_id,
-        )
-        .out_json()
-        .await?
-    } else {
-        cmd!(client, "ln-pay", invoice, "--gateway-id", gw_id,)
-            .out_json()
-            .await?
-    };
-
-    let operation_id = value["operation_id"]
-        .as_str()
-        .ok_or(anyhow!("Failed to pay invoice"))?
-        .to_string();
-    Ok(operation_id)
+/// Where the millisats of a [`payment_cycle_fees`] cycle went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeBreakdown {
+    /// Paid over lightning and received back
+    pub amount: Amount,
+    /// Net decrease of the client's ecash balance
+    pub client_deducted_msat: i64,
+    /// Fee the client reported paying the gateway for the outgoing payment
+    pub gateway_pay_fee: Amount,
+    /// Net increase of the gateway's ecash balance, its lightning balance
+    /// doesn't change as it routes `amount` both ways
+    pub gateway_fees_msat: i64,
+    /// Net increase of the federation's assets minus liabilities
+    pub federation_fees_msat: i64,
 }
 
-async fn ln_invoice(
+/// Pays an LND invoice of `amount` from `client` through `gw`, receives the
+/// same amount back from LND and checks that everything the client lost went
+/// to the gateway or the federation as fees, catching fee double counting or
+/// leakage. Nothing else may transact on the federation meanwhile.
+pub async fn payment_cycle_fees(
+    fed: &Federation,
     client: &Client,
+    gw: &Gatewayd,
+    lnd: &Lnd,
     amount: Amount,
-    description: String,
-    gw_id: String,
-) -> anyhow::Result<LnInvoiceResponse> {
-    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
-    // TODO(support:v0.2): 0.3 removed the active gateway concept and requires a
-    // `gateway-id` parameter for lightning receives
-    let ln_response_val = if fedimint_cli_version < *VERSION_0_3_0_ALPHA {
-        cmd!(
-            client,
-            "ln-invoice",
-            "--amount",
-            amount.msats,
-            format!("--description='{description}'"),
-        )
-        .out_json()
-        .await?
-    } else {
-        cmd!(
-            client,
-            "ln-invoice",
-            "--amount",
-            amount.msats,
-            format!("--description='{description}'"),
-            "--gateway-id",
-            gw_id,
-        )
-        .out_json()
-        .await?
+) -> Result<FeeBreakdown> {
+    let fed_id = fed.calculate_federation_id();
+    let gateway_balance = || async {
+        cmd!(gw, "balance", "--federation-id={fed_id}")
+            .out_json()
+            .await?
+            .as_u64()
+            .context("gateway balance must be a number")
     };
+    client.await_idle(Duration::from_secs(60)).await?;
+    let client_before = client.balance().await?;
+    let gateway_before = gateway_balance().await?;
+    let audit_before = fed.audit().await?;
+
+    let gateway_id = gw.gateway_id().await?;
+    let (invoice, _) = lnd.invoice(amount.msats).await?;
+    let pay = client.ln_pay(invoice, gateway_id.clone()).await?;
+    let receive = ln_invoice(client, amount, "payment-cycle".to_owned(), gateway_id).await?;
+    lnd.pay_bolt11_invoice(receive.invoice).await?;
+    cmd!(client, "await-invoice", receive.operation_id.fmt_full())
+        .run()
+        .await?;
 
-    let ln_invoice_response: LnInvoiceResponse = serde_json::from_value(ln_response_val)?;
-
-    Ok(ln_invoice_response)
+    // the gateway claims its contracts in the background
+    poll("payment cycle fees settling", || async {
+        let client_after = client.balance().await.map_err(ControlFlow::Continue)?;
+        let gateway_after = gateway_balance().await.map_err(ControlFlow::Continue)?;
+        let audit_after = fed.audit().await.map_err(ControlFlow::Continue)?;
+        let breakdown = FeeBreakdown {
+            amount,
+            client_deducted_msat: client_before as i64 - client_after as i64,
+            gateway_pay_fee: pay.fee,
+            gateway_fees_msat: gateway_after as i64 - gateway_before as i64,
+            federation_fees_msat: audit_after.net_assets_msat - audit_before.net_assets_msat,
+        };
+        if breakdown.client_deducted_msat
+            != breakdown.gateway_fees_msat + breakdown.federation_fees_msat
+        {
+            return Err(ControlFlow::Continue(anyhow!(
+                "client lost msats not accounted for as fees: {breakdown:?}"
+            )));
+        }
+        if breakdown.gateway_fees_msat < 0 || breakdown.federation_fees_msat < 0 {
+            return Err(ControlFlow::Break(anyhow!(
+                "fees went back to the client: {breakdown:?}"
+            )));
+        }
+        Ok(breakdown)
+    })
+    .await
 }
 
 pub async fn reconnect_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
@@ -1919,6 +2295,7 @@ pub async fn reconnect_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Re
     fed.start_server(process_mgr, 3).await?;
 
     fed.await_all_peers().await?;
+    fed.assert_consensus_consistent().await?;
 
     info!(target: LOG_DEVIMINT, "fm success: reconnect-test");
     Ok(())
@@ -2045,7 +2422,7 @@ async fn withdraw(
         "--db",
         "{data_dir}/fedimintd-default-0/database"
     )
-    .env(FM_PASSWORD_ENV, "pass")
+    .env(FM_PASSWORD_ENV, &fed.guardian_auth(0).0)
     .out_json()
     .await?;
     let outputs = output.as_array().context("expected an array")?;
@@ -2111,7 +2488,7 @@ async fn withdraw(
         "--db",
         "{data_dir}/fedimintd-default-0/database"
     )
-    .env(FM_PASSWORD_ENV, "pass")
+    .env(FM_PASSWORD_ENV, &fed.guardian_auth(0).0)
     .out_json()
     .await?
     .as_array()
@@ -2159,6 +2536,7 @@ pub async fn guardian_backup_test(dev_fed: DevFed, process_mgr: &ProcessManager)
         esplora,
         ..
     } = dev_fed;
+    let password = fed.guardian_auth(PEER_TO_TEST.into()).0.clone();
 
     fed.await_all_peers()
         .await
@@ -2183,7 +2561,7 @@ pub async fn guardian_backup_test(dev_fed: DevFed, process_mgr: &ProcessManager)
         "--our-id",
         PEER_TO_TEST.to_string(),
         "--password",
-        "pass",
+        password,
         "admin",
         "guardian-config-backup"
     )
@@ -2194,12 +2572,7 @@ pub async fn guardian_backup_test(dev_fed: DevFed, process_mgr: &ProcessManager)
         .expect("expected hex string");
     let backup_tar = hex::decode(backup_hex).expect("invalid hex");
 
-    let data_dir = fed
-        .vars
-        .get(&PEER_TO_TEST.into())
-        .expect("peer not found")
-        .FM_DATA_DIR
-        .clone();
+    let data_dir = fed.guardian_data_dir(PEER_TO_TEST.into())?;
 
     fed.terminate_server(PEER_TO_TEST.into())
         .await
@@ -2222,7 +2595,7 @@ pub async fn guardian_backup_test(dev_fed: DevFed, process_mgr: &ProcessManager)
     write_file("backup.tar", &backup_tar);
     write_file(
         fedimint_server::config::io::PLAINTEXT_PASSWORD,
-        "pass".as_bytes(),
+        password.as_bytes(),
     );
 
     assert_eq!(
@@ -2379,57 +2752,2271 @@ pub async fn cannot_replay_tx_test(dev_fed: DevFed) -> Result<()> {
     Ok(())
 }
 
-#[derive(Subcommand)]
-pub enum LatencyTest {
-    Reissue,
-    LnSend,
-    LnReceive,
-    FmPay,
-    Restore,
-}
+/// Permanently loses `f` guardians by wiping their databases, verifies the
+/// remaining `2f+1` keep reaching consensus and serving clients, then brings
+/// the wiped guardians back and verifies they recover from their peers.
+pub async fn threshold_recovery_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
 
-#[derive(Subcommand)]
-pub enum UpgradeTest {
-    Fedimintd {
-        #[arg(long, trailing_var_arg = true, num_args=1..)]
-        paths: Vec<PathBuf>,
-    },
-    FedimintCli {
-        #[arg(long, trailing_var_arg = true, num_args=1..)]
-        paths: Vec<PathBuf>,
-    },
-    Gatewayd {
-        #[arg(long, trailing_var_arg = true, num_args=1..)]
-        gatewayd_paths: Vec<PathBuf>,
-        #[arg(long, trailing_var_arg = true, num_args=1..)]
-        gateway_cli_paths: Vec<PathBuf>,
-        #[arg(long, trailing_var_arg = true, num_args=1..)]
-        gateway_cln_extension_paths: Vec<PathBuf>,
-    },
+    #[allow(unused_variables)]
+    let DevFed {
+        bitcoind,
+        cln,
+        lnd,
+        mut fed,
+        gw_cln,
+        gw_lnd,
+        electrs,
+        esplora,
+        ..
+    } = dev_fed;
+
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    let max_faulty = NumPeers::from(fed_size).max_evil();
+    if max_faulty == 0 {
+        info!("Federation of {fed_size} can't lose any guardian, skipping threshold recovery test");
+        return Ok(());
+    }
+    // Keep peer 0 online, it serves the invite code
+    let wiped_peers: Vec<usize> = (fed_size - max_faulty..fed_size).collect();
+
+    fed.await_all_peers().await?;
+    let client = fed.new_joined_client("threshold-recovery-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+    let initial_balance = client.balance().await?;
+
+    for peer_id in &wiped_peers {
+        fed.terminate_server(*peer_id).await?;
+        fed.wipe_guardian_db(*peer_id).await?;
+    }
+    info!(
+        ?wiped_peers,
+        "Wiped guardians, checking the remaining ones keep consensus"
+    );
+
+    client.wait_session().await?;
+    anyhow::ensure!(client.balance().await? == initial_balance);
+    fed.pegin_client(1_000, &client).await?;
+    let degraded_balance = client.balance().await?;
+    anyhow::ensure!(degraded_balance > initial_balance);
+    let session_count = client.get_session_count().await?;
+
+    for peer_id in &wiped_peers {
+        fed.start_server(process_mgr, *peer_id).await?;
+    }
+    fed.await_all_peers().await?;
+
+    for peer_id in &wiped_peers {
+        await_peer_session_count(&client, *peer_id, session_count).await?;
+    }
+    info!(?wiped_peers, "Wiped guardians recovered");
+
+    client.wait_session().await?;
+    anyhow::ensure!(client.balance().await? == degraded_balance);
+
+    info!(target: LOG_DEVIMINT, "fm success: threshold-recovery-test");
+    Ok(())
 }
 
-#[derive(Subcommand)]
-pub enum TestCmd {
-    /// `devfed` then checks the average latency of reissuing ecash, LN receive,
-    /// and LN send
-    LatencyTests {
-        #[clap(subcommand)]
-        r#type: LatencyTest,
+pub async fn over_threshold_offline_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    log_binary_versions().await?;
 
-        #[arg(long, default_value = "10")]
-        iterations: usize,
-    },
-    /// `devfed` then kills and restarts most of the Guardian nodes in a 4 node
-    /// fedimint
-    ReconnectTest,
-    /// `devfed` then tests a bunch of the fedimint-cli commands
-    CliTests,
-    /// `devfed` then calls binary `fedimint-load-test-tool`. See
-    /// `LoadTestArgs`.
-    LoadTestToolTest,
-    /// `devfed` then pegin CLN & LND nodes and gateways. Kill the LN nodes,
-    /// restart them, rejjoin fedimint and test payments still work
-    LightningReconnectTest,
+    let mut fed = dev_fed.fed;
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    if fed_size < 2 {
+        info!("Federation of {fed_size} has no guardian to spare, skipping over threshold offline test");
+        return Ok(());
+    }
+    let max_faulty = NumPeers::from(fed_size).max_evil();
+    // Keep peer 0 online, it serves the invite code
+    let offline_peers: Vec<usize> = (fed_size - (max_faulty + 1)..fed_size).collect();
+    let restored_peer = offline_peers[0];
+
+    fed.await_all_peers().await?;
+    let client = fed
+        .new_joined_client("over-threshold-offline-client")
+        .await?;
+    fed.pegin_client(10_000, &client).await?;
+    // A window several sessions long without progress shows consensus halted
+    // rather than just being slow
+    let session_start = Instant::now();
+    client.wait_session().await?;
+    let no_progress_window = (3 * session_start.elapsed()).max(Duration::from_secs(10));
+
+    for peer_id in &offline_peers {
+        fed.terminate_server(*peer_id).await?;
+    }
+    let session_count = peer_session_count(&client, 0).await?;
+    info!(
+        ?offline_peers,
+        session_count,
+        ?no_progress_window,
+        "Took more than f guardians offline, checking no session finalizes"
+    );
+    let outcome = fedimint_core::runtime::timeout(
+        no_progress_window,
+        cmd!(
+            client,
+            "dev",
+            "api",
+            "--peer-id",
+            0,
+            "await_session_outcome",
+            session_count
+        )
+        // the call never returns while the session can't finalize
+        .kill_on_drop(true)
+        .run(),
+    )
+    .await;
+    match outcome {
+        Err(_) => {}
+        Ok(Ok(())) => bail!(
+            "Session {session_count} finalized with {} of {fed_size} guardians offline",
+            offline_peers.len()
+        ),
+        Ok(Err(err)) => return Err(err.context("Awaiting the session outcome failed")),
+    }
+    for peer_id in (0..fed_size).filter(|peer_id| !offline_peers.contains(peer_id)) {
+        let peer_session_count = peer_session_count(&client, peer_id).await?;
+        anyhow::ensure!(
+            peer_session_count == session_count,
+            "fedimintd-{peer_id} moved to session {peer_session_count} with too many guardians offline"
+        );
+    }
+
+    fed.start_server(process_mgr, restored_peer).await?;
+    info!(
+        restored_peer,
+        "Restored a guardian, checking sessions finalize again"
+    );
+    client.wait_session_outcome(session_count).await?;
+    anyhow::ensure!(peer_session_count(&client, 0).await? > session_count);
+
+    info!(target: LOG_DEVIMINT, "fm success: over-threshold-offline-test");
+    Ok(())
+}
+
+/// Restarts the lnd gateway on the devfed's lnd attached to as an external
+/// node with [`Lnd::connect_existing`], with its TLS cert and macaroon copied
+/// to a directory of their own.
+///
+/// The originals are moved away while the gateway starts, so it only reaches
+/// lnd if it uses the paths of the external node instead of devimint's
+/// globals.
+pub async fn external_lightning_node_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed {
+        bitcoind,
+        lnd,
+        fed,
+        mut gw_lnd,
+        ..
+    } = dev_fed;
+    let globals = &process_mgr.globals;
+    let external_dir = globals.FM_TEST_DIR.join("external-lnd");
+    fs::create_dir_all(&external_dir).await?;
+    let tls_cert = external_dir.join("tls.cert");
+    let macaroon = external_dir.join("admin.macaroon");
+    fs::copy(&globals.FM_LND_TLS_CERT, &tls_cert).await?;
+    fs::copy(&globals.FM_LND_MACAROON, &macaroon).await?;
+    let external_lnd = Lnd::connect_existing(
+        bitcoind.clone(),
+        globals.FM_LND_RPC_ADDR.clone(),
+        tls_cert,
+        macaroon,
+    )
+    .await?;
+    anyhow::ensure!(external_lnd.is_external());
+
+    gw_lnd.stop().await?;
+    let moved = [&globals.FM_LND_TLS_CERT, &globals.FM_LND_MACAROON]
+        .map(|path| (path.clone(), path.with_extension("moved")));
+    for (path, moved_path) in &moved {
+        fs::rename(path, moved_path).await?;
+    }
+    gw_lnd.set_lightning_node(LightningNode::Lnd(external_lnd));
+    let started = gw_lnd.start(process_mgr).await;
+    for (path, moved_path) in &moved {
+        fs::rename(moved_path, path).await?;
+    }
+    started.context("gateway on the external lnd")?;
+
+    anyhow::ensure!(gw_lnd.lightning_pubkey().await?.to_string() == lnd.pub_key().await?);
+    let federation_id = fed.calculate_federation_id();
+    let federations = gw_lnd.get_info().await?["federations"].clone();
+    anyhow::ensure!(
+        federations
+            .as_array()
+            .context("federations must be an array")?
+            .iter()
+            .any(|federation| federation["federation_id"].as_str() == Some(federation_id.as_str())),
+        "gateway on the external lnd lost federation {federation_id}: {federations}"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: external-lightning-node-test");
+    Ok(())
+}
+
+/// Kills a guardian with SIGTERM and then SIGKILL, restarting it each time,
+/// and tests it rejoins consensus and catches up on the sessions it missed.
+///
+/// SIGKILL leaves no chance to flush anything, so this covers the guardian
+/// recovering from whatever state its database was left in.
+pub async fn guardian_kill_recovery_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    log_binary_versions().await?;
+
+    let mut fed = dev_fed.fed;
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    if NumPeers::from(fed_size).max_evil() == 0 {
+        info!("Federation of {fed_size} stops without any guardian, skipping kill recovery test");
+        return Ok(());
+    }
+    // Keep peer 0 online, it serves the invite code
+    let killed_peer = fed_size - 1;
+
+    fed.await_all_peers().await?;
+    let client = fed.new_joined_client("kill-recovery-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    for signal in [KillSignal::Sigterm, KillSignal::Sigkill] {
+        fed.kill_server(killed_peer, signal).await?;
+        info!(
+            killed_peer,
+            ?signal,
+            "Killed guardian, checking the others carry on"
+        );
+
+        let notes = cmd!(client, "spend", "1000000").out_json().await?["notes"]
+            .as_str()
+            .context("notes must be a string")?
+            .to_owned();
+        cmd!(client, "reissue", notes).run().await?;
+        client.wait_session().await?;
+        let session_count = peer_session_count(&client, 0).await?;
+
+        fed.start_server(process_mgr, killed_peer).await?;
+        fed.await_all_peers().await?;
+        await_peer_session_count(&client, killed_peer, session_count).await?;
+        client.wait_session().await?;
+        anyhow::ensure!(
+            peer_session_count(&client, killed_peer).await? > session_count,
+            "fedimintd-{killed_peer} caught up but stopped following consensus"
+        );
+        info!(killed_peer, ?signal, "Killed guardian recovered");
+    }
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-kill-recovery-test");
+    Ok(())
+}
+
+/// Session count of guardian `peer_id` alone, which works without a
+/// threshold of guardians online
+async fn peer_session_count(client: &Client, peer_id: usize) -> Result<u64> {
+    cmd!(client, "dev", "api", "--peer-id", peer_id, "session_count")
+        .out_json()
+        .await?["value"]
+        .as_u64()
+        .context("session_count must be a number")
+}
+
+/// Waits until guardian `peer_id` alone is at `session_count` or later, like
+/// after it caught up with the others
+async fn await_peer_session_count(
+    client: &Client,
+    peer_id: usize,
+    session_count: u64,
+) -> Result<()> {
+    poll(
+        &format!("fedimintd-{peer_id} reaching session {session_count}"),
+        || async {
+            let peer_session_count = peer_session_count(client, peer_id)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            if peer_session_count < session_count {
+                return Err(ControlFlow::Continue(anyhow!(
+                    "fedimintd-{peer_id} at session {peer_session_count}, expected {session_count}"
+                )));
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+/// Waits until guardian 0 sees `peer_id` as `connected`
+async fn await_peer_connection(fed: &Federation, peer_id: usize, connected: bool) -> Result<()> {
+    poll(
+        &format!("fedimintd-0 seeing fedimintd-{peer_id} connected: {connected}"),
+        || async {
+            let connection = fed
+                .peer_connections(0)
+                .await
+                .map_err(ControlFlow::Continue)?
+                .into_iter()
+                .find(|connection| connection.peer_id == PeerId::from(peer_id as u16))
+                .context("fedimintd-0 doesn't report a connection status for the peer")
+                .map_err(ControlFlow::Break)?;
+            poll_eq!(connection.connected, connected)
+        },
+    )
+    .await
+}
+
+pub async fn guardian_netns_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let fed = dev_fed.fed;
+    if !fed.has_guardian_netns() {
+        info!("Guardians don't run in network namespaces, skipping guardian netns test");
+        return Ok(());
+    }
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    if NumPeers::from(fed_size).max_evil() == 0 {
+        info!("Federation of {fed_size} can't lose any guardian, skipping guardian netns test");
+        return Ok(());
+    }
+    let cut_peer = fed_size - 1;
+
+    fed.await_all_peers().await?;
+    let client = fed.new_joined_client("guardian-netns-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    fed.cut_guardian_network(cut_peer).await?;
+    info!(cut_peer, "Cut guardian network, checking it's unreachable");
+    let peer_api = fedimint_core::runtime::timeout(
+        Duration::from_secs(10),
+        peer_session_count(&client, cut_peer),
+    )
+    .await;
+    anyhow::ensure!(
+        !matches!(peer_api, Ok(Ok(_))),
+        "fedimintd-{cut_peer} still reachable after cutting its network"
+    );
+
+    await_peer_connection(&fed, cut_peer, false).await?;
+
+    client.wait_session().await?;
+    let initial_balance = client.balance().await?;
+    fed.pegin_client(1_000, &client).await?;
+    anyhow::ensure!(client.balance().await? > initial_balance);
+    let session_count = client.get_session_count().await?;
+
+    fed.restore_guardian_network(cut_peer).await?;
+    await_peer_connection(&fed, cut_peer, true).await?;
+    await_peer_session_count(&client, cut_peer, session_count).await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-netns-test");
+    Ok(())
+}
+
+pub async fn guardian_clock_skew_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let fed = dev_fed.fed;
+    let skews: BTreeMap<_, _> = (0..process_mgr.globals.FM_FED_SIZE)
+        .map(|peer_id| (peer_id, fed.clock_skew_secs(peer_id)))
+        .filter(|(_, skew)| *skew != 0)
+        .collect();
+    if skews.is_empty() {
+        info!("No guardian has a skewed clock, skipping guardian clock skew test");
+        return Ok(());
+    }
+    info!(?skews, "Guardian clocks are skewed");
+
+    fed.await_all_peers().await?;
+    let client = fed.new_joined_client("guardian-clock-skew-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    // Sessions keep finalizing with the same outcome on every guardian, skewed
+    // or not
+    for _ in 0..3 {
+        client.wait_session().await?;
+    }
+    fed.assert_consensus_consistent().await?;
+    let initial_balance = client.balance().await?;
+    fed.pegin_client(1_000, &client).await?;
+    anyhow::ensure!(client.balance().await? > initial_balance);
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-clock-skew-test");
+    Ok(())
+}
+
+pub async fn lagging_bitcoin_backend_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    log_binary_versions().await?;
+
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    let lagging: Vec<_> =
+        crate::bitcoin_backend::guardian_backends(&process_mgr.globals, fed_size)?
+            .into_iter()
+            .filter(|(_, backend)| backend.kind == "esplora")
+            .map(|(peer_id, _)| peer_id)
+            .collect();
+    if lagging.is_empty() {
+        info!("No guardian uses esplora as its bitcoin backend, skipping lagging bitcoin backend test");
+        return Ok(());
+    }
+    anyhow::ensure!(
+        lagging.len() <= NumPeers::from(fed_size).max_evil(),
+        "guardians {lagging:?} with a lagging backend would stall consensus"
+    );
+
+    let fed = dev_fed.fed;
+    fed.await_all_peers().await?;
+    let client = fed
+        .new_joined_client("lagging-bitcoin-backend-client")
+        .await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    // while esplora is frozen the guardians using it no longer see new blocks,
+    // the others still agree on the block count and confirm deposits
+    info!(?lagging, "Freezing the bitcoin backend of guardians");
+    process_mgr.pause("esplora").await?;
+    let frozen = async {
+        let initial_balance = client.balance().await?;
+        fed.mine_then_wait_blocks_sync(10).await?;
+        fed.pegin_client(1_000, &client).await?;
+        anyhow::ensure!(client.balance().await? > initial_balance);
+        Ok(())
+    }
+    .await;
+    process_mgr.resume("esplora").await?;
+    frozen?;
+
+    // once esplora caught up every guardian follows the chain again
+    fed.mine_then_wait_blocks_sync(1).await?;
+    client.wait_session().await?;
+    fed.assert_consensus_consistent().await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: lagging-bitcoin-backend-test");
+    Ok(())
+}
+
+/// Changes the routing policy of lnd and of cln on their channel with each
+/// other, and tests each side's peer routes with the new policy
+pub async fn channel_policy_test(dev_fed: DevFed) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed { cln, lnd, .. } = dev_fed;
+    let cln = LightningNode::Cln(cln);
+    let lnd = LightningNode::Lnd(lnd);
+    let cln_pubkey = cln.pub_key().await?;
+    let lnd_pubkey = lnd.pub_key().await?;
+
+    set_channel_policy(&lnd, &cln, 1_234, 567, 144).await?;
+    let expected = ChannelPolicy {
+        base_fee_msat: 1_234,
+        fee_rate_ppm: 567,
+        cltv_delta: 144,
+    };
+    anyhow::ensure!(lnd.channel_policy(&cln_pubkey).await? == expected);
+    anyhow::ensure!(cln.peer_channel_policy(&lnd_pubkey).await? == expected);
+
+    // cln only takes its node wide cltv delta
+    let cltv_delta = cln.channel_policy(&lnd_pubkey).await?.cltv_delta;
+    set_channel_policy(&cln, &lnd, 4_321, 765, cltv_delta).await?;
+    let expected = ChannelPolicy {
+        base_fee_msat: 4_321,
+        fee_rate_ppm: 765,
+        cltv_delta,
+    };
+    anyhow::ensure!(cln.channel_policy(&lnd_pubkey).await? == expected);
+    anyhow::ensure!(lnd.peer_channel_policy(&cln_pubkey).await? == expected);
+
+    info!(target: LOG_DEVIMINT, "fm success: channel-policy-test");
+    Ok(())
+}
+
+pub async fn lnurl_pay_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed {
+        cln, fed, gw_lnd, ..
+    } = dev_fed;
+    fed.await_gateways_registered().await?;
+    let client = fed.new_joined_client("lnurl-pay-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    let lnurl_server = LnurlServer::start(process_mgr).await?;
+    let env = lnurl_server.client_env();
+    let lnurl = lnurl_server.lnurl("alice")?;
+    let address = lnurl_server.address("bob");
+    let amount = Amount::from_sats(1_000);
+    // the LNURL resolves over http, the lightning address over https
+    for lnurl in [&lnurl, &address] {
+        info!(%lnurl, "Paying LNURL");
+        let pay = client.pay_lnurl(lnurl, amount, &gw_lnd, &env).await?;
+        anyhow::ensure!(pay.amount == amount);
+
+        // the resolved invoice was issued by cln behind the LNURL server
+        let invoices = cln
+            .request(cln_rpc::model::requests::ListinvoicesRequest {
+                index: None,
+                invstring: Some(pay.invoice.clone()),
+                label: None,
+                limit: None,
+                offer_id: None,
+                payment_hash: None,
+                start: None,
+            })
+            .await?
+            .invoices;
+        anyhow::ensure!(
+            matches!(
+                invoices.as_slice(),
+                [invoice] if matches!(
+                    invoice.status,
+                    cln_rpc::model::responses::ListinvoicesInvoicesStatus::PAID
+                )
+            ),
+            "invoice {} of {lnurl} isn't a paid invoice of the LNURL server",
+            pay.invoice
+        );
+    }
+
+    anyhow::ensure!(
+        client
+            .pay_lnurl(
+                &lnurl,
+                Amount::from_msats(crate::lnurl::MAX_SENDABLE_MSAT + 1),
+                &gw_lnd,
+                &env
+            )
+            .await
+            .is_err(),
+        "paying more than the LNURL allows must fail"
+    );
+    // without trusting the server's certificate the lightning address can't
+    // be resolved
+    let untrusting_env: Vec<_> = env
+        .iter()
+        .filter(|(var, _)| var != FM_LNURL_TLS_ROOT_CERT_ENV)
+        .cloned()
+        .collect();
+    anyhow::ensure!(
+        client
+            .pay_lnurl(&address, amount, &gw_lnd, &untrusting_env)
+            .await
+            .is_err(),
+        "resolving a lightning address must check its server's certificate"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: lnurl-pay-test");
+    Ok(())
+}
+
+/// Reissues a client's notes with a mint charging fees, checking the client
+/// paid them and the federation kept them as assets
+pub async fn mint_fees_test(dev_fed: DevFed) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed { fed, .. } = dev_fed;
+    let fees = fed.mint_fees()?;
+    info!(?fees, "Mint fees");
+    anyhow::ensure!(
+        fees.note_issuance_abs != Amount::ZERO || fees.note_spend_abs != Amount::ZERO,
+        "the mint charges no fees, set {FM_MINT_FEES_ENV}"
+    );
+
+    let client = fed.new_joined_client("mint-fees-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+    client.await_idle(Duration::from_secs(60)).await?;
+    let balance_before = client.balance().await?;
+    let audit_before = fed.audit().await?;
+
+    client.consolidate_notes().await?;
+    client.await_idle(Duration::from_secs(60)).await?;
+    let balance_after = client.balance().await?;
+    let audit_after = fed.audit().await?;
+
+    anyhow::ensure!(
+        balance_after < balance_before,
+        "reissuing notes charged no fees, balance went from {balance_before} to {balance_after} msat"
+    );
+    let fees_paid = balance_before - balance_after;
+    info!(fees_paid, "Reissued notes");
+    anyhow::ensure!(
+        audit_after.issued_ecash + Amount::from_msats(fees_paid) == audit_before.issued_ecash,
+        "issued ecash went from {} to {} while the client paid {fees_paid} msat in fees",
+        audit_before.issued_ecash,
+        audit_after.issued_ecash
+    );
+    anyhow::ensure!(
+        audit_after.net_assets_msat - audit_before.net_assets_msat == fees_paid as i64,
+        "the federation didn't keep the fees, audit went from {audit_before:?} to {audit_after:?}"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: mint-fees-test");
+    Ok(())
+}
+
+/// Blocks mined while esplora is down, which it has to sync after restarting
+const SLOW_SYNC_BLOCKS: u64 = 200;
+/// Rate bitcoind serves esplora at while it syncs
+const SLOW_SYNC_BYTES_PER_SEC: u64 = 10_000;
+
+/// Restarts esplora behind a throttled bitcoind rpc after blocks were mined
+/// while it was down, checking it catches up with the chain, slowly but
+/// without errors
+pub async fn slow_bitcoind_sync_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed {
+        bitcoind, esplora, ..
+    } = dev_fed;
+    process_mgr.kill("esplora", KillSignal::Sigterm).await?;
+    drop(esplora);
+    bitcoind.mine_blocks(SLOW_SYNC_BLOCKS).await?;
+
+    let proxy = ThrottledProxy::start(
+        format!("127.0.0.1:{}", process_mgr.globals.FM_PORT_BTC_RPC).parse()?,
+    )
+    .await?;
+    proxy.set_bytes_per_sec(Some(SLOW_SYNC_BYTES_PER_SEC));
+    let sync_start = Instant::now();
+    let _esplora = Esplora::new_with_options(
+        process_mgr,
+        bitcoind.clone(),
+        EsploraOptions::default().with_daemon_rpc_addr(&proxy.addr().to_string()),
+    )
+    .await?;
+    let esplora_client = esplora_client::Builder::new(&format!(
+        "http://127.0.0.1:{}",
+        process_mgr.globals.FM_PORT_ESPLORA
+    ))
+    .build_async()?;
+    let await_synced = || async {
+        let block_count = bitcoind.get_block_count()?;
+        poll_with_timeout(
+            "esplora syncing through throttled bitcoind",
+            Duration::from_secs(300),
+            || async {
+                let height = esplora_client
+                    .get_height()
+                    .await
+                    .map_err(|err| ControlFlow::Continue(anyhow!(err)))?;
+                if u64::from(height) + 1 < block_count {
+                    return Err(ControlFlow::Continue(anyhow!(
+                        "esplora at height {height} of {block_count} blocks"
+                    )));
+                }
+                Ok(())
+            },
+        )
+        .await
+    };
+    await_synced().await?;
+    info!(
+        elapsed = ?sync_start.elapsed(),
+        bytes_served = proxy.bytes_served(),
+        "Esplora synced through throttled bitcoind"
+    );
+    anyhow::ensure!(
+        proxy.bytes_served() > 0,
+        "esplora didn't fetch the blocks through the throttled proxy"
+    );
+
+    // esplora keeps following the chain once bitcoind is fast again
+    proxy.set_bytes_per_sec(None);
+    bitcoind.mine_blocks(1).await?;
+    await_synced().await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: slow-bitcoind-sync-test");
+    Ok(())
+}
+
+/// Restarts esplora with its web frontend, testing the frontend serves the
+/// explorer pointed at esplora's API, which answers it cross-origin
+pub async fn esplora_frontend_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed {
+        bitcoind, esplora, ..
+    } = dev_fed;
+    process_mgr.kill("esplora", KillSignal::Sigterm).await?;
+    drop(esplora);
+    let esplora = Esplora::new_with_options(
+        process_mgr,
+        bitcoind.clone(),
+        EsploraOptions::default().with_frontend(true),
+    )
+    .await?;
+    let frontend_url = esplora
+        .frontend_url()
+        .context("esplora frontend wasn't launched")?;
+    let api_url = format!("http://127.0.0.1:{}/", process_mgr.globals.FM_PORT_ESPLORA);
+
+    let http = reqwest::Client::new();
+    let explorer = http
+        .get(frontend_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    anyhow::ensure!(
+        explorer.contains(&serde_json::to_string(&api_url)?),
+        "esplora frontend doesn't load the chain from {api_url}"
+    );
+
+    // what the browser sends when the explorer calls the API
+    let tip = http
+        .get(format!("{api_url}blocks/tip/height"))
+        .header(reqwest::header::ORIGIN, frontend_url)
+        .send()
+        .await?
+        .error_for_status()?;
+    let allowed_origin = tip
+        .headers()
+        .get(reqwest::header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        .context("esplora sent no CORS headers to the frontend")?
+        .to_str()?
+        .to_owned();
+    anyhow::ensure!(
+        allowed_origin == frontend_url,
+        "esplora allows origin {allowed_origin}, not the frontend at {frontend_url}"
+    );
+    let tip_height: u64 = tip.text().await?.parse()?;
+    info!(%frontend_url, tip_height, "Esplora frontend up");
+
+    info!(target: LOG_DEVIMINT, "fm success: esplora-frontend-test");
+    Ok(())
+}
+
+/// Pegs out and checks the online guardians signed the peg-out transaction,
+/// which then confirms
+pub async fn pegout_signing_test(dev_fed: DevFed) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed { bitcoind, fed, .. } = dev_fed;
+    let client = fed.new_joined_client("pegout-signing-client").await?;
+    fed.pegin_client(100_000, &client).await?;
+
+    let address = bitcoind.get_new_address().await?;
+    let pegout = fed
+        .pegout_signers(&client, &address, bitcoin::Amount::from_sat(50_000))
+        .await?;
+    info!(txid = %pegout.txid, signers = ?pegout.signers, "Peg-out signers");
+    bitcoind
+        .await_in_mempool(&pegout.txid, Duration::from_secs(60))
+        .await?;
+    bitcoind.mine_blocks(1).await?;
+    fed.assert_consensus_consistent().await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: pegout-signing-test");
+    Ok(())
+}
+
+pub async fn guardian_password_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let mut fed = dev_fed.fed;
+    let peer_id = 0;
+    let new_password = "rotated-pass";
+
+    fed.await_all_peers().await?;
+    let client = fed.new_joined_client("guardian-password-client").await?;
+    let old_password = fed.guardian_auth(peer_id).0.clone();
+
+    fed.set_guardian_password(process_mgr, peer_id, new_password)
+        .await?;
+    fed.await_all_peers().await?;
+
+    let audit = |password: String| {
+        let client = &client;
+        async move {
+            cmd!(
+                client,
+                "--password",
+                password,
+                "--our-id",
+                peer_id,
+                "admin",
+                "audit"
+            )
+            .out_json()
+            .await
+        }
+    };
+    anyhow::ensure!(
+        audit(old_password).await.is_err(),
+        "fedimintd-{peer_id} still accepts its old password"
+    );
+    audit(new_password.to_owned()).await?;
+    // helpers pick up the new password
+    fed.audit().await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-password-test");
+    Ok(())
+}
+
+pub async fn channel_churn_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+    iterations: usize,
+) -> Result<()> {
+    /// Upper bound of on-chain fees paid by a single open and close
+    const MAX_FEES_PER_ITERATION: Amount = Amount::from_sats(20_000);
+    const CHANNEL_SIZE_SATS: u64 = 1_000_000;
+
+    log_binary_versions().await?;
+
+    let DevFed {
+        bitcoind, cln, lnd, ..
+    } = dev_fed;
+
+    let lnd_pubkey = lnd.pub_key().await?;
+    let cln_pubkey = cln.pub_key().await?;
+    cln.request(cln_rpc::model::requests::ConnectRequest {
+        id: format!(
+            "{}@127.0.0.1:{}",
+            lnd_pubkey, process_mgr.globals.FM_PORT_LND_LISTEN
+        ),
+        host: None,
+        port: None,
+    })
+    .await
+    .context("connect request")?;
+
+    // fund cln on its own so the fees of the churn can be measured
+    let cln_addr = cln
+        .request(cln_rpc::model::requests::NewaddrRequest { addresstype: None })
+        .await?
+        .bech32
+        .context("bech32 should be present")?;
+    bitcoind.send_to(cln_addr, 2 * CHANNEL_SIZE_SATS).await?;
+    bitcoind.mine_blocks(10).await?;
+    cln.await_block_processing().await?;
+    let initial_balance = cln.onchain_balance().await?;
+    let initial_lnd_pending = lnd.pending_channel_count().await?;
+
+    let mut channel_ids = vec![];
+    for iteration in 0..iterations {
+        info!(iteration, "Opening churn channel");
+        let channel_id = poll("fund churn channel", || async {
+            cln.fund_channel(&lnd_pubkey, CHANNEL_SIZE_SATS)
+                .await
+                .map_err(ControlFlow::Continue)
+        })
+        .await?;
+        bitcoind.mine_blocks(10).await?;
+        poll("churn channel normal", || async {
+            let state = cln
+                .channel_state(&channel_id)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            poll_eq!(state, Some(ChannelState::CHANNELD_NORMAL))
+        })
+        .await?;
+        // both nodes need the channel in their graph to route over it
+        poll("churn channel in graphs", || async {
+            let cln_graph = cln
+                .list_channels_graph()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            let lnd_graph = lnd.describe_graph().await.map_err(ControlFlow::Continue)?;
+            for (node, graph) in [("cln", cln_graph), ("lnd", lnd_graph)] {
+                if !graph
+                    .iter()
+                    .any(|channel| channel.connects(&cln_pubkey, &lnd_pubkey))
+                {
+                    return Err(ControlFlow::Continue(anyhow!(
+                        "{node} graph has no channel between cln and lnd"
+                    )));
+                }
+            }
+            Ok(())
+        })
+        .await?;
+
+        info!(iteration, %channel_id, "Closing churn channel");
+        cln.close_channel(&channel_id).await?;
+        bitcoind.mine_blocks(10).await?;
+        tokio::try_join!(cln.await_block_processing(), lnd.await_block_processing())?;
+        poll("churn channel closed", || async {
+            let state = cln
+                .channel_state(&channel_id)
+                .await
+                .map_err(ControlFlow::Continue)?;
+            if !matches!(state, None | Some(ChannelState::ONCHAIN)) {
+                return Err(ControlFlow::Continue(anyhow!(
+                    "cln channel {channel_id} in state {state:?}"
+                )));
+            }
+            poll_eq!(
+                lnd.pending_channel_count()
+                    .await
+                    .map_err(ControlFlow::Continue)?,
+                initial_lnd_pending
+            )
+        })
+        .await?;
+        channel_ids.push(channel_id);
+    }
+
+    let mut stuck_channels = vec![];
+    for channel_id in &channel_ids {
+        let state = cln.channel_state(channel_id).await?;
+        if !matches!(state, None | Some(ChannelState::ONCHAIN)) {
+            stuck_channels.push(format!("{channel_id}: {state:?}"));
+        }
+    }
+    anyhow::ensure!(
+        stuck_channels.is_empty(),
+        "channels left in a non-terminal state: {stuck_channels:?}"
+    );
+
+    let final_balance = cln.onchain_balance().await?;
+    let max_fees = MAX_FEES_PER_ITERATION * iterations as u64;
+    anyhow::ensure!(
+        final_balance <= initial_balance && initial_balance - final_balance <= max_fees,
+        "cln balance went from {initial_balance} to {final_balance} over {iterations} channel open/close iterations"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: channel-churn-test");
+    Ok(())
+}
+
+/// Pegs in, reorgs the peg-in out of the chain before it's final and replaces
+/// it with a double spend, then verifies the federation never credits the
+/// deposit and keeps working
+pub async fn double_spend_test(dev_fed: DevFed) -> Result<()> {
+    log_binary_versions().await?;
+
+    let DevFed { bitcoind, fed, .. } = dev_fed;
+
+    if fed.get_finality_delay()? == 0 {
+        info!("Federation has no finality delay, can't reorg a peg-in before it's final");
+        return Ok(());
+    }
+
+    let client = fed.new_joined_client("double-spend-client").await?;
+    let audit_before = fed.audit().await?;
+
+    let (deposit_address, _operation_id) = client.get_deposit_addr().await?;
+    let deposit_address = deposit_address
+        .parse::<bitcoin::Address<bitcoin::address::NetworkUnchecked>>()?
+        .assume_checked();
+    let pegin_txid = bitcoind
+        .send_to_address_low_fee(&deposit_address, bitcoin::Amount::from_sat(100_000), true)
+        .await?;
+    bitcoind
+        .await_in_mempool(&pegin_txid, Duration::from_secs(30))
+        .await?;
+    // confirmed, but not final yet
+    bitcoind.mine_blocks(1).await?;
+
+    bitcoind.invalidate_blocks(1).await?;
+    bitcoind
+        .await_in_mempool(&pegin_txid, Duration::from_secs(30))
+        .await?;
+    let refund_address = bitcoind.get_new_address().await?;
+    let double_spend_txid = bitcoind.double_spend(&pegin_txid, &refund_address).await?;
+    bitcoind
+        .await_in_mempool(&double_spend_txid, Duration::from_secs(30))
+        .await?;
+
+    // makes the chain with the double spend final
+    fed.finalize_mempool_tx().await?;
+    client.wait_session().await?;
+
+    anyhow::ensure!(
+        bitcoind
+            .list_unspent(&refund_address)
+            .await?
+            .iter()
+            .any(|utxo| utxo.txid == double_spend_txid),
+        "double spend {double_spend_txid} was not confirmed"
+    );
+    anyhow::ensure!(
+        client.balance().await? == 0,
+        "client was credited for a double spent peg-in"
+    );
+    let audit_after = fed.audit().await?;
+    anyhow::ensure!(
+        audit_after.onchain_balance == audit_before.onchain_balance
+            && audit_after.issued_ecash == audit_before.issued_ecash,
+        "federation balance sheet changed from {audit_before:?} to {audit_after:?}"
+    );
+    anyhow::ensure!(audit_after.is_solvent());
+
+    // the federation keeps processing regular peg-ins
+    fed.pegin_client(10_000, &client).await?;
+    anyhow::ensure!(client.balance().await? > 0);
+
+    info!(target: LOG_DEVIMINT, "fm success: double-spend-test");
+    Ok(())
+}
+
+pub async fn guardian_full_disk_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let mut fed = dev_fed.fed;
+    let Some(peer_id) = fed
+        .members
+        .keys()
+        .copied()
+        .find(|peer_id| fed.has_guardian_disk(*peer_id))
+    else {
+        bail!(
+            "no guardian has a disk of its own, guardian-full-disk-test needs {FM_GUARDIAN_DISK_MB_ENV} and loopback mounts (linux, root and mkfs.ext4)"
+        );
+    };
+
+    fed.await_all_peers().await?;
+    let client = fed.new_joined_client("guardian-full-disk-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+    let balance = client.balance().await?;
+
+    let filled = fed.fill_guardian_disk(peer_id).await?;
+    info!(target: LOG_DEVIMINT, peer_id, filled, "Guardian disk is full");
+
+    // the remaining guardians are a threshold and keep processing transactions
+    for _ in 0..3 {
+        let notes = cmd!(client, "spend", 1_000_000).out_json().await?["notes"]
+            .as_str()
+            .context("note must be a string")?
+            .to_owned();
+        cmd!(client, "reissue", notes).run().await?;
+    }
+    client.wait_session().await?;
+
+    // the full guardian can't persist sessions, so it has to stop following
+    // consensus, either stalling or shutting down, instead of carrying on with
+    // state it never wrote
+    let session_count = client.get_session_count().await?;
+    match peer_session_count(&client, peer_id).await {
+        Ok(peer_sessions) => anyhow::ensure!(
+            peer_sessions < session_count,
+            "guardian {peer_id} completed all {session_count} sessions with a full disk"
+        ),
+        Err(err) => {
+            info!(target: LOG_DEVIMINT, peer_id, err = %format!("{err:#}"), "Guardian with a full disk stopped answering");
+        }
+    }
+
+    // once the operator frees up space and restarts it, the guardian has to catch
+    // up from the state it persisted before running out of space
+    fed.free_guardian_disk(peer_id).await?;
+    fed.terminate_server(peer_id).await?;
+    fed.start_server(process_mgr, peer_id).await?;
+    fed.await_all_peers().await?;
+    client.wait_session().await?;
+    fed.assert_consensus_consistent().await?;
+    anyhow::ensure!(
+        client.balance().await? == balance,
+        "client balance changed while a guardian's disk was full"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-full-disk-test");
+    Ok(())
+}
+
+pub async fn exact_denominations_test(dev_fed: DevFed) -> Result<()> {
+    log_binary_versions().await?;
+
+    let fed = dev_fed.fed;
+    let client = fed.new_joined_client("exact-denominations-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    // more of the smallest notes than the client holds after a peg-in
+    let counts = BTreeMap::from([
+        (Amount::from_msats(1), 20),
+        (Amount::from_msats(1 << 10), 5),
+        (Amount::from_msats(1 << 20), 2),
+    ]);
+    let notes = client.mint_exact_denominations(counts.clone()).await?;
+    let minted: BTreeMap<Amount, usize> = notes.notes().summary().iter().collect();
+    anyhow::ensure!(
+        minted == counts,
+        "requested notes {counts:?}, got {minted:?}"
+    );
+
+    let receiver = fed
+        .new_joined_client("exact-denominations-receiver")
+        .await?;
+    cmd!(receiver, "reissue", notes).run().await?;
+    anyhow::ensure!(
+        receiver.balance().await? == 20 + 5 * (1 << 10) + 2 * (1 << 20),
+        "reissued notes don't add up"
+    );
+
+    anyhow::ensure!(
+        client
+            .mint_exact_denominations(BTreeMap::from([(Amount::from_msats(3), 1)]))
+            .await
+            .is_err(),
+        "minted a denomination the mint doesn't issue"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: exact-denominations-test");
+    Ok(())
+}
+
+pub async fn transaction_size_limit_test(dev_fed: DevFed) -> Result<()> {
+    log_binary_versions().await?;
+
+    let fed = dev_fed.fed;
+    let client = fed
+        .new_joined_client("transaction-size-limit-client")
+        .await?;
+    fed.pegin_client(30_000, &client).await?;
+
+    let limit = client.probe_transaction_size_limit().await?;
+    info!(
+        target: LOG_DEVIMINT,
+        max_notes = limit.max_notes,
+        rejection = %limit.rejection,
+        "Transaction size limit"
+    );
+    anyhow::ensure!(
+        limit
+            .rejection
+            .contains("rejected by the federation for being too large"),
+        "unexpected rejection of an oversized transaction: {}",
+        limit.rejection
+    );
+
+    // guardians reject oversized transactions themselves, which clients not
+    // checking the size before submitting would otherwise only notice by
+    // their transaction never getting accepted
+    let fedimintd_version = crate::util::FedimintdCmd::version_or_default().await;
+    if fedimintd_version >= *VERSION_0_5_0_ALPHA {
+        let error = client.submit_oversized_transaction(0).await?;
+        anyhow::ensure!(
+            error.contains("transaction too large"),
+            "unexpected guardian error for an oversized transaction: {error}"
+        );
+    }
+
+    info!(target: LOG_DEVIMINT, "fm success: transaction-size-limit-test");
+    Ok(())
+}
+
+/// Takes more than `f` guardians offline and tests the gateway keeps running
+/// and reports the federation as unreachable instead of hanging, then brings
+/// them back and tests the gateway reaches the federation again.
+pub async fn gateway_unreachable_federation_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    log_binary_versions().await?;
+
+    let gateway_cli_version = crate::util::GatewayCli::version_or_default().await;
+    let gatewayd_version = crate::util::Gatewayd::version_or_default().await;
+    if gateway_cli_version < *VERSION_0_5_0_ALPHA || gatewayd_version < *VERSION_0_5_0_ALPHA {
+        info!("Gateway doesn't report federation status, skipping gateway unreachable federation test");
+        return Ok(());
+    }
+
+    #[allow(unused_variables)]
+    let DevFed {
+        bitcoind,
+        cln,
+        lnd,
+        mut fed,
+        gw_cln,
+        gw_lnd,
+        electrs,
+        esplora,
+        ..
+    } = dev_fed;
+
+    let fed_id = fed.calculate_federation_id();
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    let max_faulty = NumPeers::from(fed_size).max_evil();
+    let offline_peers: Vec<usize> = (fed_size - (max_faulty + 1)..fed_size).collect();
+
+    fed.await_all_peers().await?;
+    poll("gateway reaching federation", || async {
+        let status = gw_lnd
+            .federation_status(&fed_id)
+            .await
+            .map_err(ControlFlow::Continue)?;
+        if !status.reachable {
+            return Err(ControlFlow::Continue(anyhow!(
+                "Gateway can't reach federation yet"
+            )));
+        }
+        Ok(())
+    })
+    .await?;
+
+    for peer_id in &offline_peers {
+        fed.terminate_server(*peer_id).await?;
+    }
+    info!(
+        ?offline_peers,
+        "Took more than f guardians offline, checking the gateway reports the federation unreachable"
+    );
+    // The gateway gives up on the federation after its own timeout, waiting
+    // much longer than that means it hangs
+    let status =
+        fedimint_core::runtime::timeout(Duration::from_secs(60), gw_lnd.federation_status(&fed_id))
+            .await
+            .context("Gateway hung asking an unreachable federation for its status")??;
+    anyhow::ensure!(
+        !status.reachable,
+        "Gateway reports federation reachable with {} of {fed_size} guardians offline",
+        offline_peers.len()
+    );
+    // and keeps serving requests not involving the federation
+    let info = gw_lnd.get_info().await?;
+    anyhow::ensure!(
+        info["federations"]
+            .as_array()
+            .context("federations must be an array")?
+            .iter()
+            .any(|federation| federation["federation_id"] == fed_id.as_str()),
+        "Gateway forgot the unreachable federation"
+    );
+
+    fed.start_all_servers(process_mgr).await?;
+    info!("Guardians back online, checking the gateway reaches the federation again");
+    poll("gateway reaching federation again", || async {
+        let status = gw_lnd
+            .federation_status(&fed_id)
+            .await
+            .map_err(ControlFlow::Continue)?;
+        if !status.reachable {
+            return Err(ControlFlow::Continue(anyhow!(
+                "Gateway can't reach federation yet"
+            )));
+        }
+        Ok(())
+    })
+    .await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: gateway-unreachable-federation-test");
+    Ok(())
+}
+
+/// Creates two clients from the same mnemonic and tests they derive the same
+/// keys, while an invalid mnemonic is rejected
+pub async fn mnemonic_client_test(dev_fed: DevFed) -> Result<()> {
+    /// BIP39 test vector, so the derived keys are the same on every run
+    const MNEMONIC: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    log_binary_versions().await?;
+
+    // `restore` takes the invite code since v0.3.0 (3746d51)
+    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+    if fedimint_cli_version < *VERSION_0_3_0_ALPHA {
+        info!("fedimint-cli can't restore from an invite code, skipping mnemonic client test");
+        return Ok(());
+    }
+
+    let fed = dev_fed.fed;
+    fed.await_all_peers().await?;
+
+    match fed
+        .new_client_with_mnemonic("abandon abandon abandon")
+        .await
+    {
+        Ok(_) => bail!("Invalid mnemonic was accepted"),
+        Err(err) => anyhow::ensure!(
+            err.to_string().contains("invalid mnemonic"),
+            "Invalid mnemonic failed with an unclear error: {err:#}"
+        ),
+    }
+
+    let client_a = fed.new_client_with_mnemonic(MNEMONIC).await?;
+    let client_b = fed.new_client_with_mnemonic(MNEMONIC).await?;
+    for (name, client) in [("a", &client_a), ("b", &client_b)] {
+        let secret = cmd!(client, "print-secret").out_json().await?["secret"]
+            .as_str()
+            .context("secret must be a string")?
+            .to_owned();
+        anyhow::ensure!(
+            secret == MNEMONIC,
+            "Client {name} has secret {secret}, not the mnemonic it was created from"
+        );
+    }
+    // Peg-in addresses are derived from the client's secret
+    let (address_a, _) = client_a.get_deposit_addr().await?;
+    let (address_b, _) = client_b.get_deposit_addr().await?;
+    anyhow::ensure!(
+        address_a == address_b,
+        "Clients of the same mnemonic derived different peg-in addresses {address_a} and {address_b}"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: mnemonic-client-test");
+    Ok(())
+}
+
+/// Moves some of the lnd gateway's on-chain funds into a new channel with the
+/// cln gateway, testing the balances shift from on-chain to lightning
+pub async fn gateway_onchain_rebalance_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    const REBALANCE_SATS: u64 = 1_000_000;
+
+    log_binary_versions().await?;
+
+    let DevFed {
+        bitcoind,
+        gw_cln,
+        gw_lnd,
+        ..
+    } = &dev_fed;
+
+    let Some((before, after)) = gw_lnd
+        .rebalance_onchain(gw_cln, bitcoind, REBALANCE_SATS)
+        .await?
+    else {
+        info!("Gateway can't report its balances, skipping gateway on-chain rebalance test");
+        return Ok(());
+    };
+    info!(?before, ?after, "Gateway rebalanced");
+    gw_lnd
+        .await_liquidity(
+            LiquidityDirection::Outbound,
+            Amount::from_sats(REBALANCE_SATS / 2),
+            Duration::from_secs(60),
+        )
+        .await?;
+    // moving the funds must not leave anything in flight
+    dev_fed
+        .await_quiescent(process_mgr, Duration::from_secs(120))
+        .await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: gateway-onchain-rebalance-test");
+    Ok(())
+}
+
+/// Mines blocks and checks the whole stack ends up at the new height
+pub async fn synced_height_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    const BLOCKS: u64 = 10;
+
+    log_binary_versions().await?;
+
+    let height = dev_fed.bitcoind.get_block_count()? - 1;
+    dev_fed.bitcoind.mine_blocks(BLOCKS).await?;
+    let expected = height + BLOCKS;
+    dev_fed.assert_synced_height(process_mgr, expected).await?;
+
+    // a height the chain isn't at fails right away
+    let Err(err) = dev_fed
+        .assert_synced_height(process_mgr, expected + 1)
+        .await
+    else {
+        bail!("Stack reported synced to height {}", expected + 1);
+    };
+    anyhow::ensure!(
+        err.to_string().contains(&format!(
+            "bitcoind at height {expected}, expected {}",
+            expected + 1
+        )),
+        "Unexpected error asserting a height the chain isn't at: {err:#}"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: synced-height-test");
+    Ok(())
+}
+
+pub async fn meta_update_test(dev_fed: DevFed) -> Result<()> {
+    /// Meta field the test changes, next to whatever the federation has
+    const FIELD: &str = "devimint_meta_update";
+
+    log_binary_versions().await?;
+
+    // TODO(support:v0.4): `dev cached-meta-fields` was added in v0.5.0
+    // TODO(support:v0.3): the meta module commands used to submit values were
+    // added in v0.4.0
+    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+    let fedimintd_version = crate::util::FedimintdCmd::version_or_default().await;
+    if fedimint_cli_version < *VERSION_0_5_0_ALPHA || fedimintd_version < *VERSION_0_4_0_ALPHA {
+        info!(
+            "Meta fields can't be updated or read from the client cache, skipping meta update test"
+        );
+        return Ok(());
+    }
+
+    let fed = dev_fed.fed;
+    if !fed.runs_meta_module()? {
+        info!("Federation runs without the meta module, skipping meta update test");
+        return Ok(());
+    }
+    fed.await_all_peers().await?;
+
+    let client = fed.new_joined_client("meta-update-client").await?;
+    let before = client.cached_meta_fields().await?;
+    info!(
+        target: LOG_DEVIMINT,
+        revision = before.revision,
+        values = ?before.values,
+        "Client meta fields before the update"
+    );
+
+    let mut values = before.values.clone();
+    let mut revisions = vec![];
+    for update in 1..=2 {
+        values.insert(FIELD.to_owned(), update.to_string());
+        let expected = fed.submit_meta(&values).await?;
+        let fetched = client.await_cached_meta_fields(&expected).await?;
+        info!(
+            target: LOG_DEVIMINT,
+            update,
+            revision = fetched.revision,
+            "Client picked up the meta fields update"
+        );
+        revisions.push(fetched.revision);
+    }
+    anyhow::ensure!(
+        revisions[0] < revisions[1],
+        "Meta revision didn't advance between updates: {revisions:?}"
+    );
+    info!(
+        target: LOG_DEVIMINT,
+        before = before.revision,
+        after = revisions[1],
+        "Meta revision before and after the updates"
+    );
+
+    // a client joining afterwards starts out with the updated fields
+    let late = fed.new_joined_client("meta-update-late-client").await?;
+    let fetched = late.cached_meta_fields().await?;
+    anyhow::ensure!(
+        fetched.values.get(FIELD).map(String::as_str) == Some("2"),
+        "Client joining after the update has meta fields {fetched:?}"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: meta-update-test");
+    Ok(())
+}
+
+pub async fn byzantine_guardian_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    const SPEND_MSATS: u64 = 100_000;
+
+    log_binary_versions().await?;
+
+    let fed = dev_fed.fed;
+    let fed_size = process_mgr.globals.FM_FED_SIZE;
+    let Some(byzantine_peer) =
+        (0..fed_size).find(|peer_id| fed.has_guardian_response_proxy(*peer_id))
+    else {
+        info!("No guardian has a response proxy, skipping byzantine guardian test");
+        return Ok(());
+    };
+    if NumPeers::from(fed_size).max_evil() == 0 {
+        info!("Federation of {fed_size} can't tolerate a byzantine guardian, skipping byzantine guardian test");
+        return Ok(());
+    }
+    anyhow::ensure!(
+        fed.members.len() == fed_size,
+        "Byzantine guardian test needs all guardians online, but only {} of {fed_size} are",
+        fed.members.len()
+    );
+
+    fed.await_all_peers().await?;
+    let sender = fed.new_joined_client("byzantine-sender").await?;
+    let receiver = fed.new_joined_client("byzantine-receiver").await?;
+    fed.pegin_client(10_000, &sender).await?;
+
+    let spend_and_reissue = || async {
+        let balance = receiver.balance().await?;
+        let notes = cmd!(sender, "spend", SPEND_MSATS).out_json().await?["notes"]
+            .as_str()
+            .context("notes must be a string")?
+            .to_owned();
+        cmd!(receiver, "reissue", notes).run().await?;
+        receiver.wait_complete().await?;
+        anyhow::ensure!(
+            receiver.balance().await? == balance + SPEND_MSATS,
+            "Receiver didn't get the reissued notes"
+        );
+        // consensus queries need matching answers from a threshold of guardians
+        receiver.get_session_count().await?;
+        anyhow::Ok(())
+    };
+
+    for mode in [
+        CorruptMode::Garbage,
+        CorruptMode::InvalidResult,
+        CorruptMode::RpcError,
+    ] {
+        let corrupted = fed.guardian_responses_corrupted(byzantine_peer)?;
+        fed.corrupt_guardian_responses(byzantine_peer, mode)?;
+        spend_and_reissue().await.with_context(|| {
+            format!("Client failed with fedimintd-{byzantine_peer} responding {mode:?}")
+        })?;
+        let corrupted = fed.guardian_responses_corrupted(byzantine_peer)? - corrupted;
+        anyhow::ensure!(
+            corrupted > 0,
+            "Client never heard from fedimintd-{byzantine_peer} while it responded {mode:?}"
+        );
+        info!(
+            byzantine_peer,
+            ?mode,
+            corrupted,
+            "Client tolerated corrupted responses"
+        );
+        fed.restore_guardian_responses(byzantine_peer)?;
+    }
+
+    // the guardian answers properly again once restored
+    let corrupted = fed.guardian_responses_corrupted(byzantine_peer)?;
+    let peer_session_count = cmd!(
+        receiver,
+        "dev",
+        "api",
+        "--peer-id",
+        byzantine_peer,
+        "session_count"
+    )
+    .out_json()
+    .await?["value"]
+        .as_u64();
+    anyhow::ensure!(
+        peer_session_count.is_some(),
+        "fedimintd-{byzantine_peer} doesn't report its session count after restoring"
+    );
+    spend_and_reissue().await?;
+    anyhow::ensure!(
+        fed.guardian_responses_corrupted(byzantine_peer)? == corrupted,
+        "Responses of fedimintd-{byzantine_peer} still corrupted after restoring"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: byzantine-guardian-test");
+    Ok(())
+}
+
+/// How long [`drop_cleanup_test`] gives a dropped dev federation to terminate
+/// its daemons and free their ports
+const DROP_CLEANUP_DEADLINE: Duration = Duration::from_secs(30);
+
+/// Drops `dev_fed` like a panicking test would, without shutting it down, and
+/// checks every daemon it spawned terminated and the ports they listened on
+/// are free again within [`DROP_CLEANUP_DEADLINE`]
+pub async fn drop_cleanup_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let mut ports: Vec<u16> = process_mgr
+        .globals
+        .vars()
+        // the faucet's port is fixed rather than allocated, so it's shared with
+        // other devimints
+        .filter(|(var, _)| var.starts_with("FM_PORT_") && *var != "FM_PORT_FAUCET")
+        .filter_map(|(_, port)| port.parse().ok())
+        .collect();
+    for vars in dev_fed.fed.vars.values() {
+        for addr in [
+            &vars.FM_BIND_P2P,
+            &vars.FM_BIND_API,
+            &vars.FM_BIND_METRICS_API,
+        ] {
+            ports.extend(
+                addr.parse::<std::net::SocketAddr>()
+                    .ok()
+                    .map(|addr| addr.port()),
+            );
+        }
+    }
+    // ports nothing listened on would pass trivially
+    let ports = ports_in_use(&ports);
+    anyhow::ensure!(!ports.is_empty(), "dev federation listens on no ports");
+    info!(target: LOG_DEVIMINT, ?ports, "Dropping dev federation");
+
+    let start = Instant::now();
+    drop(dev_fed);
+    let dropped_after = start.elapsed();
+    let remaining = DROP_CLEANUP_DEADLINE.checked_sub(dropped_after).with_context(|| {
+        format!("dropping the dev federation took {dropped_after:?}, more than {DROP_CLEANUP_DEADLINE:?}")
+    })?;
+
+    fedimint_core::runtime::timeout(remaining, process_mgr.assert_no_leaked_processes())
+        .await
+        .map_err(|_| {
+            anyhow!("processes still running {DROP_CLEANUP_DEADLINE:?} after the drop")
+        })??;
+    let remaining = DROP_CLEANUP_DEADLINE.saturating_sub(start.elapsed());
+    poll_with_timeout("ports freed after drop", remaining, || async {
+        let in_use = ports_in_use(&ports);
+        if in_use.is_empty() {
+            Ok(())
+        } else {
+            Err(ControlFlow::Continue(anyhow!(
+                "ports still in use after the drop: {in_use:?}"
+            )))
+        }
+    })
+    .await?;
+
+    info!(target: LOG_DEVIMINT, ?dropped_after, cleaned_up_after = ?start.elapsed(), "Dropped dev federation cleaned up");
+    info!(target: LOG_DEVIMINT, "fm success: drop-cleanup-test");
+    Ok(())
+}
+
+/// Invoice of a random node, which no lightning node of the dev federation
+/// has a route to
+fn unroutable_invoice(amount_msats: u64) -> Result<String> {
+    use bitcoin::hashes::Hash as _;
+
+    let secp = bitcoin::secp256k1::Secp256k1::new();
+    let node_key = bitcoin::secp256k1::SecretKey::from_slice(&rand::random::<[u8; 32]>())?;
+    let invoice = lightning_invoice::InvoiceBuilder::new(lightning_invoice::Currency::Regtest)
+        .amount_milli_satoshis(amount_msats)
+        .payment_hash(bitcoin::hashes::sha256::Hash::hash(
+            &rand::random::<[u8; 32]>(),
+        ))
+        .description("unroutable".to_owned())
+        .payment_secret(lightning_invoice::PaymentSecret(rand::random()))
+        .current_timestamp()
+        .min_final_cltv_expiry_delta(18)
+        .build_signed(|msg| secp.sign_ecdsa_recoverable(msg, &node_key))?;
+    Ok(invoice.to_string())
+}
+
+/// Pays through a chosen gateway, checking the client routes the payment
+/// through it, and that a payment it can't route fails and gets refunded
+/// instead of going through the other gateway
+pub async fn ln_pay_via_test(dev_fed: DevFed) -> Result<()> {
+    const PAY_MSATS: u64 = 1_000_000;
+
+    log_binary_versions().await?;
+
+    // TODO(support:v0.3): the gateway a payment used is checked against the
+    // operation log, which older clients don't record it in
+    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+    if fedimint_cli_version < *VERSION_0_4_0_ALPHA {
+        info!("Client doesn't record which gateway paid, skipping ln pay via test");
+        return Ok(());
+    }
+
+    let DevFed {
+        fed,
+        gw_cln,
+        gw_lnd,
+        lnd,
+        ..
+    } = dev_fed;
+    let client = fed.new_joined_client("ln-pay-via-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+    let gw_cln_id = gw_cln.gateway_id().await?;
+    let gw_lnd_id = gw_lnd.gateway_id().await?;
+
+    let (invoice, _) = lnd.invoice(PAY_MSATS).await?;
+    let pay = client.ln_pay_via(invoice, &gw_cln_id).await?;
+    info!(target: LOG_DEVIMINT, gateway_id = ?pay.gateway_id, fee = %pay.fee, "Paid through the chosen gateway");
+
+    let balance_before = client.balance().await?;
+    let gateway_error = client
+        .assert_ln_pay_via_fails(unroutable_invoice(PAY_MSATS)?, &gw_lnd_id)
+        .await?;
+    info!(target: LOG_DEVIMINT, %gateway_error, "Payment the gateway can't route failed");
+    client.await_idle(Duration::from_secs(60)).await?;
+    let balance_after = client.balance().await?;
+    // without the refund the client would be out the whole invoice amount
+    anyhow::ensure!(
+        balance_before < balance_after + PAY_MSATS,
+        "Client wasn't refunded, it has {balance_after} msat, had {balance_before} msat before"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: ln-pay-via-test");
+    Ok(())
+}
+
+/// Kills the lightning node of the lnd gateway while gatewayd keeps running,
+/// checks the gateway reports itself disconnected and rejects payments as
+/// such, then restarts the node and checks the rejected payment and new ones
+/// get routed again
+pub async fn gateway_lightning_node_down_test(
+    dev_fed: DevFed,
+    process_mgr: &ProcessManager,
+) -> Result<()> {
+    log_binary_versions().await?;
+
+    // TODO(support:v0.3): gateways reporting their lightning node as
+    // unreachable in `info` was added in v0.4.0
+    // pays through `Client::ln_pay_via`, like `ln_pay_via_test`
+    let gatewayd_version = crate::util::Gatewayd::version_or_default().await;
+    let fedimint_cli_version = crate::util::FedimintCli::version_or_default().await;
+    if gatewayd_version < *VERSION_0_4_0_ALPHA || fedimint_cli_version < *VERSION_0_4_0_ALPHA {
+        info!("Gateway can't report its lightning node as down, skipping lightning node down test");
+        return Ok(());
+    }
+
+    let DevFed {
+        fed, gw_lnd, cln, ..
+    } = dev_fed;
+    let client = fed.new_joined_client("lightning-node-down-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+    let gateway_id = gw_lnd.gateway_id().await?;
+
+    /// Logged by `Gateway::handle_pay_invoice_msg` before answering
+    /// `GatewayError::Disconnected`
+    const NOT_CONNECTED: &str = "Gateway is not connected, cannot handle";
+
+    gw_lnd.kill_lightning_node().await?;
+    let info: GatewayInfo = serde_json::from_value(gw_lnd.get_info().await?)?;
+    anyhow::ensure!(
+        info.gateway_state == "Disconnected",
+        "gateway without lightning node is {} instead of Disconnected",
+        info.gateway_state
+    );
+
+    let gateway_log = process_mgr
+        .globals
+        .FM_LOGS_DIR
+        .join(format!("{}.log", gw_lnd.process.name().await));
+    let rejections = || async {
+        anyhow::Ok(
+            fs::read_to_string(&gateway_log)
+                .await
+                .with_context(|| format!("reading {}", gateway_log.display()))?
+                .matches(NOT_CONNECTED)
+                .count(),
+        )
+    };
+    let rejections_before = rejections().await?;
+    let invoice = cln
+        .invoice(
+            1_000_000,
+            "lightning node down".to_owned(),
+            "lightning-node-down".to_owned(),
+        )
+        .await?;
+    // the client retries while the gateway answers it's disconnected, so the
+    // payment only goes through once the lightning node is back
+    try_join!(client.ln_pay_via(invoice, &gateway_id), async {
+        poll("gateway rejects payment as disconnected", || async {
+            if rejections_before < rejections().await.map_err(ControlFlow::Break)? {
+                Ok(())
+            } else {
+                Err(ControlFlow::Continue(anyhow!(
+                    "gateway hasn't rejected the payment yet"
+                )))
+            }
+        })
+        .await?;
+        info!(target: LOG_DEVIMINT, "Gateway without lightning node rejected the payment as disconnected");
+
+        gw_lnd.restart_lightning_node(process_mgr).await?;
+        poll("lnd channel active again", || async {
+            let channels = gw_lnd
+                .list_active_channels()
+                .await
+                .map_err(ControlFlow::Continue)?;
+            if channels.is_empty() {
+                Err(ControlFlow::Continue(anyhow!(
+                    "gateway has no active channels yet"
+                )))
+            } else {
+                Ok(())
+            }
+        })
+        .await
+    })?;
+    let invoice = cln
+        .invoice(
+            1_000_000,
+            "lightning node restarted".to_owned(),
+            "lightning-node-restarted".to_owned(),
+        )
+        .await?;
+    client.ln_pay_via(invoice, &gateway_id).await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: gateway-lightning-node-down-test");
+    Ok(())
+}
+
+/// Pays hold invoices of lnd through the cln gateway, checking the gateway
+/// leaves the outgoing contract funded while lnd holds the HTLC, claims it
+/// once the invoice settles, and cancels it if the invoice gets cancelled or
+/// the gateway's node force-fails its in-flight HTLC
+pub async fn hold_invoice_test(dev_fed: DevFed) -> Result<()> {
+    /// Amount of each hold invoice
+    const HOLD_INVOICE_MSATS: u64 = 100_000;
+    /// How long the gateway gets to claim or cancel the outgoing contract
+    /// once lnd settled or failed the HTLC
+    const CONTRACT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    log_binary_versions().await?;
+
+    let DevFed {
+        fed, gw_cln, lnd, ..
+    } = dev_fed;
+    let client = fed.new_joined_client("hold-invoice-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+    client.use_gateway(&gw_cln).await?;
+    let gw_id = gw_cln.gateway_id().await?;
+
+    info!(target: LOG_DEVIMINT, "Paying hold invoice that gets settled");
+    let hold = lnd.hold_invoice(HOLD_INVOICE_MSATS).await?;
+    let operation_id = ln_pay(&client, hold.invoice().to_owned(), gw_id.clone(), true).await?;
+    let contract_id = client.pay_result(&operation_id).await?.contract_id;
+    hold.await_accepted().await?;
+    let held = client.outgoing_contract_state(&contract_id).await?;
+    anyhow::ensure!(
+        held == OutgoingContractState::Funded,
+        "outgoing contract {contract_id} is {held:?} while the gateway's HTLC is held"
+    );
+    let preimage = hold.preimage();
+    hold.settle().await?;
+    let received_preimage = cmd!(client, "await-ln-pay", operation_id)
+        .out_json()
+        .await?["preimage"]
+        .as_str()
+        .context("missing preimage")?
+        .to_owned();
+    assert_eq!(received_preimage, preimage.encode_hex::<String>());
+    client
+        .await_outgoing_contract_state(
+            &contract_id,
+            OutgoingContractState::Claimed,
+            CONTRACT_TIMEOUT,
+        )
+        .await?;
+
+    info!(target: LOG_DEVIMINT, "Paying hold invoice that gets cancelled");
+    let hold = lnd.hold_invoice(HOLD_INVOICE_MSATS).await?;
+    let operation_id = ln_pay(&client, hold.invoice().to_owned(), gw_id.clone(), true).await?;
+    let contract_id = client.pay_result(&operation_id).await?.contract_id;
+    hold.await_accepted().await?;
+    hold.cancel().await?;
+    anyhow::ensure!(
+        cmd!(client, "await-ln-pay", operation_id)
+            .run()
+            .await
+            .is_err(),
+        "payment of a cancelled hold invoice succeeded"
+    );
+    client
+        .await_outgoing_contract_state(
+            &contract_id,
+            OutgoingContractState::Cancelled,
+            CONTRACT_TIMEOUT,
+        )
+        .await?;
+
+    info!(target: LOG_DEVIMINT, "Force-failing the gateway's in-flight HTLC");
+    let hold = lnd.hold_invoice(HOLD_INVOICE_MSATS).await?;
+    let operation_id = ln_pay(&client, hold.invoice().to_owned(), gw_id, true).await?;
+    let contract_id = client.pay_result(&operation_id).await?.contract_id;
+    hold.await_accepted().await?;
+    let htlc = gw_cln
+        .inflight_htlcs()
+        .await?
+        .into_iter()
+        .find(|htlc| htlc.payment_hash == hold.payment_hash())
+        .context("gateway has no HTLC in flight for the held payment")?;
+    anyhow::ensure!(
+        htlc.direction == HtlcDirection::Outgoing
+            && Amount::from_msats(HOLD_INVOICE_MSATS) <= htlc.amount
+            && lnd.block_height().await? < htlc.expiry,
+        "unexpected in-flight HTLC of the gateway: {htlc:?}"
+    );
+    // lnd keeps holding the HTLC, so the gateway's node has to time it out on
+    // chain, closing its channel with lnd, which is why this comes last
+    gw_cln.force_fail_htlc(&htlc).await?;
+    anyhow::ensure!(
+        cmd!(client, "await-ln-pay", operation_id)
+            .run()
+            .await
+            .is_err(),
+        "payment of a force-failed HTLC succeeded"
+    );
+    client
+        .await_outgoing_contract_state(
+            &contract_id,
+            OutgoingContractState::Cancelled,
+            CONTRACT_TIMEOUT,
+        )
+        .await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: hold-invoice-test");
+    Ok(())
+}
+
+/// Memory limit of the guardians' cgroups in [`guardian_oom_test`], generous
+/// enough to not get in the way of setting up the federation
+pub const GUARDIAN_OOM_TEST_MEMORY_LIMIT: u64 = 2 << 30;
+
+/// Spends and reissues ecash over and over, checking no guardian's memory
+/// usage grows by more than a generous bound doing so
+pub async fn memory_growth_test(dev_fed: DevFed) -> Result<()> {
+    /// Rounds of spending and reissuing ecash the guardians have to serve
+    const ROUNDS: usize = 50;
+    /// Amount spent and reissued in each round
+    const SPEND_MSATS: u64 = 100_000;
+    /// How much a guardian may grow serving all rounds, far above what it
+    /// needs to, but far below a leak of every round's transaction
+    const LIMIT_BYTES: u64 = 64 * 1024 * 1024;
+
+    log_binary_versions().await?;
+
+    if !std::path::Path::new("/proc/self/status").exists() {
+        info!("Memory usage can't be read from /proc, skipping memory growth test");
+        return Ok(());
+    }
+
+    let client = dev_fed
+        .fed
+        .new_joined_client("memory-growth-client")
+        .await?;
+    dev_fed.fed.pegin_client(10_000, &client).await?;
+
+    let reissue = || async {
+        let notes = cmd!(client, "spend", SPEND_MSATS).out_json().await?["notes"]
+            .as_str()
+            .context("spend returned no notes")?
+            .to_owned();
+        cmd!(client, "reissue", notes).run().await
+    };
+    // caches and connections are set up by the first transactions
+    for _ in 0..3 {
+        reissue().await?;
+    }
+
+    let baseline = dev_fed.memory_baseline().await?;
+    for round in 0..ROUNDS {
+        reissue()
+            .await
+            .with_context(|| format!("reissuing ecash in round {round}"))?;
+    }
+    for member in dev_fed.fed.members.values() {
+        let name = member.process.name().await;
+        dev_fed
+            .assert_memory_growth_under(&baseline, &name, LIMIT_BYTES)
+            .await?;
+    }
+
+    info!(target: LOG_DEVIMINT, "fm success: memory-growth-test");
+    Ok(())
+}
+
+/// Squeezes the memory limit of the last guardian's cgroup below what it uses,
+/// checks the kernel OOM kills it, and that the federation keeps processing
+/// transactions without it. Then restarts it with its limit restored and
+/// checks it recovers, catching up with the others. Needs the guardians
+/// spawned in cgroups, see [`ProcessManager::with_resource_limits`].
+pub async fn guardian_oom_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    /// Amount spent and reissued while the guardian is down
+    const SPEND_MSATS: u64 = 1_000_000;
+
+    log_binary_versions().await?;
+
+    let mut fed = dev_fed.fed;
+    let Some(peer) = fed.members.keys().last().copied() else {
+        bail!("Federation has no guardians running");
+    };
+    if fed.members.len() - 1 < NumPeers::from(fed.vars.len()).threshold() {
+        info!("Federation can't lose a guardian, skipping guardian oom test");
+        return Ok(());
+    }
+    let name = format!("fedimintd-{}-{peer}", fed.name());
+    process_mgr
+        .oom_kills(&name)
+        .await
+        .context("guardian must run in a cgroup")?;
+
+    let client = fed.new_joined_client("guardian-oom-client").await?;
+    fed.pegin_client(10_000, &client).await?;
+
+    info!(target: LOG_DEVIMINT, %name, "Squeezing guardian's memory limit");
+    process_mgr.set_memory_limit(&name, 1 << 20).await?;
+    poll("guardian oom killed", || async {
+        let oom_kills = process_mgr
+            .oom_kills(&name)
+            .await
+            .map_err(ControlFlow::Break)?;
+        if 0 < oom_kills {
+            Ok(())
+        } else {
+            Err(ControlFlow::Continue(anyhow!(
+                "{name} wasn't oom killed yet"
+            )))
+        }
+    })
+    .await?;
+
+    let reissue = || async {
+        let balance = client.balance().await?;
+        let notes = cmd!(client, "spend", SPEND_MSATS).out_json().await?["notes"]
+            .as_str()
+            .context("notes must be a string")?
+            .to_owned();
+        cmd!(client, "reissue", notes).run().await?;
+        client.wait_complete().await?;
+        anyhow::ensure!(
+            client.balance().await? == balance,
+            "reissuing notes changed the balance"
+        );
+        anyhow::Ok(())
+    };
+    reissue()
+        .await
+        .context("reissuing without the oom killed guardian")?;
+
+    info!(target: LOG_DEVIMINT, %name, "Restarting oom killed guardian");
+    // reaps the killed process, and drops its cgroup along with the squeezed
+    // limit, so it gets a fresh one with the generous limit
+    fed.terminate_server(peer).await?;
+    fed.start_server(process_mgr, peer).await?;
+    let oom_kills = process_mgr.oom_kills(&name).await?;
+    reissue()
+        .await
+        .context("reissuing after restarting the oom killed guardian")?;
+    // waits for the restarted guardian to complete the latest session
+    fed.assert_consensus_consistent().await?;
+    anyhow::ensure!(
+        process_mgr.oom_kills(&name).await? == oom_kills,
+        "restarted guardian was oom killed again"
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-oom-test");
+    Ok(())
+}
+
+/// Checks the JSON logs of guardians spawned with `--guardian-json-logs` can
+/// be parsed and waited on, including the entries of a restarted guardian
+pub async fn guardian_json_logs_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    /// How long a guardian gets to log that it started
+    const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Guardian that gets restarted
+    const RESTARTED_PEER: usize = 0;
+
+    log_binary_versions().await?;
+
+    anyhow::ensure!(
+        process_mgr.guardian_json_logs(),
+        "guardian-json-logs-test needs guardians spawned with --guardian-json-logs"
+    );
+    if !FedimintdCmd::supports_json_logs().await {
+        info!(target: LOG_DEVIMINT, "fedimintd can't log JSON, skipping guardian-json-logs-test");
+        return Ok(());
+    }
+
+    let DevFed { mut fed, .. } = dev_fed;
+    let started = LogFilter {
+        min_level: Some(tracing::Level::INFO),
+        target: Some("fedimintd".to_owned()),
+        message_contains: Some("Starting fedimintd".to_owned()),
+    };
+    let peers: Vec<usize> = fed.members.keys().copied().collect();
+    for &peer in &peers {
+        let name = format!("fedimintd-{}-{peer}", fed.name());
+        let entry = process_mgr
+            .await_log(&name, &started, STARTUP_TIMEOUT)
+            .await?;
+        anyhow::ensure!(
+            entry.level() == Some(tracing::Level::INFO) && !entry.timestamp.is_empty(),
+            "unexpected startup log entry of {name}: {entry:?}"
+        );
+    }
+
+    let name = format!("fedimintd-{}-{RESTARTED_PEER}", fed.name());
+    fed.terminate_server(RESTARTED_PEER).await?;
+    fed.start_server(process_mgr, RESTARTED_PEER).await?;
+    poll_with_timeout("restarted guardian logs", STARTUP_TIMEOUT, || async {
+        let startups = process_mgr
+            .tail_logs(&name, &started, usize::MAX)
+            .await
+            .map_err(ControlFlow::Continue)?
+            .len();
+        match startups {
+            2 => Ok(()),
+            1 => Err(ControlFlow::Continue(anyhow!(
+                "{name} didn't log its restart yet"
+            ))),
+            _ => Err(ControlFlow::Break(anyhow!(
+                "{name} logged {startups} startups instead of 2"
+            ))),
+        }
+    })
+    .await?;
+    let last = process_mgr
+        .tail_logs(&name, &LogFilter::default(), 1)
+        .await?;
+    anyhow::ensure!(
+        last.len() == 1,
+        "tailing {name} returned {} entries instead of 1",
+        last.len()
+    );
+
+    info!(target: LOG_DEVIMINT, "fm success: guardian-json-logs-test");
+    Ok(())
+}
+
+pub async fn reattach_test(dev_fed: DevFed, process_mgr: &ProcessManager) -> Result<()> {
+    log_binary_versions().await?;
+
+    let handle = process_mgr.globals.FM_TEST_DIR.join("devfed-handle.json");
+    fs::write(
+        &handle,
+        serde_json::to_string(&dev_fed.to_handle(process_mgr)?)?,
+    )
+    .await?;
+    let devimint = env::current_exe()?.to_string_lossy().into_owned();
+    info!(target: LOG_DEVIMINT, "Attaching to the dev federation from another process");
+    cmd!(
+        vec![devimint],
+        "reattached-dev-fed-check",
+        "--handle",
+        handle.display()
+    )
+    .run()
+    .await?;
+
+    // the attached process must have left everything running on exit
+    dev_fed.fed.await_all_peers().await?;
+    for (name, gw) in [("gw-cln", &dev_fed.gw_cln), ("gw-lnd", &dev_fed.gw_lnd)] {
+        anyhow::ensure!(
+            gw.process.is_running().await,
+            "{name} stopped after another process detached from it"
+        );
+        gw.lightning_pubkey().await?;
+    }
+    let client = dev_fed
+        .fed
+        .new_joined_client("reattach-test-client")
+        .await?;
+    dev_fed.fed.pegin_client(10_000, &client).await?;
+
+    info!(target: LOG_DEVIMINT, "fm success: reattach-test");
+    Ok(())
+}
+
+/// Attaches to the dev federation described by the [`DevFedHandle`] in
+/// `handle` and uses it, for [`reattach_test`]
+pub async fn reattached_dev_fed_check(handle: &Path) -> Result<()> {
+    let handle: DevFedHandle = serde_json::from_str(&fs::read_to_string(handle).await?)?;
+    let process_mgr = handle.process_manager()?;
+    let dev_fed = DevFed::from_handle(&process_mgr, &handle).await?;
+
+    dev_fed.fed.await_all_peers().await?;
+    let client = dev_fed.fed.new_joined_client("reattached-client").await?;
+    dev_fed.fed.pegin_client(10_000, &client).await?;
+    anyhow::ensure!(
+        client.balance().await? == 10_000_000,
+        "reattached client didn't get its pegin"
+    );
+    for (name, gw) in [("gw-cln", &dev_fed.gw_cln), ("gw-lnd", &dev_fed.gw_lnd)] {
+        anyhow::ensure!(
+            !gw.process.is_running().await,
+            "reattached {name} claims to own its daemon"
+        );
+        gw.lightning_pubkey().await?;
+    }
+    // the daemons belong to the process that spawned them and keep running
+    drop(dev_fed);
+    Ok(())
+}
+
+/// Embeds devimint into a current-thread runtime, like a test harness with a
+/// runtime of its own, and pegs in a client from a `LocalSet` on it. Blocks
+/// the calling thread, which must not be driving a runtime.
+pub fn current_thread_test() -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let local = tokio::task::LocalSet::new();
+    local.block_on(&runtime, async {
+        let handle = tokio::runtime::Handle::current();
+        crate::run_devfed_test_on(&handle, |dev_fed, process_mgr| async move {
+            let dev_fed = dev_fed.to_dev_fed(&process_mgr).await?;
+            let client = dev_fed
+                .fed
+                .new_joined_client("current-thread-client")
+                .await?;
+            dev_fed.fed.pegin_client(10_000, &client).await?;
+            anyhow::ensure!(
+                client.balance().await? > 0,
+                "peg-in on a current-thread runtime left the client without funds"
+            );
+            // dropping stops the daemons, which may not block on the runtime
+            drop(dev_fed);
+            Ok(())
+        })
+        .await
+    })?;
+
+    info!(target: LOG_DEVIMINT, "fm success: current-thread-test");
+    Ok(())
+}
+
+/// Those of `ports` something listens on, on localhost
+fn ports_in_use(ports: &[u16]) -> Vec<u16> {
+    ports
+        .iter()
+        .copied()
+        .filter(|port| std::net::TcpListener::bind(("127.0.0.1", *port)).is_err())
+        .collect()
+}
+
+#[derive(Subcommand)]
+pub enum LatencyTest {
+    Reissue,
+    LnSend,
+    LnReceive,
+    FmPay,
+    Restore,
+}
+
+#[derive(Subcommand)]
+pub enum UpgradeTest {
+    Fedimintd {
+        #[arg(long, trailing_var_arg = true, num_args=1..)]
+        paths: Vec<PathBuf>,
+    },
+    FedimintCli {
+        #[arg(long, trailing_var_arg = true, num_args=1..)]
+        paths: Vec<PathBuf>,
+    },
+    /// Restarts a single guardian of a federation running the first of
+    /// `paths` on each of the following ones, checking its populated database
+    /// migrates and consensus resumes
+    FedimintdDbMigration {
+        #[arg(long, trailing_var_arg = true, num_args=1..)]
+        paths: Vec<PathBuf>,
+    },
+    Gatewayd {
+        #[arg(long, trailing_var_arg = true, num_args=1..)]
+        gatewayd_paths: Vec<PathBuf>,
+        #[arg(long, trailing_var_arg = true, num_args=1..)]
+        gateway_cli_paths: Vec<PathBuf>,
+        #[arg(long, trailing_var_arg = true, num_args=1..)]
+        gateway_cln_extension_paths: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TestCmd {
+    /// `devfed` then checks the average latency of reissuing ecash, LN receive,
+    /// and LN send
+    LatencyTests {
+        #[clap(subcommand)]
+        r#type: LatencyTest,
+
+        #[arg(long, default_value = "10")]
+        iterations: usize,
+    },
+    /// `devfed` then kills and restarts most of the Guardian nodes in a 4 node
+    /// fedimint
+    ReconnectTest,
+    /// `devfed` then tests a bunch of the fedimint-cli commands
+    CliTests,
+    /// `devfed` then calls binary `fedimint-load-test-tool`. See
+    /// `LoadTestArgs`.
+    LoadTestToolTest,
+    /// `devfed` then pegin CLN & LND nodes and gateways. Kill the LN nodes,
+    /// restart them, rejjoin fedimint and test payments still work
+    LightningReconnectTest,
     /// `devfed` then reboot gateway daemon for both CLN and LND. Test
     /// afterward.
     GatewayRebootTest,
@@ -2444,6 +5031,129 @@ pub enum TestCmd {
     GuardianBackup,
     /// `devfed` then tests that spent ecash cannot be double spent
     CannotReplayTransaction,
+    /// `devfed` then wipes the databases of `f` guardians and tests the
+    /// federation keeps working and the wiped guardians recover
+    ThresholdRecoveryTest,
+    /// `devfed` then kills a guardian with SIGTERM and SIGKILL, restarting it
+    /// each time, and tests it catches up with the federation
+    GuardianKillRecoveryTest,
+    /// `devfed` then restarts the lnd gateway on lnd attached to as an
+    /// external node, and tests the gateway uses the external node's TLS cert
+    /// and macaroon
+    ExternalLightningNodeTest,
+    /// `devfed` with guardians in their own network namespaces
+    /// (`FM_GUARDIAN_NETNS`), then tests a guardian's traffic can be cut and
+    /// restored
+    GuardianNetnsTest,
+    /// `devfed` with guardian clocks skewed by `FM_GUARDIAN_CLOCK_SKEW_SECS`,
+    /// then tests consensus keeps finalizing consistently
+    GuardianClockSkewTest,
+    /// `devfed` then takes more than `f` guardians offline, tests sessions
+    /// stop finalizing until one of them is back
+    OverThresholdOfflineTest,
+    /// `devfed` with some guardians on esplora from
+    /// `FM_GUARDIAN_BITCOIN_RPC`, then freezes esplora and tests consensus
+    /// keeps following the chain with the other guardians' backends
+    LaggingBitcoinBackendTest,
+    /// `devfed` then starts the LNURL server and pays one of its LNURLs
+    /// through the lnd gateway
+    LnurlPayTest,
+    /// `devfed` then changes the routing policy of lnd and cln on their
+    /// channel and tests the other side sees it
+    ChannelPolicyTest,
+    /// `devfed` with a mint charging the fees of `FM_MINT_FEES`, then
+    /// reissues notes and tests the fees are charged
+    MintFeesTest,
+    /// `devfed` then restarts esplora behind a throttled bitcoind with many
+    /// blocks to sync, testing it catches up
+    SlowBitcoindSyncTest,
+    /// `devfed` then restarts esplora with its web frontend and tests the
+    /// frontend can call esplora's API
+    EsploraFrontendTest,
+    /// `devfed` then pegs out and tests the online guardians signed the
+    /// peg-out transaction
+    PegoutSigningTest,
+    /// `devfed` then rotates a guardian's password and tests only the new one
+    /// is accepted
+    GuardianPasswordTest,
+    /// `devfed` then opens and cooperatively closes a channel between cln and
+    /// lnd `iterations` times, checking funds are conserved and no channel
+    /// gets stuck
+    ChannelChurnTest {
+        #[arg(long, default_value = "3")]
+        iterations: usize,
+    },
+    /// `devfed` then reorgs a peg-in out before it's final and double spends
+    /// it, testing the federation doesn't credit it
+    DoubleSpendTest,
+    /// `devfed` then fills the disk of a guardian with its own filesystem and
+    /// tests the federation carries on and the guardian recovers once space
+    /// is freed
+    GuardianFullDiskTest,
+    /// `devfed` then spends notes of exact denominations and tests they
+    /// reissue
+    ExactDenominationsTest,
+    /// `devfed` then reissues as many notes as fit into one transaction and
+    /// tests one more is rejected
+    TransactionSizeLimitTest,
+    /// `devfed` then takes more than `f` guardians offline and tests the
+    /// gateway reports the federation unreachable and reaches it again once
+    /// they are back
+    GatewayUnreachableFederationTest,
+    /// `devfed` then creates two clients from the same mnemonic and tests
+    /// they derive the same keys
+    MnemonicClientTest,
+    /// `devfed` then moves on-chain funds of the lnd gateway into a new
+    /// channel and tests the balances shift to lightning
+    GatewayOnchainRebalanceTest,
+    /// `devfed` then has the guardians update the federation's meta fields
+    /// through the meta module and tests clients pick up the new values,
+    /// logging the meta revision before and after. Module configs are fixed
+    /// after DKG, so there is no module config update to test instead.
+    MetaUpdateTest,
+    /// `devfed` with a proxy in front of a guardian's api
+    /// (`FM_GUARDIAN_RESPONSE_PROXY`), then has it corrupt the guardian's
+    /// responses and tests clients carry on with the other guardians
+    ByzantineGuardianTest,
+    /// `devfed` that gets dropped without shutting it down, then tests its
+    /// daemons terminated and freed their ports in time
+    DropCleanupTest,
+    /// `devfed` then kills the lnd gateway's lightning node while gatewayd
+    /// keeps running, and tests payments are rejected until it's restarted
+    GatewayLightningNodeDownTest,
+    /// `devfed` with the guardians in memory limited cgroups, then tests the
+    /// federation survives a guardian getting OOM killed
+    GuardianOomTest,
+    /// `devfed` then pays hold invoices of lnd through the cln gateway, and
+    /// tests it claims the ecash only once they settle and cancels the
+    /// payment once its HTLC fails
+    HoldInvoiceTest,
+    /// `devfed` then pays through a chosen gateway, and tests a payment it
+    /// can't route fails instead of using the other gateway
+    LnPayViaTest,
+    /// `devfed` then spends and reissues ecash over and over, and tests no
+    /// guardian's memory usage grows by much doing so
+    MemoryGrowthTest,
+    /// `devfed` then mines blocks, and tests bitcoind, the indexers, the
+    /// lightning nodes and the federation all end up at the new height
+    SyncedHeightTest,
+    /// `devfed` then attaches to it from another devimint process through a
+    /// `DevFedHandle`, and tests it keeps running after that one exits
+    ReattachTest,
+    /// Runs `devfed` on a current-thread runtime through
+    /// `run_devfed_test_on`, like a test harness embedding devimint, and pegs
+    /// in a client
+    CurrentThreadTest,
+    /// `devfed` with `--guardian-json-logs`, then tests the guardians' logs
+    /// parse and a restarted guardian's startup shows up in them
+    GuardianJsonLogsTest,
+    /// Attaches to the dev federation of the `DevFedHandle` in `handle`, run
+    /// by `reattach-test`
+    #[clap(hide = true)]
+    ReattachedDevFedCheck {
+        #[clap(long)]
+        handle: PathBuf,
+    },
     /// Test upgrade paths for a given binary
     UpgradeTests {
         #[clap(subcommand)]
@@ -2535,6 +5245,190 @@ pub async fn handle_command(cmd: TestCmd, common_args: CommonArgs) -> Result<()>
             let dev_fed = dev_fed(&process_mgr).await?;
             cannot_replay_tx_test(dev_fed).await?;
         }
+        TestCmd::ThresholdRecoveryTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            threshold_recovery_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::GuardianKillRecoveryTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_kill_recovery_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::ExternalLightningNodeTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            external_lightning_node_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::GuardianNetnsTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_netns_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::OverThresholdOfflineTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            over_threshold_offline_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::LaggingBitcoinBackendTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            lagging_bitcoin_backend_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::ChannelPolicyTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            channel_policy_test(dev_fed).await?;
+        }
+        TestCmd::LnurlPayTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            lnurl_pay_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::MintFeesTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            mint_fees_test(dev_fed).await?;
+        }
+        TestCmd::SlowBitcoindSyncTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            slow_bitcoind_sync_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::EsploraFrontendTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            esplora_frontend_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::PegoutSigningTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            pegout_signing_test(dev_fed).await?;
+        }
+        TestCmd::GuardianClockSkewTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_clock_skew_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::GuardianPasswordTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_password_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::ChannelChurnTest { iterations } => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            channel_churn_test(dev_fed, &process_mgr, iterations).await?;
+        }
+        TestCmd::DoubleSpendTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            double_spend_test(dev_fed).await?;
+        }
+        TestCmd::GuardianFullDiskTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_full_disk_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::ExactDenominationsTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            exact_denominations_test(dev_fed).await?;
+        }
+        TestCmd::TransactionSizeLimitTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            transaction_size_limit_test(dev_fed).await?;
+        }
+        TestCmd::GatewayUnreachableFederationTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            gateway_unreachable_federation_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::MnemonicClientTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            mnemonic_client_test(dev_fed).await?;
+        }
+        TestCmd::GatewayOnchainRebalanceTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            gateway_onchain_rebalance_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::MetaUpdateTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            meta_update_test(dev_fed).await?;
+        }
+        TestCmd::ByzantineGuardianTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            byzantine_guardian_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::DropCleanupTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            drop_cleanup_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::GatewayLightningNodeDownTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            gateway_lightning_node_down_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::GuardianOomTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            if let Err(err) = crate::cgroup::ensure_available() {
+                info!(err = %format!("{err:#}"), "Can't run guardians in cgroups, skipping guardian oom test");
+                return Ok(());
+            }
+            let process_mgr = process_mgr.with_resource_limits(
+                "fedimintd",
+                ResourceLimits {
+                    memory_max_bytes: Some(GUARDIAN_OOM_TEST_MEMORY_LIMIT),
+                    cpu_max_percent: None,
+                },
+            );
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_oom_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::HoldInvoiceTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            hold_invoice_test(dev_fed).await?;
+        }
+        TestCmd::LnPayViaTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            ln_pay_via_test(dev_fed).await?;
+        }
+        TestCmd::MemoryGrowthTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            memory_growth_test(dev_fed).await?;
+        }
+        TestCmd::SyncedHeightTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            synced_height_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::ReattachTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            reattach_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::CurrentThreadTest => {
+            // off this runtime's threads, which can't start another runtime
+            tokio::task::spawn_blocking(current_thread_test).await??;
+        }
+        TestCmd::GuardianJsonLogsTest => {
+            let (process_mgr, _) = setup(common_args).await?;
+            let dev_fed = dev_fed(&process_mgr).await?;
+            guardian_json_logs_test(dev_fed, &process_mgr).await?;
+        }
+        TestCmd::ReattachedDevFedCheck { handle } => {
+            fedimint_logging::TracingSetup::default().init()?;
+            reattached_dev_fed_check(&handle).await?;
+        }
         TestCmd::UpgradeTests { binary } => {
             let (process_mgr, _) = setup(common_args).await?;
             Box::pin(upgrade_tests(&process_mgr, binary)).await?;
@@ -0,0 +1,129 @@
+//! Slowing down what a daemon serves.
+//!
+//! A [`ThrottledProxy`] forwards TCP connections to a daemon and caps how
+//! fast the daemon's responses flow back, e.g. to make bitcoind serve blocks
+//! slowly to an indexer and test how it copes with a slow initial sync.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use fedimint_logging::LOG_DEVIMINT;
+use futures::future::BoxFuture;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+use crate::proxy::{Passthrough, TcpProxy, Transform};
+
+/// Largest chunk forwarded at once, smaller when throttled so the capped
+/// rate is kept smoothly
+const MAX_CHUNK: usize = 16 * 1024;
+
+/// TCP proxy to `target` capping the bytes per second `target` sends back on
+/// each connection, unthrottled until [`Self::set_bytes_per_sec`].
+///
+/// Stops accepting and drops all connections once the last clone is dropped.
+#[derive(Clone)]
+pub struct ThrottledProxy {
+    proxy: TcpProxy,
+    bytes_per_sec: Arc<AtomicU64>,
+    bytes_served: Arc<AtomicU64>,
+}
+
+/// [`Transform`] throttling the target's responses
+struct Throttle {
+    bytes_per_sec: Arc<AtomicU64>,
+    bytes_served: Arc<AtomicU64>,
+}
+
+impl Transform for Throttle {
+    fn forward<'a>(
+        &'a self,
+        reader: &'a mut (dyn AsyncBufRead + Unpin + Send),
+        writer: &'a mut (dyn AsyncWrite + Unpin + Send),
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(copy_throttled(
+            reader,
+            writer,
+            &self.bytes_per_sec,
+            &self.bytes_served,
+        ))
+    }
+}
+
+impl ThrottledProxy {
+    /// Starts proxying to `target` on a free localhost port
+    pub async fn start(target: SocketAddr) -> Result<Self> {
+        let bytes_per_sec = Arc::new(AtomicU64::new(0));
+        let bytes_served = Arc::new(AtomicU64::new(0));
+        let proxy = TcpProxy::start(
+            "throttled proxy",
+            SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+            target,
+            Passthrough,
+            Throttle {
+                bytes_per_sec: bytes_per_sec.clone(),
+                bytes_served: bytes_served.clone(),
+            },
+        )
+        .await?;
+        Ok(Self {
+            proxy,
+            bytes_per_sec,
+            bytes_served,
+        })
+    }
+
+    /// Address to connect to instead of the target
+    pub fn addr(&self) -> SocketAddr {
+        self.proxy.addr()
+    }
+
+    pub fn target(&self) -> SocketAddr {
+        self.proxy.target()
+    }
+
+    /// Caps the rate of each connection's responses to `bytes_per_sec`, or
+    /// lifts the cap with `None`. Applies to open connections too.
+    pub fn set_bytes_per_sec(&self, bytes_per_sec: Option<u64>) {
+        debug!(target: LOG_DEVIMINT, addr = %self.addr(), ?bytes_per_sec, "Throttling proxy");
+        self.bytes_per_sec
+            .store(bytes_per_sec.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Total bytes the target sent back through the proxy so far
+    pub fn bytes_served(&self) -> u64 {
+        self.bytes_served.load(Ordering::Relaxed)
+    }
+}
+
+/// Copies `reader` to `writer` at no more than `bytes_per_sec`, 0 meaning
+/// unthrottled, until `reader` is exhausted
+async fn copy_throttled(
+    reader: &mut (impl AsyncRead + Unpin + ?Sized),
+    writer: &mut (impl AsyncWrite + Unpin + ?Sized),
+    bytes_per_sec: &AtomicU64,
+    bytes_served: &AtomicU64,
+) -> Result<()> {
+    let mut buf = vec![0; MAX_CHUNK];
+    loop {
+        let rate = bytes_per_sec.load(Ordering::Relaxed);
+        let chunk = if rate == 0 {
+            MAX_CHUNK
+        } else {
+            // about 10 chunks per second
+            (rate / 10).clamp(1, MAX_CHUNK as u64) as usize
+        };
+        let read = reader.read(&mut buf[..chunk]).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        writer.write_all(&buf[..read]).await?;
+        bytes_served.fetch_add(read as u64, Ordering::Relaxed);
+        if rate != 0 {
+            fedimint_core::runtime::sleep(Duration::from_secs_f64(read as f64 / rate as f64)).await;
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use std::collections::BTreeMap;
-use std::ffi::OsStr;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{OsStr, OsString};
 use std::future::Future;
 use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
 use std::{env, unreachable};
 
-use anyhow::{anyhow, bail, format_err, Context, Result};
+use anyhow::{anyhow, bail, ensure, format_err, Context, Result};
 use fedimint_api_client::api::StatusResponse;
 use fedimint_core::admin_client::{
     ConfigGenParamsRequest, ConfigGenParamsResponse, PeerServerParams,
@@ -15,31 +16,41 @@
 use fedimint_core::config::ServerModuleConfigGenParamsRegistry;
 use fedimint_core::envs::is_env_var_set;
 use fedimint_core::module::ApiAuth;
-use fedimint_core::task::{self, block_in_place, block_on};
+use fedimint_core::task;
 use fedimint_core::time::now;
 use fedimint_core::PeerId;
 use fedimint_logging::LOG_DEVIMINT;
 use semver::Version;
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use tokio::fs::OpenOptions;
 use tokio::process::Child;
+use tokio::runtime::{Handle, RuntimeFlavor};
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+use tracing::{debug, error, info, warn};
 
+use crate::cgroup::{Cgroup, ResourceLimits};
 use crate::envs::{
     FM_BACKWARDS_COMPATIBILITY_TEST_ENV, FM_BITCOIND_BASE_EXECUTABLE_ENV,
     FM_BITCOIN_CLI_BASE_EXECUTABLE_ENV, FM_BTC_CLIENT_ENV, FM_DEVIMINT_CMD_INHERIT_STDERR_ENV,
-    FM_ELECTRS_BASE_EXECUTABLE_ENV, FM_ESPLORA_BASE_EXECUTABLE_ENV, FM_FAUCET_BASE_EXECUTABLE_ENV,
-    FM_FEDIMINTD_BASE_EXECUTABLE_ENV, FM_FEDIMINT_CLI_BASE_EXECUTABLE_ENV,
-    FM_FEDIMINT_DBTOOL_BASE_EXECUTABLE_ENV, FM_GATEWAYD_BASE_EXECUTABLE_ENV,
-    FM_GATEWAY_CLI_BASE_EXECUTABLE_ENV, FM_GATEWAY_CLN_EXTENSION_BASE_EXECUTABLE_ENV,
-    FM_GWCLI_CLN_ENV, FM_GWCLI_LND_ENV, FM_LIGHTNINGD_BASE_EXECUTABLE_ENV,
-    FM_LIGHTNING_CLI_BASE_EXECUTABLE_ENV, FM_LIGHTNING_CLI_ENV, FM_LNCLI_BASE_EXECUTABLE_ENV,
-    FM_LNCLI_ENV, FM_LND_BASE_EXECUTABLE_ENV, FM_LOAD_TEST_TOOL_BASE_EXECUTABLE_ENV,
-    FM_LOGS_DIR_ENV, FM_MINT_CLIENT_ENV, FM_RECOVERYTOOL_BASE_EXECUTABLE_ENV,
+    FM_DEVIMINT_ENV_PREFIX_ENV, FM_DEVIMINT_SUPERVISOR_PID_ENV, FM_ELECTRS_BASE_EXECUTABLE_ENV,
+    FM_ESPLORA_BASE_EXECUTABLE_ENV, FM_ESPLORA_FRONTEND_BASE_EXECUTABLE_ENV,
+    FM_FAUCET_BASE_EXECUTABLE_ENV, FM_FEDIMINTD_BASE_EXECUTABLE_ENV,
+    FM_FEDIMINT_CLI_BASE_EXECUTABLE_ENV, FM_FEDIMINT_DBTOOL_BASE_EXECUTABLE_ENV,
+    FM_GATEWAYD_BASE_EXECUTABLE_ENV, FM_GATEWAY_CLI_BASE_EXECUTABLE_ENV,
+    FM_GATEWAY_CLN_EXTENSION_BASE_EXECUTABLE_ENV, FM_GWCLI_CLN_ENV, FM_GWCLI_LND_ENV,
+    FM_LIGHTNINGD_BASE_EXECUTABLE_ENV, FM_LIGHTNING_CLI_BASE_EXECUTABLE_ENV, FM_LIGHTNING_CLI_ENV,
+    FM_LNCLI_BASE_EXECUTABLE_ENV, FM_LNCLI_ENV, FM_LND_BASE_EXECUTABLE_ENV,
+    FM_LNURL_SERVER_BASE_EXECUTABLE_ENV, FM_LOAD_TEST_TOOL_BASE_EXECUTABLE_ENV, FM_LOGS_DIR_ENV,
+    FM_MINT_CLIENT_ENV, FM_RECOVERYTOOL_BASE_EXECUTABLE_ENV,
 };
+use crate::setup_events::SetupObserver;
 use crate::version_constants::VERSION_0_5_0_ALPHA;
 
+/// File in `FM_TEST_DIR` the running devimint lists its daemons in, see
+/// [`read_process_list`]
+const PROCESS_LIST_FILE: &str = "processes.json";
+
 // If a binary doesn't provide a clap version, default to the first stable
 // release (v0.2.1)
 const DEFAULT_VERSION: Version = Version::new(0, 2, 1);
@@ -62,6 +73,137 @@ pub fn parse_map(s: &str) -> Result<BTreeMap<String, String>> {
     Ok(map)
 }
 
+/// Parses per-daemon `RUST_LOG` overrides like
+/// `fedimintd:debug;gatewayd-lnd:info,fm::net=trace`
+pub fn parse_rust_log_overrides(s: &str) -> Result<BTreeMap<String, String>> {
+    s.split(';')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (daemon, directives) = entry
+                .split_once(':')
+                .with_context(|| format!("Invalid RUST_LOG override: {entry}"))?;
+            Ok((daemon.trim().to_owned(), directives.trim().to_owned()))
+        })
+        .collect()
+}
+
+/// Parses the per-guardian list `value` of env variable `var`, like
+/// `FM_GUARDIAN_LABELS`, by peer id.
+///
+/// Entries are comma separated in peer id order and there can be at most
+/// one per guardian of a federation of `servers`. An empty entry leaves its
+/// guardian out without shifting the ones after it, so `alice,,bob`
+/// configures guardians 0 and 2.
+pub fn parse_guardian_list<T>(
+    var: &str,
+    value: &str,
+    servers: usize,
+    mut parse: impl FnMut(&str) -> Result<T>,
+) -> Result<BTreeMap<usize, T>> {
+    if value.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let entries: Vec<_> = value.split(',').map(str::trim).collect();
+    ensure!(
+        entries.len() <= servers,
+        "{var} has {} entries for {servers} guardians",
+        entries.len()
+    );
+    entries
+        .into_iter()
+        .enumerate()
+        .filter(|(_, entry)| !entry.is_empty())
+        .map(|(peer_id, entry)| {
+            let parsed = parse(entry).with_context(|| {
+                format!("{var} has invalid entry {entry} for guardian {peer_id}")
+            })?;
+            Ok((peer_id, parsed))
+        })
+        .collect()
+}
+
+/// [`parse_guardian_list`] of the env variable `var`, configuring no
+/// guardian if it's unset
+pub fn guardian_list_from_env<T>(
+    var: &str,
+    servers: usize,
+    parse: impl FnMut(&str) -> Result<T>,
+) -> Result<BTreeMap<usize, T>> {
+    parse_guardian_list(var, &env::var(var).unwrap_or_default(), servers, parse)
+}
+
+/// Parses a comma separated list of guardian peer ids, like the
+/// `FM_GUARDIAN_RESPONSE_PROXY` env variable, for a federation of `servers`
+pub fn parse_guardian_ids(var: &str, value: &str, servers: usize) -> Result<BTreeSet<usize>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|peer_id| !peer_id.is_empty())
+        .map(|peer_id| {
+            let peer_id: usize = peer_id
+                .parse()
+                .with_context(|| format!("{var} has invalid peer id {peer_id}"))?;
+            ensure!(
+                peer_id < servers,
+                "{var} has peer id {peer_id} for a federation of {servers}"
+            );
+            Ok(peer_id)
+        })
+        .collect()
+}
+
+/// [`parse_guardian_ids`] of the env variable `var`, selecting no guardian if
+/// it's unset
+pub fn guardian_ids_from_env(var: &str, servers: usize) -> Result<BTreeSet<usize>> {
+    parse_guardian_ids(var, &env::var(var).unwrap_or_default(), servers)
+}
+
+/// How [`ProcessManager::kill`] stops a daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KillSignal {
+    /// Orderly shutdown, the daemon gets to clean up
+    Sigterm,
+    /// Hard kill without any cleanup, like a power loss
+    Sigkill,
+}
+
+impl From<KillSignal> for nix::sys::signal::Signal {
+    fn from(signal: KillSignal) -> Self {
+        match signal {
+            KillSignal::Sigterm => Self::SIGTERM,
+            KillSignal::Sigkill => Self::SIGKILL,
+        }
+    }
+}
+
+/// Runs the blocking `f`, like a call to bitcoind's blocking RPC client,
+/// handing the worker's other tasks off first on a multi-threaded runtime.
+/// Elsewhere, e.g. on a current-thread runtime or in a
+/// [`tokio::task::LocalSet`] where [`tokio::task::block_in_place`] panics, it
+/// just blocks the thread while `f` waits on the daemon it talks to, see
+/// [`crate::run_devfed_test_on`].
+pub fn block_in_place<R>(f: impl FnOnce() -> R) -> R {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            fedimint_core::runtime::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// Waits up to `timeout` for `child` to exit, blocking the thread, and
+/// returns whether it did
+fn wait_blocking(child: &mut Child, timeout: Duration) -> Result<bool> {
+    let start = std::time::Instant::now();
+    while child.try_wait()?.is_none() {
+        if timeout <= start.elapsed() {
+            return Ok(false);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    Ok(true)
+}
+
 fn send_sigterm(child: &Child) {
     send_signal(child, nix::sys::signal::Signal::SIGTERM);
 }
@@ -77,6 +219,32 @@ fn send_signal(child: &Child, signal: nix::sys::signal::Signal) {
     );
 }
 
+/// Whether a daemon started on an empty data dir or on state left there by an
+/// earlier run, e.g. when `FM_TEST_DIR` points at a previous devimint session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchKind {
+    Fresh,
+    Reattached,
+}
+
+impl LaunchKind {
+    /// Detects how a daemon is launched from `state`, a path that only exists
+    /// in its data dir once the daemon ran there before. Must be called
+    /// before the daemon is spawned.
+    pub async fn detect(name: &str, state: &Path) -> Result<Self> {
+        let launch_kind = if tokio::fs::try_exists(state)
+            .await
+            .with_context(|| format!("checking {}", state.display()))?
+        {
+            Self::Reattached
+        } else {
+            Self::Fresh
+        };
+        debug!(target: LOG_DEVIMINT, %name, ?launch_kind, "Launching daemon");
+        Ok(launch_kind)
+    }
+}
+
 /// Kills process when all references to ProcessHandle are dropped.
 ///
 /// NOTE: drop order is significant make sure fields in struct are declared in
@@ -85,6 +253,18 @@ fn send_signal(child: &Child, signal: nix::sys::signal::Signal) {
 pub struct ProcessHandle(Arc<Mutex<ProcessHandleInner>>);
 
 impl ProcessHandle {
+    /// Handle to the daemon `name` that another process spawned and owns,
+    /// so it's never stopped through this handle and counts as not running
+    pub fn reattached(name: &str) -> Self {
+        Self(Arc::new(Mutex::new(ProcessHandleInner {
+            name: name.to_owned(),
+            child: None,
+            started_at: now(),
+            command: None,
+            cgroup: None,
+        })))
+    }
+
     pub async fn terminate(&self) -> Result<()> {
         let mut inner = self.0.lock().await;
         inner.terminate().await?;
@@ -93,12 +273,91 @@ pub async fn terminate(&self) -> Result<()> {
     pub async fn is_running(&self) -> bool {
         self.0.lock().await.child.is_some()
     }
+
+    /// Like [`Self::terminate`], but blocks the thread until the process
+    /// exited instead of awaiting it, for stopping it in `Drop`, which may run
+    /// where there's no runtime to await on
+    pub(crate) fn terminate_blocking(&self) -> Result<()> {
+        self.0
+            .try_lock()
+            .map_err(|_| anyhow!("process handle is in use"))?
+            .terminate_blocking()
+    }
+
+    /// Like [`Self::is_running`], `None` while the handle is in use
+    pub(crate) fn try_is_running(&self) -> Option<bool> {
+        self.0.try_lock().ok().map(|inner| inner.child.is_some())
+    }
+
+    /// Name the daemon was spawned as, like `fedimintd-default-0`
+    pub async fn name(&self) -> String {
+        self.0.lock().await.name.clone()
+    }
+
+    /// What the daemon was spawned with, `None` if it was reattached
+    pub(crate) async fn command(&self) -> Option<DaemonCommand> {
+        self.0.lock().await.command.clone()
+    }
+
+    /// Pid of the process, `None` once it was stopped
+    pub async fn pid(&self) -> Option<u32> {
+        self.0.lock().await.child.as_ref().and_then(Child::id)
+    }
+
+    /// Sends `signal` to the process and waits until it exited, without
+    /// escalating like [`Self::terminate`] does
+    pub async fn kill(&self, signal: KillSignal) -> Result<()> {
+        self.0.lock().await.kill(signal).await
+    }
+
+    /// Changes the memory limit of the daemon's cgroup, e.g. to put it under
+    /// memory pressure, see [`ProcessManager::with_resource_limits`]
+    pub async fn set_memory_limit(&self, bytes: u64) -> Result<()> {
+        self.cgroup().await?.set_memory_max(bytes)
+    }
+
+    /// How many times the kernel OOM killed the daemon or something it forked
+    pub async fn oom_kills(&self) -> Result<u64> {
+        self.cgroup().await?.oom_kills()
+    }
+
+    async fn cgroup(&self) -> Result<Arc<Cgroup>> {
+        let inner = self.0.lock().await;
+        inner.cgroup.clone().with_context(|| {
+            format!(
+                "{} runs in no cgroup, see ProcessManager::with_resource_limits",
+                inner.name
+            )
+        })
+    }
+
+    /// Freezes the process with `SIGSTOP` until [`Self::resume`], so it stops
+    /// making progress and answering requests while keeping its state
+    pub async fn pause(&self) -> Result<()> {
+        self.0
+            .lock()
+            .await
+            .signal(nix::sys::signal::Signal::SIGSTOP)
+    }
+
+    /// Lets a process frozen by [`Self::pause`] continue
+    pub async fn resume(&self) -> Result<()> {
+        self.0
+            .lock()
+            .await
+            .signal(nix::sys::signal::Signal::SIGCONT)
+    }
 }
 
 #[derive(Debug)]
 pub struct ProcessHandleInner {
     name: String,
     child: Option<Child>,
+    /// When `child` was spawned
+    started_at: SystemTime,
+    command: Option<DaemonCommand>,
+    /// See [`ProcessManager::with_resource_limits`]
+    cgroup: Option<Arc<Cgroup>>,
 }
 
 impl ProcessHandleInner {
@@ -140,6 +399,76 @@ async fn terminate(&mut self) -> anyhow::Result<()> {
         self.child.take();
         Ok(())
     }
+
+    /// [`Self::terminate`] blocking the thread
+    fn terminate_blocking(&mut self) -> anyhow::Result<()> {
+        if let Some(child) = self.child.as_mut() {
+            debug!(
+                target: LOG_DEVIMINT,
+                name=%self.name,
+                signal="SIGTERM",
+                "sending signal to terminate child process"
+            );
+
+            send_sigterm(child);
+
+            if !wait_blocking(child, Duration::from_secs(2))? {
+                debug!(
+                    target: LOG_DEVIMINT,
+                    name=%self.name,
+                    signal="SIGKILL",
+                    "sending signal to terminate child process"
+                );
+
+                send_sigkill(child);
+
+                if !wait_blocking(child, Duration::from_secs(5))
+                    .with_context(|| format!("Failed to terminate child process {}", self.name))?
+                {
+                    bail!("Failed to terminate child process {}: timeout", self.name);
+                }
+            }
+        }
+        // only drop the child handle if succeeded to terminate
+        self.child.take();
+        Ok(())
+    }
+
+    fn signal(&self, signal: nix::sys::signal::Signal) -> anyhow::Result<()> {
+        let Some(child) = self.child.as_ref() else {
+            bail!("Child process {} is not running", self.name);
+        };
+        debug!(target: LOG_DEVIMINT, name=%self.name, ?signal, "sending signal to child process");
+        send_signal(child, signal);
+        Ok(())
+    }
+
+    async fn kill(&mut self, signal: KillSignal) -> anyhow::Result<()> {
+        let Some(child) = self.child.as_mut() else {
+            bail!("Child process {} is not running", self.name);
+        };
+        debug!(
+            target: LOG_DEVIMINT,
+            name=%self.name,
+            ?signal,
+            "sending signal to kill child process"
+        );
+        send_signal(child, signal.into());
+        match fedimint_core::runtime::timeout(Duration::from_secs(30), child.wait()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(err)) => {
+                bail!("Failed to kill child process {}: {}", self.name, err);
+            }
+            Err(_) => {
+                bail!(
+                    "Child process {} did not exit after {signal:?}: timeout",
+                    self.name
+                );
+            }
+        }
+        self.child.take();
+        Ok(())
+    }
 }
 
 impl Drop for ProcessHandleInner {
@@ -148,8 +477,9 @@ fn drop(&mut self) {
             return;
         }
 
+        // blocking rather than awaiting, so dropping works on any runtime
         block_in_place(|| {
-            if let Err(err) = block_on(self.terminate()) {
+            if let Err(err) = self.terminate_blocking() {
                 warn!(target: LOG_DEVIMINT,
                         name=%self.name,
                         %err,
@@ -162,42 +492,832 @@ fn drop(&mut self) {
 #[derive(Clone)]
 pub struct ProcessManager {
     pub globals: super::vars::Global,
+    /// `RUST_LOG` directives per daemon, see [`Self::with_rust_log`]
+    rust_log: BTreeMap<String, String>,
+    /// See [`Self::with_resource_limits`]
+    resource_limits: BTreeMap<String, ResourceLimits>,
+    /// Latest daemon spawned under each name, see [`Self::kill`]
+    daemons: Arc<Daemons>,
+    /// Held while updating [`PROCESS_LIST_FILE`], so concurrent spawns don't
+    /// overwrite it with an outdated list
+    process_list_lock: Arc<Mutex<()>>,
+    /// See [`Self::with_run_id`]
+    run_id: Option<String>,
+    /// See [`Self::with_watchdog`]
+    max_restarts: Option<u32>,
+    /// Times each daemon got restarted by the watchdog, by name
+    restarts: Arc<std::sync::Mutex<BTreeMap<String, u32>>>,
+    /// See [`Self::with_setup_observer`]
+    setup_observer: Option<Arc<dyn SetupObserver>>,
+    /// Name of every daemon ever spawned, by pid, see
+    /// [`Self::assert_no_leaked_processes`]
+    spawned: Arc<std::sync::Mutex<BTreeMap<u32, String>>>,
+    /// See [`Self::with_process_group`]
+    process_group: Option<Arc<ProcessGroup>>,
+    /// See [`Self::with_guardian_json_logs`]
+    guardian_json_logs: bool,
+    /// See [`Self::with_guardian_labels`]
+    guardian_labels: Option<String>,
 }
 
 impl ProcessManager {
     pub fn new(globals: super::vars::Global) -> Self {
-        Self { globals }
+        Self {
+            globals,
+            rust_log: BTreeMap::new(),
+            resource_limits: BTreeMap::new(),
+            daemons: Arc::default(),
+            process_list_lock: Arc::default(),
+            run_id: None,
+            max_restarts: None,
+            restarts: Arc::default(),
+            setup_observer: None,
+            spawned: Arc::default(),
+            process_group: None,
+            guardian_json_logs: false,
+            guardian_labels: None,
+        }
+    }
+
+    /// Reports the progress of dev federations set up with this process
+    /// manager to `observer`
+    pub fn with_setup_observer(mut self, observer: Arc<dyn SetupObserver>) -> Self {
+        self.setup_observer = Some(observer);
+        self
+    }
+
+    pub fn setup_observer(&self) -> Option<Arc<dyn SetupObserver>> {
+        self.setup_observer.clone()
+    }
+
+    /// Restarts daemons spawned from now on against their data dirs when they
+    /// crash, up to `max_restarts` times each, to keep soak tests running
+    /// through transient crashes. Off by default, as most tests want crashes
+    /// to fail loudly.
+    ///
+    /// Daemons stopped through their [`ProcessHandle`] or [`Self::kill`]
+    /// aren't restarted.
+    pub fn with_watchdog(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = Some(max_restarts);
+        self
+    }
+
+    /// Creates a process group and spawns daemons from now on into it, so a
+    /// single `kill -- -<pgid>` stops all of them with everything they
+    /// forked, even when devimint itself got killed. Once the last clone of
+    /// this process manager is dropped, whatever is left in the group gets
+    /// `SIGKILL`.
+    ///
+    /// The daemons then no longer get the terminal's `SIGINT` on Ctrl-C,
+    /// devimint shuts them down instead.
+    pub fn with_process_group(mut self) -> Result<Self> {
+        self.process_group = Some(Arc::new(ProcessGroup::new()?));
+        Ok(self)
+    }
+
+    /// Id of the process group of [`Self::with_process_group`]
+    pub fn process_group_id(&self) -> Option<i32> {
+        self.process_group.as_ref().map(|group| group.id())
+    }
+
+    /// Has guardians spawned from now on log JSON lines instead of text,
+    /// where the fedimintd binary supports it, so tests can parse and filter
+    /// their logs with [`Self::tail_logs`] regardless of how the text format
+    /// changes
+    pub fn with_guardian_json_logs(mut self) -> Self {
+        self.guardian_json_logs = true;
+        self
+    }
+
+    pub fn guardian_json_logs(&self) -> bool {
+        self.guardian_json_logs
+    }
+
+    /// Labels guardians of dev federations like `alice,,bob`, overriding
+    /// `FM_GUARDIAN_LABELS`
+    pub fn with_guardian_labels(mut self, labels: &str) -> Self {
+        self.guardian_labels = Some(labels.to_owned());
+        self
+    }
+
+    pub fn guardian_labels(&self) -> Option<&str> {
+        self.guardian_labels.as_deref()
+    }
+
+    /// How many times the watchdog restarted the daemon `name`
+    pub fn restart_count(&self, name: &str) -> u32 {
+        self.restarts
+            .lock()
+            .expect("locking can't fail")
+            .get(name)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Names this run, so the env vars of devimints running concurrently on
+    /// one machine (e.g. CI shards) can be told apart, see
+    /// [`Self::env_vars`]. Ports don't need it, they are already allocated
+    /// through the cross-process `fedimint_portalloc`.
+    pub fn with_run_id(mut self, run_id: &str) -> Result<Self> {
+        run_env_prefix(run_id)?;
+        self.run_id = Some(run_id.to_owned());
+        Ok(self)
+    }
+
+    pub fn run_id(&self) -> Option<&str> {
+        self.run_id.as_deref()
+    }
+
+    /// Prefix of this run's env var names, empty without a run id
+    pub fn env_prefix(&self) -> String {
+        self.run_id
+            .as_deref()
+            .map(|run_id| run_env_prefix(run_id).expect("checked by with_run_id"))
+            .unwrap_or_default()
+    }
+
+    /// The global env vars named with [`Self::env_prefix`], plus
+    /// `FM_DEVIMINT_ENV_PREFIX` holding the prefix itself so tooling can find
+    /// them. Daemons are still spawned with the unprefixed names.
+    pub fn env_vars(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        let prefix = self.env_prefix();
+        std::iter::once((FM_DEVIMINT_ENV_PREFIX_ENV.to_owned(), prefix.clone())).chain(
+            self.globals
+                .vars()
+                .map(move |(var, value)| (format!("{prefix}{var}"), value)),
+        )
+    }
+
+    /// Env file lines exporting `var`, also under [`Self::env_prefix`] with a
+    /// run id, for the dev federation's own vars like its invite code
+    pub fn env_exports(&self, var: &str, value: &str) -> String {
+        env_exports(&self.env_prefix(), var, value)
+    }
+
+    /// Sends `signal` to the running daemon spawned as `name` (e.g.
+    /// `fedimintd-default-0`) and returns once it exited.
+    ///
+    /// The daemon's owner isn't notified and still considers it spawned, for
+    /// guardians use [`crate::federation::Federation::kill_server`] instead.
+    pub async fn kill(&self, name: &str, signal: KillSignal) -> Result<()> {
+        self.daemon(name)?.kill(signal).await
+    }
+
+    /// Freezes the running daemon spawned as `name` until [`Self::resume`],
+    /// e.g. to make a backend stall, see [`ProcessHandle::pause`]
+    pub async fn pause(&self, name: &str) -> Result<()> {
+        self.daemon(name)?.pause().await
+    }
+
+    /// Lets the daemon `name` frozen by [`Self::pause`] continue
+    pub async fn resume(&self, name: &str) -> Result<()> {
+        self.daemon(name)?.resume().await
+    }
+
+    fn daemon(&self, name: &str) -> Result<ProcessHandle> {
+        let inner = self
+            .daemons
+            .lock()
+            .expect("locking can't fail")
+            .get(name)
+            .and_then(Weak::upgrade)
+            .with_context(|| format!("No daemon named {name} was spawned"))?;
+        Ok(ProcessHandle(inner))
+    }
+
+    /// Spawn daemons named `daemon` (e.g. `fedimintd`, `gatewayd-lnd`) with
+    /// `RUST_LOG=directives` instead of the inherited one. A name also
+    /// matches all instances, so `fedimintd` covers every guardian. Only
+    /// affects daemons that read `RUST_LOG`.
+    pub fn with_rust_log(mut self, daemon: &str, directives: &str) -> Self {
+        self.rust_log
+            .insert(daemon.to_owned(), directives.to_owned());
+        self
+    }
+
+    /// Most specific `RUST_LOG` override for the daemon `name`, if any
+    fn rust_log_for(&self, name: &str) -> Option<&str> {
+        most_specific(&self.rust_log, name).map(String::as_str)
+    }
+
+    /// Spawn daemons named `daemon`, matched like in [`Self::with_rust_log`],
+    /// in a linux cgroup of their own with `limits`, to test them with
+    /// constrained resources or keep them from running away with a CI
+    /// runner's. Where cgroups can't be created, see [`crate::cgroup`],
+    /// spawning these daemons fails.
+    pub fn with_resource_limits(mut self, daemon: &str, limits: ResourceLimits) -> Self {
+        self.resource_limits.insert(daemon.to_owned(), limits);
+        self
+    }
+
+    /// Changes the memory limit of the daemon spawned as `name`, see
+    /// [`ProcessHandle::set_memory_limit`]
+    pub async fn set_memory_limit(&self, name: &str, bytes: u64) -> Result<()> {
+        self.daemon(name)?.set_memory_limit(bytes).await
+    }
+
+    /// How many times the kernel OOM killed the daemon `name`, see
+    /// [`ProcessHandle::oom_kills`]
+    pub async fn oom_kills(&self, name: &str) -> Result<u64> {
+        self.daemon(name)?.oom_kills().await
     }
 
     /// Logs to $FM_LOGS_DIR/{name}.{out,err}
     pub async fn spawn_daemon(&self, name: &str, mut cmd: Command) -> Result<ProcessHandle> {
-        let logs_dir = env::var(FM_LOGS_DIR_ENV)?;
-        let path = format!("{logs_dir}/{name}.log");
-        let log = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(path)
-            .await?
-            .into_std()
-            .await;
-        cmd.cmd.kill_on_drop(false); // we handle killing ourself
-        cmd.cmd.stdout(log.try_clone()?);
-        cmd.cmd.stderr(log);
-        let child = cmd
-            .cmd
-            .spawn()
-            .with_context(|| format!("Could not spawn: {name}"))?;
+        if let Some(directives) = self.rust_log_for(name) {
+            cmd.cmd.env("RUST_LOG", directives);
+        }
+        let daemon_cmd = DaemonCommand::new(&cmd);
+        let mut cgroup = most_specific(&self.resource_limits, name)
+            .map(|limits| {
+                Cgroup::create(name, *limits)
+                    .with_context(|| format!("running {name} with resource limits"))
+            })
+            .transpose()?
+            .map(Arc::new);
+        let child = spawn_logged(name, cmd.cmd, self.process_group.as_deref(), &mut cgroup).await?;
+        record_spawned(&self.spawned, name, &child);
+        let started_at = now();
         let handle = ProcessHandle(Arc::new(Mutex::new(ProcessHandleInner {
             name: name.to_owned(),
             child: Some(child),
+            started_at,
+            command: Some(daemon_cmd.clone()),
+            cgroup,
         })));
+        self.daemons
+            .lock()
+            .expect("locking can't fail")
+            .insert(name.to_owned(), Arc::downgrade(&handle.0));
+        if let Some(max_restarts) = self.max_restarts {
+            self.spawn_watchdog(name, daemon_cmd, &handle, started_at, max_restarts);
+        }
+        write_process_list(
+            &self.daemons,
+            &self.process_list_lock,
+            &self.globals.FM_TEST_DIR,
+        )
+        .await;
         Ok(handle)
     }
+
+    /// Spawns the stopped daemon of `handle` again with what it was spawned
+    /// with, against the same data dir. The new process is tracked by
+    /// `handle` and all its clones.
+    pub async fn respawn(&self, handle: &ProcessHandle) -> Result<()> {
+        let mut inner = handle.0.lock().await;
+        ensure!(
+            inner.child.is_none(),
+            "{} is still running, stop it before respawning it",
+            inner.name
+        );
+        let daemon_cmd = inner
+            .command
+            .clone()
+            .with_context(|| format!("{} wasn't spawned by devimint", inner.name))?;
+        let name = inner.name.clone();
+        let child = spawn_logged(
+            &name,
+            daemon_cmd.to_command(),
+            self.process_group.as_deref(),
+            &mut inner.cgroup,
+        )
+        .await?;
+        record_spawned(&self.spawned, &name, &child);
+        let started_at = now();
+        inner.child = Some(child);
+        inner.started_at = started_at;
+        drop(inner);
+        if let Some(max_restarts) = self.max_restarts {
+            self.spawn_watchdog(&name, daemon_cmd, handle, started_at, max_restarts);
+        }
+        write_process_list(
+            &self.daemons,
+            &self.process_list_lock,
+            &self.globals.FM_TEST_DIR,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Every daemon spawned so far, the latest one under each name, ordered by
+    /// name. Daemons stopped or dropped by their owner are listed as
+    /// [`ProcessStatus::Stopped`].
+    ///
+    /// Only briefly locks each daemon's handle, so it's cheap enough to poll,
+    /// though it waits for daemons that are being stopped.
+    pub async fn list(&self) -> Vec<ProcessInfo> {
+        list_daemons(&self.daemons).await
+    }
+
+    fn spawn_watchdog(
+        &self,
+        name: &str,
+        daemon_cmd: DaemonCommand,
+        handle: &ProcessHandle,
+        mut started_at: SystemTime,
+        max_restarts: u32,
+    ) {
+        let name = name.to_owned();
+        let weak_inner = Arc::downgrade(&handle.0);
+        let restarts = self.restarts.clone();
+        let spawned = self.spawned.clone();
+        let process_group = self.process_group.clone();
+        let daemons = self.daemons.clone();
+        let process_list_lock = self.process_list_lock.clone();
+        let test_dir = self.globals.FM_TEST_DIR.clone();
+        fedimint_core::runtime::spawn(&format!("watchdog {name}"), async move {
+            loop {
+                fedimint_core::runtime::sleep(WATCHDOG_INTERVAL).await;
+                // the owner dropped the daemon
+                let Some(inner) = weak_inner.upgrade() else {
+                    return;
+                };
+                let mut inner = inner.lock().await;
+                // respawned by `ProcessManager::respawn`, which watches the
+                // new process itself
+                if inner.started_at != started_at {
+                    return;
+                }
+                // stopped on purpose
+                let Some(child) = inner.child.as_mut() else {
+                    return;
+                };
+                let status = match child.try_wait() {
+                    Ok(None) => continue,
+                    Ok(Some(status)) => status,
+                    Err(err) => {
+                        warn!(target: LOG_DEVIMINT, %name, %err, "Watchdog failed to check daemon");
+                        return;
+                    }
+                };
+                let restart_count = restarts
+                    .lock()
+                    .expect("locking can't fail")
+                    .get(&name)
+                    .copied()
+                    .unwrap_or_default();
+                if max_restarts <= restart_count {
+                    error!(target: LOG_DEVIMINT, %name, %status, restart_count, "Daemon crashed, giving up restarting it");
+                    return;
+                }
+                warn!(target: LOG_DEVIMINT, %name, %status, restart_count, "Daemon crashed, restarting it");
+                match spawn_logged(
+                    &name,
+                    daemon_cmd.to_command(),
+                    process_group.as_deref(),
+                    &mut inner.cgroup,
+                )
+                .await
+                {
+                    Ok(child) => {
+                        record_spawned(&spawned, &name, &child);
+                        inner.child = Some(child);
+                        inner.started_at = now();
+                        started_at = inner.started_at;
+                    }
+                    Err(err) => {
+                        error!(target: LOG_DEVIMINT, %name, %err, "Failed to restart crashed daemon");
+                        return;
+                    }
+                }
+                *restarts
+                    .lock()
+                    .expect("locking can't fail")
+                    .entry(name.clone())
+                    .or_default() += 1;
+                drop(inner);
+                write_process_list(&daemons, &process_list_lock, &test_dir).await;
+            }
+        });
+    }
+}
+
+impl ProcessManager {
+    /// Errors unless every daemon spawned so far has exited, along with
+    /// everything they forked off, even processes that double-forked to
+    /// detach from their daemon. Lists each survivor's pid and command line.
+    ///
+    /// Meant for after all daemons were terminated. Processes get a few
+    /// seconds to finish exiting.
+    pub async fn assert_no_leaked_processes(&self) -> Result<()> {
+        poll_with_timeout("no leaked processes", Duration::from_secs(10), || async {
+            let survivors = self.surviving_processes();
+            if survivors.is_empty() {
+                return Ok(());
+            }
+            let survivors = survivors
+                .iter()
+                .map(|(pid, cmdline)| format!("{pid}: {cmdline}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Err(ControlFlow::Continue(anyhow!(
+                "processes spawned by devimint are still running:\n{survivors}"
+            )))
+        })
+        .await
+    }
+
+    /// Spawned daemons and their descendants that are still running, with
+    /// their command lines
+    fn surviving_processes(&self) -> BTreeMap<u32, String> {
+        let spawned = self.spawned.lock().expect("locking can't fail").clone();
+        // pids of exited daemons may get reused, so where descendants can be
+        // told apart by environment that's all that is checked
+        let candidates = match descendant_pids() {
+            Some(pids) => pids
+                .into_iter()
+                .map(|pid| (pid, spawned.get(&pid).cloned().unwrap_or_default()))
+                .collect(),
+            None => spawned,
+        };
+        candidates
+            .into_iter()
+            .filter(|(pid, _)| process_is_running(*pid))
+            .map(|(pid, name)| (pid, process_cmdline(pid).unwrap_or(name)))
+            .collect()
+    }
+}
+
+type Daemons = std::sync::Mutex<BTreeMap<String, Weak<Mutex<ProcessHandleInner>>>>;
+
+/// What a daemon of the [`ProcessManager`] is for, told by its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessRole {
+    Bitcoind,
+    /// cln or lnd
+    Lightning,
+    Electrs,
+    /// esplora or its web frontend
+    Esplora,
+    Guardian,
+    Gateway,
+    /// Anything else, like the faucet
+    Other,
+}
+
+impl ProcessRole {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "bitcoind" => Self::Bitcoind,
+            "lightningd" | "lnd" => Self::Lightning,
+            "electrs" => Self::Electrs,
+            "esplora" | "esplora-frontend" => Self::Esplora,
+            _ if name.starts_with("fedimintd-") => Self::Guardian,
+            _ if name.starts_with("gatewayd-") => Self::Gateway,
+            _ => Self::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessStatus {
+    Running,
+    /// Exited on its own, e.g. crashed, with its exit code unless it was
+    /// killed by a signal or exited unobserved
+    Exited {
+        code: Option<i32>,
+    },
+    /// Stopped on purpose, or dropped by its owner
+    Stopped,
+}
+
+/// A daemon of the [`ProcessManager`], see [`ProcessManager::list`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    /// Name it was spawned as, like `fedimintd-default-0`
+    pub name: String,
+    pub role: ProcessRole,
+    /// `None` unless it is running
+    pub pid: Option<u32>,
+    pub status: ProcessStatus,
+    /// When its current process was spawned, later after a watchdog restart
+    pub started_at: Option<SystemTime>,
+}
+
+impl ProcessInfo {
+    /// How long it has been running, `None` unless it is
+    pub fn uptime(&self) -> Option<Duration> {
+        if self.status != ProcessStatus::Running {
+            return None;
+        }
+        now().duration_since(self.started_at?).ok()
+    }
+}
+
+async fn list_daemons(daemons: &Daemons) -> Vec<ProcessInfo> {
+    let daemons: Vec<_> = daemons
+        .lock()
+        .expect("locking can't fail")
+        .iter()
+        .map(|(name, inner)| (name.clone(), inner.clone()))
+        .collect();
+    let mut list = Vec::with_capacity(daemons.len());
+    for (name, inner) in daemons {
+        let role = ProcessRole::from_name(&name);
+        let Some(inner) = inner.upgrade() else {
+            list.push(ProcessInfo {
+                name,
+                role,
+                pid: None,
+                status: ProcessStatus::Stopped,
+                started_at: None,
+            });
+            continue;
+        };
+        let mut inner = inner.lock().await;
+        let started_at = inner.started_at;
+        let (pid, status) = match inner.child.as_mut() {
+            None => (None, ProcessStatus::Stopped),
+            Some(child) => match child.try_wait() {
+                Ok(Some(status)) => (
+                    None,
+                    ProcessStatus::Exited {
+                        code: status.code(),
+                    },
+                ),
+                Ok(None) | Err(_) => (child.id(), ProcessStatus::Running),
+            },
+        };
+        list.push(ProcessInfo {
+            name,
+            role,
+            pid,
+            status,
+            started_at: Some(started_at),
+        });
+    }
+    list
+}
+
+/// Lists the daemons in [`PROCESS_LIST_FILE`] for [`read_process_list`].
+/// Failing to write it only warns, the list is a debugging aid.
+async fn write_process_list(daemons: &Daemons, lock: &Mutex<()>, test_dir: &Path) {
+    let _guard = lock.lock().await;
+    let list = list_daemons(daemons).await;
+    let result = async {
+        let tmp = test_dir.join(format!("{PROCESS_LIST_FILE}.tmp"));
+        tokio::fs::write(&tmp, serde_json::to_vec_pretty(&list)?).await?;
+        tokio::fs::rename(&tmp, test_dir.join(PROCESS_LIST_FILE)).await?;
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(err) = result {
+        warn!(target: LOG_DEVIMINT, %err, "Failed to write process list");
+    }
+}
+
+/// Daemons of the devimint running in `test_dir`, as it last listed them
+/// when spawning one. Daemons that since exited are reported as exited, for
+/// an up to date status use [`ProcessManager::list`] from within devimint.
+pub async fn read_process_list(test_dir: &Path) -> Result<Vec<ProcessInfo>> {
+    let path = test_dir.join(PROCESS_LIST_FILE);
+    let list = tokio::fs::read(&path)
+        .await
+        .with_context(|| format!("reading {}, is devimint running?", path.display()))?;
+    let mut list: Vec<ProcessInfo> = serde_json::from_slice(&list)?;
+    for info in &mut list {
+        if info.status == ProcessStatus::Running && !info.pid.is_some_and(process_is_running) {
+            info.pid = None;
+            info.status = ProcessStatus::Exited { code: None };
+        }
+    }
+    Ok(list)
+}
+
+fn record_spawned(spawned: &std::sync::Mutex<BTreeMap<u32, String>>, name: &str, child: &Child) {
+    if let Some(pid) = child.id() {
+        spawned
+            .lock()
+            .expect("locking can't fail")
+            .insert(pid, name.to_owned());
+    }
+}
+
+/// Processes carrying the `FM_DEVIMINT_SUPERVISOR_PID` of this devimint in
+/// their environment, i.e. descendants of the daemons it spawned however they
+/// got detached. `None` without a Linux `/proc` to read environments from.
+fn descendant_pids() -> Option<Vec<u32>> {
+    let marker = format!("{FM_DEVIMINT_SUPERVISOR_PID_ENV}={}", std::process::id());
+    let entries = std::fs::read_dir("/proc").ok()?;
+    let pids = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| {
+            std::fs::read(format!("/proc/{pid}/environ")).is_ok_and(|environ| {
+                environ
+                    .split(|byte| *byte == 0)
+                    .any(|var| var == marker.as_bytes())
+            })
+        })
+        .collect();
+    Some(pids)
+}
+
+/// Whether `pid` is alive, zombies waiting to be reaped count as exited
+fn process_is_running(pid: u32) -> bool {
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+        // the state follows the parenthesized command name, which may itself
+        // contain parentheses
+        return stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.trim_start().chars().next())
+            .is_some_and(|state| !matches!(state, 'Z' | 'X'));
+    }
+    nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as _), None).is_ok()
+}
+
+fn process_cmdline(pid: u32) -> Option<String> {
+    let cmdline = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let cmdline = cmdline
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!cmdline.is_empty()).then_some(cmdline)
+}
+
+/// Process group shared by all daemons, see
+/// [`ProcessManager::with_process_group`]
+#[derive(Debug)]
+pub struct ProcessGroup {
+    /// Idle process leading the group, so the group exists, and its id can't
+    /// be reused, however often all daemons in it exited. It waits to read
+    /// its stdin, which closes along with devimint.
+    leader: std::sync::Mutex<std::process::Child>,
+    pgid: i32,
+}
+
+impl ProcessGroup {
+    pub fn new() -> Result<Self> {
+        use std::os::unix::process::CommandExt as _;
+
+        let leader = std::process::Command::new("cat")
+            .process_group(0)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Could not spawn process group leader")?;
+        let pgid = i32::try_from(leader.id())?;
+        info!(target: LOG_DEVIMINT, pgid, "Spawning daemons into process group");
+        Ok(Self {
+            leader: std::sync::Mutex::new(leader),
+            pgid,
+        })
+    }
+
+    /// Spawns `cmd` into the group
+    pub fn spawn(&self, cmd: &mut tokio::process::Command) -> std::io::Result<Child> {
+        cmd.process_group(self.pgid).spawn()
+    }
+
+    pub fn id(&self) -> i32 {
+        self.pgid
+    }
+
+    /// Sends `signal` to every process in the group
+    ///
+    /// Refuses once the leader exited: the group may be gone by then, and its
+    /// id taken by an unrelated group
+    pub fn signal(&self, signal: nix::sys::signal::Signal) -> Result<()> {
+        let pgid = self.pgid;
+        // the leader's pid, and so the group id, stays taken until it's reaped
+        let mut leader = self.leader.lock().expect("locking can't fail");
+        if let Some(status) = leader.try_wait()? {
+            bail!("Leader of process group {pgid} exited with {status}, not sending {signal}");
+        }
+        nix::sys::signal::killpg(nix::unistd::Pid::from_raw(pgid), signal)
+            .with_context(|| format!("Failed to send {signal} to process group {pgid}"))
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        // daemons were terminated gracefully by their handles already, this
+        // only catches what escaped them, the leader included
+        let _ = self.signal(nix::sys::signal::Signal::SIGKILL);
+        let _ = self.leader.lock().expect("locking can't fail").wait();
+    }
+}
+
+/// How often the watchdog of [`ProcessManager::with_watchdog`] checks whether
+/// a daemon crashed
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Spawns `cmd` with its output appended to `$FM_LOGS_DIR/{name}.log`, into
+/// `group` and `cgroup` if given. Clears `cgroup` if moving the process into
+/// it failed, as it then runs without its limits.
+async fn spawn_logged(
+    name: &str,
+    mut cmd: tokio::process::Command,
+    group: Option<&ProcessGroup>,
+    cgroup: &mut Option<Arc<Cgroup>>,
+) -> Result<Child> {
+    let logs_dir = env::var(FM_LOGS_DIR_ENV)?;
+    let path = format!("{logs_dir}/{name}.log");
+    let log = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .await?
+        .into_std()
+        .await;
+    cmd.kill_on_drop(false); // we handle killing ourself
+    cmd.env(
+        FM_DEVIMINT_SUPERVISOR_PID_ENV,
+        std::process::id().to_string(),
+    );
+    cmd.stdout(log.try_clone()?);
+    cmd.stderr(log);
+    let child = match group {
+        Some(group) => group.spawn(&mut cmd),
+        None => cmd.spawn(),
+    }
+    .with_context(|| format!("Could not spawn: {name}"))?;
+    if let (Some(added), Some(pid)) = (cgroup.as_deref(), child.id()) {
+        if let Err(err) = added.add(pid) {
+            warn!(target: LOG_DEVIMINT, %name, err = %format!("{err:#}"), "Failed to move daemon into its cgroup, running it without resource limits");
+            *cgroup = None;
+        }
+    }
+    Ok(child)
+}
+
+/// Value of the most specific key of `map` matching the daemon `name`, which
+/// is `name` itself or a prefix of it up to a `-`, so `fedimintd` matches
+/// every guardian
+fn most_specific<'a, T>(map: &'a BTreeMap<String, T>, name: &str) -> Option<&'a T> {
+    map.iter()
+        .filter(|(daemon, _)| {
+            name == daemon.as_str()
+                || name
+                    .strip_prefix(daemon.as_str())
+                    .is_some_and(|rest| rest.starts_with('-'))
+        })
+        .max_by_key(|(daemon, _)| daemon.len())
+        .map(|(_, value)| value)
+}
+
+/// What a daemon was spawned with, as [`tokio::process::Command`] can't be
+/// cloned or spawned a second time
+#[derive(Debug, Clone)]
+pub(crate) struct DaemonCommand {
+    pub(crate) program: OsString,
+    pub(crate) args: Vec<OsString>,
+    /// Whether the daemon starts without devimint's env variables, see
+    /// [`Command::env_clear`]
+    pub(crate) env_clear: bool,
+    /// Env variables set or, if `None`, removed on top of devimint's own
+    pub(crate) envs: Vec<(OsString, Option<OsString>)>,
+    pub(crate) current_dir: Option<PathBuf>,
+}
+
+impl DaemonCommand {
+    fn new(command: &Command) -> Self {
+        let cmd = command.cmd.as_std();
+        Self {
+            env_clear: command.env_clear,
+            program: cmd.get_program().to_owned(),
+            args: cmd.get_args().map(ToOwned::to_owned).collect(),
+            envs: cmd
+                .get_envs()
+                .map(|(key, value)| (key.to_owned(), value.map(ToOwned::to_owned)))
+                .collect(),
+            current_dir: cmd.get_current_dir().map(ToOwned::to_owned),
+        }
+    }
+
+    fn to_command(&self) -> tokio::process::Command {
+        self.to_std_command().into()
+    }
+
+    fn to_std_command(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new(&self.program);
+        cmd.args(&self.args);
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        for (key, value) in &self.envs {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+        if let Some(current_dir) = &self.current_dir {
+            cmd.current_dir(current_dir);
+        }
+        cmd
+    }
 }
 
 pub struct Command {
     pub cmd: tokio::process::Command,
     pub args_debug: Vec<String>,
+    /// See [`Self::env_clear`]
+    env_clear: bool,
 }
 
 impl Command {
@@ -234,6 +1354,15 @@ pub fn envs<I, K, V>(mut self, env: I) -> Self
         self
     }
 
+    /// Starts the command without any of devimint's env variables, only the
+    /// ones set with [`Self::env`]. Daemons spawned from it keep that when the
+    /// watchdog restarts them, so clear it here rather than on `cmd`.
+    pub fn env_clear(mut self) -> Self {
+        self.cmd.env_clear();
+        self.env_clear = true;
+        self
+    }
+
     pub fn kill_on_drop(mut self, kill: bool) -> Self {
         self.cmd.kill_on_drop(kill);
         self
@@ -299,9 +1428,26 @@ pub async fn assert_error_contains(&mut self, error: &str) -> Result<()> {
         .await
     }
 
-    pub async fn run_inner(&mut self, expect_success: bool) -> Result<std::process::Output> {
-        debug!(target: LOG_DEVIMINT, "> {}", self.command_debug());
+    /// Returns the json output if the command succeeds and the json error if
+    /// it has a non-zero exit code, for commands that may go either way
+    pub async fn out_or_err_json(
+        &mut self,
+    ) -> Result<Result<serde_json::Value, serde_json::Value>> {
         let output = self
+            .output()
+            .await
+            .with_context(|| format!("command: {}", self.command_debug()))?;
+        let json = serde_json::from_str(String::from_utf8(output.stdout)?.trim())?;
+        Ok(if output.status.success() {
+            Ok(json)
+        } else {
+            Err(json)
+        })
+    }
+
+    async fn output(&mut self) -> Result<std::process::Output> {
+        debug!(target: LOG_DEVIMINT, "> {}", self.command_debug());
+        Ok(self
             .cmd
             .stdout(Stdio::piped())
             .stderr(if is_env_var_set(FM_DEVIMINT_CMD_INHERIT_STDERR_ENV) {
@@ -311,7 +1457,11 @@ pub async fn run_inner(&mut self, expect_success: bool) -> Result<std::process::
             })
             .spawn()?
             .wait_with_output()
-            .await?;
+            .await?)
+    }
+
+    pub async fn run_inner(&mut self, expect_success: bool) -> Result<std::process::Output> {
+        let output = self.output().await?;
 
         if output.status.success() != expect_success {
             bail!(
@@ -333,6 +1483,24 @@ pub async fn run(&mut self) -> Result<()> {
         Ok(())
     }
 
+    /// Runs the command to completion blocking the thread, for where there's
+    /// no runtime to await it on, like in `Drop`
+    pub fn run_blocking(&self) -> Result<()> {
+        let output = DaemonCommand::new(self)
+            .to_std_command()
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("command: {}", self.command_debug()))?;
+        ensure!(
+            output.status.success(),
+            "command: {} failed with {}: {}",
+            self.command_debug(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(())
+    }
+
     /// Run the command logging the output and error
     pub async fn run_with_logging(&mut self, name: String) -> Result<()> {
         let logs_dir = env::var(FM_LOGS_DIR_ENV)?;
@@ -528,6 +1696,7 @@ fn cmd(self) -> Command {
         Command {
             cmd: tokio::process::Command::new(self),
             args_debug: vec![self.to_owned()],
+            env_clear: false,
         }
     }
 }
@@ -570,7 +1739,7 @@ pub fn get_gateway_cli_path() -> Vec<String> {
     )
 }
 
-const GATEWAY_CLN_EXTENSION_FALLBACK: &str = "gateway-cln-extension";
+pub(crate) const GATEWAY_CLN_EXTENSION_FALLBACK: &str = "gateway-cln-extension";
 
 pub fn get_gateway_cln_extension_path(default_path: &str) -> String {
     let paths = get_command_str_for_alias(
@@ -624,10 +1793,14 @@ pub fn get_bitcoin_cli_path() -> Vec<String> {
 
 const ESPLORA_FALLBACK: &str = "esplora";
 
+const ESPLORA_FRONTEND_FALLBACK: &str = "esplora-frontend";
+
 const RECOVERYTOOL_FALLBACK: &str = "fedimint-recoverytool";
 
 const FAUCET_FALLBACK: &str = "faucet";
 
+const LNURL_SERVER_FALLBACK: &str = "lnurl-server";
+
 const FEDIMINT_DBTOOL_FALLBACK: &str = "fedimint-dbtool";
 
 pub fn get_fedimint_dbtool_cli_path() -> Vec<String> {
@@ -666,6 +1839,15 @@ pub async fn version_or_default() -> Version {
                 .unwrap_or(DEFAULT_VERSION),
         }
     }
+
+    /// Whether fedimintd takes `--log-json`, which pre-release builds of a
+    /// version may or may not have, so its version doesn't tell
+    pub async fn supports_json_logs() -> bool {
+        cmd!(FedimintdCmd, "--help")
+            .out_string()
+            .await
+            .is_ok_and(|help| help.contains("--log-json"))
+    }
 }
 
 pub struct Gatewayd;
@@ -1123,6 +2305,19 @@ pub fn cmd(self) -> Command {
     }
 }
 
+/// Esplora web frontend reading `API_URL` and `PORT`, devimint's own
+/// `esplora-frontend` unless overridden with Blockstream's, e.g. a wrapper
+/// running `npm run dev-server` in its checkout
+pub struct EsploraFrontend;
+impl EsploraFrontend {
+    pub fn cmd(self) -> Command {
+        to_command(get_command_str_for_alias(
+            &[FM_ESPLORA_FRONTEND_BASE_EXECUTABLE_ENV],
+            &[ESPLORA_FRONTEND_FALLBACK],
+        ))
+    }
+}
+
 pub struct Recoverytool;
 impl Recoverytool {
     pub fn cmd(self) -> Command {
@@ -1143,6 +2338,16 @@ pub fn cmd(self) -> Command {
     }
 }
 
+pub struct LnurlServer;
+impl LnurlServer {
+    pub fn cmd(self) -> Command {
+        to_command(get_command_str_for_alias(
+            &[FM_LNURL_SERVER_BASE_EXECUTABLE_ENV],
+            &[LNURL_SERVER_FALLBACK],
+        ))
+    }
+}
+
 fn get_command_str_for_alias(aliases: &[&str], default: &[&str]) -> Vec<String> {
     // try to use one of the aliases if set
     for alias in aliases {
@@ -1160,6 +2365,51 @@ fn to_command(cli: Vec<String>) -> Command {
     Command {
         cmd,
         args_debug: cli,
+        env_clear: false,
+    }
+}
+
+/// Summary of a series of latencies, see [`stats_for`]
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub min: Duration,
+    pub avg: Duration,
+    pub median: Duration,
+    pub p90: Duration,
+    pub max: Duration,
+    pub sum: Duration,
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "min: {:.1}s", self.min.as_secs_f32())?;
+        write!(f, ", avg: {:.1}s", self.avg.as_secs_f32())?;
+        write!(f, ", median: {:.1}s", self.median.as_secs_f32())?;
+        write!(f, ", p90: {:.1}s", self.p90.as_secs_f32())?;
+        write!(f, ", max: {:.1}s", self.max.as_secs_f32())?;
+        write!(f, ", sum: {:.1}s", self.sum.as_secs_f32())?;
+        Ok(())
+    }
+}
+
+/// Summarizes the non-empty `v`
+pub fn stats_for(mut v: Vec<Duration>) -> Stats {
+    assert!(!v.is_empty());
+    v.sort();
+    let n = v.len();
+    let min = v.first().unwrap().to_owned();
+    let max = v.iter().last().unwrap().to_owned();
+    let median = v[n / 2];
+    let sum: Duration = v.iter().sum();
+    let avg = sum / n as u32;
+    let p90 = v[(n as f32 * 0.9) as usize];
+    Stats {
+        min,
+        avg,
+        median,
+        p90,
+        max,
+        sum,
     }
 }
 
@@ -1197,3 +2447,219 @@ fn test_parse_clap_version() -> Result<()> {
 
     Ok(())
 }
+
+/// Env var name prefix of the run `run_id`, e.g. `FM_RUN_SHARD_3_` for
+/// `shard_3`
+pub(crate) fn run_env_prefix(run_id: &str) -> Result<String> {
+    ensure!(
+        !run_id.is_empty()
+            && run_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        "Run id must be non-empty ascii alphanumerics or `_`, got: {run_id:?}"
+    );
+    Ok(format!("FM_RUN_{}_", run_id.to_ascii_uppercase()))
+}
+
+/// Env file lines exporting `var`, and `{prefix}{var}` unless `prefix` is
+/// empty
+pub(crate) fn env_exports(prefix: &str, var: &str, value: &str) -> String {
+    // hope that value doesn't contain a "
+    let mut exports = format!("export {var}=\"{value}\"\n");
+    if !prefix.is_empty() {
+        exports.push_str(&format!("export {prefix}{var}=\"{value}\"\n"));
+    }
+    exports
+}
+
+#[tokio::test]
+async fn test_process_group_signal_stops_all_members() -> Result<()> {
+    use tokio::io::AsyncBufReadExt as _;
+
+    let group = ProcessGroup::new()?;
+    let mut first = group.spawn(tokio::process::Command::new("sleep").arg("60"))?;
+    // forks a grandchild and reports its pid, like a daemon detaching a helper
+    let mut member = group.spawn(
+        tokio::process::Command::new("sh")
+            .args(["-c", "sleep 60 & echo $!; wait"])
+            .stdout(Stdio::piped()),
+    )?;
+    let mut grandchild_pid = String::new();
+    tokio::io::BufReader::new(member.stdout.take().expect("stdout is piped"))
+        .read_line(&mut grandchild_pid)
+        .await?;
+    let grandchild_pid: u32 = grandchild_pid.trim().parse()?;
+    assert!(process_is_running(grandchild_pid));
+
+    group.signal(nix::sys::signal::Signal::SIGKILL)?;
+    fedimint_core::runtime::timeout(Duration::from_secs(5), async {
+        first.wait().await?;
+        member.wait().await?;
+        while process_is_running(grandchild_pid) {
+            fedimint_core::runtime::sleep(Duration::from_millis(10)).await;
+        }
+        Ok::<_, anyhow::Error>(())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[test]
+fn test_process_group_not_signalled_after_leader_exited() -> Result<()> {
+    let group = ProcessGroup::new()?;
+    {
+        let mut leader = group.leader.lock().expect("locking can't fail");
+        leader.kill()?;
+        leader.wait()?;
+    }
+    assert!(group.signal(nix::sys::signal::Signal::SIGTERM).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_process_role_from_name() {
+    assert_eq!(ProcessRole::from_name("bitcoind"), ProcessRole::Bitcoind);
+    assert_eq!(ProcessRole::from_name("lnd"), ProcessRole::Lightning);
+    assert_eq!(
+        ProcessRole::from_name("esplora-frontend"),
+        ProcessRole::Esplora
+    );
+    assert_eq!(
+        ProcessRole::from_name("fedimintd-default-0"),
+        ProcessRole::Guardian
+    );
+    assert_eq!(ProcessRole::from_name("gatewayd-ldk"), ProcessRole::Gateway);
+    assert_eq!(ProcessRole::from_name("faucet"), ProcessRole::Other);
+    // only names of the form `fedimintd-<federation>-<peer>` are guardians
+    assert_eq!(ProcessRole::from_name("fedimintd"), ProcessRole::Other);
+}
+
+#[test]
+fn test_run_env_prefix() -> Result<()> {
+    assert_eq!(run_env_prefix("shard_3")?, "FM_RUN_SHARD_3_");
+    assert!(run_env_prefix("").is_err());
+    assert!(run_env_prefix("shard-3").is_err());
+    assert_eq!(
+        env_exports("FM_RUN_SHARD_3_", "FM_INVITE_CODE", "fed11"),
+        "export FM_INVITE_CODE=\"fed11\"\nexport FM_RUN_SHARD_3_FM_INVITE_CODE=\"fed11\"\n"
+    );
+    assert_eq!(
+        env_exports("", "FM_INVITE_CODE", "fed11"),
+        "export FM_INVITE_CODE=\"fed11\"\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_rust_log_overrides() -> Result<()> {
+    assert_eq!(parse_rust_log_overrides("")?, BTreeMap::new());
+    assert_eq!(
+        parse_rust_log_overrides("fedimintd:debug; gatewayd-lnd:info,fm::net=trace;")?,
+        BTreeMap::from([
+            ("fedimintd".to_owned(), "debug".to_owned()),
+            ("gatewayd-lnd".to_owned(), "info,fm::net=trace".to_owned()),
+        ])
+    );
+    assert!(parse_rust_log_overrides("fedimintd=debug").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_guardian_list() -> Result<()> {
+    let parse = |value| parse_guardian_list("FM_TEST", value, 4, |entry| Ok(entry.to_owned()));
+    assert_eq!(
+        parse("alice,,bob")?,
+        BTreeMap::from([(0, "alice".to_owned()), (2, "bob".to_owned())])
+    );
+    assert_eq!(
+        parse(" , carol ,,")?,
+        BTreeMap::from([(1, "carol".to_owned())])
+    );
+    assert_eq!(parse("")?, BTreeMap::new());
+    assert!(parse("a,b,c,d,e").is_err());
+
+    let err =
+        parse_guardian_list("FM_TEST", "1,x", 2, |entry| Ok(entry.parse::<u64>()?)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "FM_TEST has invalid entry x for guardian 1"
+    );
+
+    assert_eq!(
+        parse_guardian_ids("FM_TEST", "3,, 1,3", 4)?,
+        BTreeSet::from([1, 3])
+    );
+    assert!(parse_guardian_ids("FM_TEST", "4", 4).is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_watchdog_restarts_killed_daemon() -> Result<()> {
+    let test_dir = env::temp_dir().join(format!("devimint-watchdog-{}", std::process::id()));
+    let globals = super::vars::Global::new(&test_dir, 1, 0).await?;
+    let process_mgr = ProcessManager::new(globals).with_watchdog(1);
+    // exits right away, and so keeps crashing, if it sees devimint's env
+    let daemon = process_mgr
+        .spawn_daemon(
+            "watchdog-test",
+            cmd!(
+                "sh",
+                "-c",
+                r#"[ -z "$FM_TEST_DIR" ] && [ "$FM_WATCHDOG_TEST" = 1 ] && exec sleep 60"#
+            )
+            .env_clear()
+            .env("PATH", env::var_os("PATH").unwrap_or_default())
+            .env("FM_WATCHDOG_TEST", "1"),
+        )
+        .await?;
+    let pid = daemon.pid().await.context("daemon is running")?;
+
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(i32::try_from(pid)?),
+        nix::sys::signal::Signal::SIGKILL,
+    )?;
+    let restarted_pid = poll_with_timeout(
+        "watchdog restarting the daemon",
+        Duration::from_secs(10),
+        || async {
+            match daemon.pid().await {
+                Some(restarted_pid) if restarted_pid != pid => Ok(restarted_pid),
+                _ => Err(ControlFlow::Continue(anyhow!("daemon not restarted yet"))),
+            }
+        },
+    )
+    .await?;
+    assert_eq!(process_mgr.restart_count("watchdog-test"), 1);
+
+    // still running a few watchdog intervals later, so it was restarted with
+    // its env cleared
+    fedimint_core::runtime::sleep(3 * WATCHDOG_INTERVAL).await;
+    assert!(process_is_running(restarted_pid));
+    assert_eq!(daemon.pid().await, Some(restarted_pid));
+
+    daemon.terminate().await?;
+    std::fs::remove_dir_all(&test_dir)?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_dropping_daemon_on_current_thread_runtime_stops_it() -> Result<()> {
+    let test_dir = env::temp_dir().join(format!("devimint-drop-{}", std::process::id()));
+    let globals = super::vars::Global::new(&test_dir, 1, 0).await?;
+    let process_mgr = ProcessManager::new(globals);
+    let daemon = process_mgr
+        .spawn_daemon("drop-test", cmd!("sleep", "60"))
+        .await?;
+    let pid = daemon.pid().await.context("daemon is running")?;
+
+    // would panic if dropping blocked on the runtime
+    drop(daemon);
+    assert!(!process_is_running(pid));
+
+    std::fs::remove_dir_all(&test_dir)?;
+    Ok(())
+}
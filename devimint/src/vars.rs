@@ -2,6 +2,7 @@
 
 use std::path::{Path, PathBuf};
 use std::str::FromStr as _;
+use std::sync::atomic::{AtomicU16, Ordering};
 
 use fedimint_core::envs::FM_ENABLE_MODULE_LNV2_ENV;
 
@@ -9,6 +10,11 @@ pub trait ToEnvVar {
     fn to_env_value(&self) -> Option<String>;
 }
 
+/// Inverse of [`ToEnvVar`], `value` is `None` where `to_env_value` was
+pub trait FromEnvVar: Sized {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self>;
+}
+
 macro_rules! declare_vars {
     ($struct:ident = ($($args:tt)*) =>
         {
@@ -40,6 +46,23 @@ pub fn vars(&self) -> impl Iterator<Item = (&'static str, String)> {
                 )*
                 env.into_iter()
             }
+
+            /// Reads back what [`Self::vars`] returned, without creating
+            /// directories or allocating ports like [`Self::init`]
+            pub fn from_vars(
+                vars: &::std::collections::BTreeMap<String, String>,
+            ) -> ::anyhow::Result<Self> {
+                Ok(Self {
+                    $(
+                        $name: ::anyhow::Context::with_context(
+                            $crate::vars::FromEnvVar::from_env_value(
+                                vars.get($env).map(String::as_str),
+                            ),
+                            || format!("reading {}", $env),
+                        )?
+                    ),*
+                })
+            }
         }
     };
 }
@@ -83,6 +106,48 @@ fn to_env_value(&self) -> Option<String> {
     }
 }
 
+fn required(value: Option<&str>) -> anyhow::Result<&str> {
+    value.ok_or_else(|| anyhow::anyhow!("missing"))
+}
+
+impl FromEnvVar for PathBuf {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self> {
+        Ok(required(value)?.into())
+    }
+}
+
+impl FromEnvVar for String {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self> {
+        Ok(required(value)?.to_owned())
+    }
+}
+
+impl FromEnvVar for usize {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self> {
+        Ok(required(value)?.parse()?)
+    }
+}
+
+impl FromEnvVar for u16 {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self> {
+        Ok(required(value)?.parse()?)
+    }
+}
+
+impl<T: FromEnvVar> FromEnvVar for Option<T> {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self> {
+        value
+            .map(|value| T::from_env_value(Some(value)))
+            .transpose()
+    }
+}
+
+impl FromEnvVar for ApiSecrets {
+    fn from_env_value(value: Option<&str>) -> anyhow::Result<Self> {
+        value.map_or_else(|| Ok(Self::none()), ApiSecrets::from_str)
+    }
+}
+
 pub async fn mkdir(dir: PathBuf) -> anyhow::Result<PathBuf> {
     if !dir.exists() {
         tokio::fs::create_dir(&dir).await?;
@@ -100,15 +165,58 @@ pub async fn mkdir(dir: PathBuf) -> anyhow::Result<PathBuf> {
 use fedimintd::envs::FM_FORCE_API_SECRETS_ENV;
 use format as f;
 
+use crate::envs::FM_BITCOIN_NETWORK_ENV;
+
 pub fn utf8(path: &Path) -> &str {
     path.as_os_str().to_str().expect("must be valid utf8")
 }
 
+/// Where [`Global`] gets its ports and directories from
+#[derive(Debug)]
+pub enum Reserve {
+    /// Ports from the shared allocator and directories created on disk, to
+    /// launch daemons with
+    Launch,
+    /// Consecutive ports from `next_port` on, neither bound nor checked, and
+    /// no directories created, to plan a launch, see
+    /// [`crate::devfed::DevFed::dry_run`]
+    DryRun { next_port: AtomicU16 },
+}
+
+impl Reserve {
+    pub fn dry_run(base_port: u16) -> Self {
+        Self::DryRun {
+            next_port: AtomicU16::new(base_port),
+        }
+    }
+
+    /// First of `range_size` consecutive ports
+    pub fn ports(&self, range_size: u16) -> anyhow::Result<u16> {
+        match self {
+            Self::Launch => port_alloc(range_size),
+            Self::DryRun { next_port } => next_port
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |port| {
+                    port.checked_add(range_size)
+                })
+                .map_err(|port| anyhow::anyhow!("no {range_size} ports left after {port}")),
+        }
+    }
+
+    pub async fn dir(&self, dir: PathBuf) -> anyhow::Result<PathBuf> {
+        match self {
+            Self::Launch => mkdir(dir).await,
+            Self::DryRun { .. } => Ok(dir),
+        }
+    }
+}
+
 declare_vars! {
-    Global = (test_dir: &Path, fed_size: usize, offline_nodes: usize) =>
+    Global = (test_dir: &Path, fed_size: usize, offline_nodes: usize, reserve: &Reserve) =>
     {
         FM_USE_UNKNOWN_MODULE: String = std::env::var(FM_USE_UNKNOWN_MODULE_ENV).unwrap_or_else(|_| "1".into()); env: "FM_USE_UNKNOWN_MODULE";
         FM_ENABLE_MODULE_LNV2: String = std::env::var(FM_ENABLE_MODULE_LNV2_ENV).unwrap_or_else(|_| "1".into()); env: "FM_ENABLE_MODULE_LNV2";
+        // Checked by `Global::new`, see `crate::external::bitcoin_network`
+        FM_BITCOIN_NETWORK: String = std::env::var(FM_BITCOIN_NETWORK_ENV).unwrap_or_else(|_| "regtest".into()); env: FM_BITCOIN_NETWORK_ENV;
 
 
         FM_FORCE_API_SECRETS: ApiSecrets = std::env::var(FM_FORCE_API_SECRETS_ENV).ok().and_then(|s| {
@@ -122,48 +230,50 @@ pub fn utf8(path: &Path) -> &str {
 
         FM_FED_SIZE: usize = fed_size; env: "FM_FED_SIZE";
         FM_OFFLINE_NODES: usize = offline_nodes; env: "FM_OFFLINE_NODES";
-        FM_TMP_DIR: PathBuf = mkdir(test_dir.into()).await?; env: "FM_TMP_DIR";
+        FM_TMP_DIR: PathBuf = reserve.dir(test_dir.into()).await?; env: "FM_TMP_DIR";
         FM_TEST_DIR: PathBuf = FM_TMP_DIR.clone(); env: "FM_TEST_DIR";
         FM_TEST_FAST_WEAK_CRYPTO: String = "1"; env: "FM_TEST_FAST_WEAK_CRYPTO";
-        FM_LOGS_DIR: PathBuf = mkdir(FM_TEST_DIR.join("logs")).await?; env: "FM_LOGS_DIR";
-
-        FM_PORT_BTC_RPC: u16 = port_alloc(1)?; env: "FM_PORT_BTC_RPC";
-        FM_PORT_BTC_P2P: u16 = port_alloc(1)?; env: "FM_PORT_BTC_P2P";
-        FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK: u16 = port_alloc(1)?; env: "FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK";
-        FM_PORT_BTC_ZMQ_PUB_RAW_TX: u16 = port_alloc(1)?; env: "FM_PORT_BTC_ZMQ_PUB_RAW_TX";
-        FM_PORT_CLN: u16 = port_alloc(1)?; env: "FM_PORT_CLN";
-        FM_PORT_LND_LISTEN: u16 = port_alloc(1)?; env: "FM_PORT_LND_LISTEN";
-        FM_PORT_LDK: u16 = port_alloc(1)?; env: "FM_PORT_LDK";
-        FM_PORT_LND_RPC: u16 = port_alloc(1)?; env: "FM_PORT_LND_RPC";
-        FM_PORT_LND_REST: u16 = port_alloc(1)?; env: "FM_PORT_LND_REST";
-        FM_PORT_ELECTRS: u16 = port_alloc(1)?; env: "FM_PORT_ELECTRS";
-        FM_PORT_ELECTRS_MONITORING: u16 = port_alloc(1)?; env: "FM_PORT_ELECTRS_MONITORING";
-        FM_PORT_ESPLORA: u16 = port_alloc(1)?; env: "FM_PORT_ESPLORA";
+        FM_LOGS_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("logs")).await?; env: "FM_LOGS_DIR";
+
+        FM_PORT_BTC_RPC: u16 = reserve.ports(1)?; env: "FM_PORT_BTC_RPC";
+        FM_PORT_BTC_P2P: u16 = reserve.ports(1)?; env: "FM_PORT_BTC_P2P";
+        FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK: u16 = reserve.ports(1)?; env: "FM_PORT_BTC_ZMQ_PUB_RAW_BLOCK";
+        FM_PORT_BTC_ZMQ_PUB_RAW_TX: u16 = reserve.ports(1)?; env: "FM_PORT_BTC_ZMQ_PUB_RAW_TX";
+        FM_PORT_CLN: u16 = reserve.ports(1)?; env: "FM_PORT_CLN";
+        FM_PORT_LND_LISTEN: u16 = reserve.ports(1)?; env: "FM_PORT_LND_LISTEN";
+        FM_PORT_LDK: u16 = reserve.ports(1)?; env: "FM_PORT_LDK";
+        FM_PORT_LND_RPC: u16 = reserve.ports(1)?; env: "FM_PORT_LND_RPC";
+        FM_PORT_LND_REST: u16 = reserve.ports(1)?; env: "FM_PORT_LND_REST";
+        FM_PORT_ELECTRS: u16 = reserve.ports(1)?; env: "FM_PORT_ELECTRS";
+        FM_PORT_ELECTRS_MONITORING: u16 = reserve.ports(1)?; env: "FM_PORT_ELECTRS_MONITORING";
+        FM_PORT_ESPLORA: u16 = reserve.ports(1)?; env: "FM_PORT_ESPLORA";
+        FM_PORT_ESPLORA_FRONTEND: u16 = reserve.ports(1)?; env: "FM_PORT_ESPLORA_FRONTEND";
         // 3 = p2p + api + metrics env: "// ";
-        FM_PORT_FEDIMINTD_BASE: u16 = port_alloc((3 * fed_size).try_into().unwrap())?; env: "FM_PORT_FEDIMINTD_BASE";
-        FM_PORT_GW_CLN: u16 = port_alloc(1)?; env: "FM_PORT_GW_CLN";
-        FM_PORT_GW_LND: u16 = port_alloc(1)?; env: "FM_PORT_GW_LND";
-        FM_PORT_GW_LDK: u16 = port_alloc(1)?; env: "FM_PORT_GW_LDK";
-        FM_PORT_CLN_EXTENSION: u16 = port_alloc(1)?; env: "FM_PORT_CLN_EXTENSION";
+        FM_PORT_FEDIMINTD_BASE: u16 = reserve.ports((3 * fed_size).try_into().unwrap())?; env: "FM_PORT_FEDIMINTD_BASE";
+        FM_PORT_GW_CLN: u16 = reserve.ports(1)?; env: "FM_PORT_GW_CLN";
+        FM_PORT_GW_LND: u16 = reserve.ports(1)?; env: "FM_PORT_GW_LND";
+        FM_PORT_GW_LDK: u16 = reserve.ports(1)?; env: "FM_PORT_GW_LDK";
+        FM_PORT_CLN_EXTENSION: u16 = reserve.ports(1)?; env: "FM_PORT_CLN_EXTENSION";
         FM_PORT_FAUCET: u16 = 15243u16; env: "FM_PORT_FAUCET";
+        FM_PORT_LNURL_SERVER: u16 = reserve.ports(1)?; env: "FM_PORT_LNURL_SERVER";
 
         FM_LDK_ESPLORA_SERVER_URL: String = format!("http://127.0.0.1:{FM_PORT_ESPLORA}"); env: "FM_LDK_ESPLORA_SERVER_URL";
 
-        FM_CLN_DIR: PathBuf = mkdir(FM_TEST_DIR.join("cln")).await?; env: "FM_CLN_DIR";
-        FM_LND_DIR: PathBuf = mkdir(FM_TEST_DIR.join("lnd")).await?; env: "FM_LND_DIR";
-        FM_LDK_DIR: PathBuf = mkdir(FM_TEST_DIR.join("ldk")).await?; env: "FM_LDK_DIR";
-        FM_BTC_DIR: PathBuf = mkdir(FM_TEST_DIR.join("bitcoin")).await?; env: "FM_BTC_DIR";
+        FM_CLN_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("cln")).await?; env: "FM_CLN_DIR";
+        FM_LND_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("lnd")).await?; env: "FM_LND_DIR";
+        FM_LDK_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("ldk")).await?; env: "FM_LDK_DIR";
+        FM_BTC_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("bitcoin")).await?; env: "FM_BTC_DIR";
         FM_DATA_DIR: PathBuf = FM_TEST_DIR.clone(); env: "FM_DATA_DIR";
-        FM_CLIENT_BASE_DIR: PathBuf = mkdir(FM_TEST_DIR.join("clients")).await?; env: "FM_CLIENT_BASE_DIR";
-        FM_CLIENT_DIR: PathBuf = mkdir(FM_TEST_DIR.join("clients").join("default-0")).await?; env: "FM_CLIENT_DIR";
-        FM_ELECTRS_DIR: PathBuf = mkdir(FM_TEST_DIR.join("electrs")).await?; env: "FM_ELECTRS_DIR";
-        FM_ESPLORA_DIR: PathBuf = mkdir(FM_TEST_DIR.join("esplora")).await?; env: "FM_ESPLORA_DIR";
+        FM_CLIENT_BASE_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("clients")).await?; env: "FM_CLIENT_BASE_DIR";
+        FM_CLIENT_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("clients").join("default-0")).await?; env: "FM_CLIENT_DIR";
+        FM_ELECTRS_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("electrs")).await?; env: "FM_ELECTRS_DIR";
+        FM_ESPLORA_DIR: PathBuf = reserve.dir(FM_TEST_DIR.join("esplora")).await?; env: "FM_ESPLORA_DIR";
         FM_READY_FILE: PathBuf = FM_TEST_DIR.join("ready"); env: "FM_READY_FILE";
 
-        FM_CLN_SOCKET: PathBuf = FM_CLN_DIR.join("regtest/lightning-rpc"); env: "FM_CLN_SOCKET";
+        FM_CLN_SOCKET: PathBuf = FM_CLN_DIR.join(&FM_BITCOIN_NETWORK).join("lightning-rpc"); env: "FM_CLN_SOCKET";
         FM_LND_RPC_ADDR: String = f!("https://localhost:{FM_PORT_LND_RPC}"); env: "FM_LND_RPC_ADDR";
         FM_LND_TLS_CERT: PathBuf = FM_LND_DIR.join("tls.cert"); env: "FM_LND_TLS_CERT";
-        FM_LND_MACAROON: PathBuf = FM_LND_DIR.join("data/chain/bitcoin/regtest/admin.macaroon"); env: "FM_LND_MACAROON";
+        FM_LND_MACAROON: PathBuf = FM_LND_DIR.join("data/chain/bitcoin").join(&FM_BITCOIN_NETWORK).join("admin.macaroon"); env: "FM_LND_MACAROON";
 
         FM_GATEWAY_API_ADDR: String = f!("http://127.0.0.1:{FM_PORT_GW_CLN}"); env: "FM_GATEWAY_API_ADDR";
         FM_GATEWAY_PASSWORD: String = "theresnosecondbest"; env: "FM_GATEWAY_PASSWORD";
@@ -174,15 +284,17 @@ pub fn utf8(path: &Path) -> &str {
         FM_CLN_EXTENSION_LISTEN_ADDRESS: String = f!("0.0.0.0:{FM_PORT_CLN_EXTENSION}"); env: "FM_CLN_EXTENSION_LISTEN_ADDRESS";
         FM_GATEWAY_LIGHTNING_ADDR: String = f!("http://localhost:{FM_PORT_CLN_EXTENSION}"); env: "FM_GATEWAY_LIGHTNING_ADDR";
         FM_FAUCET_BIND_ADDR: String = f!("0.0.0.0:{FM_PORT_FAUCET}"); env: "FM_FAUCET_BIND_ADDR";
+        FM_LNURL_SERVER_BIND_ADDR: String = f!("127.0.0.1:{FM_PORT_LNURL_SERVER}"); env: "FM_LNURL_SERVER_BIND_ADDR";
+        FM_LNURL_SERVER_CERT: PathBuf = FM_TEST_DIR.join("lnurl-server-cert.pem"); env: "FM_LNURL_SERVER_CERT";
 
         // clients env: "// ";
-        FM_LIGHTNING_CLI: String = f!("{lightning_cli} --network regtest --lightning-dir={lightning_dir}",
+        FM_LIGHTNING_CLI: String = f!("{lightning_cli} --network {FM_BITCOIN_NETWORK} --lightning-dir={lightning_dir}",
             lightning_cli = crate::util::get_lightning_cli_path().join(" "),
             lightning_dir = utf8(&FM_CLN_DIR)); env: "FM_LIGHTNING_CLI";
-        FM_LNCLI: String = f!("{lncli} -n regtest --lnddir={lnddir} --rpcserver=localhost:{FM_PORT_LND_RPC}",
+        FM_LNCLI: String = f!("{lncli} -n {FM_BITCOIN_NETWORK} --lnddir={lnddir} --rpcserver=localhost:{FM_PORT_LND_RPC}",
             lncli = crate::util::get_lncli_path().join(" "),
             lnddir = utf8(&FM_LND_DIR)); env: "FM_LNCLI";
-        FM_BTC_CLIENT: String = f!("{bitcoin_cli} -regtest -rpcuser=bitcoin -rpcpassword=bitcoin -datadir={datadir}",             bitcoin_cli = crate::util::get_bitcoin_cli_path().join(" "),
+        FM_BTC_CLIENT: String = f!("{bitcoin_cli} -chain={FM_BITCOIN_NETWORK} -rpcuser=bitcoin -rpcpassword=bitcoin -datadir={datadir}",             bitcoin_cli = crate::util::get_bitcoin_cli_path().join(" "),
             datadir = utf8(&FM_BTC_DIR)); env: "FM_BTC_CLIENT";
 
         FM_MINT_CLIENT: String = f!("{fedimint_cli} --data-dir {datadir}",
@@ -214,19 +326,30 @@ pub async fn new(
         fed_size: usize,
         offline_nodes: usize,
     ) -> anyhow::Result<Self> {
-        let this = Self::init(test_dir, fed_size, offline_nodes).await?;
+        Self::new_with_reserve(test_dir, fed_size, offline_nodes, &Reserve::Launch).await
+    }
+
+    /// Like [`Self::new`], but with ports and directories from `reserve`
+    pub async fn new_with_reserve(
+        test_dir: &Path,
+        fed_size: usize,
+        offline_nodes: usize,
+        reserve: &Reserve,
+    ) -> anyhow::Result<Self> {
+        let this = Self::init(test_dir, fed_size, offline_nodes, reserve).await?;
+        crate::external::parse_bitcoin_network(&this.FM_BITCOIN_NETWORK)?;
         Ok(this)
     }
 }
 
 declare_vars! {
-    Fedimintd = (globals: &Global, params: ConfigGenParams, federation_name: String, base_port: u16) => {
+    Fedimintd = (globals: &Global, params: ConfigGenParams, federation_name: String, base_port: u16, reserve: &Reserve) => {
         FM_BIND_P2P: String = params.local.p2p_bind.to_string(); env: "FM_BIND_P2P";
         FM_BIND_API: String = params.local.api_bind.to_string(); env: "FM_BIND_API";
         FM_P2P_URL: String = params.consensus.peers[&params.local.our_id].p2p_url.to_string(); env: "FM_P2P_URL";
         FM_API_URL: String = params.consensus.peers[&params.local.our_id].api_url.to_string(); env: "FM_API_URL";
         FM_BIND_METRICS_API: String = format!("127.0.0.1:{}", base_port as usize + 2 * globals.FM_FED_SIZE + params.local.our_id.to_usize()); env: "FM_BIND_METRICS_API";
-        FM_DATA_DIR: PathBuf = mkdir(globals.FM_DATA_DIR.join(format!("fedimintd-{}-{}", federation_name, params.local.our_id.to_usize()))).await?; env: "FM_DATA_DIR";
+        FM_DATA_DIR: PathBuf = reserve.dir(globals.FM_DATA_DIR.join(format!("fedimintd-{}-{}", federation_name, params.local.our_id.to_usize()))).await?; env: "FM_DATA_DIR";
 
         // We only need to force the current bitcoind rpc on fedimintd, other daemons take their
         // rpc settings over command-line etc. so always will use the right ones.
@@ -234,3 +357,21 @@ pub async fn new(
         FM_FORCE_BITCOIN_RPC_KIND: String = "bitcoind"; env: FM_FORCE_BITCOIN_RPC_KIND_ENV;
     }
 }
+
+#[test]
+fn test_env_value_round_trip() -> anyhow::Result<()> {
+    fn round_trip<T: ToEnvVar + FromEnvVar>(value: &T) -> anyhow::Result<T> {
+        T::from_env_value(value.to_env_value().as_deref())
+    }
+
+    assert_eq!(round_trip(&Some(18443u16))?, Some(18443));
+    assert_eq!(round_trip(&None::<String>)?, None);
+    assert_eq!(
+        round_trip(&ApiSecrets::from_str("a,b")?)?.get_all(),
+        ["a", "b"]
+    );
+    assert!(round_trip(&ApiSecrets::none())?.is_empty());
+    assert!(String::from_env_value(None).is_err());
+
+    Ok(())
+}
@@ -340,7 +340,7 @@ pub async fn handle_command(
             warn!("Command deprecated. Use `fedimint-cli module ln invoice` instead.");
             let lightning_module = client.get_first_module::<LightningClientModule>();
             let ln_gateway = lightning_module
-                .get_gateway(gateway_id, force_internal)
+                .get_gateway_to_receive(gateway_id, force_internal)
                 .await?;
 
             let lightning_module = client.get_first_module::<LightningClientModule>();
@@ -398,7 +398,7 @@ pub async fn handle_command(
             info!("Paying invoice: {bolt11}");
             let lightning_module = client.get_first_module::<LightningClientModule>();
             let ln_gateway = lightning_module
-                .get_gateway(gateway_id, force_internal)
+                .get_gateway_to_pay(gateway_id, force_internal)
                 .await?;
 
             let lightning_module = client.get_first_module::<LightningClientModule>();
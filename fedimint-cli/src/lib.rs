@@ -36,7 +36,7 @@
     DynGlobalApi, FederationApiExt, FederationError, IRawFederationApi, WsFederationApi,
 };
 use fedimint_bip39::Bip39RootSecretStrategy;
-use fedimint_client::meta::{FetchKind, MetaSource};
+use fedimint_client::meta::{FetchKind, MetaService, MetaSource};
 use fedimint_client::module::init::{ClientModuleInit, ClientModuleInitRegistry};
 use fedimint_client::secret::{get_default_client_secret, RootSecretStrategy};
 use fedimint_client::{AdminCreds, Client, ClientBuilder, ClientHandleArc};
@@ -214,7 +214,12 @@ struct Opts {
 
     #[cfg(feature = "tor")]
     /// Activate usage of Tor as the Connector when building the Client
-    #[arg(long, env = FM_USE_TOR_ENV)]
+    #[arg(
+        long,
+        env = FM_USE_TOR_ENV,
+        action = clap::ArgAction::SetTrue,
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
     use_tor: bool,
 
     /// Activate more verbose logging, for full control use the RUST_LOG env
@@ -555,6 +560,10 @@ enum DevCmd {
     /// meta module these are returned, otherwise the legacy mechanism
     /// (config+override file) is used.
     MetaFields,
+    /// Returns the meta fields cached by the client, once it refreshed them
+    /// from the meta module, or the legacy mechanism without it, after being
+    /// opened
+    CachedMetaFields,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -670,8 +679,15 @@ async fn client_join(
     }
 
     async fn client_open(&self, cli: &Opts) -> CliResult<ClientHandleArc> {
-        let mut client_builder = self.make_client_builder(cli).await?;
+        let client_builder = self.make_client_builder(cli).await?;
+        self.client_open_with_builder(cli, client_builder).await
+    }
 
+    async fn client_open_with_builder(
+        &self,
+        cli: &Opts,
+        mut client_builder: ClientBuilder,
+    ) -> CliResult<ClientHandleArc> {
         if let Some(our_id) = cli.our_id {
             client_builder.set_admin_creds(AdminCreds {
                 peer_id: our_id,
@@ -1118,6 +1134,26 @@ struct ReactorLogState {
                     serde_json::to_value(meta_fields).expect("Can be encoded"),
                 ))
             }
+            Command::Dev(DevCmd::CachedMetaFields) => {
+                // Unlike the other commands, cache the meta module's fields
+                // like `dev meta-fields` returns them, not only the legacy
+                // ones from the config and override file
+                let mut client_builder = self.make_client_builder(&cli).await?;
+                client_builder
+                    .with_meta_service(MetaService::new(MetaModuleOrLegacyMetaSource::default()));
+                let client = self.client_open_with_builder(&cli, client_builder).await?;
+                client.meta_service().wait_initialization().await;
+
+                let meta_fields = client
+                    .meta_service()
+                    .get_cached_values(client.db())
+                    .await
+                    .ok_or_cli_msg("client has no cached meta fields")?;
+
+                Ok(CliOutput::Raw(
+                    serde_json::to_value(meta_fields).expect("Can be encoded"),
+                ))
+            }
             Command::Completion { shell } => {
                 clap_complete::generate(
                     shell,
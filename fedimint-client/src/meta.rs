@@ -111,6 +111,24 @@ async fn get_field_from_db<V: DeserializeOwned + 'static>(
         })
     }
 
+    /// All meta fields as currently cached, with the revision they were
+    /// fetched at. `None` before the first fetch.
+    pub async fn get_cached_values(&self, db: &Database) -> Option<MetaValues> {
+        let dbtx = &mut db.begin_transaction_nc().await;
+        let info = dbtx.get_value(&MetaServiceInfoKey).await?;
+        let values = dbtx
+            .find_by_prefix(&MetaFieldPrefix)
+            .await
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect();
+        Some(MetaValues {
+            values,
+            revision: info.revision,
+        })
+    }
+
     async fn current_revision(&self, dbtx: &mut DatabaseTransaction<'_>) -> Option<u64> {
         dbtx.get_value(&MetaServiceInfoKey)
             .await
@@ -80,9 +80,30 @@ pub fn from_map(
         api_secret: Option<String>,
     ) -> Self {
         let max_size = peer_to_url_map.to_num_peers().max_evil() + 1;
-        let mut code_vec: Vec<InviteCodePart> = peer_to_url_map
-            .iter()
-            .take(max_size)
+        Self::from_peers(
+            peer_to_url_map.iter().take(max_size),
+            federation_id,
+            api_secret,
+        )
+    }
+
+    /// Like [`Self::from_map`], but references every guardian of
+    /// `peer_to_url_map` instead of only as many as needed for one of them to
+    /// be honest, e.g. to only reference a chosen subset of guardians
+    pub fn from_map_all_peers(
+        peer_to_url_map: &BTreeMap<PeerId, SafeUrl>,
+        federation_id: FederationId,
+        api_secret: Option<String>,
+    ) -> Self {
+        Self::from_peers(peer_to_url_map.iter(), federation_id, api_secret)
+    }
+
+    fn from_peers<'a>(
+        peers: impl Iterator<Item = (&'a PeerId, &'a SafeUrl)>,
+        federation_id: FederationId,
+        api_secret: Option<String>,
+    ) -> Self {
+        let mut code_vec: Vec<InviteCodePart> = peers
             .map(|(peer, url)| InviteCodePart::Api {
                 url: url.clone(),
                 peer: *peer,
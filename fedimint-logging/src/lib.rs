@@ -15,12 +15,22 @@
 //! side.
 
 use std::fs::File;
-use std::{env, io};
+use std::{env, fmt, io};
 
+use serde_json::{json, Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Layer};
 
+#[cfg(feature = "telemetry")]
+mod otlp;
+
 pub const LOG_BLOCKCHAIN: &str = "fm::net::blockchain";
 pub const LOG_CONSENSUS: &str = "fm::consensus";
 pub const LOG_CORE: &str = "fm::core";
@@ -61,8 +71,11 @@ pub struct TracingSetup {
     #[cfg(feature = "telemetry")]
     with_jaeger: bool,
     #[cfg(feature = "telemetry")]
+    otlp_endpoint: Option<String>,
+    #[cfg(feature = "telemetry")]
     with_chrome: bool,
     with_file: Option<File>,
+    json: bool,
 }
 
 impl TracingSetup {
@@ -80,6 +93,14 @@ pub fn with_jaeger(&mut self, enabled: bool) -> &mut Self {
         self
     }
 
+    /// Setup telemetry through an OTLP/HTTP collector at `endpoint`, like
+    /// `http://localhost:4318`, if set
+    #[cfg(feature = "telemetry")]
+    pub fn with_otlp_endpoint(&mut self, endpoint: Option<String>) -> &mut Self {
+        self.otlp_endpoint = endpoint;
+        self
+    }
+
     /// Setup telemetry through Chrome <https://docs.rs/tracing-chrome>
     #[cfg(feature = "telemetry")]
     pub fn with_chrome(&mut self, enabled: bool) -> &mut Self {
@@ -92,6 +113,13 @@ pub fn with_file(&mut self, file: Option<File>) -> &mut Self {
         self
     }
 
+    /// Log a JSON object per line instead of human readable text, for tools
+    /// that parse the logs
+    pub fn with_json(&mut self, enabled: bool) -> &mut Self {
+        self.json = enabled;
+        self
+    }
+
     /// Sets the log level applied to most modules. Some overly chatty modules
     /// are muted even if this is set to a lower log level, use the `RUST_LOG`
     /// environment variable to override.
@@ -136,8 +164,15 @@ pub fn init(&mut self) -> anyhow::Result<()> {
 
         let fmt_layer = tracing_subscriber::fmt::layer()
             .with_thread_names(false) // can be enabled for debugging
-            .with_writer(fmt_writer)
-            .with_filter(filter_layer);
+            .with_writer(fmt_writer);
+        let fmt_layer = if self.json {
+            fmt_layer
+                .event_format(JsonFormat)
+                .with_filter(filter_layer)
+                .boxed()
+        } else {
+            fmt_layer.with_filter(filter_layer).boxed()
+        };
 
         let console_opt = || -> Option<Box<dyn Layer<_> + Send + Sync + 'static>> {
             #[cfg(feature = "telemetry")]
@@ -153,25 +188,47 @@ pub fn init(&mut self) -> anyhow::Result<()> {
             None
         };
 
-        let telemetry_layer_opt = || -> Option<Box<dyn Layer<_> + Send + Sync + 'static>> {
-            #[cfg(feature = "telemetry")]
-            if self.with_jaeger {
-                // TODO: https://github.com/fedimint/fedimint/issues/4591
-                #[allow(deprecated)]
-                let tracer = opentelemetry_jaeger::new_agent_pipeline()
-                    .with_service_name("fedimint")
-                    .install_simple()
-                    .unwrap();
-
-                return Some(tracing_opentelemetry::layer().with_tracer(tracer).boxed());
-            }
-            None
-        };
+        let telemetry_layer_opt =
+            || -> anyhow::Result<Option<Box<dyn Layer<_> + Send + Sync + 'static>>> {
+                #[cfg(feature = "telemetry")]
+                if let Some(endpoint) = self.otlp_endpoint.as_deref() {
+                    use opentelemetry::trace::TracerProvider as _;
+
+                    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+                        .with_config(opentelemetry_sdk::trace::Config::default().with_resource(
+                            opentelemetry_sdk::Resource::new([opentelemetry::KeyValue::new(
+                                "service.name",
+                                "fedimint",
+                            )]),
+                        ))
+                        .with_simple_exporter(otlp::OtlpHttpExporter::new(endpoint)?)
+                        .build();
+                    let tracer = provider.tracer("fedimint");
+                    opentelemetry::global::set_tracer_provider(provider);
+
+                    return Ok(Some(
+                        tracing_opentelemetry::layer().with_tracer(tracer).boxed(),
+                    ));
+                }
+                #[cfg(feature = "telemetry")]
+                if self.with_jaeger {
+                    // TODO: https://github.com/fedimint/fedimint/issues/4591
+                    #[allow(deprecated)]
+                    let tracer = opentelemetry_jaeger::new_agent_pipeline()
+                        .with_service_name("fedimint")
+                        .install_simple()?;
+
+                    return Ok(Some(
+                        tracing_opentelemetry::layer().with_tracer(tracer).boxed(),
+                    ));
+                }
+                Ok(None)
+            };
 
         tracing_subscriber::registry()
             .with(fmt_layer)
             .with(console_opt())
-            .with(telemetry_layer_opt())
+            .with(telemetry_layer_opt()?)
             .try_init()?;
         Ok(())
     }
@@ -181,3 +238,72 @@ pub fn shutdown() {
     #[cfg(feature = "telemetry")]
     opentelemetry::global::shutdown_tracer_provider();
 }
+
+/// Formats each event as a JSON object on a line of its own, like
+/// `{"timestamp":..,"level":"INFO","fields":{"message":..},"target":..,"spans":[..]}`
+/// in the layout of `tracing_subscriber`'s `json` formatter
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let spans: Vec<Value> = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(tracing_subscriber::registry::Scope::from_root)
+            .map(|span| json!({ "name": span.name() }))
+            .collect();
+        let metadata = event.metadata();
+        let entry = json!({
+            "timestamp": timestamp,
+            "level": metadata.level().as_str(),
+            "fields": fields.0,
+            "target": metadata.target(),
+            "spans": spans,
+        });
+        writeln!(writer, "{entry}")
+    }
+}
+
+/// Collects the fields of an event as JSON values
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), json!(format!("{value:?}")));
+    }
+}
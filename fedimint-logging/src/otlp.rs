@@ -0,0 +1,253 @@
+//! Minimal OpenTelemetry exporter sending spans to an OTLP/HTTP collector
+//! (Grafana Tempo, Jaeger, the OpenTelemetry collector, ...) as JSON
+//!
+//! Spans are encoded on the thread ending them and sent from a thread of
+//! their own, so a slow or missing collector never holds up the traced code.
+
+use std::fmt;
+use std::future::Future;
+use std::io::{BufRead as _, BufReader, Write as _};
+use std::net::{TcpStream, ToSocketAddrs as _};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use opentelemetry::trace::{SpanId, SpanKind, Status, TraceError};
+use opentelemetry::{Array, KeyValue, Value as OtelValue};
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use serde_json::{json, Value};
+
+/// Path collectors accept traces on, appended to endpoints without a path
+const TRACES_PATH: &str = "/v1/traces";
+/// Bounds connecting to and talking with the collector
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Export requests waiting for the sender thread, more are dropped
+const MAX_QUEUED_REQUESTS: usize = 1024;
+
+/// Collector endpoint like `http://localhost:4318`
+#[derive(Debug, Clone)]
+struct Endpoint {
+    authority: String,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(endpoint: &str) -> anyhow::Result<Self> {
+        let Some(rest) = endpoint.strip_prefix("http://") else {
+            anyhow::bail!("OTLP endpoint {endpoint} must be a plain http:// url");
+        };
+        let (authority, path) = match rest.split_once('/') {
+            Some((authority, path)) if !path.is_empty() => (authority, format!("/{path}")),
+            _ => (rest.trim_end_matches('/'), TRACES_PATH.to_owned()),
+        };
+        anyhow::ensure!(
+            !authority.is_empty(),
+            "OTLP endpoint {endpoint} has no host"
+        );
+        let authority = if authority.contains(':') {
+            authority.to_owned()
+        } else {
+            format!("{authority}:80")
+        };
+        Ok(Self { authority, path })
+    }
+
+    fn post(&self, body: &[u8]) -> anyhow::Result<()> {
+        let addr = self
+            .authority
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow::format_err!("{} does not resolve", self.authority))?;
+        let mut stream = TcpStream::connect_timeout(&addr, REQUEST_TIMEOUT)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.authority,
+            body.len()
+        )?;
+        stream.write_all(body)?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+        anyhow::ensure!(
+            status.starts_with('2'),
+            "collector responded with {}",
+            status_line.trim()
+        );
+        Ok(())
+    }
+}
+
+/// [`SpanExporter`] posting each batch of spans to an OTLP/HTTP endpoint
+pub struct OtlpHttpExporter {
+    requests: Option<mpsc::SyncSender<Vec<u8>>>,
+    sender: Option<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for OtlpHttpExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtlpHttpExporter").finish_non_exhaustive()
+    }
+}
+
+impl OtlpHttpExporter {
+    /// Exports to the collector at `endpoint`, like `http://localhost:4318`,
+    /// posting to `/v1/traces` unless the url has a path
+    pub fn new(endpoint: &str) -> anyhow::Result<Self> {
+        let endpoint = Endpoint::parse(endpoint)?;
+        let (requests, queued) = mpsc::sync_channel::<Vec<u8>>(MAX_QUEUED_REQUESTS);
+        let sender = thread::Builder::new()
+            .name("otlp-exporter".to_owned())
+            .spawn(move || {
+                for body in queued {
+                    if let Err(err) = endpoint.post(&body) {
+                        opentelemetry::global::handle_error(TraceError::from(format!(
+                            "failed to export spans to {}{}: {err}",
+                            endpoint.authority, endpoint.path
+                        )));
+                    }
+                }
+            })?;
+        Ok(Self {
+            requests: Some(requests),
+            sender: Some(sender),
+        })
+    }
+}
+
+impl SpanExporter for OtlpHttpExporter {
+    fn export(
+        &mut self,
+        batch: Vec<SpanData>,
+    ) -> Pin<Box<dyn Future<Output = ExportResult> + Send + 'static>> {
+        let result = match self.requests.as_ref() {
+            Some(requests) => requests
+                .try_send(encode_request(&batch).to_string().into_bytes())
+                .map_err(|err| TraceError::from(format!("dropping spans: {err}"))),
+            None => Err(TraceError::from("exporter is shut down")),
+        };
+        Box::pin(std::future::ready(result))
+    }
+
+    fn shutdown(&mut self) {
+        // the sender thread drains the queue and exits once it is closed
+        self.requests.take();
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.join();
+        }
+    }
+}
+
+/// Encodes `batch` as an `ExportTraceServiceRequest` in OTLP's JSON mapping
+fn encode_request(batch: &[SpanData]) -> Value {
+    let resource_spans: Vec<Value> = batch
+        .iter()
+        .map(|span| {
+            let resource: Vec<Value> = span
+                .resource
+                .iter()
+                .map(|(key, value)| encode_key_value(key.as_str(), value))
+                .collect();
+            json!({
+                "resource": { "attributes": resource },
+                "scopeSpans": [{
+                    "scope": {
+                        "name": span.instrumentation_lib.name,
+                        "version": span.instrumentation_lib.version,
+                    },
+                    "spans": [encode_span(span)],
+                }],
+            })
+        })
+        .collect();
+    json!({ "resourceSpans": resource_spans })
+}
+
+fn encode_span(span: &SpanData) -> Value {
+    let kind = match span.span_kind {
+        SpanKind::Internal => 1,
+        SpanKind::Server => 2,
+        SpanKind::Client => 3,
+        SpanKind::Producer => 4,
+        SpanKind::Consumer => 5,
+    };
+    let (status_code, status_message) = match &span.status {
+        Status::Unset => (0, ""),
+        Status::Ok => (1, ""),
+        Status::Error { description } => (2, description.as_ref()),
+    };
+    let events: Vec<Value> = span
+        .events
+        .iter()
+        .map(|event| {
+            json!({
+                "timeUnixNano": unix_nanos(event.timestamp),
+                "name": event.name,
+                "attributes": encode_attributes(&event.attributes),
+            })
+        })
+        .collect();
+    let mut encoded = json!({
+        "traceId": span.span_context.trace_id().to_string(),
+        "spanId": span.span_context.span_id().to_string(),
+        "name": span.name,
+        "kind": kind,
+        "startTimeUnixNano": unix_nanos(span.start_time),
+        "endTimeUnixNano": unix_nanos(span.end_time),
+        "attributes": encode_attributes(&span.attributes),
+        "events": events,
+        "status": { "code": status_code, "message": status_message },
+    });
+    if span.parent_span_id != SpanId::INVALID {
+        encoded["parentSpanId"] = json!(span.parent_span_id.to_string());
+    }
+    encoded
+}
+
+fn encode_attributes(attributes: &[KeyValue]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|kv| encode_key_value(kv.key.as_str(), &kv.value))
+        .collect()
+}
+
+fn encode_key_value(key: &str, value: &OtelValue) -> Value {
+    json!({ "key": key, "value": encode_value(value) })
+}
+
+fn encode_value(value: &OtelValue) -> Value {
+    // OTLP's JSON mapping encodes 64 bit integers as strings
+    match value {
+        OtelValue::Bool(value) => json!({ "boolValue": value }),
+        OtelValue::I64(value) => json!({ "intValue": value.to_string() }),
+        OtelValue::F64(value) => json!({ "doubleValue": value }),
+        OtelValue::String(value) => json!({ "stringValue": value.as_str() }),
+        OtelValue::Array(array) => {
+            let values: Vec<Value> = match array {
+                Array::Bool(values) => values.iter().map(|v| json!({ "boolValue": v })).collect(),
+                Array::I64(values) => values
+                    .iter()
+                    .map(|v| json!({ "intValue": v.to_string() }))
+                    .collect(),
+                Array::F64(values) => values.iter().map(|v| json!({ "doubleValue": v })).collect(),
+                Array::String(values) => values
+                    .iter()
+                    .map(|v| json!({ "stringValue": v.as_str() }))
+                    .collect(),
+            };
+            json!({ "arrayValue": { "values": values } })
+        }
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
@@ -522,6 +522,11 @@ pub fn server_endpoints() -> Vec<ApiEndpoint<ConsensusApi>> {
                     .try_into_inner(&fedimint.modules.decoder_registry())
                     .map_err(|e| ApiError::bad_request(e.to_string()))?;
 
+                // consensus would silently drop it for not fitting into a unit
+                if transaction.consensus_encode_to_vec().len() > Transaction::MAX_TX_SIZE {
+                    return Err(ApiError::bad_request("transaction too large".into()));
+                }
+
                 // we return an inner error if and only if the submitted transaction is
                 // invalid and will be rejected if we were to submit it to consensus
                 Ok((&TransactionSubmissionOutcome(fedimint.submit_transaction(transaction).await)).into())
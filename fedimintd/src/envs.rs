@@ -42,3 +42,6 @@
 
 // Can be used to absolutely override the values stored in the db
 pub const FM_FORCE_API_SECRETS_ENV: &str = "FM_FORCE_API_SECRETS";
+
+// Env variable to log JSON lines instead of human readable text
+pub const FM_LOG_JSON_ENV: &str = "FM_LOG_JSON";
@@ -46,8 +46,8 @@
 use crate::envs::{
     FM_API_URL_ENV, FM_BIND_API_ENV, FM_BIND_METRICS_API_ENV, FM_BIND_P2P_ENV,
     FM_BITCOIN_NETWORK_ENV, FM_DATA_DIR_ENV, FM_DISABLE_META_MODULE_ENV, FM_EXTRA_DKG_META_ENV,
-    FM_FINALITY_DELAY_ENV, FM_FORCE_API_SECRETS_ENV, FM_P2P_URL_ENV, FM_PASSWORD_ENV,
-    FM_TOKIO_CONSOLE_BIND_ENV,
+    FM_FINALITY_DELAY_ENV, FM_FORCE_API_SECRETS_ENV, FM_LOG_JSON_ENV, FM_P2P_URL_ENV,
+    FM_PASSWORD_ENV, FM_TOKIO_CONSOLE_BIND_ENV,
 };
 use crate::fedimintd::metrics::APP_START_TS;
 
@@ -71,6 +71,9 @@ struct ServerOpts {
     /// Enable telemetry logging
     #[arg(long, default_value = "false")]
     with_telemetry: bool,
+    /// Log JSON lines instead of human readable text
+    #[arg(long, env = FM_LOG_JSON_ENV)]
+    log_json: bool,
 
     /// Address we bind to for federation communication
     #[arg(long, env = FM_BIND_P2P_ENV, default_value = "127.0.0.1:8173")]
@@ -250,6 +253,7 @@ pub fn new(
         TracingSetup::default()
             .tokio_console_bind(opts.tokio_console_bind)
             .with_jaeger(opts.with_telemetry)
+            .with_json(opts.log_json)
             .init()
             .unwrap();
 
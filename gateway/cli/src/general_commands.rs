@@ -8,8 +8,8 @@
 use ln_gateway::rpc::rpc_client::GatewayRpcClient;
 use ln_gateway::rpc::{
     BackupPayload, BalancePayload, ConfigPayload, ConnectFedPayload, DepositAddressPayload,
-    FederationRoutingFees, LeaveFedPayload, ReceiveEcashPayload, RestorePayload,
-    SetConfigurationPayload, SpendEcashPayload, WithdrawPayload,
+    FederationRoutingFees, FederationStatusPayload, LeaveFedPayload, ReceiveEcashPayload,
+    RestorePayload, SetConfigurationPayload, SpendEcashPayload, WithdrawPayload,
 };
 
 use crate::print_response;
@@ -60,6 +60,11 @@ pub enum GeneralCommands {
     },
     /// Get the total on-chain, lightning, and eCash balances of the gateway.
     GetBalances,
+    /// Check whether the specified federation is reachable by the gateway.
+    FederationStatus {
+        #[clap(long)]
+        federation_id: FederationId,
+    },
     /// Generate a new peg-in address to a federation that the gateway can claim
     /// e-cash for later.
     Address {
@@ -184,6 +189,13 @@ pub async fn handle(
                 let response = create_client().get_balances().await?;
                 print_response(response);
             }
+            Self::FederationStatus { federation_id } => {
+                let response = create_client()
+                    .get_federation_status(FederationStatusPayload { federation_id })
+                    .await?;
+
+                print_response(response);
+            }
             Self::Address { federation_id } => {
                 let response = create_client()
                     .get_deposit_address(DepositAddressPayload { federation_id })
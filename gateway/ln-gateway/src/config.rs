@@ -1,5 +1,6 @@
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use bitcoin::Network;
 use clap::Parser;
@@ -8,7 +9,7 @@
 
 use super::envs;
 use super::lightning::LightningMode;
-use super::rpc::V1_API_ENDPOINT;
+use super::rpc::{GatewayMode, V1_API_ENDPOINT};
 
 /// Command line parameters for starting the gateway. `mode`, `data_dir`,
 /// `listen`, and `api_addr` are all required.
@@ -50,6 +51,27 @@ pub struct GatewayOpts {
         default_value_t = super::DEFAULT_NUM_ROUTE_HINTS
     )]
     num_route_hints: u32,
+
+    /// How long the gateway's registration with a federation stays valid, in
+    /// seconds. The gateway refreshes it before it expires, so it must be at
+    /// least a second.
+    #[arg(
+        long = "registration-ttl-secs",
+        env = envs::FM_GATEWAY_REGISTRATION_TTL_SECS_ENV,
+        default_value_t = super::GW_ANNOUNCEMENT_TTL.as_secs(),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    registration_ttl_secs: u64,
+
+    /// Directions the gateway routes payments in, restricting it to one is
+    /// useful for testing how clients handle such gateways
+    #[arg(
+        long = "gateway-mode",
+        env = envs::FM_GATEWAY_MODE_ENV,
+        value_enum,
+        default_value_t = GatewayMode::Full
+    )]
+    gateway_mode: GatewayMode,
 }
 
 impl GatewayOpts {
@@ -69,6 +91,8 @@ pub fn to_gateway_parameters(&self) -> anyhow::Result<GatewayParameters> {
             network: self.network,
             num_route_hints: self.num_route_hints,
             fees: self.fees.clone(),
+            registration_ttl: Duration::from_secs(self.registration_ttl_secs),
+            gateway_mode: self.gateway_mode,
         })
     }
 }
@@ -87,4 +111,6 @@ pub struct GatewayParameters {
     pub network: Option<Network>,
     pub num_route_hints: u32,
     pub fees: Option<GatewayFee>,
+    pub registration_ttl: Duration,
+    pub gateway_mode: GatewayMode,
 }
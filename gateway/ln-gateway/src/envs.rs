@@ -19,6 +19,14 @@
 // Env variable to TODO
 pub const FM_GATEWAY_FEES_ENV: &str = "FM_GATEWAY_FEES";
 
+// Env variable to set how long the gateway's registrations with federations
+// stay valid, in seconds
+pub const FM_GATEWAY_REGISTRATION_TTL_SECS_ENV: &str = "FM_GATEWAY_REGISTRATION_TTL_SECS";
+
+// Env variable to restrict the directions the gateway routes payments in, one
+// of `full`, `receive-only` or `send-only`
+pub const FM_GATEWAY_MODE_ENV: &str = "FM_GATEWAY_MODE";
+
 // Env variable to TODO
 pub const FM_NUMBER_OF_ROUTE_HINTS_ENV: &str = "FM_NUMBER_OF_ROUTE_HINTS";
 
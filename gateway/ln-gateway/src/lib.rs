@@ -37,7 +37,7 @@ pub mod gateway_lnrpc {
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, ensure};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use bitcoin::{Address, Network, Txid};
@@ -93,11 +93,11 @@ pub mod gateway_lnrpc {
 use rand::Rng;
 use rpc::{
     CloseChannelsWithPeerPayload, CreateInvoiceForSelfPayload, FederationInfo, GatewayFedConfig,
-    GatewayInfo, LeaveFedPayload, OpenChannelPayload, PayInvoicePayload, ReceiveEcashPayload,
-    ReceiveEcashResponse, SetConfigurationPayload, SpendEcashPayload, SpendEcashResponse,
-    V1_API_ENDPOINT,
+    GatewayInfo, GatewayMode, LeaveFedPayload, OpenChannelPayload, PayInvoicePayload,
+    ReceiveEcashPayload, ReceiveEcashResponse, SetConfigurationPayload, SpendEcashPayload,
+    SpendEcashResponse, V1_API_ENDPOINT,
 };
-use state_machine::pay::OutgoingPaymentError;
+use state_machine::pay::{OutgoingPaymentError, OutgoingPaymentErrorType};
 use state_machine::{GatewayClientModule, GatewayExtPayStates};
 use thiserror::Error;
 use tokio::sync::RwLock;
@@ -112,13 +112,18 @@ pub mod gateway_lnrpc {
 use crate::rpc::rpc_server::{hash_password, run_webserver};
 use crate::rpc::{
     BackupPayload, BalancePayload, ConnectFedPayload, DepositAddressPayload, FederationBalanceInfo,
-    GatewayBalances, RestorePayload, WithdrawPayload,
+    FederationStatus, FederationStatusPayload, GatewayBalances, RestorePayload, WithdrawPayload,
 };
 use crate::types::PrettyInterceptHtlcRequest;
 
-/// How long a gateway announcement stays valid
+/// How long a gateway announcement stays valid, unless overridden with
+/// `--registration-ttl-secs`
 const GW_ANNOUNCEMENT_TTL: Duration = Duration::from_secs(600);
 
+/// How long the gateway waits for a federation to answer before reporting it
+/// as unreachable
+const FEDERATION_STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// The default number of route hints that the legacy gateway provides for
 /// invoice creation.
 const DEFAULT_NUM_ROUTE_HINTS: u32 = 1;
@@ -226,6 +231,12 @@ pub struct Gateway {
 
     /// The socket the gateway listens on.
     listen: SocketAddr,
+
+    /// How long the gateway's registrations with federations stay valid.
+    registration_ttl: Duration,
+
+    /// The directions the gateway routes payments in.
+    gateway_mode: GatewayMode,
 }
 
 impl std::fmt::Debug for Gateway {
@@ -239,6 +250,8 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             .field("gateway_id", &self.gateway_id)
             .field("versioned_api", &self.versioned_api)
             .field("listen", &self.listen)
+            .field("registration_ttl", &self.registration_ttl)
+            .field("gateway_mode", &self.gateway_mode)
             .finish_non_exhaustive()
     }
 }
@@ -271,6 +284,8 @@ pub async fn new_with_custom_registry(
                 num_route_hints,
                 fees: Some(GatewayFee(fees)),
                 network,
+                registration_ttl: GW_ANNOUNCEMENT_TTL,
+                gateway_mode: GatewayMode::Full,
             },
             gateway_db,
             client_builder,
@@ -358,6 +373,8 @@ async fn new(
             gateway_db,
             versioned_api: gateway_parameters.versioned_api,
             listen: gateway_parameters.listen,
+            registration_ttl: gateway_parameters.registration_ttl,
+            gateway_mode: gateway_parameters.gateway_mode,
         })
     }
 
@@ -595,7 +612,11 @@ async fn handle_htlc(
             return;
         }
 
-        if self.try_handle_htlc_ln_legacy(&htlc_request).await.is_ok() {
+        if self
+            .try_handle_htlc_ln_legacy(&htlc_request, lightning_context)
+            .await
+            .is_ok()
+        {
             return;
         }
 
@@ -627,6 +648,11 @@ async fn try_handle_htlc_lnv2(
             return Err(GatewayError::PaymentNotRegisteredWithLNv2Error);
         };
 
+        if !self.gateway_mode.receives() {
+            Self::cancel_htlc(htlc_request, lightning_context).await;
+            return Ok(());
+        }
+
         if let Err(error) = client
             .get_first_module::<GatewayClientModuleV2>()
             .relay_incoming_htlc(
@@ -658,7 +684,11 @@ async fn try_handle_htlc_lnv2(
 
     /// Tries to handle an HTLC using the legacy lightning protocol.
     /// Returns `Ok` if the HTLC was handled, `Err` otherwise.
-    async fn try_handle_htlc_ln_legacy(&self, htlc_request: &InterceptHtlcRequest) -> Result<()> {
+    async fn try_handle_htlc_ln_legacy(
+        &self,
+        htlc_request: &InterceptHtlcRequest,
+        lightning_context: &LightningContext,
+    ) -> Result<()> {
         // Check if the HTLC corresponds to a federation supporting legacy Lightning.
         let Some(short_channel_id) = htlc_request.short_channel_id else {
             return Err(GatewayError::IncomingLNv1PaymentError(anyhow::anyhow!(
@@ -679,6 +709,11 @@ async fn try_handle_htlc_ln_legacy(&self, htlc_request: &InterceptHtlcRequest) -
             )));
         };
 
+        if !self.gateway_mode.receives() {
+            Self::cancel_htlc(htlc_request, lightning_context).await;
+            return Ok(());
+        }
+
         client
             .borrow()
             .with(|client| async {
@@ -721,6 +756,30 @@ async fn forward_htlc(
         }
     }
 
+    /// Fails an HTLC paying into a federation because the gateway doesn't
+    /// receive payments, see [`GatewayMode::SendOnly`].
+    async fn cancel_htlc(
+        htlc_request: &InterceptHtlcRequest,
+        lightning_context: &LightningContext,
+    ) {
+        warn!(
+            "Cancelling HTLC {}, the gateway does not receive payments",
+            PrettyInterceptHtlcRequest(htlc_request)
+        );
+        let outcome = InterceptHtlcResponse {
+            action: Some(Action::Cancel(Cancel {
+                reason: "Gateway does not receive payments".to_string(),
+            })),
+            payment_hash: htlc_request.payment_hash.clone(),
+            incoming_chan_id: htlc_request.incoming_chan_id,
+            htlc_id: htlc_request.htlc_id,
+        };
+
+        if let Err(error) = lightning_context.lnrpc.complete_htlc(outcome).await {
+            error!("Error sending HTLC response to lightning node: {error:?}");
+        }
+    }
+
     /// Helper function for atomically changing the Gateway's internal state.
     async fn set_gateway_state(&self, state: GatewayState) {
         let mut lock = self.state.write().await;
@@ -744,6 +803,7 @@ pub async fn handle_get_info(&self) -> Result<GatewayInfo> {
                 synced_to_chain: false,
                 api: self.versioned_api.clone(),
                 lightning_mode: None,
+                gateway_mode: self.gateway_mode,
             });
         };
 
@@ -782,6 +842,7 @@ pub async fn handle_get_info(&self) -> Result<GatewayInfo> {
             synced_to_chain: node_info.4,
             api: self.versioned_api.clone(),
             lightning_mode: self.lightning_builder.lightning_mode(),
+            gateway_mode: self.gateway_mode,
         })
     }
 
@@ -831,6 +892,37 @@ pub async fn handle_balance_msg(&self, payload: BalancePayload) -> Result<Amount
             .await)
     }
 
+    /// Returns whether the requested federation that the Gateway is connected
+    /// to currently answers, giving up after [`FEDERATION_STATUS_TIMEOUT`] so
+    /// an unreachable federation doesn't hold up the caller.
+    pub async fn handle_federation_status_msg(
+        &self,
+        payload: FederationStatusPayload,
+    ) -> Result<FederationStatus> {
+        let client = self.select_client(payload.federation_id).await?;
+        let session_count = match fedimint_core::runtime::timeout(
+            FEDERATION_STATUS_TIMEOUT,
+            client.value().api().session_count(),
+        )
+        .await
+        {
+            Ok(Ok(session_count)) => Some(session_count),
+            Ok(Err(err)) => {
+                debug!(federation_id = %payload.federation_id, %err, "Federation is unreachable");
+                None
+            }
+            Err(_) => {
+                debug!(federation_id = %payload.federation_id, "Federation didn't answer in time");
+                None
+            }
+        };
+        Ok(FederationStatus {
+            federation_id: payload.federation_id,
+            reachable: session_count.is_some(),
+            session_count,
+        })
+    }
+
     /// Returns a Bitcoin deposit on-chain address for pegging in Bitcoin for a
     /// specific connected federation.
     pub async fn handle_address_msg(&self, payload: DepositAddressPayload) -> Result<Address> {
@@ -1082,7 +1174,7 @@ pub async fn handle_connect_federation(
             .register_with_federation(
                 // Route hints will be updated in the background
                 Vec::new(),
-                GW_ANNOUNCEMENT_TTL,
+                self.registration_ttl,
                 gw_client_cfg.fees,
                 lightning_context,
             )
@@ -1448,7 +1540,7 @@ async fn register_federations(
                             .get_first_module::<GatewayClientModule>()
                             .register_with_federation(
                                 route_hints.clone(),
-                                GW_ANNOUNCEMENT_TTL,
+                                self.registration_ttl,
                                 federation_config.fees,
                                 lightning_context.clone(),
                             )
@@ -1592,7 +1684,7 @@ fn register_clients_timer(&self, task_group: &TaskGroup) {
                 } else {
                     // Allow a 15% buffer of the TTL before the re-registering gateway
                     // with the federations.
-                    GW_ANNOUNCEMENT_TTL.mul_f32(0.85)
+                    gateway.registration_ttl.mul_f32(0.85)
                 };
 
                 sleep(registration_delay).await;
@@ -1717,6 +1809,11 @@ async fn send_payment_v2(
         &self,
         payload: SendPaymentPayload,
     ) -> anyhow::Result<std::result::Result<[u8; 32], Signature>> {
+        ensure!(
+            self.gateway_mode.sends(),
+            "This gateway does not pay invoices, it only receives payments"
+        );
+
         self.select_client_v2(payload.federation_id)
             .await?
             .get_first_module::<GatewayClientModuleV2>()
@@ -1732,6 +1829,11 @@ async fn create_bolt11_invoice_v2(
         &self,
         payload: CreateBolt11InvoicePayload,
     ) -> anyhow::Result<Bolt11Invoice> {
+        ensure!(
+            self.gateway_mode.receives(),
+            "This gateway does not receive payments, it only pays invoices"
+        );
+
         if !payload.contract.verify() {
             bail!("The contract is invalid")
         }
@@ -1907,6 +2009,15 @@ fn into_response(self) -> Response {
         // the request back to the client to prevent malicious clients from
         // deducing state about the gateway/lightning node.
         let (error_message, status_code) = match self {
+            GatewayError::OutgoingPaymentError(error)
+                if error.error_type == OutgoingPaymentErrorType::sending_disabled() =>
+            {
+                (
+                    "This gateway does not pay invoices, it only receives payments. Outgoing contract will be refunded."
+                        .to_string(),
+                    StatusCode::BAD_REQUEST,
+                )
+            }
             GatewayError::OutgoingPaymentError(_) => (
                 "Error while paying lightning invoice. Outgoing contract will be refunded."
                     .to_string(),
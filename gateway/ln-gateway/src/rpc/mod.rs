@@ -55,6 +55,11 @@ pub struct BalancePayload {
     pub federation_id: FederationId,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FederationStatusPayload {
+    pub federation_id: FederationId,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DepositAddressPayload {
     pub federation_id: FederationId,
@@ -76,6 +81,16 @@ pub struct FederationInfo {
     pub routing_fees: Option<FederationRoutingFees>,
 }
 
+/// Whether a connected federation currently answers the gateway
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FederationStatus {
+    pub federation_id: FederationId,
+    /// Whether enough guardians answered to agree on the current session
+    pub reachable: bool,
+    /// The session the federation agreed to be in, if it was reachable
+    pub session_count: Option<u64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct GatewayInfo {
     pub version_hash: String,
@@ -99,6 +114,35 @@ pub struct GatewayInfo {
     pub synced_to_chain: bool,
     pub api: SafeUrl,
     pub lightning_mode: Option<LightningMode>,
+    #[serde(default)]
+    pub gateway_mode: GatewayMode,
+}
+
+/// Which directions a gateway routes payments in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GatewayMode {
+    /// Pays invoices for clients and receives payments into federations
+    #[default]
+    Full,
+    /// Only receives payments into federations, refusing to pay invoices for
+    /// clients
+    ReceiveOnly,
+    /// Only pays invoices for clients, refusing incoming payments to
+    /// federations
+    SendOnly,
+}
+
+impl GatewayMode {
+    /// Whether the gateway pays invoices for clients
+    pub fn sends(self) -> bool {
+        self != Self::ReceiveOnly
+    }
+
+    /// Whether the gateway accepts incoming payments to federations
+    pub fn receives(self) -> bool {
+        self != Self::SendOnly
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -4,11 +4,11 @@
 use fedimint_core::{Amount, TransactionId};
 use fedimint_ln_common::gateway_endpoint_constants::{
     ADDRESS_ENDPOINT, BACKUP_ENDPOINT, BALANCE_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT,
-    CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT, GATEWAY_INFO_ENDPOINT,
-    GATEWAY_INFO_POST_ENDPOINT, GET_BALANCES_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT,
-    LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT, OPEN_CHANNEL_ENDPOINT,
-    RECEIVE_ECASH_ENDPOINT, RESTORE_ENDPOINT, SET_CONFIGURATION_ENDPOINT, SPEND_ECASH_ENDPOINT,
-    WITHDRAW_ENDPOINT,
+    CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT, FEDERATION_STATUS_ENDPOINT,
+    GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT, GET_BALANCES_ENDPOINT,
+    GET_LN_ONCHAIN_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
+    OPEN_CHANNEL_ENDPOINT, RECEIVE_ECASH_ENDPOINT, RESTORE_ENDPOINT, SET_CONFIGURATION_ENDPOINT,
+    SPEND_ECASH_ENDPOINT, WITHDRAW_ENDPOINT,
 };
 use fedimint_lnv2_common::endpoint_constants::{
     CREATE_BOLT11_INVOICE_FOR_SELF_ENDPOINT, PAY_INVOICE_SELF_ENDPOINT,
@@ -21,10 +21,11 @@
 
 use super::{
     BackupPayload, BalancePayload, CloseChannelsWithPeerPayload, ConfigPayload, ConnectFedPayload,
-    CreateInvoiceForSelfPayload, DepositAddressPayload, FederationInfo, GatewayBalances,
-    GatewayFedConfig, GatewayInfo, GetLnOnchainAddressPayload, LeaveFedPayload, OpenChannelPayload,
-    PayInvoicePayload, ReceiveEcashPayload, ReceiveEcashResponse, RestorePayload,
-    SetConfigurationPayload, SpendEcashPayload, SpendEcashResponse, WithdrawPayload,
+    CreateInvoiceForSelfPayload, DepositAddressPayload, FederationInfo, FederationStatus,
+    FederationStatusPayload, GatewayBalances, GatewayFedConfig, GatewayInfo,
+    GetLnOnchainAddressPayload, LeaveFedPayload, OpenChannelPayload, PayInvoicePayload,
+    ReceiveEcashPayload, ReceiveEcashResponse, RestorePayload, SetConfigurationPayload,
+    SpendEcashPayload, SpendEcashResponse, WithdrawPayload,
 };
 use crate::lightning::ChannelInfo;
 use crate::CloseChannelsWithPeerResponse;
@@ -85,6 +86,17 @@ pub async fn get_balance(&self, payload: BalancePayload) -> GatewayRpcResult<Amo
         self.call_post(url, payload).await
     }
 
+    pub async fn get_federation_status(
+        &self,
+        payload: FederationStatusPayload,
+    ) -> GatewayRpcResult<FederationStatus> {
+        let url = self
+            .base_url
+            .join(FEDERATION_STATUS_ENDPOINT)
+            .expect("invalid base url");
+        self.call_post(url, payload).await
+    }
+
     pub async fn get_deposit_address(
         &self,
         payload: DepositAddressPayload,
@@ -12,11 +12,12 @@
 use fedimint_core::task::TaskGroup;
 use fedimint_ln_common::gateway_endpoint_constants::{
     ADDRESS_ENDPOINT, BACKUP_ENDPOINT, BALANCE_ENDPOINT, CLOSE_CHANNELS_WITH_PEER_ENDPOINT,
-    CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT, GATEWAY_INFO_ENDPOINT,
-    GATEWAY_INFO_POST_ENDPOINT, GET_BALANCES_ENDPOINT, GET_GATEWAY_ID_ENDPOINT,
-    GET_LN_ONCHAIN_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT, LIST_ACTIVE_CHANNELS_ENDPOINT,
-    OPEN_CHANNEL_ENDPOINT, PAY_INVOICE_ENDPOINT, RECEIVE_ECASH_ENDPOINT, RESTORE_ENDPOINT,
-    SET_CONFIGURATION_ENDPOINT, SPEND_ECASH_ENDPOINT, WITHDRAW_ENDPOINT,
+    CONFIGURATION_ENDPOINT, CONNECT_FED_ENDPOINT, FEDERATION_STATUS_ENDPOINT,
+    GATEWAY_INFO_ENDPOINT, GATEWAY_INFO_POST_ENDPOINT, GET_BALANCES_ENDPOINT,
+    GET_GATEWAY_ID_ENDPOINT, GET_LN_ONCHAIN_ADDRESS_ENDPOINT, LEAVE_FED_ENDPOINT,
+    LIST_ACTIVE_CHANNELS_ENDPOINT, OPEN_CHANNEL_ENDPOINT, PAY_INVOICE_ENDPOINT,
+    RECEIVE_ECASH_ENDPOINT, RESTORE_ENDPOINT, SET_CONFIGURATION_ENDPOINT, SPEND_ECASH_ENDPOINT,
+    WITHDRAW_ENDPOINT,
 };
 use fedimint_lnv2_client::{CreateBolt11InvoicePayload, SendPaymentPayload};
 use fedimint_lnv2_common::endpoint_constants::{
@@ -31,9 +32,10 @@
 
 use super::{
     BackupPayload, BalancePayload, CloseChannelsWithPeerPayload, ConnectFedPayload,
-    CreateInvoiceForSelfPayload, DepositAddressPayload, GetLnOnchainAddressPayload, InfoPayload,
-    LeaveFedPayload, OpenChannelPayload, PayInvoicePayload, ReceiveEcashPayload, RestorePayload,
-    SetConfigurationPayload, SpendEcashPayload, WithdrawPayload, V1_API_ENDPOINT,
+    CreateInvoiceForSelfPayload, DepositAddressPayload, FederationStatusPayload,
+    GetLnOnchainAddressPayload, InfoPayload, LeaveFedPayload, OpenChannelPayload,
+    PayInvoicePayload, ReceiveEcashPayload, RestorePayload, SetConfigurationPayload,
+    SpendEcashPayload, WithdrawPayload, V1_API_ENDPOINT,
 };
 use crate::rpc::ConfigPayload;
 use crate::{Gateway, GatewayError};
@@ -168,6 +170,7 @@ fn v1_routes(gateway: Arc<Gateway>) -> Router {
     // Authenticated, public routes used for gateway administration
     let always_authenticated_routes = Router::new()
         .route(BALANCE_ENDPOINT, post(balance))
+        .route(FEDERATION_STATUS_ENDPOINT, post(federation_status))
         .route(ADDRESS_ENDPOINT, post(address))
         .route(WITHDRAW_ENDPOINT, post(withdraw))
         .route(CONNECT_FED_ENDPOINT, post(connect_fed))
@@ -262,6 +265,16 @@ async fn balance(
     Ok(Json(json!(amount)))
 }
 
+/// Check whether a connected federation is reachable
+#[instrument(skip_all, err, fields(?payload))]
+async fn federation_status(
+    Extension(gateway): Extension<Arc<Gateway>>,
+    Json(payload): Json<FederationStatusPayload>,
+) -> Result<impl IntoResponse, GatewayError> {
+    let status = gateway.handle_federation_status_msg(payload).await?;
+    Ok(Json(json!(status)))
+}
+
 /// Generate deposit address
 #[instrument(skip_all, err, fields(?payload))]
 async fn address(
@@ -255,6 +255,8 @@ fn to_gateway_registration_info(
             },
             ttl,
             vetted: false,
+            pays_invoices: self.gateway.gateway_mode.sends(),
+            receives_payments: self.gateway.gateway_mode.receives(),
         }
     }
 
@@ -158,6 +158,22 @@ pub enum OutgoingPaymentErrorType {
     InvalidInvoicePreimage,
 }
 
+impl OutgoingPaymentErrorType {
+    /// Error of a gateway refusing to pay because it only receives payments.
+    ///
+    /// This type is persisted in the gateway's client database, so rather
+    /// than adding a variant old gateways couldn't decode, it's a lightning
+    /// payment failure with a reason of its own.
+    pub fn sending_disabled() -> Self {
+        OutgoingPaymentErrorType::LightningPayError {
+            lightning_error: LightningRpcError::FailedPayment {
+                failure_reason: "The gateway does not pay invoices, it only receives payments"
+                    .to_string(),
+            },
+        }
+    }
+}
+
 #[derive(
     Error, Debug, Serialize, Deserialize, Encodable, Decodable, Clone, Eq, PartialEq, Hash,
 )]
@@ -267,6 +283,22 @@ async fn buy_preimage(
             };
         }
 
+        if !context.gateway.gateway_mode.sends() {
+            warn!("Refusing to pay for contract {contract:?}, the gateway only receives payments");
+            let error = OutgoingPaymentError {
+                contract_id: contract.contract.contract_id(),
+                contract: Some(contract.clone()),
+                error_type: OutgoingPaymentErrorType::sending_disabled(),
+            };
+            return GatewayPayStateMachine {
+                common,
+                state: GatewayPayStates::CancelContract(Box::new(GatewayPayCancelContract {
+                    contract,
+                    error,
+                })),
+            };
+        }
+
         if let Some(client) =
             Self::check_swap_to_federation(context.clone(), payment_parameters.payment_data.clone())
                 .await
@@ -325,10 +325,14 @@ fn filter_duplicate_gateways(
     gateways_by_gateway_id
         .into_values()
         .flat_map(|announcements| {
-            let mut gateways: HashMap<LightningGateway, Duration> = HashMap::new();
+            let mut gateways: HashMap<(LightningGateway, bool, bool), Duration> = HashMap::new();
             for announcement in announcements {
                 let ttl = announcement.ttl;
-                let gateway = announcement.info.clone();
+                let gateway = (
+                    announcement.info.clone(),
+                    announcement.pays_invoices,
+                    announcement.receives_payments,
+                );
                 // Only insert if the TTL is longer than the one we already have
                 gateways
                     .entry(gateway)
@@ -342,10 +346,14 @@ fn filter_duplicate_gateways(
 
             gateways
                 .into_iter()
-                .map(|(gateway, ttl)| LightningGatewayAnnouncement {
-                    info: gateway,
-                    ttl,
-                    vetted: false,
+                .map(|((gateway, pays_invoices, receives_payments), ttl)| {
+                    LightningGatewayAnnouncement {
+                        info: gateway,
+                        ttl,
+                        vetted: false,
+                        pays_invoices,
+                        receives_payments,
+                    }
                 })
         })
         .collect()
@@ -65,7 +65,9 @@ pub(crate) async fn handle_cli_command(
             gateway_id,
             force_internal,
         } => {
-            let ln_gateway = module.get_gateway(gateway_id, force_internal).await?;
+            let ln_gateway = module
+                .get_gateway_to_receive(gateway_id, force_internal)
+                .await?;
 
             let desc = Description::new(description)?;
             let (operation_id, invoice, _) = module
@@ -93,7 +95,9 @@ pub(crate) async fn handle_cli_command(
         } => {
             let bolt11 = crate::get_invoice(&payment_info, amount, lnurl_comment).await?;
             info!("Paying invoice: {bolt11}");
-            let ln_gateway = module.get_gateway(gateway_id, force_internal).await?;
+            let ln_gateway = module
+                .get_gateway_to_pay(gateway_id, force_internal)
+                .await?;
 
             let OutgoingLightningPayment {
                 payment_type,
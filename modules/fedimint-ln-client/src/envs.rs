@@ -0,0 +1,5 @@
+// Env variable to set a PEM file of an extra root certificate to trust when
+// resolving LNURLs and lightning addresses, like the one of a self-hosted
+// LNURL server. Only read with the `devimint-lnurl-root-cert` feature and in a
+// test environment
+pub const FM_LNURL_TLS_ROOT_CERT_ENV: &str = "FM_LNURL_TLS_ROOT_CERT";
@@ -10,6 +10,7 @@
 #[cfg(feature = "cli")]
 pub mod cli;
 pub mod db;
+pub mod envs;
 pub mod incoming;
 pub mod pay;
 pub mod receive;
@@ -1644,30 +1645,77 @@ pub async fn get_gateway(
         &self,
         gateway_id: Option<secp256k1::PublicKey>,
         force_internal: bool,
+    ) -> anyhow::Result<Option<LightningGateway>> {
+        self.get_gateway_supporting(gateway_id, force_internal, "payments", |_| true)
+            .await
+    }
+
+    /// Like [`Self::get_gateway`], but only selects gateways paying invoices
+    /// for clients. Errors if the gateway `gateway_id` announced it doesn't.
+    pub async fn get_gateway_to_pay(
+        &self,
+        gateway_id: Option<secp256k1::PublicKey>,
+        force_internal: bool,
+    ) -> anyhow::Result<Option<LightningGateway>> {
+        self.get_gateway_supporting(gateway_id, force_internal, "paying invoices", |gw| {
+            gw.pays_invoices
+        })
+        .await
+    }
+
+    /// Like [`Self::get_gateway`], but only selects gateways accepting incoming
+    /// payments to clients. Errors if the gateway `gateway_id` announced it
+    /// doesn't.
+    pub async fn get_gateway_to_receive(
+        &self,
+        gateway_id: Option<secp256k1::PublicKey>,
+        force_internal: bool,
+    ) -> anyhow::Result<Option<LightningGateway>> {
+        self.get_gateway_supporting(gateway_id, force_internal, "receiving payments", |gw| {
+            gw.receives_payments
+        })
+        .await
+    }
+
+    async fn get_gateway_supporting(
+        &self,
+        gateway_id: Option<secp256k1::PublicKey>,
+        force_internal: bool,
+        purpose: &str,
+        supports: fn(&LightningGatewayAnnouncement) -> bool,
     ) -> anyhow::Result<Option<LightningGateway>> {
         match gateway_id {
             Some(gateway_id) => {
-                if let Some(gw) = self.select_gateway(&gateway_id).await {
-                    Ok(Some(gw))
-                } else {
+                let mut gw = self.cached_gateway(&gateway_id).await;
+                if gw.is_none() {
                     // Refresh the gateway cache in case the target gateway was registered since the
                     // last update.
                     self.update_gateway_cache().await?;
-                    Ok(self.select_gateway(&gateway_id).await)
+                    gw = self.cached_gateway(&gateway_id).await;
+                }
+                match gw {
+                    Some(gw) if !supports(&gw) => {
+                        bail!("Gateway {gateway_id} does not support {purpose}")
+                    }
+                    gw => Ok(gw.map(|gw| gw.info)),
                 }
             }
             None if !force_internal => {
                 // Refresh the gateway cache to find a random gateway to select from.
                 self.update_gateway_cache().await?;
                 let gateways = self.list_gateways().await;
-                let gw = gateways.into_iter().choose(&mut OsRng).map(|gw| gw.info);
+                let gw = gateways
+                    .into_iter()
+                    .filter(supports)
+                    .choose(&mut OsRng)
+                    .map(|gw| gw.info);
                 if let Some(gw) = gw {
                     let gw_id = gw.gateway_id;
                     info!(%gw_id, "Using random gateway");
                     Ok(Some(gw))
                 } else {
                     Err(anyhow!(
-                        "No gateways exist in gateway cache and `force_internal` is false"
+                        "No gateways supporting {purpose} exist in gateway cache and `force_internal` is false"
                     ))
                 }
             }
@@ -1675,6 +1723,16 @@ pub async fn get_gateway(
         }
     }
 
+    async fn cached_gateway(
+        &self,
+        gateway_id: &secp256k1::PublicKey,
+    ) -> Option<LightningGatewayAnnouncement> {
+        self.list_gateways()
+            .await
+            .into_iter()
+            .find(|gw| gw.info.gateway_id == *gateway_id)
+    }
+
     pub async fn wait_for_ln_payment(
         &self,
         payment_type: PayType,
@@ -1947,6 +2005,27 @@ fn tweak_user_secret_key<Ctx: Verification + Signing>(
     KeyPair::from_secret_key(secp, &sk_tweaked)
 }
 
+/// HTTP client resolving LNURLs
+///
+/// Only with the `devimint-lnurl-root-cert` feature and inside a test
+/// environment it also trusts the root certificate of
+/// [`envs::FM_LNURL_TLS_ROOT_CERT_ENV`], so devimint can serve LNURLs with a
+/// self-signed certificate.
+fn lnurl_http_client() -> anyhow::Result<reqwest::Client> {
+    #[allow(unused_mut)]
+    let mut builder = reqwest::Client::builder();
+    #[cfg(all(feature = "devimint-lnurl-root-cert", not(target_family = "wasm")))]
+    if let Some(path) = std::env::var(envs::FM_LNURL_TLS_ROOT_CERT_ENV)
+        .ok()
+        .filter(|_| fedimint_core::envs::is_running_in_test_env())
+    {
+        let pem = std::fs::read(&path)
+            .with_context(|| format!("reading LNURL root certificate {path}"))?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    Ok(builder.build()?)
+}
+
 /// Get LN invoice with given settings
 pub async fn get_invoice(
     info: &str,
@@ -1978,7 +2057,7 @@ pub async fn get_invoice(
             };
             debug!("Parsed parameter as lnurl: {lnurl:?}");
             let amount = amount.context("When using a lnurl, an amount must be specified")?;
-            let async_client = lnurl::AsyncClient::from_client(reqwest::Client::new());
+            let async_client = lnurl::AsyncClient::from_client(lnurl_http_client()?);
             let response = async_client.make_request(&lnurl.url).await?;
             match response {
                 lnurl::LnUrlResponse::LnUrlPayResponse(response) => {
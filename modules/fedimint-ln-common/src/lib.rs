@@ -264,6 +264,18 @@ pub struct LightningGatewayRegistration {
     /// Limits the validity of the announcement to allow updates, anchored to
     /// local system time
     pub valid_until: SystemTime,
+    /// Indicates if the gateway pays invoices for clients
+    #[serde(default = "default_direction_supported")]
+    pub pays_invoices: bool,
+    /// Indicates if the gateway accepts incoming payments to clients
+    #[serde(default = "default_direction_supported")]
+    pub receives_payments: bool,
+}
+
+/// Gateways announced before they could restrict the directions they route
+/// payments in support both
+fn default_direction_supported() -> bool {
+    true
 }
 
 impl Encodable for LightningGatewayRegistration {
@@ -306,6 +318,8 @@ pub fn unanchor(self) -> LightningGatewayAnnouncement {
                 .duration_since(fedimint_core::time::now())
                 .unwrap_or_default(),
             vetted: self.vetted,
+            pays_invoices: self.pays_invoices,
+            receives_payments: self.receives_payments,
         }
     }
 
@@ -330,6 +344,12 @@ pub struct LightningGatewayAnnouncement {
     /// local system time to allow sharing between nodes with unsynchronized
     /// clocks
     pub ttl: Duration,
+    /// Indicates if the gateway pays invoices for clients
+    #[serde(default = "default_direction_supported")]
+    pub pays_invoices: bool,
+    /// Indicates if the gateway accepts incoming payments to clients
+    #[serde(default = "default_direction_supported")]
+    pub receives_payments: bool,
 }
 
 impl LightningGatewayAnnouncement {
@@ -340,6 +360,8 @@ pub fn anchor(self) -> LightningGatewayRegistration {
             info: self.info,
             vetted: self.vetted,
             valid_until: fedimint_core::time::now() + self.ttl,
+            pays_invoices: self.pays_invoices,
+            receives_payments: self.receives_payments,
         }
     }
 }
@@ -743,3 +765,52 @@ pub fn create_gateway_remove_message(
     message_preimage.append(&mut challenge.consensus_encode_to_vec());
     Message::from_hashed_data::<sha256::Hash>(message_preimage.as_slice())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use fedimint_core::util::SafeUrl;
+    use lightning_invoice::RoutingFees;
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    use super::{LightningGateway, LightningGatewayAnnouncement};
+
+    #[test]
+    fn announcement_without_directions_supports_both() {
+        let pk = PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[1; 32]).expect("valid secret key"),
+        );
+        let announcement = LightningGatewayAnnouncement {
+            info: LightningGateway {
+                mint_channel_id: 1,
+                gateway_redeem_key: pk,
+                node_pub_key: pk,
+                lightning_alias: "alias".to_owned(),
+                api: SafeUrl::parse("http://example.com").expect("valid url"),
+                route_hints: vec![],
+                fees: RoutingFees {
+                    base_msat: 0,
+                    proportional_millionths: 0,
+                },
+                gateway_id: pk,
+                supports_private_payments: false,
+            },
+            vetted: false,
+            ttl: Duration::from_secs(60),
+            pays_invoices: false,
+            receives_payments: false,
+        };
+
+        let mut json = serde_json::to_value(&announcement).expect("serializable");
+        let fields = json.as_object_mut().expect("announcement is an object");
+        fields.remove("pays_invoices");
+        fields.remove("receives_payments");
+
+        let legacy: LightningGatewayAnnouncement =
+            serde_json::from_value(json).expect("deserializable");
+        assert!(legacy.pays_invoices);
+        assert!(legacy.receives_payments);
+    }
+}
@@ -768,6 +768,8 @@ async fn create_server_db_with_v0_data(db: Database) {
             },
             valid_until: fedimint_core::time::now(),
             vetted: false,
+            pays_invoices: true,
+            receives_payments: true,
         };
         dbtx.insert_new_entry(&LightningGatewayKey(pk), &gateway)
             .await;
@@ -833,6 +835,8 @@ async fn create_client_db_with_v0_data(db: Database) {
             info: gateway_info,
             vetted: false,
             valid_until: fedimint_core::time::now(),
+            pays_invoices: true,
+            receives_payments: true,
         };
 
         dbtx.insert_new_entry(
@@ -135,6 +135,8 @@ let
     ] ++ lib.optionals (!stdenv.isDarwin) [
       util-linux
       iproute2
+      libfaketime
+      e2fsprogs
     ] ++ lib.optionals stdenv.isDarwin [
       libiconv
       darwin.apple_sdk.frameworks.Security
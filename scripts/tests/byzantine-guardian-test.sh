@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+# Runs a test corrupting the api responses of a guardian
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+export FM_GUARDIAN_RESPONSE_PROXY="${FM_GUARDIAN_RESPONSE_PROXY:-1}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint byzantine-guardian-test
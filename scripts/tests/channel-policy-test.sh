@@ -0,0 +1,12 @@
+#!/usr/bin/env bash
+# Runs a test to see if lnd and cln see the routing policy their peer sets on their channel
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint channel-policy-test
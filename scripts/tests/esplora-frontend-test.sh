@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+# Runs a test restarting esplora with its web frontend and calling esplora's
+# API the way the frontend does
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint esplora-frontend-test
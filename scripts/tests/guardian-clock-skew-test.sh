@@ -0,0 +1,19 @@
+#!/usr/bin/env bash
+# Runs a test of consensus with guardian clocks skewed within bounds, needs
+# libfaketime (provided by the dev shell)
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+export FM_GUARDIAN_CLOCK_SKEW_SECS="${FM_GUARDIAN_CLOCK_SKEW_SECS:-0,0,-30,30}"
+
+if ! command -v faketime >/dev/null; then
+  >&2 echo "faketime is required by the guardian clock skew test, but is not installed"
+  exit 1
+fi
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint guardian-clock-skew-test
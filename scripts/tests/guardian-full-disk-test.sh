@@ -0,0 +1,18 @@
+#!/usr/bin/env bash
+# Runs a test filling the disk of a guardian, which needs loopback mounts and
+# thus root, gotten through sudo if not running as root already
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+export FM_GUARDIAN_DISK_MB="${FM_GUARDIAN_DISK_MB:-,64}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+if [ "$(id -u)" -ne 0 ]; then
+  # CI runners have passwordless sudo, fail instead of prompting elsewhere
+  exec sudo --non-interactive --preserve-env env "PATH=$PATH" devimint guardian-full-disk-test
+fi
+devimint guardian-full-disk-test
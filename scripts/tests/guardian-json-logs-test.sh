@@ -0,0 +1,12 @@
+#!/usr/bin/env bash
+# Runs guardians logging JSON and tests their logs can be parsed and waited on
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint --guardian-json-logs guardian-json-logs-test
@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+# Runs a test cutting and restoring a guardian's network, skipped unless
+# network namespaces are available (linux, root)
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+export FM_GUARDIAN_NETNS=1
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint guardian-netns-test
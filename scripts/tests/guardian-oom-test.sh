@@ -0,0 +1,12 @@
+#!/usr/bin/env bash
+# Runs a test squeezing a guardian's memory limit until it gets OOM killed
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint guardian-oom-test
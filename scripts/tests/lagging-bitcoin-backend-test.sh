@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+# Runs a test of consensus with the last guardian's wallet on esplora, which
+# the test freezes so that guardian's view of the chain lags behind
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+export FM_GUARDIAN_BITCOIN_RPC="${FM_GUARDIAN_BITCOIN_RPC:-,,,esplora}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint lagging-bitcoin-backend-test
@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+# Runs a test reissuing notes with a mint that charges fees per note
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+export FM_MINT_FEES="${FM_MINT_FEES:-10,20}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint mint-fees-test
@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+# Runs a test restarting esplora behind a throttled bitcoind with many blocks
+# to sync
+
+set -euo pipefail
+export RUST_LOG="${RUST_LOG:-info}"
+
+source scripts/_common.sh
+build_workspace
+add_target_dir_to_path
+make_fm_test_marker
+
+devimint slow-bitcoind-sync-test
@@ -120,6 +120,92 @@ function guardian_backup() {
 }
 export -f guardian_backup
 
+function threshold_recovery() {
+  # threshold-recovery-test takes guardians down itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/threshold-recovery-test.sh
+}
+export -f threshold_recovery
+
+function guardian_kill_recovery() {
+  # guardian-kill-recovery-test kills a guardian itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/guardian-kill-recovery-test.sh
+}
+export -f guardian_kill_recovery
+
+function external_lightning_node() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/external-lightning-node-test.sh
+}
+export -f external_lightning_node
+
+function guardian_netns() {
+  # guardian-netns-test cuts a guardian's network itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/guardian-netns-test.sh
+}
+export -f guardian_netns
+
+function guardian_clock_skew() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/guardian-clock-skew-test.sh
+}
+export -f guardian_clock_skew
+
+function over_threshold_offline() {
+  # over-threshold-offline-test takes guardians down itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/over-threshold-offline-test.sh
+}
+export -f over_threshold_offline
+
+function lagging_bitcoin_backend() {
+  # the lagging guardian plus an offline one would stall consensus, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/lagging-bitcoin-backend-test.sh
+}
+export -f lagging_bitcoin_backend
+
+function lnurl_pay() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/lnurl-pay-test.sh
+}
+export -f lnurl_pay
+
+function channel_policy() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/channel-policy-test.sh
+}
+export -f channel_policy
+
+function mint_fees() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/mint-fees-test.sh
+}
+export -f mint_fees
+
+function slow_bitcoind_sync() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/slow-bitcoind-sync-test.sh
+}
+export -f slow_bitcoind_sync
+
+function esplora_frontend() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/esplora-frontend-test.sh
+}
+export -f esplora_frontend
+
+function pegout_signing() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/pegout-signing-test.sh
+}
+export -f pegout_signing
+
+function guardian_password() {
+  # guardian-password-test restarts a guardian, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/guardian-password-test.sh
+}
+export -f guardian_password
+
+function channel_churn() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/channel-churn-test.sh
+}
+export -f channel_churn
+
+function double_spend() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/double-spend-test.sh
+}
+export -f double_spend
+
 function cannot_replay_tx() {
   fm-run-test "${FUNCNAME[0]}" ./scripts/tests/cannot-replay-tx.sh
 }
@@ -135,6 +221,100 @@ function wallet_recovery() {
 }
 export -f wallet_recovery
 
+function guardian_full_disk() {
+  # guardian-full-disk-test needs a threshold of guardians besides the full one
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/guardian-full-disk-test.sh
+}
+export -f guardian_full_disk
+
+function exact_denominations() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/exact-denominations-test.sh
+}
+export -f exact_denominations
+
+function transaction_size_limit() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/transaction-size-limit-test.sh
+}
+export -f transaction_size_limit
+
+function gateway_unreachable_federation() {
+  # gateway-unreachable-federation-test takes guardians down itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/gateway-unreachable-federation-test.sh
+}
+export -f gateway_unreachable_federation
+
+function mnemonic_client() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/mnemonic-client-test.sh
+}
+export -f mnemonic_client
+
+function gateway_onchain_rebalance() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/gateway-onchain-rebalance-test.sh
+}
+export -f gateway_onchain_rebalance
+
+function meta_update() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/meta-update-test.sh
+}
+export -f meta_update
+
+function byzantine_guardian() {
+  # byzantine-guardian-test makes a guardian misbehave itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/byzantine-guardian-test.sh
+}
+export -f byzantine_guardian
+
+function drop_cleanup() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/drop-cleanup-test.sh
+}
+export -f drop_cleanup
+
+function gateway_lightning_node_down() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/gateway-lightning-node-down-test.sh
+}
+export -f gateway_lightning_node_down
+
+function guardian_oom() {
+  # guardian-oom-test takes a guardian down itself, so we need to override FM_OFFLINE_NODES
+  fm-run-test "${FUNCNAME[0]}" env FM_OFFLINE_NODES=0 ./scripts/tests/guardian-oom-test.sh
+}
+export -f guardian_oom
+
+function hold_invoice() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/hold-invoice-test.sh
+}
+export -f hold_invoice
+
+function ln_pay_via() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/ln-pay-via-test.sh
+}
+export -f ln_pay_via
+
+function memory_growth() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/memory-growth-test.sh
+}
+export -f memory_growth
+
+function synced_height() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/synced-height-test.sh
+}
+export -f synced_height
+
+function reattach() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/reattach-test.sh
+}
+export -f reattach
+
+function current_thread() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/current-thread-test.sh
+}
+export -f current_thread
+
+function guardian_json_logs() {
+  fm-run-test "${FUNCNAME[0]}" ./scripts/tests/guardian-json-logs-test.sh
+}
+export -f guardian_json_logs
+
 function devimint_cli_test() {
   fm-run-test "${FUNCNAME[0]}" ./scripts/tests/devimint-cli-test.sh
 }
@@ -305,8 +485,42 @@ tests_to_run_in_parallel+=(
   "meta_module"
   "mint_client_sanity"
   "cannot_replay_tx"
+  "threshold_recovery"
+  "guardian_kill_recovery"
+  "external_lightning_node"
+  "guardian_netns"
+  "guardian_clock_skew"
+  "over_threshold_offline"
+  "lagging_bitcoin_backend"
+  "lnurl_pay"
+  "channel_policy"
+  "mint_fees"
+  "slow_bitcoind_sync"
+  "esplora_frontend"
+  "pegout_signing"
+  "guardian_password"
+  "channel_churn"
+  "double_spend"
   "circular_deposit"
   "wallet_recovery"
+  "guardian_full_disk"
+  "exact_denominations"
+  "transaction_size_limit"
+  "gateway_unreachable_federation"
+  "mnemonic_client"
+  "gateway_onchain_rebalance"
+  "meta_update"
+  "byzantine_guardian"
+  "drop_cleanup"
+  "gateway_lightning_node_down"
+  "guardian_oom"
+  "hold_invoice"
+  "ln_pay_via"
+  "memory_growth"
+  "synced_height"
+  "reattach"
+  "current_thread"
+  "guardian_json_logs"
 )
 done
 
@@ -18,7 +18,7 @@ else
   versions=("$@")
 fi
 
-default_test_kinds=("fedimintd" "fedimint-cli" "gateway")
+default_test_kinds=("fedimintd" "fedimintd-db-migration" "fedimint-cli" "gateway")
 
 # runs a subset of tests if the user provides `TEST_KINDS`
 # ex: TEST_KINDS=fedimint-cli,gateway
@@ -63,6 +63,22 @@ if contains "fedimintd" "${test_kinds[@]}"; then
   )
 fi
 
+if contains "fedimintd-db-migration" "${test_kinds[@]}"; then
+  fedimintd_paths=()
+  for version in "${versions[@]}"; do
+    if [ "$version" == "current" ]; then
+      # Add current binaries from PATH
+      fedimintd_paths+=("fedimintd")
+    else
+      fedimintd_paths+=("$(nix_build_binary_for_version 'fedimintd' "$version")")
+    fi
+  done
+
+  upgrade_tests+=(
+    "devimint upgrade-tests fedimintd-db-migration --paths $(printf "%s " "${fedimintd_paths[@]}")"
+  )
+fi
+
 if contains "fedimint-cli" "${test_kinds[@]}"; then
   fedimint_cli_paths=()
   for version in "${versions[@]}"; do